@@ -0,0 +1,52 @@
+// Times the simulation's tick with the approaches updated one after another and on worker
+// threads, at the busiest the intersection gets. Run with `cargo bench --bench update`.
+//
+// Measured on a single-core Linux Xeon, with 58 vehicles on the road:
+//
+//   sequential   6.48 ms per 100 ticks
+//   parallel     7.18 ms per 100 ticks   (0.90x)
+//
+// With one core that is all overhead, about 7 µs a tick for handing out the approaches.
+// A single intersection never holds enough traffic to win it back, so the simulation only
+// goes parallel from `PARALLEL_VEHICLES` vehicles up; rerun this on more cores before
+// lowering it.
+
+use std::time::Instant;
+
+use road_intersection::config::Config;
+use road_intersection::simulation::TrafficSimulation;
+
+const WARM_UP_TICKS: u32 = 6000;
+const TIMED_TICKS: u32 = 6000;
+
+fn busy() -> Config {
+    let mut config = Config { seed: Some(1), ..Config::default() };
+    config.demand.vehicles_per_minute = 600.0;
+    config
+}
+
+// Milliseconds per 100 ticks, and the mean number of vehicles on the road.
+fn time(parallel_vehicles: usize) -> (f64, f64) {
+    let mut simulation = TrafficSimulation::with_config(&busy()).unwrap();
+    simulation.parallel_vehicles = parallel_vehicles;
+    for _ in 0..WARM_UP_TICKS {
+        simulation.update();
+        simulation.drain_events();
+    }
+    let (mut vehicles, started) = (0, Instant::now());
+    for _ in 0..TIMED_TICKS {
+        simulation.update();
+        simulation.drain_events();
+        vehicles += simulation.vehicles().len();
+    }
+    let per_hundred = started.elapsed().as_secs_f64() * 1000.0 * 100.0 / (TIMED_TICKS as f64);
+    (per_hundred, (vehicles as f64) / (TIMED_TICKS as f64))
+}
+
+fn main() {
+    let (sequential, vehicles) = time(usize::MAX);
+    let (parallel, _) = time(0);
+    println!("{:.0} vehicles on the road", vehicles);
+    println!("sequential   {:.2} ms per 100 ticks", sequential);
+    println!("parallel     {:.2} ms per 100 ticks   ({:.2}x)", parallel, sequential / parallel);
+}
@@ -0,0 +1,44 @@
+// Two-sided 95% critical values of Student's t for 1 to 30 degrees of freedom; beyond that
+// the normal value is close enough.
+const T_CRITICAL_95: [f32; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+const Z_CRITICAL_95: f32 = 1.96;
+
+// A paired comparison of one measure, in seconds, between a baseline and an alternative
+// run on the same seeds: the mean of the per-seed differences, alternative minus
+// baseline, and its 95% confidence interval from the t distribution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairedDifference {
+    pub pairs: usize,
+    pub mean: f32,
+    // Unknown for a single pair, which says nothing about the spread.
+    pub confidence_interval: Option<(f32, f32)>,
+}
+
+impl PairedDifference {
+    // From (baseline, alternative) pairs; None without any.
+    pub fn from_pairs(pairs: &[(f32, f32)]) -> Option<Self> {
+        if pairs.is_empty() {
+            return None;
+        }
+        let differences: Vec<f32> = pairs.iter().map(|&(a, b)| b - a).collect();
+        let n = differences.len();
+        let mean = differences.iter().sum::<f32>() / (n as f32);
+        let confidence_interval = (n > 1).then(|| {
+            let variance =
+                differences.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / ((n - 1) as f32);
+            let t = T_CRITICAL_95.get(n - 2).copied().unwrap_or(Z_CRITICAL_95);
+            let half_width = t * (variance / (n as f32)).sqrt();
+            (mean - half_width, mean + half_width)
+        });
+        Some(Self { pairs: n, mean, confidence_interval })
+    }
+
+    // Whether the interval leaves out zero, so the alternative is reliably better or worse.
+    pub fn is_significant(&self) -> bool {
+        self.confidence_interval.is_some_and(|(low, high)| low > 0.0 || high < 0.0)
+    }
+}
@@ -0,0 +1,316 @@
+use hecs::{ Component, Entity, Query, Ref, ViewBorrow, World };
+use std::collections::HashMap;
+
+use crate::cyclist::Cyclist;
+use crate::lane::Lane;
+use crate::pedestrian::Pedestrian;
+use crate::sink::node_id;
+use crate::systems::Room;
+use crate::traffic_light::TrafficLight;
+use crate::vehicle::{ Direction, Vehicle, VehicleId };
+
+// Vehicles this close to the intersection stand over an approach's detector.
+pub const DETECTOR_DISTANCE: f32 = 12.0;
+
+// The loop detector out on each approach, as the light controllers and spawn policies see
+// the traffic. Readings are taken with `Agents::detect`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detector {
+    pub approach: Direction,
+    // A vehicle still on its approach is within DETECTOR_DISTANCE of the intersection.
+    pub occupied: bool,
+    // Vehicles held up on the approach, plus those waiting upstream to get onto it.
+    pub queue: usize,
+    // Vehicles on the road from the approach, past the intersection included.
+    pub vehicles: usize,
+}
+
+// Road users with component `T`, read in place in the world for as long as this is held,
+// in the order the systems go over them.
+pub struct Roster<'a, T: Component> {
+    view: ViewBorrow<'a, &'static T>,
+    entities: &'a [Vec<Entity>],
+}
+
+impl<'a, T: Component> Roster<'a, T> {
+    pub fn iter(&self) -> impl Iterator<Item = &T> + Clone + '_ {
+        self.entries().map(|(_, agent)| agent)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (Entity, &T)> + Clone + '_ {
+        self.entities.iter().flatten().map(|&entity| {
+            (entity, self.view.get(entity).expect("listed agents are in the world"))
+        })
+    }
+
+    // Each approach's, for vehicles and cyclists.
+    pub fn groups(&self) -> Vec<Vec<&T>> {
+        self.entities
+            .iter()
+            .map(|listed| {
+                listed
+                    .iter()
+                    .map(|&entity| self.view.get(entity).expect("listed agents are in the world"))
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// The simulation's road users and signal equipment, as entities in a hecs world: every
+// vehicle, cyclist and pedestrian, the traffic light, and a detector on each approach.
+// Lanes keep the road itself.
+pub struct Agents {
+    world: World,
+    light: Entity,
+    detectors: [Entity; 4],
+    // Each approach's vehicles and cyclists, in north, south, east, west order, each in the
+    // order they got onto it. Systems go over them in this order, so a run comes out the
+    // same however the world happens to store them.
+    vehicles: [Vec<Entity>; 4],
+    cyclists: [Vec<Entity>; 4],
+    // Pedestrians waiting at the corners, and out on the crosswalks, in the order they got
+    // there.
+    waiting: Vec<Entity>,
+    crossing: Vec<Entity>,
+    ids: HashMap<VehicleId, Entity>,
+    // Id for the next vehicle onto the road; ids are unique within a run.
+    next_vehicle_id: VehicleId,
+}
+
+impl Agents {
+    pub fn new(light: TrafficLight) -> Self {
+        let mut world = World::new();
+        let light = world.spawn((light,));
+        let detectors = [Direction::North, Direction::South, Direction::East, Direction::West]
+            .map(|approach| {
+                world.spawn((Detector { approach, occupied: false, queue: 0, vehicles: 0 },))
+            });
+        Self {
+            world,
+            light,
+            detectors,
+            vehicles: Default::default(),
+            cyclists: Default::default(),
+            waiting: Vec::new(),
+            crossing: Vec::new(),
+            ids: HashMap::new(),
+            next_vehicle_id: VehicleId(1),
+        }
+    }
+
+    pub fn light(&self) -> Ref<'_, TrafficLight> {
+        self.world.get::<&TrafficLight>(self.light).expect("the traffic light is never despawned")
+    }
+
+    pub fn light_mut(&mut self) -> &mut TrafficLight {
+        self.world
+            .query_one_mut::<&mut TrafficLight>(self.light)
+            .expect("the traffic light is never despawned")
+    }
+
+    // Hands out the id of a vehicle about to get onto the road.
+    pub fn new_vehicle_id(&mut self) -> VehicleId {
+        let id = self.next_vehicle_id;
+        self.next_vehicle_id.0 += 1;
+        id
+    }
+
+    pub fn add_vehicle(&mut self, vehicle: Vehicle) {
+        let entity = self.world.spawn((vehicle, Room::default()));
+        self.vehicles[node_id(vehicle.approach)].push(entity);
+        self.ids.insert(vehicle.id, entity);
+    }
+
+    pub fn add_cyclist(&mut self, cyclist: Cyclist) {
+        let entity = self.world.spawn((cyclist,));
+        self.cyclists[node_id(cyclist.direction)].push(entity);
+    }
+
+    // A pedestrian arriving at a corner to wait for the walk.
+    pub fn add_waiting(&mut self, pedestrian: Pedestrian) {
+        self.waiting.push(self.world.spawn((pedestrian,)));
+    }
+
+    // A pedestrian stepping straight out into the road.
+    pub fn add_crossing(&mut self, pedestrian: Pedestrian) {
+        self.crossing.push(self.world.spawn((pedestrian,)));
+    }
+
+    // Sends everyone waiting at the corners out onto the crosswalks.
+    pub fn start_crossing(&mut self) {
+        self.crossing.append(&mut self.waiting);
+    }
+
+    // Every vehicle on the road, approach by approach.
+    pub fn vehicles(&self) -> Roster<'_, Vehicle> {
+        Roster { view: self.world.view(), entities: &self.vehicles }
+    }
+
+    // The vehicles that came in on `approach`, in the order they got onto it.
+    pub fn vehicles_on(&self, approach: Direction) -> Roster<'_, Vehicle> {
+        let entities = std::slice::from_ref(&self.vehicles[node_id(approach)]);
+        Roster { view: self.world.view(), entities }
+    }
+
+    pub fn cyclists(&self) -> Roster<'_, Cyclist> {
+        Roster { view: self.world.view(), entities: &self.cyclists }
+    }
+
+    pub fn cyclists_on(&self, approach: Direction) -> Roster<'_, Cyclist> {
+        let entities = std::slice::from_ref(&self.cyclists[node_id(approach)]);
+        Roster { view: self.world.view(), entities }
+    }
+
+    pub fn waiting_pedestrians(&self) -> Roster<'_, Pedestrian> {
+        Roster { view: self.world.view(), entities: std::slice::from_ref(&self.waiting) }
+    }
+
+    pub fn crossing_pedestrians(&self) -> Roster<'_, Pedestrian> {
+        Roster { view: self.world.view(), entities: std::slice::from_ref(&self.crossing) }
+    }
+
+    // Each approach's vehicles as the components `Q` asks for, in the order they got onto
+    // it, for a system to work through approach by approach.
+    pub fn vehicle_rows<Q: Query>(&mut self) -> Vec<Vec<Q::Item<'_>>> {
+        rows::<Q>(&mut self.world, &self.vehicles)
+    }
+
+    pub fn cyclist_rows<Q: Query>(&mut self) -> Vec<Vec<Q::Item<'_>>> {
+        rows::<Q>(&mut self.world, &self.cyclists)
+    }
+
+    pub fn crossing_rows<Q: Query>(&mut self) -> Vec<Q::Item<'_>> {
+        rows::<Q>(&mut self.world, std::slice::from_ref(&self.crossing)).remove(0)
+    }
+
+    // Every vehicle, in no particular order, for changes that don't depend on one.
+    pub fn vehicles_mut(&mut self) -> impl Iterator<Item = &mut Vehicle> {
+        self.world.query_mut::<&mut Vehicle>().into_iter().map(|(_, vehicle)| vehicle)
+    }
+
+    pub fn vehicle_mut(&mut self, id: VehicleId) -> Option<&mut Vehicle> {
+        let entity = *self.ids.get(&id)?;
+        self.world.query_one_mut::<&mut Vehicle>(entity).ok()
+    }
+
+    pub fn remove_vehicle(&mut self, id: VehicleId) -> Option<Vehicle> {
+        let entity = self.ids.remove(&id)?;
+        let vehicle = *self.world.get::<&Vehicle>(entity).ok()?;
+        self.vehicles[node_id(vehicle.approach)].retain(|&other| other != entity);
+        self.world.despawn(entity).ok()?;
+        Some(vehicle)
+    }
+
+    // Takes the cyclists `leaves` picks off the road.
+    pub fn remove_cyclists(&mut self, leaves: impl Fn(&Cyclist) -> bool) {
+        for entities in &mut self.cyclists {
+            remove_where(&mut self.world, entities, &leaves);
+        }
+    }
+
+    // Takes the pedestrians out on the crosswalks `leaves` picks off the road.
+    pub fn remove_crossing(&mut self, leaves: impl Fn(&Pedestrian) -> bool) {
+        remove_where(&mut self.world, &mut self.crossing, &leaves);
+    }
+
+    // Takes every vehicle, cyclist and pedestrian off the road; the light and detectors
+    // stay.
+    pub fn clear_traffic(&mut self) {
+        let vehicles = self.vehicles.iter_mut().flat_map(std::mem::take);
+        let cyclists = self.cyclists.iter_mut().flat_map(std::mem::take);
+        let pedestrians = self.waiting.drain(..).chain(self.crossing.drain(..));
+        for entity in vehicles.chain(cyclists).chain(pedestrians) {
+            let _ = self.world.despawn(entity);
+        }
+        self.ids.clear();
+    }
+
+    // The vehicle or cyclist that is `entity`'s flag for having been in a collision.
+    pub fn collided_flag(&mut self, entity: Entity) -> Option<&mut bool> {
+        if self.world.satisfies::<&Vehicle>(entity).unwrap_or(false) {
+            let vehicle = self.world.query_one_mut::<&mut Vehicle>(entity).ok()?;
+            return Some(&mut vehicle.collided);
+        }
+        let cyclist = self.world.query_one_mut::<&mut Cyclist>(entity).ok()?;
+        Some(&mut cyclist.collided)
+    }
+
+    // Each approach's detector, in north, south, east, west order.
+    pub fn detectors(&self) -> Vec<Detector> {
+        let detectors = self.world.view::<&Detector>();
+        self.detectors
+            .iter()
+            .map(|&entity| *detectors.get(entity).expect("detectors are never despawned"))
+            .collect()
+    }
+
+    // Reads every detector off the traffic as it stands and the arrivals waiting on `lanes`.
+    pub fn detect(&mut self, lanes: &[Lane]) {
+        let readings: Vec<(bool, usize, usize)> = lanes
+            .iter()
+            .map(|lane| {
+                let vehicles = self.vehicles_on(lane.direction);
+                let occupied = vehicles.iter().any(|vehicle| {
+                    vehicle.state.on_approach() &&
+                        vehicle.distance_to_intersection() < DETECTOR_DISTANCE
+                });
+                (occupied, lane.queue_length(vehicles.iter()), vehicles.len())
+            })
+            .collect();
+        for (lane, (occupied, queue, vehicles)) in lanes.iter().zip(readings) {
+            let entity = self.detectors[node_id(lane.direction)];
+            let detector = self.world
+                .query_one_mut::<&mut Detector>(entity)
+                .expect("detectors are never despawned");
+            *detector = Detector { occupied, queue, vehicles, ..*detector };
+        }
+    }
+}
+
+// The components `Q` of each group of `entities`, group by group in their listed order.
+fn rows<'a, Q: Query>(world: &'a mut World, entities: &[Vec<Entity>]) -> Vec<Vec<Q::Item<'a>>> {
+    let places: HashMap<Entity, (usize, usize)> = entities
+        .iter()
+        .enumerate()
+        .flat_map(|(group, listed)| {
+            listed.iter().enumerate().map(move |(place, &entity)| (entity, (group, place)))
+        })
+        .collect();
+    let mut rows: Vec<Vec<Option<Q::Item<'a>>>> = entities
+        .iter()
+        .map(|listed| listed.iter().map(|_| None).collect())
+        .collect();
+    for (entity, item) in world.query_mut::<Q>() {
+        if let Some(&(group, place)) = places.get(&entity) {
+            rows[group][place] = Some(item);
+        }
+    }
+    rows.into_iter()
+        .map(|row| row.into_iter().map(|item| item.expect("listed agents are in the world")))
+        .map(Iterator::collect)
+        .collect()
+}
+
+// Despawns the `entities` with a component `T` that `leaves` picks, and unlists them.
+fn remove_where<T: Component>(
+    world: &mut World,
+    entities: &mut Vec<Entity>,
+    leaves: impl Fn(&T) -> bool
+) {
+    entities.retain(|&entity| {
+        let gone = world.get::<&T>(entity).is_ok_and(|agent| leaves(&agent));
+        if gone {
+            let _ = world.despawn(entity);
+        }
+        !gone
+    });
+}
@@ -0,0 +1,239 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::{ Duration, Instant };
+
+use crate::config::{ ApproachDemand, DemandConfig };
+use crate::error::RenderError;
+use crate::render::{ font, Rect, Renderer };
+use crate::simulation::TrafficSimulation;
+use crate::ui::{ Mouse, Panel };
+use crate::vehicle::{ Direction, VehicleId };
+use crate::WINDOW_WIDTH;
+
+// Real time each demand pattern and each highlight lasts.
+const PATTERN_TIME: Duration = Duration::from_secs(45);
+const HIGHLIGHT_TIME: Duration = Duration::from_secs(8);
+const BANNER_WIDTH: u32 = 420;
+const BANNER_HEIGHT: u32 = 64;
+
+// What the screen points out in turn while the demo runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Highlight {
+    Served,
+    FollowVehicle,
+    Delay,
+    Heatmap,
+    Emissions,
+    Counts,
+    Queue,
+    Noise,
+}
+
+const HIGHLIGHTS: [Highlight; 8] = [
+    Highlight::Served,
+    Highlight::FollowVehicle,
+    Highlight::Delay,
+    Highlight::Heatmap,
+    Highlight::Emissions,
+    Highlight::Counts,
+    Highlight::Queue,
+    Highlight::Noise,
+];
+
+// What the demo changes, put back when someone takes over again.
+struct Saved {
+    demand: DemandConfig,
+    show_heatmap: bool,
+    show_counts: bool,
+    show_noise: bool,
+    selected_vehicle: Option<VehicleId>,
+}
+
+struct Running {
+    saved: Saved,
+    pattern: String,
+    next_pattern: Instant,
+    highlight: usize,
+    next_highlight: Instant,
+}
+
+// Takes over once nobody has touched the keyboard, mouse or a controller for the idle time:
+// traffic arrives in a new random pattern every so often, and the overlays, a followed
+// vehicle and the headline stats take turns on screen. Any input hands control back with
+// the demand and overlays as they were.
+pub struct AttractMode {
+    // None leaves the demo off.
+    idle_time: Option<Duration>,
+    last_input: Instant,
+    running: Option<Running>,
+}
+
+impl AttractMode {
+    pub fn new(idle_secs: f32) -> Self {
+        Self {
+            idle_time: (idle_secs > 0.0).then(|| Duration::from_secs_f32(idle_secs)),
+            last_input: Instant::now(),
+            running: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    // For every key, click, mouse movement or controller button. Returns whether it ended
+    // the demo, so it shouldn't act on the scene as well.
+    pub fn input(&mut self, simulation: &mut TrafficSimulation) -> bool {
+        self.last_input = Instant::now();
+        let was_running = self.is_running();
+        self.stop(simulation);
+        was_running
+    }
+
+    // Starts the demo once idle for long enough, then moves it on to its next pattern and
+    // highlight as they come due. Returns whether it has just started.
+    pub fn update(&mut self, simulation: &mut TrafficSimulation) -> bool {
+        let now = Instant::now();
+        let Some(running) = &mut self.running else {
+            let idle = self.idle_time.is_some_and(|time| self.last_input.elapsed() >= time);
+            if idle {
+                self.start(simulation, now);
+            }
+            return idle;
+        };
+        if now >= running.next_pattern {
+            running.pattern = random_pattern(&mut simulation.demand);
+            running.next_pattern = now + PATTERN_TIME;
+            tracing::info!("demo traffic: {}", running.pattern.to_lowercase());
+        }
+        if now >= running.next_highlight {
+            running.highlight = (running.highlight + 1) % HIGHLIGHTS.len();
+            running.next_highlight = now + HIGHLIGHT_TIME;
+            show(HIGHLIGHTS[running.highlight], simulation);
+        }
+        false
+    }
+
+    // Hands control back, restoring what the demo changed.
+    pub fn stop(&mut self, simulation: &mut TrafficSimulation) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        let saved = running.saved;
+        simulation.demand = saved.demand;
+        simulation.show_heatmap = saved.show_heatmap;
+        simulation.show_counts = saved.show_counts;
+        simulation.show_noise = saved.show_noise;
+        simulation.selected_vehicle = saved.selected_vehicle;
+        tracing::info!("demo ended");
+    }
+
+    fn start(&mut self, simulation: &mut TrafficSimulation, now: Instant) {
+        let saved = Saved {
+            demand: simulation.demand.clone(),
+            show_heatmap: simulation.show_heatmap,
+            show_counts: simulation.show_counts,
+            show_noise: simulation.show_noise,
+            selected_vehicle: simulation.selected_vehicle,
+        };
+        let pattern = random_pattern(&mut simulation.demand);
+        show(HIGHLIGHTS[0], simulation);
+        tracing::info!("no input, demo started with {}", pattern.to_lowercase());
+        self.running = Some(Running {
+            saved,
+            pattern,
+            next_pattern: now + PATTERN_TIME,
+            highlight: 0,
+            next_highlight: now + HIGHLIGHT_TIME,
+        });
+    }
+
+    // A banner across the top with the traffic pattern and the current highlight.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        simulation: &TrafficSimulation
+    ) -> Result<(), RenderError> {
+        let Some(running) = &self.running else {
+            return Ok(());
+        };
+        let x = (WINDOW_WIDTH as i32) / 2 - (BANNER_WIDTH as i32) / 2;
+        let area = Rect::new(x, 10, BANNER_WIDTH, BANNER_HEIGHT);
+        let palette = simulation.theme().palette();
+        let mut panel = Panel::begin(renderer, palette, Mouse::default(), area)?;
+        panel.label(&format!("DEMO - {}", running.pattern))?;
+        panel.label(&caption(HIGHLIGHTS[running.highlight], simulation))?;
+        let hint = "PRESS ANY KEY TO TAKE OVER";
+        let hint_x = (WINDOW_WIDTH as i32) / 2 - font::GLYPH_ADVANCE * (hint.len() as i32) / 2;
+        let hint_y = 10 + (BANNER_HEIGHT as i32) + 6;
+        renderer.draw_text(hint, hint_x, hint_y, palette.text)
+    }
+}
+
+// Replaces `demand` with a random one of a few kinds of traffic, returning its name.
+fn random_pattern(demand: &mut DemandConfig) -> String {
+    let mut rng = rand::thread_rng();
+    demand.schedule.clear();
+    demand.approaches = ApproachDemand::default();
+    match rng.gen_range(0..4) {
+        0 => {
+            demand.vehicles_per_minute = rng.gen_range(8.0..20.0f32).round();
+            "LIGHT TRAFFIC".to_string()
+        }
+        1 => {
+            demand.vehicles_per_minute = rng.gen_range(40.0..60.0f32).round();
+            "BUSY TRAFFIC".to_string()
+        }
+        2 => {
+            let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+            let direction = *directions.choose(&mut rng).unwrap_or(&Direction::North);
+            demand.vehicles_per_minute = rng.gen_range(10.0..20.0f32).round();
+            *demand.approaches.rate_mut(direction) = rng.gen_range(15.0..30.0f32).round();
+            format!("{}BOUND RUSH", format!("{:?}", direction).to_uppercase())
+        }
+        _ => {
+            demand.vehicles_per_minute = rng.gen_range(5.0..10.0f32).round();
+            let commute = rng.gen_range(12.0..24.0f32).round();
+            demand.approaches.east = commute;
+            demand.approaches.west = commute;
+            "EAST-WEST COMMUTE".to_string()
+        }
+    }
+}
+
+// Turns on the overlay or vehicle the highlight is about, and only that.
+fn show(highlight: Highlight, simulation: &mut TrafficSimulation) {
+    simulation.show_heatmap = highlight == Highlight::Heatmap;
+    simulation.show_counts = highlight == Highlight::Counts;
+    simulation.show_noise = highlight == Highlight::Noise;
+    simulation.selected_vehicle = if highlight == Highlight::FollowVehicle {
+        let vehicles: Vec<VehicleId> = simulation
+            .vehicles()
+            .iter()
+            .filter(|vehicle| vehicle.distance_to_intersection() > 0.0)
+            .map(|vehicle| vehicle.id)
+            .collect();
+        vehicles.choose(&mut rand::thread_rng()).copied()
+    } else {
+        None
+    };
+}
+
+fn caption(highlight: Highlight, simulation: &TrafficSimulation) -> String {
+    let stats = &simulation.stats;
+    match highlight {
+        Highlight::Served => format!("{} VEHICLES SERVED SO FAR", stats.vehicles_completed),
+        Highlight::FollowVehicle => "FOLLOWING ONE VEHICLE THROUGH".to_string(),
+        Highlight::Delay => {
+            let delay = stats.average_vehicle_delay().as_secs_f32();
+            format!("AVERAGE DELAY {:.1}S PER VEHICLE", delay)
+        }
+        Highlight::Heatmap => "WHERE VEHICLES WAIT".to_string(),
+        Highlight::Emissions => {
+            format!("{:.0}G OF CO2 PER VEHICLE", stats.co2_per_vehicle() * 1000.0)
+        }
+        Highlight::Counts => "VEHICLES BY MOVEMENT".to_string(),
+        Highlight::Queue => format!("LONGEST QUEUE {} VEHICLES", stats.max_queue),
+        Highlight::Noise => "TRAFFIC NOISE LEVELS".to_string(),
+    }
+}
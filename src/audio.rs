@@ -0,0 +1,177 @@
+use crate::simulation::SimEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sound {
+    Click,
+    Horn,
+    Crash,
+}
+
+impl Sound {
+    pub fn for_event(event: &SimEvent) -> Option<Sound> {
+        match event {
+            SimEvent::LightChanged => Some(Sound::Click),
+            SimEvent::VehicleWaiting => Some(Sound::Horn),
+            SimEvent::Collision => Some(Sound::Crash),
+            SimEvent::VehicleSpawned { .. } |
+            SimEvent::VehicleEnteredIntersection { .. } |
+            SimEvent::VehicleTurned { .. } |
+            SimEvent::VehicleCollided { .. } |
+            SimEvent::VehicleExited { .. } |
+            SimEvent::RedLightViolation { .. } |
+            SimEvent::SpeedMeasured { .. } |
+            SimEvent::NoiseMeasured { .. } |
+            SimEvent::WeatherChanged { .. } |
+            SimEvent::TravelTimeMeasured { .. } |
+            SimEvent::QueueHeadway { .. } |
+            SimEvent::ArrivalQueued { .. } |
+            SimEvent::ArrivalReleased { .. } |
+            SimEvent::Starvation { .. } |
+            SimEvent::Gridlock { .. } |
+            SimEvent::PedestrianServed { .. } |
+            SimEvent::JaywalkerCrossed { .. } |
+            SimEvent::EmergencyBrake { .. } |
+            SimEvent::CrossingClosed |
+            SimEvent::CrossingOpened |
+            SimEvent::LaneClosed { .. } |
+            SimEvent::LaneReopened { .. } |
+            SimEvent::TurnBayOverflowed { .. } |
+            SimEvent::TurnBayCleared { .. } |
+            SimEvent::LaneChangeViolation { .. } |
+            SimEvent::RouteReassigned { .. } |
+            SimEvent::ClearanceChecked { .. } |
+            SimEvent::ManualControl { .. } |
+            SimEvent::ManualOverride { .. } |
+            SimEvent::VehicleWrecked { .. } |
+            SimEvent::WreckCleared { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+pub use enabled::AudioPlayer;
+#[cfg(not(feature = "audio"))]
+pub use disabled::AudioPlayer;
+
+#[cfg(feature = "audio")]
+mod enabled {
+    use rand::Rng;
+    use sdl2::audio::{ AudioQueue, AudioSpecDesired };
+    use std::f32::consts::PI;
+
+    use super::Sound;
+    use crate::simulation::SimEvent;
+
+    const SAMPLE_RATE: i32 = 44100;
+    // Drop new sounds rather than let a burst of events queue up seconds of audio.
+    const MAX_QUEUED_BYTES: u32 = (SAMPLE_RATE as u32) * 4;
+
+    // Sounds are synthesized on the fly and pushed to an SDL audio queue, so no sample
+    // files need to ship with the binary.
+    pub struct AudioPlayer {
+        queue: Option<AudioQueue<f32>>,
+        muted: bool,
+    }
+
+    impl AudioPlayer {
+        pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+            let queue = sdl_context
+                .audio()
+                .and_then(|audio| {
+                    let desired = AudioSpecDesired {
+                        freq: Some(SAMPLE_RATE),
+                        channels: Some(1),
+                        samples: None,
+                    };
+                    audio.open_queue::<f32, _>(None, &desired)
+                })
+                .inspect(|queue| queue.resume());
+            match queue {
+                Ok(queue) => Self { queue: Some(queue), muted: false },
+                Err(e) => {
+                    tracing::warn!("audio disabled: {}", e);
+                    Self { queue: None, muted: true }
+                }
+            }
+        }
+
+        pub fn toggle_mute(&mut self) -> bool {
+            self.muted = !self.muted;
+            if let Some(queue) = &self.queue {
+                if self.muted {
+                    queue.clear();
+                }
+            }
+            self.muted
+        }
+
+        pub fn play(&mut self, sound: Sound) {
+            let Some(queue) = &self.queue else {
+                return;
+            };
+            if self.muted || queue.size() > MAX_QUEUED_BYTES {
+                return;
+            }
+            if let Err(e) = queue.queue_audio(&synthesize(sound, queue.spec().freq)) {
+                tracing::warn!("audio error: {}", e);
+            }
+        }
+
+        pub fn handle_events(&mut self, events: &[SimEvent]) {
+            for event in events {
+                if let Some(sound) = Sound::for_event(event) {
+                    self.play(sound);
+                }
+            }
+        }
+    }
+
+    fn synthesize(sound: Sound, sample_rate: i32) -> Vec<f32> {
+        let rate = sample_rate as f32;
+        let (seconds, volume) = match sound {
+            Sound::Click => (0.015, 0.5),
+            Sound::Horn => (0.45, 0.2),
+            Sound::Crash => (0.6, 0.4),
+        };
+        let mut rng = rand::thread_rng();
+        (0..((seconds * rate) as usize))
+            .map(|i| {
+                let t = (i as f32) / rate;
+                let sample = match sound {
+                    Sound::Click => (2.0 * PI * 2000.0 * t).sin() * (-t * 300.0).exp(),
+                    Sound::Horn => {
+                        let square = |freq: f32| (2.0 * PI * freq * t).sin().signum();
+                        let envelope = (t * 50.0).min(1.0) * ((seconds - t) * 50.0).min(1.0);
+                        (square(392.0) + square(494.0)) * 0.5 * envelope
+                    }
+                    Sound::Crash => rng.gen_range(-1.0..1.0) * (-t * 6.0).exp(),
+                };
+                sample * volume
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod disabled {
+    use super::Sound;
+    use crate::simulation::SimEvent;
+
+    pub struct AudioPlayer {
+        muted: bool,
+    }
+
+    impl AudioPlayer {
+        pub fn new(_sdl_context: &sdl2::Sdl) -> Self {
+            Self { muted: true }
+        }
+
+        pub fn toggle_mute(&mut self) -> bool {
+            self.muted
+        }
+
+        pub fn play(&mut self, _sound: Sound) {}
+
+        pub fn handle_events(&mut self, _events: &[SimEvent]) {}
+    }
+}
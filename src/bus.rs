@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use crate::units::Area;
+use crate::vehicle::{ lane_center, offset_from_center, Direction, Route };
+use crate::{
+    BIKE_LANE_WIDTH,
+    CAR_LENGTH,
+    LANES_PER_DIRECTION,
+    ROAD_WIDTH,
+    VEHICLE_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+pub const BUS_LENGTH: f32 = CAR_LENGTH * 2.0;
+pub const BUS_SPEED_FACTOR: f32 = 0.8;
+pub const BUS_DWELL_TIME: Duration = Duration::from_secs(3);
+
+// Fixed bus routes as (approach, movement). Buses run in the curb lane and serve one
+// near-side stop on their approach.
+pub const BUS_LINES: [(Direction, Route); 2] = [
+    (Direction::North, Route::Straight),
+    (Direction::East, Route::Right),
+];
+
+pub const BUS_STOP_LANE: usize = LANES_PER_DIRECTION - 1;
+
+// Coordinate along the approach where a stopped bus's center sits: the bus front ends
+// just short of the corner.
+pub fn bus_stop_along(direction: Direction) -> f32 {
+    let center_x = WORLD_WIDTH / 2.0;
+    let center_y = WORLD_HEIGHT / 2.0;
+    let setback = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 1.0 + BUS_LENGTH / 2.0;
+    match direction {
+        Direction::North => center_y + setback,
+        Direction::South => center_y - setback,
+        Direction::East => center_x - setback,
+        Direction::West => center_x + setback,
+    }
+}
+
+// The marked stopping box in the curb lane.
+pub fn bus_stop_rect(direction: Direction) -> Area {
+    let along = bus_stop_along(direction);
+    let across = lane_center(direction, BUS_STOP_LANE as f32);
+    let length = BUS_LENGTH + 1.0;
+    let width = VEHICLE_WIDTH + 0.4;
+    match direction {
+        Direction::North | Direction::South => Area::centered(across, along, width, length),
+        Direction::East | Direction::West => Area::centered(along, across, length, width),
+    }
+}
+
+// The shelter on the sidewalk next to the stop.
+pub fn bus_shelter_rect(direction: Direction) -> Area {
+    let along = bus_stop_along(direction);
+    let across = offset_from_center(direction, ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 1.2);
+    let (length, depth) = (BUS_LENGTH / 2.0, 1.0);
+    match direction {
+        Direction::North | Direction::South => Area::centered(across, along, depth, length),
+        Direction::East | Direction::West => Area::centered(along, across, length, depth),
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs::{ self, File };
+use std::io::{ self, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use std::process::{ Child, Command, Stdio };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use crate::error::SimError;
+use crate::render::Texture;
+
+pub fn save_png(path: &Path, frame: &Texture) -> Result<(), SimError> {
+    let file = File::create(path)
+        .map_err(|source| SimError::Output { path: path.to_path_buf(), source })?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), frame.width, frame.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    Ok(writer.write_image_data(&frame.pixels)?)
+}
+
+pub fn save_screenshot(frame: &Texture) -> Result<PathBuf, SimError> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = PathBuf::from(format!("screenshot_{}.png", millis));
+    save_png(&path, frame)?;
+    Ok(path)
+}
+
+// Destination for `--record`: a directory of numbered PNGs, or an ffmpeg process when the
+// target looks like a video file.
+pub enum FrameRecorder {
+    Directory {
+        dir: PathBuf,
+        frame_index: u32,
+    },
+    Ffmpeg {
+        output: PathBuf,
+        child: Option<Child>,
+        fps: u32,
+    },
+}
+
+impl FrameRecorder {
+    pub fn new(target: &str, fps: u32) -> Result<Self, SimError> {
+        let path = PathBuf::from(target);
+        let is_video = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("mp4" | "mkv" | "webm" | "mov" | "gif")
+        );
+        if is_video {
+            return Ok(FrameRecorder::Ffmpeg { output: path, child: None, fps });
+        }
+        match fs::create_dir_all(&path) {
+            Ok(()) => Ok(FrameRecorder::Directory { dir: path, frame_index: 0 }),
+            Err(source) => Err(SimError::Output { path, source }),
+        }
+    }
+
+    pub fn record(&mut self, frame: &Texture) -> Result<(), SimError> {
+        match self {
+            FrameRecorder::Directory { dir, frame_index } => {
+                let path = dir.join(format!("frame_{:06}.png", frame_index));
+                save_png(&path, frame)?;
+                *frame_index += 1;
+                Ok(())
+            }
+            FrameRecorder::Ffmpeg { output, child, fps } => {
+                if child.is_none() {
+                    *child = Some(spawn_ffmpeg(output, frame.width, frame.height, *fps)?);
+                }
+                let stdin = child
+                    .as_mut()
+                    .and_then(|c| c.stdin.as_mut())
+                    .ok_or_else(|| SimError::Ffmpeg(io::ErrorKind::BrokenPipe.into()))?;
+                stdin.write_all(&frame.pixels).map_err(SimError::Ffmpeg)
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<(), SimError> {
+        if let FrameRecorder::Ffmpeg { child: Some(child), .. } = self {
+            drop(child.stdin.take());
+            child.wait().map_err(SimError::Ffmpeg)?;
+        }
+        Ok(())
+    }
+}
+
+fn spawn_ffmpeg(output: &Path, width: u32, height: u32, fps: u32) -> Result<Child, SimError> {
+    Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", width, height), "-r", &fps.to_string(), "-i", "-"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(output)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(SimError::Ffmpeg)
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+// Simulated time covered by one physics step: vehicles move and the lights are checked this
+// often, however long the configured tick is.
+pub const TICK: Duration = Duration::from_millis(10);
+
+// Simulated time since the start of the run. It only moves when the simulation ticks, so
+// pausing, fast-forwarding and headless runs faster than real time all see the same
+// timeline as a run watched live.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimClock {
+    now: Duration,
+}
+
+impl SimClock {
+    // A clock already `now` into the run, as for showing a recorded tick.
+    pub fn at(now: Duration) -> Self {
+        Self { now }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn tick(&mut self) {
+        self.now += TICK;
+    }
+
+    // Time since the start of the run as h:mm:ss.
+    pub fn label(&self) -> String {
+        let secs = self.now.as_secs();
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
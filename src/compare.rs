@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::{ ConfigError, RenderError };
+use crate::plugin::Registry;
+use crate::render::{ font, Rect, Renderer, Viewport };
+use crate::simulation::TrafficSimulation;
+use crate::stats::Stats;
+use crate::ui::{ Mouse, Panel };
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+// Each run is drawn at half size, side by side under a header line.
+const SCENE_TOP: i32 = 30;
+const SCENE_WIDTH: u32 = WINDOW_WIDTH / 2;
+const SCENE_HEIGHT: u32 = WINDOW_HEIGHT / 2;
+
+// Two runs of the same seed and demand, one under the configured light controller and one
+// under another, stepped and drawn together so their delays can be watched side by side.
+pub struct Comparison {
+    pub runs: [TrafficSimulation; 2],
+    // The light controller each run uses, by registered name.
+    pub controllers: [String; 2],
+}
+
+impl Comparison {
+    // A seed is drawn for both runs if the config doesn't set one.
+    pub fn new(config: &Config, controller: &str) -> Result<Self, ConfigError> {
+        let mut config = config.clone();
+        config.seed = Some(config.seed.unwrap_or_else(rand::random));
+        let mut challenger = config.clone();
+        challenger.plugins.light_controller = controller.to_string();
+        let registry = Registry::default();
+        Ok(Self {
+            runs: [
+                TrafficSimulation::with_registry(&config, registry.clone())?,
+                TrafficSimulation::with_registry(&challenger, registry)?,
+            ],
+            controllers: [config.plugins.light_controller, controller.to_string()],
+        })
+    }
+
+    pub fn update(&mut self) {
+        for run in &mut self.runs {
+            run.update();
+            run.drain_events();
+        }
+    }
+
+    // Both runs start over on the shared seed.
+    pub fn reset(&mut self) {
+        for run in &mut self.runs {
+            run.reset(None);
+        }
+    }
+
+    pub fn scenario_ended(&self) -> bool {
+        self.runs.iter().all(|run| run.scenario_ended())
+    }
+
+    pub fn render(&self, renderer: &mut dyn Renderer) -> Result<(), RenderError> {
+        let palette = self.runs[0].theme().palette();
+        renderer.clear(palette.ground)?;
+        for (i, (run, controller)) in self.runs.iter().zip(&self.controllers).enumerate() {
+            let left = (i as i32) * (SCENE_WIDTH as i32);
+            let title = controller.to_uppercase();
+            renderer.draw_text(&title, left + 10, 8, palette.text)?;
+            let scene = Rect::new(left, SCENE_TOP, SCENE_WIDTH, SCENE_HEIGHT);
+            run.render(&mut Viewport::new(renderer, scene))?;
+            let top = SCENE_TOP + (SCENE_HEIGHT as i32) + 10;
+            let area = Rect::new(left + 10, top, SCENE_WIDTH - 20, 184);
+            let mut panel = Panel::begin(renderer, palette, Mouse::default(), area)?;
+            let stats = &run.stats;
+            panel.label(&format!("ELAPSED {}", run.time.label()))?;
+            panel.label(&format!("VEHICLES SERVED {}", stats.vehicles_completed))?;
+            let delay = stats.average_vehicle_delay().as_secs_f32();
+            panel.label(&format!("AVERAGE DELAY {:.1}S", delay))?;
+            panel.label(&format!("CO2 PER VEHICLE {:.0}G", stats.co2_per_vehicle() * 1000.0))?;
+            panel.label(&format!("QUEUED NOW {}", queued(run)))?;
+            panel.label(&format!("MAX QUEUE {}", stats.max_queue))?;
+            panel.label(&format!("COLLISIONS {}", stats.collisions))?;
+        }
+        let verdict = verdict(&self.runs[0].stats, &self.runs[1].stats, &self.controllers);
+        let x = (WINDOW_WIDTH as i32) / 2 - font::GLYPH_ADVANCE * (verdict.len() as i32) / 2;
+        renderer.draw_text(&verdict, x.max(10), (WINDOW_HEIGHT as i32) - 40, palette.text)
+    }
+}
+
+fn queued(run: &TrafficSimulation) -> usize {
+    run.lanes.iter().map(|lane| run.queue_length(lane.direction)).sum()
+}
+
+// Which controller has the lower average delay so far, and by how much.
+fn verdict(first: &Stats, second: &Stats, controllers: &[String; 2]) -> String {
+    if first.vehicles_completed == 0 || second.vehicles_completed == 0 {
+        return "WAITING FOR VEHICLES TO FINISH".to_string();
+    }
+    let delays = [first.average_vehicle_delay(), second.average_vehicle_delay()];
+    let (better, worse) = if delays[1] < delays[0] { (1, 0) } else { (0, 1) };
+    let saved = delays[worse].saturating_sub(delays[better]);
+    if saved < Duration::from_millis(50) {
+        return "SAME AVERAGE DELAY".to_string();
+    }
+    let share = saved.as_secs_f32() / delays[worse].as_secs_f32() * 100.0;
+    format!(
+        "{} DELAYS {:.1}S ({:.0}%) LESS",
+        controllers[better].to_uppercase(),
+        saved.as_secs_f32(),
+        share
+    )
+}
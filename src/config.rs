@@ -0,0 +1,859 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+use toml_edit::{ table, value, DocumentMut };
+
+use crate::clock::TICK;
+use crate::error::ConfigError;
+use crate::keymap::Keymap;
+use crate::lane::MAX_PLATOON_SIZE;
+use crate::map::MapLayout;
+use crate::median::{ MAX_TURN_BAY_LENGTH, MIN_TURN_BAY_LENGTH };
+use crate::no_change_zone::MAX_NO_CHANGE_ZONE;
+use crate::plugin::PluginConfig;
+use crate::scenario::Scenario;
+use crate::script::Script;
+use crate::sink::{ Sinks, MAX_SINK_INSET };
+use crate::theme::Theme;
+use crate::traffic_light::{
+    LeftTurnSequences,
+    LeftTurns,
+    Phase,
+    TrafficLight,
+    ALL_RED_TIME,
+    CLEARANCE_TIME,
+    GREEN_TIME,
+    LEFT_ARROW_TIME,
+    MAX_RED_TIME,
+    MIN_GREEN_TIME,
+    WALK_TIME,
+    YELLOW_TIME,
+};
+use crate::vehicle::Direction;
+use crate::weather::Weather;
+use crate::units::{ kmh_to_mps, DEFAULT_METERS_PER_PIXEL };
+use crate::{ LANE_CHANGE_LENGTH, VEHICLE_SPEED };
+
+// Read from the working directory when no `--config` path is given, if it exists.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const MAX_ENTRY_SETBACK: f32 = 25.0;
+const MAX_TARGET_FPS: u32 = 240;
+// Longest a driver takes to react, in seconds.
+const MAX_REACTION_SECS: f32 = 5.0;
+// From zoomed in on the box to the world a few times over.
+const MIN_METERS_PER_PIXEL: f32 = 0.02;
+const MAX_METERS_PER_PIXEL: f32 = 1.0;
+// Longest tick, in ms; a second between updates is as coarse as traces are any use at.
+const MAX_TICK_MS: u32 = 1000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Seed for the simulation's random choices; a fresh one is drawn each run if unset.
+    pub seed: Option<u64>,
+    pub theme: Theme,
+    pub display: DisplayConfig,
+    pub clock: ClockConfig,
+    pub attract: AttractConfig,
+    pub speed_limits: SpeedLimits,
+    pub weather: WeatherConfig,
+    pub day_night: DayNightConfig,
+    pub demand: DemandConfig,
+    pub keymap: Keymap,
+    pub sinks: Sinks,
+    pub gridlock: GridlockConfig,
+    pub lights: LightsConfig,
+    pub flashing: FlashingConfig,
+    pub travel_times: TravelTimeConfig,
+    pub turning_counts: TurningCountConfig,
+    pub rail: RailConfig,
+    pub incidents: IncidentConfig,
+    pub median: MedianConfig,
+    pub lane_changes: LaneChangeConfig,
+    pub platoons: PlatoonConfig,
+    pub route_choice: RouteChoiceConfig,
+    pub reactions: ReactionConfig,
+    pub jaywalking: JaywalkingConfig,
+    pub plugins: PluginConfig,
+    // Set by `--strict`: every tick checks that no vehicle jumped, as debug builds always do,
+    // and the run stops at the first that does instead of only logging it.
+    #[serde(skip)]
+    pub strict: bool,
+    // Loaded from their own files rather than the config's.
+    #[serde(skip)]
+    pub scenario: Scenario,
+    #[serde(skip)]
+    pub map: MapLayout,
+    #[serde(skip)]
+    pub script: Option<Script>,
+}
+
+// Posted speed per road, in km/h.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedLimits {
+    pub north_south_kmh: f32,
+    pub east_west_kmh: f32,
+}
+
+impl Default for SpeedLimits {
+    fn default() -> Self {
+        Self { north_south_kmh: VEHICLE_SPEED, east_west_kmh: VEHICLE_SPEED }
+    }
+}
+
+impl SpeedLimits {
+    // In m/s, as vehicles drive.
+    pub fn for_direction(&self, direction: Direction) -> f32 {
+        kmh_to_mps(match direction {
+            Direction::North | Direction::South => self.north_south_kmh,
+            Direction::East | Direction::West => self.east_west_kmh,
+        })
+    }
+}
+
+// Starting weather, plus changes applied at set times into the run.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub condition: Weather,
+    pub schedule: Vec<WeatherChange>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeatherChange {
+    pub after_secs: f32,
+    pub condition: Weather,
+}
+
+// How the window paces its frames: in step with the display's refresh where the renderer
+// can wait for it, and otherwise at `target_fps`. The world is drawn `meters_per_pixel`
+// to the pixel around the intersection.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub vsync: bool,
+    pub target_fps: u32,
+    pub meters_per_pixel: f32,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self { vsync: true, target_fps: 60, meters_per_pixel: DEFAULT_METERS_PER_PIXEL }
+    }
+}
+
+// Simulated time each update covers, in ms. It is stepped through in TICK physics steps,
+// so it must be a whole number of them.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockConfig {
+    pub tick_ms: u32,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self { tick_ms: TICK.as_millis() as u32 }
+    }
+}
+
+impl ClockConfig {
+    pub fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms as u64)
+    }
+
+    // Physics steps in each tick.
+    pub fn substeps(&self) -> u32 {
+        ((self.tick().as_nanos() / TICK.as_nanos()) as u32).max(1)
+    }
+}
+
+// The window runs a demo after `idle_secs` of real time without input; 0 never does.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttractConfig {
+    pub idle_secs: f32,
+}
+
+// Length of a simulated day in seconds of simulated time, and the hour the run starts at.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DayNightConfig {
+    pub day_length_secs: f32,
+    pub start_hour: f32,
+}
+
+impl Default for DayNightConfig {
+    fn default() -> Self {
+        Self { day_length_secs: 240.0, start_hour: 8.0 }
+    }
+}
+
+// Automatic arrivals in vehicles per minute over all approaches, optionally varying with the
+// time of day, plus steady arrivals on each approach. Zero leaves spawning to the keyboard.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DemandConfig {
+    pub vehicles_per_minute: f32,
+    pub schedule: Vec<DemandPeriod>,
+    pub approaches: ApproachDemand,
+    pub target: TargetDemand,
+}
+
+// What the "target-density" spawn policy holds the network at, in place of the rates above:
+// a total vehicle count, or failing that a density per km of lane. Each approach gets its
+// share by lane length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetDemand {
+    pub vehicles: u32,
+    pub vehicles_per_km: f32,
+}
+
+// Vehicles per minute arriving on each approach, by direction of travel, on top of those
+// spread over all four.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApproachDemand {
+    pub north: f32,
+    pub south: f32,
+    pub east: f32,
+    pub west: f32,
+}
+
+// Demand between two hours of the simulated day; the period wraps past midnight when
+// `to_hour` is earlier than `from_hour`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DemandPeriod {
+    pub from_hour: f32,
+    pub to_hour: f32,
+    pub vehicles_per_minute: f32,
+}
+
+// Travel times are measured per movement from an entry line `entry_setback` before the stop
+// line to an exit line `exit_distance` past the far side of the intersection.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TravelTimeConfig {
+    pub entry_setback: f32,
+    pub exit_distance: f32,
+}
+
+impl Default for TravelTimeConfig {
+    fn default() -> Self {
+        Self { entry_setback: 15.0, exit_distance: 10.0 }
+    }
+}
+
+// Turning movement counts are totalled over intervals of this much simulated time.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TurningCountConfig {
+    pub interval_secs: f32,
+}
+
+impl Default for TurningCountConfig {
+    fn default() -> Self {
+        Self { interval_secs: 900.0 }
+    }
+}
+
+// Signal timing. `green_secs` is the average green per road, divided between the two so the
+// north-south road gets `north_south_split` of the total. Each road's yellow is followed by
+// `all_red_secs` with both roads at red, zero for none. A controller that ends greens early
+// gives each at least `min_green_secs`. Starvation watchdog: a road with traffic waiting
+// gets its green once it has been red for `max_red_secs`, however long the other road's
+// green was meant to last. A pedestrian call adds an all-red walk phase of
+// `walk_secs`, then `clearance_secs` of flashing don't-walk for those still crossing.
+// Approaches whose `left_turns` are protected-permissive get a left arrow for
+// `left_arrow_secs`, leading or lagging their road's green by `left_turn_sequence`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightsConfig {
+    pub green_secs: f32,
+    pub yellow_secs: f32,
+    pub all_red_secs: f32,
+    pub north_south_split: f32,
+    pub min_green_secs: f32,
+    pub max_red_secs: f32,
+    pub walk_secs: f32,
+    pub clearance_secs: f32,
+    pub left_arrow_secs: f32,
+    pub left_turns: LeftTurns,
+    pub left_turn_sequence: LeftTurnSequences,
+}
+
+impl Default for LightsConfig {
+    fn default() -> Self {
+        Self {
+            green_secs: GREEN_TIME.as_secs_f32(),
+            yellow_secs: YELLOW_TIME.as_secs_f32(),
+            all_red_secs: ALL_RED_TIME.as_secs_f32(),
+            north_south_split: 0.5,
+            min_green_secs: MIN_GREEN_TIME.as_secs_f32(),
+            max_red_secs: MAX_RED_TIME.as_secs_f32(),
+            walk_secs: WALK_TIME.as_secs_f32(),
+            clearance_secs: CLEARANCE_TIME.as_secs_f32(),
+            left_arrow_secs: LEFT_ARROW_TIME.as_secs_f32(),
+            left_turns: LeftTurns::default(),
+            left_turn_sequence: LeftTurnSequences::default(),
+        }
+    }
+}
+
+// Late-night flashing operation between two hours of the simulated day, wrapping past
+// midnight like a demand period: `main_road` flashes yellow and the other road red.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlashingConfig {
+    pub enabled: bool,
+    pub from_hour: f32,
+    pub to_hour: f32,
+    pub main_road: Phase,
+}
+
+impl Default for FlashingConfig {
+    fn default() -> Self {
+        Self { enabled: false, from_hour: 23.0, to_hour: 5.0, main_road: Phase::NorthSouth }
+    }
+}
+
+impl FlashingConfig {
+    // The road flashing yellow at `hour`, if the lights are flashing then.
+    pub fn main_road_at(&self, hour: f32) -> Option<Phase> {
+        let active = self.enabled && in_period(hour, self.from_hour, self.to_hour);
+        active.then_some(self.main_road)
+    }
+}
+
+impl LightsConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let times = [
+            self.green_secs,
+            self.yellow_secs,
+            self.min_green_secs,
+            self.max_red_secs,
+            self.walk_secs,
+            self.clearance_secs,
+            self.left_arrow_secs,
+        ];
+        if times.iter().any(|&secs| secs <= 0.0) {
+            return Err(ConfigError::Invalid("light times must be positive".to_string()));
+        }
+        if self.all_red_secs < 0.0 {
+            return Err(ConfigError::Invalid("all-red time must not be negative".to_string()));
+        }
+        if self.north_south_split <= 0.0 || self.north_south_split >= 1.0 {
+            return Err(ConfigError::Invalid(
+                "north-south split must be between 0 and 1".to_string()
+            ));
+        }
+        Ok(())
+    }
+
+    // Sets the green time for a full cycle of both roads' green, yellow and all-red.
+    pub fn set_cycle(&mut self, cycle_secs: f32) {
+        self.green_secs = cycle_secs / 2.0 - self.yellow_secs - self.all_red_secs;
+    }
+
+    // The timing `light` runs on now, which the control panel and the timing editor change.
+    pub fn from_light(light: &TrafficLight) -> Self {
+        Self {
+            green_secs: light.green_time.as_secs_f32(),
+            yellow_secs: light.yellow_time.as_secs_f32(),
+            all_red_secs: light.all_red_time.as_secs_f32(),
+            north_south_split: light.north_south_split,
+            min_green_secs: light.min_green_time.as_secs_f32(),
+            max_red_secs: light.max_red_time.as_secs_f32(),
+            walk_secs: light.walk_time.as_secs_f32(),
+            clearance_secs: light.clearance_time.as_secs_f32(),
+            left_arrow_secs: light.left_arrow_time.as_secs_f32(),
+            left_turns: light.left_turns,
+            left_turn_sequence: light.left_turn_sequences,
+        }
+    }
+
+    pub fn apply_to(&self, light: &mut TrafficLight) {
+        light.green_time = Duration::from_secs_f32(self.green_secs);
+        light.yellow_time = Duration::from_secs_f32(self.yellow_secs);
+        light.all_red_time = Duration::from_secs_f32(self.all_red_secs);
+        light.north_south_split = self.north_south_split;
+        light.min_green_time = Duration::from_secs_f32(self.min_green_secs);
+        light.max_red_time = Duration::from_secs_f32(self.max_red_secs);
+        light.walk_time = Duration::from_secs_f32(self.walk_secs);
+        light.clearance_time = Duration::from_secs_f32(self.clearance_secs);
+        light.left_arrow_time = Duration::from_secs_f32(self.left_arrow_secs);
+        light.left_turns = self.left_turns;
+        light.left_turn_sequences = self.left_turn_sequence;
+    }
+
+    // The full green `phase` gets each cycle.
+    pub fn green_for(&self, phase: Phase) -> f32 {
+        match phase {
+            Phase::NorthSouth => self.green_secs * 2.0 * self.north_south_split,
+            Phase::EastWest => self.green_secs * 2.0 * (1.0 - self.north_south_split),
+        }
+    }
+
+    // Gives `phase` a green of `secs`, keeping the other road's.
+    pub fn set_green_for(&mut self, phase: Phase, secs: f32) {
+        let other = self.green_for(phase.next());
+        let total = secs + other;
+        self.green_secs = total / 2.0;
+        self.north_south_split = match phase {
+            Phase::NorthSouth => secs / total,
+            Phase::EastWest => other / total,
+        };
+    }
+
+    // Both roads' green, yellow and all-red.
+    pub fn cycle_secs(&self) -> f32 {
+        2.0 * (self.green_secs + self.yellow_secs + self.all_red_secs)
+    }
+
+    // Writes the signal timing into the config file at `path`, creating it if needed, and
+    // keeping everything else in the file like `DemandConfig::save`.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        edit_file(path, |document| {
+            let lights = document["lights"].or_insert(table());
+            let times = [
+                ("green_secs", self.green_secs),
+                ("yellow_secs", self.yellow_secs),
+                ("all_red_secs", self.all_red_secs),
+                ("north_south_split", self.north_south_split),
+                ("min_green_secs", self.min_green_secs),
+                ("max_red_secs", self.max_red_secs),
+                ("walk_secs", self.walk_secs),
+                ("clearance_secs", self.clearance_secs),
+                ("left_arrow_secs", self.left_arrow_secs),
+            ];
+            for (key, secs) in times {
+                // Rounded so the file doesn't fill up with float noise.
+                lights[key] = value((((secs as f64) * 1000.0).round()) / 1000.0);
+            }
+        })
+    }
+}
+
+// Trains over the level crossing on the east arm. One arrives on average every
+// `mean_interval_secs` (zero leaves them to the keyboard), with the gates coming down
+// `warning_secs` before it reaches the road and going back up over `raise_secs` after its
+// tail has left it. Trains are `train_length` m long and run at `train_speed_kmh`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RailConfig {
+    pub mean_interval_secs: f32,
+    pub warning_secs: f32,
+    pub raise_secs: f32,
+    pub train_length: f32,
+    pub train_speed_kmh: f32,
+}
+
+impl Default for RailConfig {
+    fn default() -> Self {
+        Self {
+            mean_interval_secs: 0.0,
+            warning_secs: 6.0,
+            raise_secs: 3.0,
+            train_length: 70.0,
+            train_speed_kmh: 54.0,
+        }
+    }
+}
+
+// Vehicles in a collision or a scripted incident are left where they stopped, blocking
+// traffic, for `clearance_secs` before they are towed away. Zero turns incidents off:
+// colliding vehicles drive on and scripted ones do nothing.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IncidentConfig {
+    pub clearance_secs: f32,
+}
+
+impl Default for IncidentConfig {
+    fn default() -> Self {
+        Self { clearance_secs: 15.0 }
+    }
+}
+
+// Divides the roads with a median over the inner lane of each approach, which opens into a
+// left-turn bay `turn_bay_length` m long before the stop line. Zero leaves the roads
+// undivided, with two lanes for any movement.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MedianConfig {
+    pub turn_bay_length: f32,
+}
+
+impl MedianConfig {
+    pub fn turn_bay(&self) -> Option<f32> {
+        (self.turn_bay_length > 0.0).then_some(self.turn_bay_length)
+    }
+}
+
+// Lane changes are forbidden over the last `no_change_zone` m before each stop line,
+// where the lane lines are drawn solid. Zero allows them up to the intersection.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaneChangeConfig {
+    pub no_change_zone: f32,
+}
+
+impl LaneChangeConfig {
+    pub fn no_change_zone(&self) -> Option<f32> {
+        (self.no_change_zone > 0.0).then_some(self.no_change_zone)
+    }
+}
+
+// The platoon key injects `size` cars nose to tail on the approach picked next.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlatoonConfig {
+    pub size: u32,
+}
+
+impl Default for PlatoonConfig {
+    fn default() -> Self {
+        Self { size: 6 }
+    }
+}
+
+// A random arrival whose exit road already holds `saturated_at` vehicles re-picks its route
+// among those with room, with probability `reroute_chance`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteChoiceConfig {
+    pub reroute_chance: f64,
+    pub saturated_at: usize,
+}
+
+impl Default for RouteChoiceConfig {
+    fn default() -> Self {
+        Self { reroute_chance: 0.0, saturated_at: 8 }
+    }
+}
+
+// Each driver takes a reaction time drawn evenly from `min_secs` to `max_secs` to move off
+// once the light turns green or the vehicle ahead pulls away, so a queue starts up one
+// vehicle after another.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReactionConfig {
+    pub min_secs: f32,
+    pub max_secs: f32,
+}
+
+impl Default for ReactionConfig {
+    fn default() -> Self {
+        Self { min_secs: 1.0, max_secs: 2.0 }
+    }
+}
+
+impl ReactionConfig {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let secs = if self.max_secs > self.min_secs {
+            rng.gen_range(self.min_secs..self.max_secs)
+        } else {
+            self.min_secs
+        };
+        Duration::from_secs_f32(secs)
+    }
+}
+
+// Pedestrians who don't wait for the walk: each one pressing a button sets straight off
+// against the signal with probability `against_signal_chance`, and `mid_block_per_minute`
+// more cross the roads between the crosswalks. Zero for both turns jaywalking off.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JaywalkingConfig {
+    pub against_signal_chance: f64,
+    pub mid_block_per_minute: f32,
+}
+
+// The run counts as gridlocked once no vehicle has moved for `timeout_secs` while one is
+// inside the intersection.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridlockConfig {
+    pub timeout_secs: f32,
+}
+
+impl Default for GridlockConfig {
+    fn default() -> Self {
+        Self { timeout_secs: 10.0 }
+    }
+}
+
+impl DemandConfig {
+    // The first scheduled period covering `hour` wins over the base rate.
+    pub fn rate_at(&self, hour: f32) -> f32 {
+        self.schedule
+            .iter()
+            .find(|period| in_period(hour, period.from_hour, period.to_hour))
+            .map_or(self.vehicles_per_minute, |period| period.vehicles_per_minute)
+    }
+
+    // Writes the base rate and the per-approach rates into the config file at `path`,
+    // creating it if needed. Everything else in the file, comments included, is kept.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        edit_file(path, |document| {
+            let demand = document["demand"].or_insert(table());
+            demand["vehicles_per_minute"] = value(self.vehicles_per_minute as f64);
+            let approaches = demand["approaches"].or_insert(table());
+            let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+            for direction in directions {
+                let name = format!("{:?}", direction).to_lowercase();
+                approaches[name.as_str()] = value(self.approaches.rate(direction) as f64);
+            }
+        })
+    }
+}
+
+// Applies `edit` to the config file at `path`, creating it if needed.
+fn edit_file(path: &Path, edit: impl FnOnce(&mut DocumentMut)) -> Result<(), ConfigError> {
+    let write = || -> Result<(), ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        let mut document = text.parse::<DocumentMut>()?;
+        edit(&mut document);
+        Ok(fs::write(path, document.to_string())?)
+    };
+    write().map_err(|e| e.in_file(path))
+}
+
+impl ApproachDemand {
+    pub fn rate(&self, direction: Direction) -> f32 {
+        match direction {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East => self.east,
+            Direction::West => self.west,
+        }
+    }
+
+    pub fn rate_mut(&mut self, direction: Direction) -> &mut f32 {
+        match direction {
+            Direction::North => &mut self.north,
+            Direction::South => &mut self.south,
+            Direction::East => &mut self.east,
+            Direction::West => &mut self.west,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::read(path).map_err(|e| e.in_file(path))
+    }
+
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // An explicit path must exist; otherwise the default file is used if present.
+    pub fn load_or_default(path: Option<&str>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Config::load(Path::new(path)),
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Config::load(Path::new(DEFAULT_CONFIG_PATH))
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !(1..=MAX_TARGET_FPS).contains(&self.display.target_fps) {
+            return Err(ConfigError::Invalid(format!(
+                "target fps must be between 1 and {}",
+                MAX_TARGET_FPS
+            )));
+        }
+        let scale = self.display.meters_per_pixel;
+        if !(MIN_METERS_PER_PIXEL..=MAX_METERS_PER_PIXEL).contains(&scale) {
+            return Err(ConfigError::Invalid(format!(
+                "display scale must be between {} and {} meters per pixel",
+                MIN_METERS_PER_PIXEL,
+                MAX_METERS_PER_PIXEL
+            )));
+        }
+        let tick_ms = self.clock.tick_ms;
+        let step_ms = TICK.as_millis() as u32;
+        if !(step_ms..=MAX_TICK_MS).contains(&tick_ms) || !tick_ms.is_multiple_of(step_ms) {
+            return Err(ConfigError::Invalid(format!(
+                "tick must be a multiple of {} ms up to {} ms",
+                step_ms,
+                MAX_TICK_MS
+            )));
+        }
+        if self.attract.idle_secs < 0.0 {
+            return Err(ConfigError::Invalid("attract idle time must not be negative".to_string()));
+        }
+        let limits = self.speed_limits;
+        if limits.north_south_kmh <= 0.0 || limits.east_west_kmh <= 0.0 {
+            return Err(ConfigError::Invalid("speed limits must be positive".to_string()));
+        }
+        if self.weather.schedule.iter().any(|change| change.after_secs < 0.0) {
+            return Err(ConfigError::Invalid(
+                "weather schedule times must not be negative".to_string()
+            ));
+        }
+        let day_night = self.day_night;
+        if day_night.day_length_secs <= 0.0 {
+            return Err(ConfigError::Invalid("day length must be positive".to_string()));
+        }
+        let valid_hour = |hour: f32| (0.0..24.0).contains(&hour);
+        if !valid_hour(day_night.start_hour) {
+            return Err(ConfigError::Invalid("start hour must be between 0 and 24".to_string()));
+        }
+        let demand = &self.demand;
+        let approaches = demand.approaches;
+        let rates = [approaches.north, approaches.south, approaches.east, approaches.west];
+        if demand.vehicles_per_minute < 0.0 || rates.iter().any(|&rate| rate < 0.0) {
+            return Err(ConfigError::Invalid("demand must not be negative".to_string()));
+        }
+        for period in &demand.schedule {
+            if !valid_hour(period.from_hour) || !valid_hour(period.to_hour) {
+                return Err(ConfigError::Invalid(
+                    "demand schedule hours must be between 0 and 24".to_string()
+                ));
+            }
+            if period.vehicles_per_minute < 0.0 {
+                return Err(ConfigError::Invalid("demand must not be negative".to_string()));
+            }
+        }
+        if demand.target.vehicles_per_km < 0.0 {
+            return Err(ConfigError::Invalid("target density must not be negative".to_string()));
+        }
+        let target_set = demand.target.vehicles > 0 || demand.target.vehicles_per_km > 0.0;
+        if self.plugins.spawn_policy == "target-density" && !target_set {
+            return Err(ConfigError::Invalid(
+                "the target-density spawn policy needs a target vehicle count or density"
+                    .to_string()
+            ));
+        }
+        if !valid_hour(self.flashing.from_hour) || !valid_hour(self.flashing.to_hour) {
+            return Err(ConfigError::Invalid(
+                "flashing hours must be between 0 and 24".to_string()
+            ));
+        }
+        let travel_times = self.travel_times;
+        if travel_times.entry_setback <= 0.0 || travel_times.exit_distance <= 0.0 {
+            return Err(ConfigError::Invalid(
+                "travel time lines must be beyond the intersection".to_string()
+            ));
+        }
+        // The north-south approaches leave about 27.5 m between spawn point and stop line.
+        if travel_times.entry_setback > MAX_ENTRY_SETBACK {
+            return Err(ConfigError::Invalid(format!(
+                "entry line must be at most {} m out",
+                MAX_ENTRY_SETBACK
+            )));
+        }
+        if self.turning_counts.interval_secs <= 0.0 {
+            return Err(ConfigError::Invalid("turning count interval must be positive".to_string()));
+        }
+        self.lights.validate()?;
+        let rail = self.rail;
+        if rail.mean_interval_secs < 0.0 {
+            return Err(ConfigError::Invalid("train interval must not be negative".to_string()));
+        }
+        let train = [rail.warning_secs, rail.raise_secs, rail.train_length, rail.train_speed_kmh];
+        if train.iter().any(|&value| value <= 0.0) {
+            return Err(ConfigError::Invalid(
+                "train timing, length and speed must be positive".to_string()
+            ));
+        }
+        if self.incidents.clearance_secs < 0.0 {
+            return Err(ConfigError::Invalid(
+                "incident clearance time must not be negative".to_string()
+            ));
+        }
+        let bay = self.median.turn_bay_length;
+        if bay != 0.0 && !(MIN_TURN_BAY_LENGTH..=MAX_TURN_BAY_LENGTH).contains(&bay) {
+            return Err(ConfigError::Invalid(format!(
+                "turn bays must be 0 or between {} and {} m long",
+                MIN_TURN_BAY_LENGTH,
+                MAX_TURN_BAY_LENGTH
+            )));
+        }
+        let zone = self.lane_changes.no_change_zone;
+        if !(0.0..=MAX_NO_CHANGE_ZONE).contains(&zone) {
+            return Err(ConfigError::Invalid(format!(
+                "no-change zones must be between 0 and {} m",
+                MAX_NO_CHANGE_ZONE
+            )));
+        }
+        // Left-turners have to get into the bay before the solid lines start.
+        if bay != 0.0 && zone + LANE_CHANGE_LENGTH > bay {
+            return Err(ConfigError::Invalid(format!(
+                "no-change zones must leave {} m of the turn bay to move into it",
+                LANE_CHANGE_LENGTH
+            )));
+        }
+        if !(1..=MAX_PLATOON_SIZE).contains(&self.platoons.size) {
+            return Err(ConfigError::Invalid(format!(
+                "platoons must be between 1 and {} cars",
+                MAX_PLATOON_SIZE
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.route_choice.reroute_chance) {
+            return Err(ConfigError::Invalid("reroute chance must be between 0 and 1".to_string()));
+        }
+        if self.route_choice.saturated_at == 0 {
+            let message = "exits must hold at least one vehicle to be saturated".to_string();
+            return Err(ConfigError::Invalid(message));
+        }
+        if !(0.0..=1.0).contains(&self.jaywalking.against_signal_chance) {
+            return Err(ConfigError::Invalid(
+                "jaywalking chance must be between 0 and 1".to_string()
+            ));
+        }
+        if self.jaywalking.mid_block_per_minute < 0.0 {
+            return Err(ConfigError::Invalid(
+                "mid-block jaywalkers per minute must not be negative".to_string()
+            ));
+        }
+        let reactions = self.reactions;
+        let ordered = 0.0 <= reactions.min_secs && reactions.min_secs <= reactions.max_secs;
+        if !ordered || reactions.max_secs > MAX_REACTION_SECS {
+            return Err(ConfigError::Invalid(format!(
+                "reaction times must run from a minimum to a maximum between 0 and {} s",
+                MAX_REACTION_SECS
+            )));
+        }
+        if self.gridlock.timeout_secs <= 0.0 {
+            return Err(ConfigError::Invalid("gridlock timeout must be positive".to_string()));
+        }
+        let sinks = self.sinks;
+        if [sinks.north, sinks.south, sinks.east, sinks.west].iter().any(|&o| o < -MAX_SINK_INSET) {
+            return Err(ConfigError::Invalid(format!(
+                "sinks must be at most {} m inside the world",
+                MAX_SINK_INSET
+            )));
+        }
+        self.keymap.validate()?;
+        Ok(())
+    }
+}
+
+// Whether `hour` falls from `from_hour` up to `to_hour`, past midnight if `to_hour` is the
+// earlier.
+fn in_period(hour: f32, from_hour: f32, to_hour: f32) -> bool {
+    if from_hour <= to_hour {
+        hour >= from_hour && hour < to_hour
+    } else {
+        hour >= from_hour || hour < to_hour
+    }
+}
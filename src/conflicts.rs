@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use crate::config::LightsConfig;
+use crate::error::ConfigError;
+use crate::map::MapLayout;
+use crate::stats::movements;
+use crate::traffic_light::{ LightState, SignalHead, TrafficLight };
+use crate::vehicle::{ opposite, turned_direction, Direction, Route };
+
+// The signal plan is checked at this resolution, over this many of each road's greens.
+const STEP: Duration = Duration::from_millis(100);
+const CYCLES: usize = 2;
+
+// Which movements through the intersection, as (approach, route), cross or merge and so
+// must not both have the right of way. Movements from the same approach never conflict,
+// nor do opposing straights and rights, opposing lefts, which pass in front of each other,
+// or rights turning away from the crossing road's traffic. Every other pair does.
+pub struct ConflictMatrix {
+    movements: Vec<(Direction, Route)>,
+    conflicts: Vec<Vec<bool>>,
+}
+
+impl ConflictMatrix {
+    // Over the movements `map` serves.
+    pub fn new(map: &MapLayout) -> Self {
+        let movements: Vec<_> = movements().filter(|&(d, route)| map.serves(d, route)).collect();
+        let conflicts = movements
+            .iter()
+            .map(|&a| movements.iter().map(|&b| crosses(a, b)).collect())
+            .collect();
+        Self { movements, conflicts }
+    }
+
+    pub fn conflicts(&self, a: (Direction, Route), b: (Direction, Route)) -> bool {
+        let index = |movement| self.movements.iter().position(|&m| m == movement);
+        match (index(a), index(b)) {
+            (Some(a), Some(b)) => self.conflicts[a][b],
+            _ => false,
+        }
+    }
+
+    // The first two conflicting movements the heads `head_for` gives show both to have the
+    // right of way, leaving out lefts without an arrow, which yield to oncoming traffic.
+    pub fn conflict_in(
+        &self,
+        head_for: impl Fn(Direction) -> SignalHead
+    ) -> Option<((Direction, Route), (Direction, Route))> {
+        let heads: Vec<SignalHead> = self.movements.iter().map(|&(d, _)| head_for(d)).collect();
+        let going = |a: usize| has_right_of_way(heads[a].for_route(self.movements[a].1));
+        let yields = |a: usize, b: usize| {
+            let (approach, route) = self.movements[a];
+            route == Route::Left &&
+                !heads[a].protects(route) &&
+                self.movements[b].0 == opposite(approach)
+        };
+        for a in 0..self.movements.len() {
+            for b in (a + 1)..self.movements.len() {
+                let both = going(a) && going(b);
+                if both && self.conflicts[a][b] && !yields(a, b) && !yields(b, a) {
+                    return Some((self.movements[a], self.movements[b]));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Runs the lights `lights` sets up over a couple of cycles, refusing them if they ever give
+// two conflicting movements the right of way together.
+pub fn check_signal_plan(lights: &LightsConfig, map: &MapLayout) -> Result<(), ConfigError> {
+    let matrix = ConflictMatrix::new(map);
+    let mut light = TrafficLight::new();
+    lights.apply_to(&mut light);
+    let (mut now, mut phase, mut starts) = (Duration::ZERO, light.phase, 0);
+    while starts < CYCLES * 2 {
+        light.update(now);
+        if let Some((a, b)) = matrix.conflict_in(|d| light.head_for(d)) {
+            return Err(ConfigError::Invalid(format!(
+                "the signal plan gives {} and {} the right of way together, but they cross",
+                movement_name(a),
+                movement_name(b)
+            )));
+        }
+        starts += (light.phase != phase) as usize;
+        phase = light.phase;
+        now += STEP;
+    }
+    Ok(())
+}
+
+fn crosses((a, a_route): (Direction, Route), (b, b_route): (Direction, Route)) -> bool {
+    if a == b {
+        false
+    } else if turned_direction(a, a_route) == turned_direction(b, b_route) {
+        true
+    } else if b == opposite(a) {
+        (a_route == Route::Left) != (b_route == Route::Left)
+    } else {
+        a_route != Route::Right && b_route != Route::Right
+    }
+}
+
+// Green, or yellow while it clears.
+fn has_right_of_way(state: LightState) -> bool {
+    matches!(state, LightState::Green | LightState::Yellow | LightState::FlashingYellow)
+}
+
+// Such as "northbound left".
+fn movement_name((direction, route): (Direction, Route)) -> String {
+    format!("{:?}bound {:?}", direction, route).to_lowercase()
+}
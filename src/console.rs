@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+
+use crate::error::RenderError;
+use crate::render::{ font, Rect, Renderer };
+use crate::simulation::TrafficSimulation;
+use crate::theme::Palette;
+use crate::traffic_light::Phase;
+use crate::units::mps_to_kmh;
+use crate::vehicle::{ opposite, turned_direction, Direction, Route };
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+// Fastest simulation speed `set speed` allows, in times real time.
+pub const MAX_SPEED: u32 = 8;
+// Lines of past commands and replies kept on screen above the input line.
+const HISTORY_LINES: usize = 12;
+// Vehicles listed one by one by `dump lane`; the rest are only counted.
+const DUMPED_VEHICLES: usize = 8;
+const LINE_HEIGHT: i32 = 18;
+const PADDING: i32 = 8;
+
+const HELP: [&str; 6] = [
+    "spawn <approach> [straight|left|right]",
+    "set-phase <1|2|north-south|east-west>",
+    "dump lane <approach>",
+    "set speed <1-8>",
+    "clear",
+    "approaches are the way traffic travels: north, south, east or west",
+];
+
+// A line of typed debugging commands run against the simulation, with the commands and
+// their replies scrolling above it. Open, it takes all keyboard input.
+#[derive(Debug, Clone, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    // Oldest first.
+    history: VecDeque<String>,
+}
+
+impl Console {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // Adds typed text to the input line, leaving out the console key's own character.
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.chars().filter(|&c| c != '`' && !c.is_control()));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    // Runs the input line. `speed` is the front end's simulation speed, which `set speed`
+    // changes.
+    pub fn submit(&mut self, simulation: &mut TrafficSimulation, speed: &mut u32) {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if line == "clear" {
+            self.history.clear();
+            return;
+        }
+        self.push(format!("> {}", line));
+        let replies = run(line, simulation, speed).unwrap_or_else(|e| vec![e]);
+        for reply in replies {
+            self.push(reply);
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        self.history.push_back(line);
+        while self.history.len() > HISTORY_LINES {
+            self.history.pop_front();
+        }
+    }
+
+    // Along the bottom of the window, over everything else.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        palette: &Palette
+    ) -> Result<(), RenderError> {
+        let height = ((HISTORY_LINES as i32) + 1) * LINE_HEIGHT + 2 * PADDING;
+        let top = (WINDOW_HEIGHT as i32) - height - 10;
+        let area = Rect::new(10, top, WINDOW_WIDTH - 20, height as u32);
+        renderer.draw_rect(area, palette.panel)?;
+        let x = area.x + PADDING;
+        let mut y = top + PADDING + ((HISTORY_LINES - self.history.len()) as i32) * LINE_HEIGHT;
+        for line in &self.history {
+            renderer.draw_text(line, x, y, palette.text)?;
+            y += LINE_HEIGHT;
+        }
+        let prompt = format!("> {}_", self.input);
+        // The end of a long input stays in view.
+        let fits = ((area.w as i32) - 2 * PADDING) / font::GLYPH_ADVANCE;
+        let skip = prompt.chars().count().saturating_sub(fits as usize);
+        let visible: String = prompt.chars().skip(skip).collect();
+        renderer.draw_text(&visible, x, y, palette.slider_fill)
+    }
+}
+
+// Runs one command line and returns the lines to show for it.
+fn run(
+    line: &str,
+    simulation: &mut TrafficSimulation,
+    simulation_speed: &mut u32
+) -> Result<Vec<String>, String> {
+    let words: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["help"] => Ok(HELP.iter().map(|line| line.to_string()).collect()),
+        ["spawn", approach] => {
+            let approach = parse_direction(approach)?;
+            if !simulation.layout().has_approach(approach) {
+                return Err(format!("no traffic travels {}", name(approach)));
+            }
+            simulation.spawn_vehicle(approach);
+            Ok(vec![format!("spawned a car travelling {}", name(approach))])
+        }
+        ["spawn", approach, route] => {
+            let approach = parse_direction(approach)?;
+            let route = parse_route(route)?;
+            let to = turned_direction(approach, route);
+            match simulation.spawn_trip(opposite(approach), to) {
+                Ok(true) => {
+                    let reply = format!("spawned a car travelling {}, {:?}", name(approach), route);
+                    Ok(vec![reply])
+                }
+                Ok(false) => Ok(vec!["approach full, the car waits upstream".to_string()]),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        ["set-phase", phase] => {
+            let (phase, road) = match *phase {
+                "1" | "north-south" | "ns" => (Phase::NorthSouth, "north-south"),
+                "2" | "east-west" | "ew" => (Phase::EastWest, "east-west"),
+                _ => {
+                    return Err(format!("unknown phase {}", phase));
+                }
+            };
+            let now = simulation.time.now();
+            if simulation.traffic_light_mut().request_phase(phase, now) {
+                Ok(vec![format!("switching to the {} phase", road)])
+            } else {
+                Ok(vec![format!("the {} phase is already being served", road)])
+            }
+        }
+        ["dump", "lane", approach] => dump_lane(simulation, parse_direction(approach)?),
+        ["set", "speed", speed] => {
+            match speed.trim_end_matches('x').parse::<u32>() {
+                Ok(speed) if (1..=MAX_SPEED).contains(&speed) => {
+                    *simulation_speed = speed;
+                    Ok(vec![format!("simulation speed {}x", speed)])
+                }
+                _ => Err(format!("speed must be 1 to {}", MAX_SPEED)),
+            }
+        }
+        _ => Err(format!("unknown command {}, try help", line)),
+    }
+}
+
+// The approach's counts and light, then its vehicles from the front of the queue back.
+fn dump_lane(
+    simulation: &TrafficSimulation,
+    approach: Direction
+) -> Result<Vec<String>, String> {
+    let Some(lane) = simulation.lanes.iter().find(|lane| lane.direction == approach) else {
+        return Err(format!("no lane travelling {}", name(approach)));
+    };
+    let vehicles = simulation.vehicles_on(approach);
+    let mut lines = vec![
+        format!(
+            "{}: {} on road taking {:.0} of {:.0} m, {} upstream, {} in platoon, light {:?}",
+            name(approach),
+            vehicles.len(),
+            lane.occupied(vehicles.iter()),
+            lane.geometry.capacity,
+            lane.upstream.len(),
+            lane.platoon.len(),
+            simulation.traffic_light().state_for(approach)
+        )
+    ];
+    for vehicle in vehicles.iter().take(DUMPED_VEHICLES) {
+        lines.push(
+            format!(
+                "#{} {:?} {:?} lane {} at {:.0} km/h, {:.1} m out, {}",
+                vehicle.id.0,
+                vehicle.kind,
+                vehicle.route,
+                vehicle.lane,
+                mps_to_kmh(vehicle.speed),
+                vehicle.distance_to_intersection(),
+                vehicle.state.label()
+            )
+        );
+    }
+    if vehicles.len() > DUMPED_VEHICLES {
+        lines.push(format!("and {} more", vehicles.len() - DUMPED_VEHICLES));
+    }
+    Ok(lines)
+}
+
+fn parse_direction(word: &str) -> Result<Direction, String> {
+    match word {
+        "north" | "n" => Ok(Direction::North),
+        "south" | "s" => Ok(Direction::South),
+        "east" | "e" => Ok(Direction::East),
+        "west" | "w" => Ok(Direction::West),
+        _ => Err(format!("unknown approach {}", word)),
+    }
+}
+
+fn parse_route(word: &str) -> Result<Route, String> {
+    match word {
+        "straight" => Ok(Route::Straight),
+        "left" => Ok(Route::Left),
+        "right" => Ok(Route::Right),
+        _ => Err(format!("unknown route {}", word)),
+    }
+}
+
+fn name(direction: Direction) -> String {
+    format!("{:?}", direction).to_lowercase()
+}
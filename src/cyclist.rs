@@ -0,0 +1,68 @@
+use crate::geometry::SPAWN_INSET;
+use crate::map::stop_bar_rect;
+use crate::units::{ per_tick, Area };
+use crate::vehicle::{ heading, offset_from_center, Direction };
+use crate::{ BIKE_LANE_WIDTH, ROAD_WIDTH, SAFETY_GAP, WORLD_HEIGHT, WORLD_WIDTH };
+
+pub const CYCLIST_LENGTH: f32 = 1.6;
+pub const CYCLIST_WIDTH: f32 = 0.8;
+// In m/s.
+pub const CYCLIST_SPEED: f32 = 10.0;
+pub const CYCLIST_MIN_GAP: f32 = CYCLIST_LENGTH + SAFETY_GAP / 2.0;
+
+// Cyclists ride straight through in the bike lane along the curb.
+#[derive(Debug, Clone, Copy)]
+pub struct Cyclist {
+    pub x: f32,
+    pub y: f32,
+    pub direction: Direction,
+    pub speed: f32,
+    pub collided: bool,
+}
+
+impl Cyclist {
+    pub fn new(direction: Direction, speed: f32) -> Self {
+        let across = bike_lane_center(direction);
+        let (x, y) = match direction {
+            Direction::North => (across, WORLD_HEIGHT - SPAWN_INSET),
+            Direction::South => (across, SPAWN_INSET),
+            Direction::East => (SPAWN_INSET, across),
+            Direction::West => (WORLD_WIDTH - SPAWN_INSET, across),
+        };
+        Self { x, y, direction, speed, collided: false }
+    }
+}
+
+pub fn bike_lane_center(direction: Direction) -> f32 {
+    offset_from_center(direction, ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH / 2.0)
+}
+
+// Across the bike lane where cyclists stop, at the curb of the crossing road and ahead of
+// the stop line for the travel lanes.
+pub fn bike_stop_line_rect(direction: Direction) -> Area {
+    let stop = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH;
+    stop_bar_rect(direction, stop, bike_lane_center(direction), BIKE_LANE_WIDTH)
+}
+
+// Moves the cyclist `distance` along its way, at most as far as its speed takes it in a tick.
+pub fn move_cyclist(cyclist: &mut Cyclist, distance: f32) {
+    let (hx, hy) = heading(cyclist.direction);
+    let step = distance.min(per_tick(cyclist.speed));
+    cyclist.x += hx * step;
+    cyclist.y += hy * step;
+}
+
+pub fn cyclist_off_screen(cyclist: &Cyclist) -> bool {
+    cyclist.x < -CYCLIST_LENGTH ||
+        cyclist.x > WORLD_WIDTH + CYCLIST_LENGTH ||
+        cyclist.y < -CYCLIST_LENGTH ||
+        cyclist.y > WORLD_HEIGHT + CYCLIST_LENGTH
+}
+
+pub fn cyclist_rect(cyclist: &Cyclist) -> Area {
+    let (w, h) = match cyclist.direction {
+        Direction::North | Direction::South => (CYCLIST_WIDTH, CYCLIST_LENGTH),
+        Direction::East | Direction::West => (CYCLIST_LENGTH, CYCLIST_WIDTH),
+    };
+    Area::centered(cyclist.x, cyclist.y, w, h)
+}
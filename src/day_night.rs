@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use crate::render::Color;
+
+// Hours at which dawn and dusk begin; the light changes gradually over TWILIGHT_HOURS.
+const DAWN: f32 = 5.0;
+const DUSK: f32 = 18.0;
+const TWILIGHT_HOURS: f32 = 2.0;
+// Vehicles switch their lights on once it is at least this dark.
+pub const LIGHTS_ON_DARKNESS: f32 = 0.3;
+
+// Time of day, advancing a full day every `day_length` of simulated time. `now` is the
+// simulated time since the start of the run.
+pub struct DayClock {
+    day_length: Duration,
+    start_hour: f32,
+}
+
+impl DayClock {
+    pub fn new(day_length: Duration, start_hour: f32) -> Self {
+        Self { day_length, start_hour }
+    }
+
+    // Hour of the day in [0, 24).
+    pub fn hour(&self, now: Duration) -> f32 {
+        let days = now.as_secs_f32() / self.day_length.as_secs_f32();
+        (self.start_hour + days * 24.0).rem_euclid(24.0)
+    }
+
+    // 0 in full daylight, 1 at night.
+    pub fn darkness(&self, now: Duration) -> f32 {
+        darkness(self.hour(now))
+    }
+
+    // The time of day as HH:MM.
+    pub fn label(&self, now: Duration) -> String {
+        let minutes = (self.hour(now) * 60.0) as u32;
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+pub fn darkness(hour: f32) -> f32 {
+    if !(DAWN..DUSK + TWILIGHT_HOURS).contains(&hour) {
+        1.0
+    } else if hour < DAWN + TWILIGHT_HOURS {
+        1.0 - (hour - DAWN) / TWILIGHT_HOURS
+    } else if hour < DUSK {
+        0.0
+    } else {
+        (hour - DUSK) / TWILIGHT_HOURS
+    }
+}
+
+// Overlay that dims the scene behind the traffic, if it is dark at all.
+pub fn night_overlay(darkness: f32) -> Option<Color> {
+    (darkness > 0.0).then(|| Color::rgba(0, 0, 20, (darkness * 170.0) as u8))
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::ConfigError;
+use crate::simulation::{ SimEvent, TrafficSimulation };
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// A fingerprint of a run's event log: each event with the simulated time it came at, in
+// its debug form, folded into a 64-bit FNV-1a hash. Floats print as the shortest text that
+// reads back to the same bits, so two logs only hash alike if every event matched to the
+// bit. FNV rather than the standard library's hasher, whose algorithm may change between
+// releases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventLogHash {
+    pub hash: u64,
+    pub events: u64,
+}
+
+impl Default for EventLogHash {
+    fn default() -> Self {
+        Self { hash: FNV_OFFSET_BASIS, events: 0 }
+    }
+}
+
+impl EventLogHash {
+    pub fn record(&mut self, at: Duration, event: &SimEvent) {
+        for byte in format!("{} {:?}\n", at.as_millis(), event).bytes() {
+            self.hash = (self.hash ^ (byte as u64)).wrapping_mul(FNV_PRIME);
+        }
+        self.events += 1;
+    }
+}
+
+// Runs `config`, which needs a seed to be repeatable, for up to `ticks` with no window as
+// the batch mode does, and hashes its event log.
+pub fn hash_run(config: &Config, ticks: u64) -> Result<EventLogHash, ConfigError> {
+    let mut simulation = TrafficSimulation::with_config(config)?;
+    let mut hash = EventLogHash::default();
+    for _ in 0..ticks {
+        if simulation.scenario_ended() {
+            break;
+        }
+        simulation.update();
+        let now = simulation.time.now();
+        for event in simulation.drain_events() {
+            hash.record(now, &event);
+        }
+    }
+    Ok(hash)
+}
@@ -0,0 +1,37 @@
+use rand::Rng;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriverProfile {
+    Cautious,
+    Normal,
+    Aggressive,
+}
+
+impl DriverProfile {
+    pub fn random(rng: &mut (impl Rng + ?Sized)) -> Self {
+        match rng.gen_range(0..100) {
+            0..=29 => DriverProfile::Cautious,
+            30..=84 => DriverProfile::Normal,
+            _ => DriverProfile::Aggressive,
+        }
+    }
+
+    // Cruising speed as a multiple of the road's speed limit.
+    pub fn speed_factor_range(self) -> Range<f32> {
+        match self {
+            DriverProfile::Cautious => 0.8..0.95,
+            DriverProfile::Normal => 0.9..1.1,
+            DriverProfile::Aggressive => 1.05..1.3,
+        }
+    }
+
+    // Chance of carrying on through a yellow or red that catches the driver at the stop line.
+    pub fn red_light_run_chance(self) -> f64 {
+        match self {
+            DriverProfile::Cautious => 0.0,
+            DriverProfile::Normal => 0.05,
+            DriverProfile::Aggressive => 0.3,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::clock::TICK;
+use crate::portable_math::exp;
+use crate::vehicle::VehicleKind;
+
+// VT-Micro coefficients for the natural log of fuel use in litres per second, by powers
+// of speed in km/h (row) and acceleration in km/h/s (column), for accelerating or cruising
+// and for slowing down (Rakha, Ahn and Trani, 2004).
+const ACCELERATING: [[f32; 4]; 4] = [
+    [-7.73452, 0.22946, -0.00561, 9.773e-5],
+    [0.02799, 0.0068, -7.7221e-4, 8.38e-6],
+    [-2.228e-4, -4.402e-5, 7.90e-7, 8.17e-7],
+    [1.09e-6, 4.80e-8, 3.27e-8, -7.79e-9],
+];
+const DECELERATING: [[f32; 4]; 4] = [
+    [-7.73452, -0.01799, -0.00427, 1.8829e-4],
+    [0.02804, 0.00772, 8.3744e-4, -3.387e-5],
+    [-2.1988e-4, -5.219e-5, -7.44e-6, 2.77e-7],
+    [1.08e-6, 2.47e-7, 4.87e-8, 3.79e-10],
+];
+// Where the polynomial stays well behaved. Vehicles here speed up and brake much harder
+// than real ones, so their acceleration is held to it: a hard stop burns what coasting
+// down would, and a launch what a brisk one would.
+const MAX_SPEED_KMH: f32 = 120.0;
+const ACCELERATION_RANGE_KMH_S: (f32, f32) = (-5.0, 10.0);
+// A bus burns about three times a car's fuel at the same speed.
+const BUS_FUEL_FACTOR: f32 = 3.0;
+// Carbon dioxide per litre burned: petrol for cars, diesel for buses.
+const CAR_CO2_KG_PER_LITRE: f32 = 2.31;
+const BUS_CO2_KG_PER_LITRE: f32 = 2.68;
+
+// Fuel burned, in litres per second, at `speed` m/s while accelerating at `acceleration`
+// m/s², negative for braking.
+pub fn fuel_rate(kind: VehicleKind, speed: f32, acceleration: f32) -> f32 {
+    let v = (speed * 3.6).clamp(0.0, MAX_SPEED_KMH);
+    let (low, high) = ACCELERATION_RANGE_KMH_S;
+    let a = (acceleration * 3.6).clamp(low, high);
+    let coefficients = if a >= 0.0 { &ACCELERATING } else { &DECELERATING };
+    let mut exponent = 0.0;
+    let mut v_power = 1.0;
+    for row in coefficients {
+        let mut a_power = 1.0;
+        for k in row {
+            exponent += k * v_power * a_power;
+            a_power *= a;
+        }
+        v_power *= v;
+    }
+    let factor = match kind {
+        VehicleKind::Car => 1.0,
+        VehicleKind::Bus => BUS_FUEL_FACTOR,
+    };
+    (exp(exponent as f64) as f32) * factor
+}
+
+// Litres burned over one tick that ends at `speed` after starting at `previous_speed`, both
+// in m/s.
+pub fn fuel_per_tick(kind: VehicleKind, previous_speed: f32, speed: f32) -> f32 {
+    let tick = TICK.as_secs_f32();
+    let acceleration = (speed - previous_speed) / tick;
+    fuel_rate(kind, speed, acceleration) * tick
+}
+
+// Kilograms of carbon dioxide from burning `litres` of the vehicle's fuel.
+pub fn co2_kg(kind: VehicleKind, litres: f32) -> f32 {
+    match kind {
+        VehicleKind::Car => litres * CAR_CO2_KG_PER_LITRE,
+        VehicleKind::Bus => litres * BUS_CO2_KG_PER_LITRE,
+    }
+}
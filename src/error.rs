@@ -0,0 +1,136 @@
+use sdl2::video::WindowBuildError;
+use sdl2::IntegerOrSdlError;
+use std::fmt;
+use std::io;
+use std::path::{ Path, PathBuf };
+use thiserror::Error;
+
+use crate::vehicle::Direction;
+
+const HEADLESS_HINT: &str = "run headless with --ticks, or in a terminal with --tui";
+
+// Drawing a frame or reading it back failed.
+#[derive(Debug, Error)]
+pub enum RenderError {
+    // SDL only describes its errors in text.
+    #[error("{0}")]
+    Sdl(String),
+    #[error("terminal: {0}")]
+    Terminal(#[from] io::Error),
+    #[error("frame capture is not supported by this renderer")]
+    CaptureUnsupported,
+}
+
+// A config or scenario file that couldn't be read, parsed or saved, or that holds values the
+// simulation can't run with.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+    #[error(transparent)]
+    Edit(#[from] toml_edit::TomlError),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<ConfigError>,
+    },
+}
+
+impl ConfigError {
+    // Names the file the error came from.
+    pub fn in_file(self, path: &Path) -> Self {
+        ConfigError::File { path: path.to_path_buf(), source: Box::new(self) }
+    }
+}
+
+// A map file or OpenStreetMap extract that couldn't be read or written, or that describes a
+// road network the simulator can't run.
+#[derive(Debug, Error)]
+pub enum MapError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+    #[error(transparent)]
+    Serialize(#[from] toml::ser::Error),
+    #[error("map file version {found} is newer than this build reads, {supported} at most")]
+    NewerVersion {
+        found: u32,
+        supported: u32,
+    },
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<MapError>,
+    },
+}
+
+impl MapError {
+    // Names the file the error came from.
+    pub fn in_file(self, path: &Path) -> Self {
+        MapError::File { path: path.to_path_buf(), source: Box::new(self) }
+    }
+}
+
+// Why a run couldn't start, had to stop or couldn't do what was asked of it.
+#[derive(Error)]
+pub enum SimError {
+    // SDL or its video subsystem wouldn't start, as where there is no display.
+    #[error("could not start video: {0}; {hint}", hint = HEADLESS_HINT)]
+    Video(String),
+    #[error("could not open a window: {0}; {hint}", hint = HEADLESS_HINT)]
+    Window(#[source] WindowBuildError),
+    // Neither an accelerated nor a software renderer could be made for the window.
+    #[error("could not draw to the window: {0}")]
+    Canvas(#[source] IntegerOrSdlError),
+    #[error(transparent)]
+    Render(#[from] RenderError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Map(#[from] MapError),
+    // Writing an export, a screenshot or a recording.
+    #[error("{}: {source}", path.display())]
+    Output {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // Reading back a state recording for playback.
+    #[error("{}: {reason}", path.display())]
+    Recording {
+        path: PathBuf,
+        reason: String,
+    },
+    #[error("PNG encoding: {0}")]
+    Png(#[from] png::EncodingError),
+    #[error("ffmpeg: {0}")]
+    Ffmpeg(#[source] io::Error),
+    #[error("{address}: {source}")]
+    Listen {
+        address: String,
+        source: io::Error,
+    },
+    // Trips the road network has no route for.
+    #[error("no route from the {0:?} end back to itself")]
+    UTurn(Direction),
+    #[error("no way in from the {0:?} end")]
+    NoWayIn(Direction),
+    #[error("no way out at the {0:?} end")]
+    NoWayOut(Direction),
+    // A command line argument that is missing or doesn't parse.
+    #[error("{0}")]
+    Usage(String),
+}
+
+// `main` reports an error with its Debug form, so that is the message as well.
+impl fmt::Debug for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use crate::error::SimError;
+use crate::simulation::TrafficSimulation;
+use crate::vehicle::VehicleKind;
+use crate::WORLD_HEIGHT;
+
+// SUMO's default step length.
+const PERIOD: Duration = Duration::from_secs(1);
+
+// Writes vehicle positions and speeds in SUMO's floating car data format (fcd-output), one
+// timestep per simulated second, so SUMO's tools can read a run. Positions are in meters
+// with y pointing up from the bottom of the window, angles in degrees clockwise from
+// north, as SUMO has them. Cyclists and pedestrians are left out.
+pub struct FcdWriter {
+    path: PathBuf,
+    out: BufWriter<File>,
+    next_timestep: Duration,
+}
+
+impl FcdWriter {
+    pub fn create(path: &Path) -> Result<Self, SimError> {
+        let file = File::create(path)
+            .map_err(|source| SimError::Output { path: path.to_path_buf(), source })?;
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+            next_timestep: PERIOD,
+        };
+        writer.write(|out| {
+            writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(
+                out,
+                "<fcd-export xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+                 xsi:noNamespaceSchemaLocation=\"http://sumo.dlr.de/xsd/fcd_file.xsd\">"
+            )
+        })?;
+        Ok(writer)
+    }
+
+    // Writes a timestep if one is due; front ends call this after every update or frame.
+    pub fn record(&mut self, simulation: &TrafficSimulation) -> Result<(), SimError> {
+        let (now, half_tick) = (simulation.time.now(), simulation.tick() / 2);
+        // Simulated time moves in whole ticks, which may not land on the period exactly.
+        if now + half_tick < self.next_timestep {
+            return Ok(());
+        }
+        while self.next_timestep <= now + half_tick {
+            self.next_timestep += PERIOD;
+        }
+        self.write(|out| {
+            writeln!(out, "    <timestep time=\"{:.2}\">", now.as_secs_f32())?;
+            for vehicle in simulation.vehicles().iter() {
+                let (hx, hy) = vehicle.heading;
+                let angle = hx.atan2(-hy).to_degrees().rem_euclid(360.0);
+                let y = WORLD_HEIGHT - vehicle.y;
+                let kind = match vehicle.kind {
+                    VehicleKind::Car => "car",
+                    VehicleKind::Bus => "bus",
+                };
+                writeln!(
+                    out,
+                    "        <vehicle id=\"{}\" x=\"{:.2}\" y=\"{:.2}\" angle=\"{:.2}\" \
+                     type=\"{}\" speed=\"{:.2}\"/>",
+                    vehicle.id.0,
+                    vehicle.x,
+                    y,
+                    angle,
+                    kind,
+                    vehicle.speed
+                )?;
+            }
+            writeln!(out, "    </timestep>")
+        })
+    }
+
+    pub fn finish(mut self) -> Result<(), SimError> {
+        self.write(|out| {
+            writeln!(out, "</fcd-export>")?;
+            out.flush()
+        })
+    }
+
+    fn write(
+        &mut self,
+        f: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>
+    ) -> Result<(), SimError> {
+        f(&mut self.out).map_err(|source| SimError::Output { path: self.path.clone(), source })
+    }
+}
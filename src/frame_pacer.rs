@@ -0,0 +1,60 @@
+use std::time::{ Duration, Instant };
+
+// After a stall, such as the window being dragged, the simulation falls behind rather than
+// racing through the missed time in one frame.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+// Keeps the window's frames at a steady rate and simulated time in step with real time
+// whatever that rate is. With vsync presenting the frame already waits for the display;
+// otherwise the rest of each frame at the target rate is slept away.
+pub struct FramePacer {
+    // None when vsync does the waiting.
+    frame_time: Option<Duration>,
+    // Simulated time in each tick.
+    tick: Duration,
+    next_frame: Instant,
+    last_ticks: Instant,
+    // Real time not yet turned into ticks.
+    owed: Duration,
+}
+
+impl FramePacer {
+    pub fn new(target_fps: u32, vsync: bool, tick: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            frame_time: (!vsync).then(|| Duration::from_secs(1) / target_fps.max(1)),
+            tick,
+            next_frame: now,
+            last_ticks: now,
+            owed: Duration::ZERO,
+        }
+    }
+
+    // Ticks of simulated time that the real time since the last call covers.
+    pub fn ticks(&mut self) -> u32 {
+        let now = Instant::now();
+        self.owed += now - self.last_ticks;
+        self.last_ticks = now;
+        let ticks = (self.owed.as_nanos() / self.tick.as_nanos()) as u32;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.owed = Duration::ZERO;
+            return MAX_TICKS_PER_FRAME;
+        }
+        self.owed -= self.tick * ticks;
+        ticks
+    }
+
+    // Called once a frame has been presented; waits out the rest of it without vsync.
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+        self.next_frame += frame_time;
+        let now = Instant::now();
+        match self.next_frame.checked_duration_since(now) {
+            Some(wait) => std::thread::sleep(wait),
+            // Running late: the next frame is timed from now instead of hurrying to catch up.
+            None => self.next_frame = now,
+        }
+    }
+}
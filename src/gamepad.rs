@@ -0,0 +1,63 @@
+use sdl2::controller::{ Button, GameController };
+use sdl2::GameControllerSubsystem;
+
+use crate::keymap::Action;
+
+// What a controller button does: the action of a key, or a step in simulation speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadInput {
+    Action(Action),
+    Faster,
+    Slower,
+}
+
+// The D-pad spawns from the way it points like the arrow keys, the shoulder buttons speed
+// the simulation up and slow it down, and Start pauses.
+pub fn input_for(button: Button) -> Option<GamepadInput> {
+    match button {
+        Button::DPadUp => Some(GamepadInput::Action(Action::SpawnNorth)),
+        Button::DPadDown => Some(GamepadInput::Action(Action::SpawnSouth)),
+        Button::DPadRight => Some(GamepadInput::Action(Action::SpawnEast)),
+        Button::DPadLeft => Some(GamepadInput::Action(Action::SpawnWest)),
+        Button::RightShoulder => Some(GamepadInput::Faster),
+        Button::LeftShoulder => Some(GamepadInput::Slower),
+        Button::Start => Some(GamepadInput::Action(Action::Pause)),
+        _ => None,
+    }
+}
+
+// Game controllers opened as they are plugged in, so their buttons arrive as events. SDL
+// reports the ones already plugged in at startup the same way.
+pub struct Gamepads {
+    subsystem: Option<GameControllerSubsystem>,
+    open: Vec<GameController>,
+}
+
+impl Gamepads {
+    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+        let subsystem = sdl_context
+            .game_controller()
+            .inspect_err(|e| tracing::warn!("game controllers disabled: {}", e))
+            .ok();
+        Self { subsystem, open: Vec::new() }
+    }
+
+    // For the controller added at joystick `index`.
+    pub fn connected(&mut self, index: u32) {
+        let Some(subsystem) = &self.subsystem else {
+            return;
+        };
+        match subsystem.open(index) {
+            Ok(controller) => {
+                tracing::info!("game controller connected: {}", controller.name());
+                self.open.push(controller);
+            }
+            Err(e) => tracing::warn!("could not open game controller {}: {}", index, e),
+        }
+    }
+
+    // For the controller removed with joystick instance `id`.
+    pub fn disconnected(&mut self, id: u32) {
+        self.open.retain(|controller| controller.instance_id() != id);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::map::MapLayout;
+use crate::vehicle::{ lane_center, Direction };
+use crate::{
+    BIKE_LANE_WIDTH,
+    LANES_PER_DIRECTION,
+    ROAD_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+// Vehicles and cyclists enter this far inside the world's edge.
+pub const SPAWN_INSET: f32 = 3.0;
+
+// Where one approach's traffic enters, where it stops and how much of it fits in between,
+// worked out from the world and road dimensions and the map. A lane is given a fresh one
+// whenever any of those change, so its capacity, spawn points and stop line always agree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub direction: Direction,
+    // How far back from the crossing road's bike lane traffic stops.
+    pub stop_line_setback: f32,
+    // From the center of the intersection out to the stop line, and from there on out to
+    // the world's edge traffic enters at.
+    pub stop_distance: f32,
+    pub approach_length: f32,
+    // Meters of queue the approach holds across its travel lanes, which vehicles fill with
+    // their length and the gap behind them.
+    pub capacity: f32,
+}
+
+impl Geometry {
+    pub fn new(direction: Direction, map: &MapLayout) -> Self {
+        let stop_line_setback = map.approach(direction).stop_line_setback;
+        let stop_distance = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + stop_line_setback;
+        let approach_length = half_world(direction) - stop_distance;
+        Self {
+            direction,
+            stop_line_setback,
+            stop_distance,
+            approach_length,
+            capacity: approach_length * (LANES_PER_DIRECTION as f32),
+        }
+    }
+
+    // Center of a vehicle entering in travel lane `lane`.
+    pub fn spawn_position(&self, lane: usize) -> (f32, f32) {
+        let across = lane_center(self.direction, lane as f32);
+        match self.direction {
+            Direction::North => (across, WORLD_HEIGHT - SPAWN_INSET),
+            Direction::South => (across, SPAWN_INSET),
+            Direction::East => (SPAWN_INSET, across),
+            Direction::West => (WORLD_WIDTH - SPAWN_INSET, across),
+        }
+    }
+}
+
+// From the center of the intersection out to the world's edge along `direction`'s road.
+fn half_world(direction: Direction) -> f32 {
+    match direction {
+        Direction::North | Direction::South => WORLD_HEIGHT / 2.0,
+        Direction::East | Direction::West => WORLD_WIDTH / 2.0,
+    }
+}
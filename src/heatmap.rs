@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crate::error::RenderError;
+use crate::render::{ Color, Renderer };
+use crate::units::{ Area, View };
+use crate::vehicle::Vehicle;
+use crate::{ WORLD_HEIGHT, WORLD_WIDTH };
+
+// In meters.
+const CELL_SIZE: f32 = 1.0;
+
+// Seconds vehicles have spent with their center in each CELL_SIZE square of the world,
+// so the places queues form stand out.
+pub struct Heatmap {
+    columns: usize,
+    rows: usize,
+    seconds: Vec<f32>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        let columns = (WORLD_WIDTH / CELL_SIZE).ceil() as usize;
+        let rows = (WORLD_HEIGHT / CELL_SIZE).ceil() as usize;
+        Self { columns, rows, seconds: vec![0.0; columns * rows] }
+    }
+
+    pub fn record(&mut self, vehicle: &Vehicle, dt: Duration) {
+        if vehicle.x < 0.0 || vehicle.y < 0.0 {
+            return;
+        }
+        let column = (vehicle.x / CELL_SIZE) as usize;
+        let row = (vehicle.y / CELL_SIZE) as usize;
+        if column < self.columns && row < self.rows {
+            self.seconds[row * self.columns + column] += dt.as_secs_f32();
+        }
+    }
+
+    // Cells shade from translucent blue to opaque red relative to the busiest one.
+    pub fn draw(&self, renderer: &mut dyn Renderer, view: &View) -> Result<(), RenderError> {
+        let busiest = self.seconds.iter().copied().fold(0.0, f32::max);
+        if busiest <= 0.0 {
+            return Ok(());
+        }
+        for (i, &seconds) in self.seconds.iter().enumerate() {
+            if seconds <= 0.0 {
+                continue;
+            }
+            // The square root keeps short stops visible next to long queues.
+            let heat = (seconds / busiest).sqrt();
+            let color = Color::rgba(
+                (255.0 * heat) as u8,
+                (160.0 * (1.0 - (2.0 * heat - 1.0).abs())) as u8,
+                (255.0 * (1.0 - heat)) as u8,
+                (60.0 + 140.0 * heat) as u8
+            );
+            let x = ((i % self.columns) as f32) * CELL_SIZE;
+            let y = ((i / self.columns) as f32) * CELL_SIZE;
+            renderer.draw_rect(view.rect(Area::new(x, y, CELL_SIZE, CELL_SIZE)), color)?;
+        }
+        Ok(())
+    }
+}
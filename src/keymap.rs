@@ -0,0 +1,230 @@
+use sdl2::keyboard::Keycode;
+use serde::Deserialize;
+
+use crate::error::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    SpawnNorth,
+    SpawnSouth,
+    SpawnEast,
+    SpawnWest,
+    SpawnRandom,
+    SpawnCyclist,
+    SpawnBus,
+    SpawnPlatoon,
+    CycleWeather,
+    ToggleHeatmap,
+    ToggleCounts,
+    ToggleNoise,
+    SelectNext,
+    CallWalk,
+    SendTrain,
+    ToggleClosure,
+    CauseIncident,
+    ToggleWebster,
+    ApplyWebster,
+    ToggleManual,
+    ManualNorthSouth,
+    ManualEastWest,
+    ToggleEditor,
+    ToggleTimingEditor,
+    ToggleConsole,
+    Pause,
+    SpeedUp,
+    Reset,
+    Screenshot,
+    ToggleSound,
+    Quit,
+}
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::SpawnNorth => "Spawn vehicle from South",
+            Action::SpawnSouth => "Spawn vehicle from North",
+            Action::SpawnEast => "Spawn vehicle from West",
+            Action::SpawnWest => "Spawn vehicle from East",
+            Action::SpawnRandom => "Spawn random vehicle",
+            Action::SpawnCyclist => "Spawn cyclist from a random direction",
+            Action::SpawnBus => "Spawn bus on a fixed bus line",
+            Action::SpawnPlatoon => "Inject a platoon on the approach of the next spawn arrow",
+            Action::CycleWeather => "Cycle weather (clear, rain, ice)",
+            Action::ToggleHeatmap => "Show or hide the dwell time heatmap",
+            Action::ToggleCounts => "Show or hide vehicle counts by movement",
+            Action::ToggleNoise => "Show or hide traffic noise levels",
+            Action::SelectNext => "Select the next vehicle to trace its path",
+            Action::CallWalk => "Press the walk button at a random corner",
+            Action::SendTrain => "Send a train over the level crossing",
+            Action::ToggleClosure => "Close a lane on a random approach, or reopen closed lanes",
+            Action::CauseIncident => "Wreck the vehicle nearest the middle of the intersection",
+            Action::ToggleWebster => "Start or stop measuring flows for Webster's cycle length",
+            Action::ApplyWebster => "Apply Webster's cycle length and green split to the lights",
+            Action::ToggleManual => "Take the lights over from the controller, or hand them back",
+            Action::ManualNorthSouth => "Manual: all red while held, north-south green on release",
+            Action::ManualEastWest => "Manual: all red while held, east-west green on release",
+            Action::ToggleEditor => "Edit stop lines and lights, saving the map on leaving",
+            Action::ToggleTimingEditor => "Open or close the signal timing editor",
+            Action::ToggleConsole => "Open or close the debug console (type help in it)",
+            Action::Pause => "Pause or resume",
+            Action::SpeedUp => "Cycle simulation speed (1x, 2x, 4x)",
+            Action::Reset => "Reset the simulation",
+            Action::Screenshot => "Save screenshot",
+            Action::ToggleSound => "Toggle sound",
+            Action::Quit => "Exit simulation",
+        }
+    }
+
+    // Spawning actions share a cooldown so a held key does not flood an approach.
+    pub fn is_spawn(self) -> bool {
+        matches!(
+            self,
+            Action::SpawnNorth |
+                Action::SpawnSouth |
+                Action::SpawnEast |
+                Action::SpawnWest |
+                Action::SpawnRandom |
+                Action::SpawnCyclist |
+                Action::SpawnBus
+        )
+    }
+}
+
+// Key bound to each action, by SDL key name ("Up", "Space", "R", ...). Names are matched
+// case-insensitively so the terminal renderer can share them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keymap {
+    pub spawn_north: String,
+    pub spawn_south: String,
+    pub spawn_east: String,
+    pub spawn_west: String,
+    pub spawn_random: String,
+    pub spawn_cyclist: String,
+    pub spawn_bus: String,
+    pub spawn_platoon: String,
+    pub cycle_weather: String,
+    pub toggle_heatmap: String,
+    pub toggle_counts: String,
+    pub toggle_noise: String,
+    pub select_next: String,
+    pub call_walk: String,
+    pub send_train: String,
+    pub toggle_closure: String,
+    pub cause_incident: String,
+    pub toggle_webster: String,
+    pub apply_webster: String,
+    pub toggle_manual: String,
+    pub manual_north_south: String,
+    pub manual_east_west: String,
+    pub toggle_editor: String,
+    pub toggle_timing_editor: String,
+    pub toggle_console: String,
+    pub pause: String,
+    pub speed_up: String,
+    pub reset: String,
+    pub screenshot: String,
+    pub toggle_sound: String,
+    pub quit: String,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let key = |name: &str| name.to_string();
+        Self {
+            spawn_north: key("Up"),
+            spawn_south: key("Down"),
+            spawn_east: key("Right"),
+            spawn_west: key("Left"),
+            spawn_random: key("R"),
+            spawn_cyclist: key("B"),
+            spawn_bus: key("T"),
+            spawn_platoon: key("L"),
+            cycle_weather: key("W"),
+            toggle_heatmap: key("H"),
+            toggle_counts: key("V"),
+            toggle_noise: key("Z"),
+            select_next: key("Tab"),
+            call_walk: key("C"),
+            send_train: key("G"),
+            toggle_closure: key("K"),
+            cause_incident: key("I"),
+            toggle_webster: key("A"),
+            apply_webster: key("Y"),
+            toggle_manual: key("O"),
+            manual_north_south: key("1"),
+            manual_east_west: key("2"),
+            toggle_editor: key("E"),
+            toggle_timing_editor: key("S"),
+            toggle_console: key("`"),
+            pause: key("Space"),
+            speed_up: key("F"),
+            reset: key("N"),
+            screenshot: key("P"),
+            toggle_sound: key("M"),
+            quit: key("Escape"),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn bindings(&self) -> [(Action, &str); 31] {
+        [
+            (Action::SpawnNorth, &self.spawn_north),
+            (Action::SpawnSouth, &self.spawn_south),
+            (Action::SpawnEast, &self.spawn_east),
+            (Action::SpawnWest, &self.spawn_west),
+            (Action::SpawnRandom, &self.spawn_random),
+            (Action::SpawnCyclist, &self.spawn_cyclist),
+            (Action::SpawnBus, &self.spawn_bus),
+            (Action::SpawnPlatoon, &self.spawn_platoon),
+            (Action::CycleWeather, &self.cycle_weather),
+            (Action::ToggleHeatmap, &self.toggle_heatmap),
+            (Action::ToggleCounts, &self.toggle_counts),
+            (Action::ToggleNoise, &self.toggle_noise),
+            (Action::SelectNext, &self.select_next),
+            (Action::CallWalk, &self.call_walk),
+            (Action::SendTrain, &self.send_train),
+            (Action::ToggleClosure, &self.toggle_closure),
+            (Action::CauseIncident, &self.cause_incident),
+            (Action::ToggleWebster, &self.toggle_webster),
+            (Action::ApplyWebster, &self.apply_webster),
+            (Action::ToggleManual, &self.toggle_manual),
+            (Action::ManualNorthSouth, &self.manual_north_south),
+            (Action::ManualEastWest, &self.manual_east_west),
+            (Action::ToggleEditor, &self.toggle_editor),
+            (Action::ToggleTimingEditor, &self.toggle_timing_editor),
+            (Action::ToggleConsole, &self.toggle_console),
+            (Action::Pause, &self.pause),
+            (Action::SpeedUp, &self.speed_up),
+            (Action::Reset, &self.reset),
+            (Action::Screenshot, &self.screenshot),
+            (Action::ToggleSound, &self.toggle_sound),
+            (Action::Quit, &self.quit),
+        ]
+    }
+
+    pub fn action_for(&self, key_name: &str) -> Option<Action> {
+        self.bindings()
+            .into_iter()
+            .find(|(_, bound)| bound.eq_ignore_ascii_case(key_name))
+            .map(|(action, _)| action)
+    }
+
+    // Every key must be one SDL knows, and no key may trigger two actions.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let bindings = self.bindings();
+        for (i, (_, key)) in bindings.iter().enumerate() {
+            if Keycode::from_name(key).is_none() {
+                return Err(ConfigError::Invalid(format!("unknown key \"{}\" in keymap", key)));
+            }
+            if bindings[..i].iter().any(|(_, other)| other.eq_ignore_ascii_case(key)) {
+                return Err(ConfigError::Invalid(format!(
+                    "key \"{}\" is bound to more than one action",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+}
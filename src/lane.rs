@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use rand::Rng;
+
+use crate::agents::Agents;
+use crate::bus::{ BUS_LENGTH, BUS_SPEED_FACTOR, BUS_STOP_LANE };
+use crate::config::{ ReactionConfig, TravelTimeConfig };
+use crate::cyclist::{ Cyclist, CYCLIST_MIN_GAP, CYCLIST_SPEED };
+use crate::geometry::Geometry;
+use crate::median::MEDIAN_LANE;
+use crate::plugin::DriverModel;
+use crate::portable_math::hypot;
+use crate::simulation::SimEvent;
+use crate::sink::Sinks;
+use crate::vehicle::{ queue_space, turned_direction, Direction, Route, Vehicle, VehicleKind };
+use crate::{ LANES_PER_DIRECTION, SAFETY_GAP, SPAWN_COOLDOWN };
+
+// Most vehicles one platoon can inject at once.
+pub const MAX_PLATOON_SIZE: u32 = 20;
+
+// All vehicles entering from one side of the intersection, across its travel lanes.
+pub struct Lane {
+    pub direction: Direction,
+    pub speed_limit: f32,
+    // Capacity, spawn points and stop line, replaced whenever the map moves the stop line.
+    pub geometry: Geometry,
+    // Arrivals that found the approach full up to the spawn point, in arrival order. They
+    // enter one at a time as room opens up.
+    pub upstream: VecDeque<(VehicleKind, Route)>,
+    // Routes of the injected platoon's cars still to enter, all in `platoon_lane`. Each
+    // follows the last on as soon as the spawn point is clear, without the spawn cooldown.
+    pub platoon: VecDeque<Route>,
+    platoon_lane: usize,
+    // Travel lane coned off over the work zone, if any.
+    pub closed_lane: Option<usize>,
+    // Length of the left-turn bay on a divided road, whose median takes the inner lane
+    // upstream of it.
+    pub turn_bay: Option<f32>,
+    // A left-turner is held beside the full bay, blocking the through lane.
+    pub bay_overflowing: bool,
+    // Stretch before the stop line where lane lines are solid and lanes can't be changed.
+    pub no_change_zone: Option<f32>,
+    // Picks the driver of each car entering.
+    driver_model: Box<dyn DriverModel>,
+    sinks: Sinks,
+    pub travel_times: TravelTimeConfig,
+    reactions: ReactionConfig,
+    // Simulated times of the last spawns, for the spawn cooldown.
+    last_spawn: Duration,
+    last_cyclist_spawn: Duration,
+}
+
+impl Lane {
+    pub fn new(
+        direction: Direction,
+        speed_limit: f32,
+        geometry: Geometry,
+        sinks: Sinks,
+        travel_times: TravelTimeConfig,
+        reactions: ReactionConfig,
+        driver_model: Box<dyn DriverModel>
+    ) -> Self {
+        Self {
+            direction,
+            speed_limit,
+            geometry,
+            upstream: VecDeque::new(),
+            platoon: VecDeque::new(),
+            platoon_lane: 0,
+            closed_lane: None,
+            turn_bay: None,
+            bay_overflowing: false,
+            no_change_zone: None,
+            driver_model,
+            sinks,
+            travel_times,
+            reactions,
+            last_spawn: Duration::ZERO,
+            last_cyclist_spawn: Duration::ZERO,
+        }
+    }
+    // Of the approach's `vehicles`, those held up on it, plus arrivals and platoon cars
+    // waiting upstream to get onto it.
+    pub fn queue_length<'a>(&self, vehicles: impl IntoIterator<Item = &'a Vehicle>) -> usize {
+        let waiting = vehicles.into_iter().filter(|v| v.wait_started.is_some()).count();
+        waiting + self.upstream.len() + self.platoon.len()
+    }
+    // Cones off `lane`, or reopens the approach with None. Vehicles already in the work
+    // zone carry on, moving out of the closed lane when they can.
+    pub fn set_closed_lane(&mut self, agents: &mut Agents, lane: Option<usize>) {
+        self.closed_lane = lane;
+        if lane.is_some() {
+            let vehicles = agents.vehicles_mut().filter(|v| v.approach == self.direction);
+            for vehicle in vehicles {
+                vehicle.through_work_zone |= !vehicle.in_intersection();
+            }
+        }
+    }
+    // Meters of the approach's capacity its `vehicles` take up.
+    pub fn occupied<'a>(&self, vehicles: impl IntoIterator<Item = &'a Vehicle>) -> f32 {
+        vehicles.into_iter().map(|vehicle| queue_space(vehicle.kind)).sum()
+    }
+    // An empty approach takes any vehicle, however short of room it is.
+    fn has_room(&self, vehicles: &[&Vehicle], kind: VehicleKind) -> bool {
+        vehicles.is_empty() ||
+            self.occupied(vehicles.iter().copied()) + queue_space(kind) <= self.geometry.capacity
+    }
+    pub fn can_spawn(&self, vehicles: &[&Vehicle], now: Duration, kind: VehicleKind) -> bool {
+        now.saturating_sub(self.last_spawn) >= SPAWN_COOLDOWN && self.has_room(vehicles, kind)
+    }
+    // A new arrival enters if there is room and nobody is queued upstream ahead of it, and
+    // joins the upstream queue otherwise. Returns whether it entered.
+    pub fn arrive(
+        &mut self,
+        agents: &mut Agents,
+        kind: VehicleKind,
+        route: Route,
+        now: Duration,
+        rng: &mut impl Rng,
+        events: &mut Vec<SimEvent>
+    ) -> bool {
+        if self.upstream.is_empty() && self.enter(agents, kind, route, now, rng, events) {
+            return true;
+        }
+        self.upstream.push_back((kind, route));
+        events.push(SimEvent::ArrivalQueued { approach: self.direction });
+        false
+    }
+    // Lets the first vehicle held upstream onto the road if there is room for it now.
+    pub fn release_upstream(
+        &mut self,
+        agents: &mut Agents,
+        now: Duration,
+        rng: &mut impl Rng,
+        events: &mut Vec<SimEvent>
+    ) {
+        let Some(&(kind, route)) = self.upstream.front() else {
+            return;
+        };
+        if self.enter(agents, kind, route, now, rng, events) {
+            self.upstream.pop_front();
+            events.push(SimEvent::ArrivalReleased { approach: self.direction });
+        }
+    }
+    // Queues cars on `routes` to enter nose to tail in one travel lane, joining the end of
+    // a platoon still entering if there is one.
+    pub fn inject_platoon(&mut self, routes: impl IntoIterator<Item = Route>, rng: &mut impl Rng) {
+        if self.platoon.is_empty() {
+            let lanes: Vec<usize> =
+                (0..LANES_PER_DIRECTION).filter(|&lane| self.lane_open(lane)).collect();
+            if lanes.is_empty() {
+                return;
+            }
+            self.platoon_lane = lanes[rng.gen_range(0..lanes.len())];
+        }
+        self.platoon.extend(routes);
+    }
+    // Puts the platoon's next car on the road a safety gap behind the one before, if the
+    // approach has room for it. Platoon cars all cruise at the speed limit so they keep
+    // their spacing.
+    pub fn release_platoon(
+        &mut self,
+        agents: &mut Agents,
+        now: Duration,
+        rng: &mut impl Rng,
+        events: &mut Vec<SimEvent>
+    ) {
+        let Some(&route) = self.platoon.front() else {
+            return;
+        };
+        // A lane coned off since the platoon was injected sends the rest of it next door.
+        if !self.lane_open(self.platoon_lane) {
+            let Some(lane) = (0..LANES_PER_DIRECTION).find(|&l| self.lane_open(l)) else {
+                return;
+            };
+            self.platoon_lane = lane;
+        }
+        let length = VehicleKind::Car.length();
+        let roster = agents.vehicles_on(self.direction);
+        let vehicles: Vec<&Vehicle> = roster.iter().collect();
+        let has_room = self.has_room(&vehicles, VehicleKind::Car);
+        if !has_room || !self.spawn_point_clear(&vehicles, self.platoon_lane, length) {
+            return;
+        }
+        drop(roster);
+        self.platoon.pop_front();
+        let mut vehicle = self.spawn_car(self.platoon_lane, route, rng);
+        vehicle.speed = self.speed_limit;
+        vehicle.desired_speed = self.speed_limit;
+        self.entered(agents, vehicle, now, events);
+    }
+    // Puts the vehicle on the road if the spawn cooldown is over and there is room.
+    fn enter(
+        &mut self,
+        agents: &mut Agents,
+        kind: VehicleKind,
+        route: Route,
+        now: Duration,
+        rng: &mut impl Rng,
+        events: &mut Vec<SimEvent>
+    ) -> bool {
+        let roster = agents.vehicles_on(self.direction);
+        let vehicles: Vec<&Vehicle> = roster.iter().collect();
+        if !self.can_spawn(&vehicles, now, kind) {
+            return false;
+        }
+        let vehicle = match kind {
+            VehicleKind::Car => self.spawn_vehicle(&vehicles, route, rng),
+            VehicleKind::Bus => self.spawn_bus(&vehicles, route, rng),
+        };
+        drop(roster);
+        let Some(vehicle) = vehicle else {
+            return false;
+        };
+        self.entered(agents, vehicle, now, events);
+        true
+    }
+    // Gives the vehicle its id, puts it on the road and announces it.
+    fn entered(
+        &mut self,
+        agents: &mut Agents,
+        mut vehicle: Vehicle,
+        now: Duration,
+        events: &mut Vec<SimEvent>
+    ) {
+        self.last_spawn = now;
+        vehicle.id = agents.new_vehicle_id();
+        vehicle.entered_road = now;
+        vehicle.through_work_zone = self.closed_lane.is_some();
+        events.push(SimEvent::VehicleSpawned {
+            vehicle_id: vehicle.id,
+            kind: vehicle.kind,
+            approach: self.direction,
+            route: vehicle.route,
+        });
+        agents.add_vehicle(vehicle);
+    }
+    // Travel lanes cars can enter in: not coned off, nor under the median.
+    fn lane_open(&self, lane: usize) -> bool {
+        Some(lane) != self.closed_lane && (lane != MEDIAN_LANE || self.turn_bay.is_none())
+    }
+    fn spawn_vehicle(
+        &mut self,
+        vehicles: &[&Vehicle],
+        route: Route,
+        rng: &mut impl Rng
+    ) -> Option<Vehicle> {
+        let first_choice = rng.gen_range(0..LANES_PER_DIRECTION);
+        let lane = (0..LANES_PER_DIRECTION)
+            .map(|offset| (first_choice + offset) % LANES_PER_DIRECTION)
+            .filter(|&lane| self.lane_open(lane))
+            .find(|&lane| self.spawn_point_clear(vehicles, lane, VehicleKind::Car.length()))?;
+        Some(self.spawn_car(lane, route, rng))
+    }
+    fn spawn_car(&mut self, lane: usize, route: Route, rng: &mut impl Rng) -> Vehicle {
+        let profile = self.driver_model.driver(rng);
+        let speed = self.speed_limit * rng.gen_range(profile.speed_factor_range());
+        let position = self.geometry.spawn_position(lane);
+        let mut vehicle = Vehicle::new(
+            VehicleKind::Car,
+            self.direction,
+            route,
+            lane,
+            position,
+            speed,
+            self.sinks.offset(turned_direction(self.direction, route))
+        );
+        vehicle.profile = profile;
+        vehicle.reaction_time = self.reactions.sample(rng);
+        vehicle
+    }
+    // Buses enter in the curb lane for their stop, or the next one out while it is closed.
+    fn spawn_bus(
+        &mut self,
+        vehicles: &[&Vehicle],
+        route: Route,
+        rng: &mut impl Rng
+    ) -> Option<Vehicle> {
+        let lane = (0..=BUS_STOP_LANE).rev().find(|&lane| Some(lane) != self.closed_lane)?;
+        if !self.spawn_point_clear(vehicles, lane, BUS_LENGTH) {
+            return None;
+        }
+        let speed = self.speed_limit * BUS_SPEED_FACTOR;
+        let position = self.geometry.spawn_position(lane);
+        let mut bus = Vehicle::new(
+            VehicleKind::Bus,
+            self.direction,
+            route,
+            lane,
+            position,
+            speed,
+            self.sinks.offset(turned_direction(self.direction, route))
+        );
+        bus.reaction_time = self.reactions.sample(rng);
+        Some(bus)
+    }
+    pub fn spawn_cyclist(&mut self, agents: &mut Agents, now: Duration, rng: &mut impl Rng) {
+        if now.saturating_sub(self.last_cyclist_spawn) < SPAWN_COOLDOWN {
+            return;
+        }
+        let cyclist = Cyclist::new(self.direction, CYCLIST_SPEED * rng.gen_range(0.8..1.2));
+        let blocked = agents.cyclists_on(self.direction).iter().any(|c| {
+            hypot(c.x - cyclist.x, c.y - cyclist.y) < CYCLIST_MIN_GAP
+        });
+        if blocked {
+            return;
+        }
+        agents.add_cyclist(cyclist);
+        self.last_cyclist_spawn = now;
+    }
+    fn spawn_point_clear(&self, vehicles: &[&Vehicle], lane: usize, length: f32) -> bool {
+        let (x, y) = self.geometry.spawn_position(lane);
+        vehicles
+            .iter()
+            .filter(|v| v.direction == self.direction && occupies(v, lane))
+            .all(|v| {
+                let required = (v.length() + length) / 2.0 + SAFETY_GAP;
+                hypot(v.x - x, v.y - y) >= required
+            })
+    }
+
+}
+
+// A vehicle mid-change blocks both the lane it is leaving and the one it is entering.
+pub fn occupies(vehicle: &Vehicle, lane: usize) -> bool {
+    vehicle.lane == lane || (vehicle.lateral() - (lane as f32)).abs() < 1.0
+}
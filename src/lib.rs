@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+pub mod ab_test;
+pub mod agents;
+pub mod attract;
+pub mod audio;
+pub mod bus;
+pub mod capture;
+pub mod clock;
+pub mod compare;
+pub mod config;
+pub mod conflicts;
+pub mod determinism;
+pub mod console;
+pub mod cyclist;
+pub mod day_night;
+pub mod driver;
+pub mod emissions;
+pub mod error;
+pub mod fcd;
+pub mod frame_pacer;
+pub mod gamepad;
+pub mod geometry;
+pub mod heatmap;
+pub mod keymap;
+pub mod lane;
+pub mod map;
+pub mod map_file;
+pub mod median;
+pub mod metrics;
+pub mod minimap;
+pub mod motion_guard;
+pub mod no_change_zone;
+pub mod noise;
+pub mod osm;
+pub mod path;
+pub mod pedestrian;
+pub mod plugin;
+pub mod portable_math;
+pub mod rail;
+pub mod remote;
+pub mod render;
+pub mod saturation;
+pub mod scenario;
+pub mod script;
+pub mod simulation;
+pub mod sink;
+pub mod stats;
+pub mod sweep;
+pub mod systems;
+pub mod tape;
+pub mod theme;
+pub mod timing_editor;
+pub mod traffic_light;
+pub mod trail;
+pub mod ui;
+pub mod units;
+pub mod vehicle;
+pub mod weather;
+pub mod webster;
+pub mod work_zone;
+
+pub const WINDOW_WIDTH: u32 = 1000;
+pub const WINDOW_HEIGHT: u32 = 800;
+// The world is laid out in meters, with the intersection at its center; the window shows
+// it through a `units::View`.
+pub const WORLD_WIDTH: f32 = 100.0;
+pub const WORLD_HEIGHT: f32 = 80.0;
+pub const LANES_PER_DIRECTION: usize = 2;
+pub const LANE_WIDTH: f32 = 3.5;
+pub const ROAD_WIDTH: f32 = LANE_WIDTH * 2.0 * (LANES_PER_DIRECTION as f32);
+// Bike lanes run along both curbs, outside the travel lanes.
+pub const BIKE_LANE_WIDTH: f32 = 1.4;
+// Every vehicle is as wide as a car; how long one is depends on its kind.
+pub const VEHICLE_WIDTH: f32 = 3.0;
+pub const CAR_LENGTH: f32 = 3.0;
+pub const SAFETY_GAP: f32 = 1.5;
+// Default speed limit, in km/h, as on urban streets with signalised intersections.
+pub const VEHICLE_SPEED: f32 = 50.0;
+// Longitudinal limits in m/s²: a brisk start from the light and firm braking short of an
+// emergency stop. Braking is scaled down by the weather.
+pub const ACCELERATION: f32 = 2.5;
+pub const BRAKING_DECELERATION: f32 = 6.0;
+// Distance travelled along the road while moving over by one lane.
+pub const LANE_CHANGE_LENGTH: f32 = 5.0;
+pub const SPAWN_COOLDOWN: Duration = Duration::from_millis(500);
+pub const HORN_WAIT_THRESHOLD: Duration = Duration::from_secs(3);
@@ -1,371 +1,1567 @@
-use sdl2::pixels::Color;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::render::WindowCanvas;
-use sdl2::rect::Rect;
+use sdl2::keyboard::{ Keycode, Mod };
+use sdl2::mouse::MouseButton;
+use sdl2::render::{ Canvas, CanvasBuilder };
+use sdl2::sys::SDL_RendererFlags;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+use std::path::Path;
 use std::time::{ Duration, Instant };
-use std::collections::VecDeque;
-use rand::Rng;
-
-const WINDOW_WIDTH: u32 = 1000;
-const WINDOW_HEIGHT: u32 = 800;
-const ROAD_WIDTH: i32 = 100;
-const LANE_WIDTH: i32 = 30;
-const VEHICLE_SIZE: i32 = 30;
-const SAFETY_GAP: i32 = 15;
-const VEHICLE_SPEED: i32 = 2;
-const SPAWN_COOLDOWN: Duration = Duration::from_millis(500);
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Direction {
-    North,
-    South,
-    East,
-    West,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Route {
-    Straight,
-    Left,
-    Right,
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Vehicle {
-    x: f32,
-    y: f32,
-    direction: Direction,
-    route: Route,
-    color: Color,
-    has_turned: bool,
-}
-
-struct Lane {
-    vehicles: VecDeque<Vehicle>,
-    direction: Direction,
-    capacity: usize,
-    last_spawn: Instant,
-}
-
-impl Lane {
-    fn new(direction: Direction) -> Self {
-        let lane_length = match direction {
-            Direction::North | Direction::South => ((WINDOW_HEIGHT as i32) - ROAD_WIDTH) / 2,
-            Direction::East | Direction::West => ((WINDOW_WIDTH as i32) - ROAD_WIDTH) / 2,
-        };
-        let capacity = (lane_length / (VEHICLE_SIZE + SAFETY_GAP)) as usize;
-        Self {
-            vehicles: VecDeque::new(),
-            direction,
-            capacity: capacity.max(1),
-            last_spawn: Instant::now(),
+use tracing::Level;
+
+use road_intersection::attract::AttractMode;
+use road_intersection::audio::AudioPlayer;
+use road_intersection::capture::{ self, FrameRecorder };
+use road_intersection::compare::Comparison;
+use road_intersection::config::{ Config, LightsConfig, DEFAULT_CONFIG_PATH };
+use road_intersection::conflicts::check_signal_plan;
+use road_intersection::console::Console;
+use road_intersection::determinism::hash_run;
+use road_intersection::error::{ RenderError, SimError };
+use road_intersection::fcd::FcdWriter;
+use road_intersection::frame_pacer::FramePacer;
+use road_intersection::gamepad::{ self, GamepadInput, Gamepads };
+use road_intersection::keymap::Action;
+use road_intersection::map::{ Handle, MapLayout, DEFAULT_MAP_PATH };
+use road_intersection::metrics::MetricsServer;
+use road_intersection::minimap;
+use road_intersection::osm::OsmIntersection;
+use road_intersection::pedestrian::button_at;
+use road_intersection::plugin::Registry;
+use road_intersection::remote::RemoteServer;
+use road_intersection::render::{ Rect, Renderer, SdlRenderer };
+use road_intersection::scenario::Scenario;
+use road_intersection::script::Script;
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::sink::{ node_id, node_name, SINK_COUNT };
+use road_intersection::stats::{ level_of_service, movements, Stats };
+use road_intersection::sweep::{ self, Sweep, SweepResult };
+use road_intersection::tape::{ Playback, Tape, TapeWriter };
+use road_intersection::timing_editor::TimingEditor;
+use road_intersection::traffic_light::Phase;
+use road_intersection::ui::{ Mouse, Panel };
+use road_intersection::units::mps_to_kmh;
+use road_intersection::vehicle::{ Direction, Route, VehicleState };
+use road_intersection::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+const SPAWN_KEY_COOLDOWN: Duration = Duration::from_millis(700);
+// Batch runs refresh the metrics once per simulated second rather than every tick.
+const BATCH_METRICS_INTERVAL: Duration = Duration::from_secs(1);
+
+fn main() -> Result<(), SimError> {
+    let args: Vec<String> = std::env::args().collect();
+    init_logging(&args)?;
+    let config_path = flag_value(&args, "--config")?;
+    let mut config = Config::load_or_default(config_path)?;
+    config.strict = args.iter().any(|arg| arg == "--strict");
+    // This binary only links the built-in plugins.
+    Registry::default().check(&config.plugins)?;
+    if let Some(path) = flag_value(&args, "--osm")? {
+        let node = flag_value(&args, "--osm-node")?
+            .map(|node| node.parse::<i64>().map_err(|_| invalid("node id", node)))
+            .transpose()?;
+        let intersection = OsmIntersection::load(Path::new(path), node)?;
+        tracing::info!("intersection at node {} from {}", intersection.node, path);
+        intersection.apply(&mut config);
+    }
+    if args.get(1).is_some_and(|arg| arg == "sweep") {
+        check_signal_plan(&config.lights, &config.map)?;
+        return run_sweep(&config, &args);
+    }
+    if let Some(path) = flag_value(&args, "--scenario")? {
+        config.scenario = Scenario::load(Path::new(path))?;
+    }
+    if let Some(path) = flag_value(&args, "--script")? {
+        config.script = Some(Script::load(Path::new(path))?);
+    }
+    let map_path = flag_value(&args, "--map")?;
+    config.map = MapLayout::load_or_default(map_path)?;
+    check_signal_plan(&config.lights, &config.map)?;
+    if let Some(controller) = flag_value(&args, "--compare")? {
+        if args.iter().any(|arg| arg == "--ticks" || arg == "--tui") {
+            let message = "--compare runs in a window, not with --ticks or --tui";
+            return Err(SimError::Usage(message.to_string()));
         }
+        return run_compare(&config, controller);
     }
-    fn can_spawn(&self) -> bool {
-        self.last_spawn.elapsed() >= SPAWN_COOLDOWN && self.vehicles.len() < self.capacity
+    if let Some(path) = flag_value(&args, "--playback")? {
+        if args.iter().any(|arg| arg == "--ticks" || arg == "--tui") {
+            let message = "--playback runs in a window, not with --ticks or --tui";
+            return Err(SimError::Usage(message.to_string()));
+        }
+        return run_playback(&config, Tape::load(Path::new(path))?);
     }
-    fn spawn_vehicle(&mut self) {
-        if !self.can_spawn() {
-            return;
+    let speed_export = flag_value(&args, "--export-speeds")?;
+    let travel_time_export = flag_value(&args, "--export-travel-times")?;
+    let turning_count_export = flag_value(&args, "--export-turning-counts")?;
+    let level_of_service_export = flag_value(&args, "--export-level-of-service")?;
+    let saturation_flow_export = flag_value(&args, "--export-saturation-flows")?;
+    let stops_export = flag_value(&args, "--export-stops")?;
+    let noise_export = flag_value(&args, "--export-noise")?;
+    let arrival_export = flag_value(&args, "--export-arrivals")?;
+    let speed = flag_value(&args, "--speed")?.map(parse_speed).transpose()?;
+    let ticks = flag_value(&args, "--ticks")?
+        .map(|ticks| ticks.parse::<u64>().map_err(|_| invalid("tick count", ticks)))
+        .transpose()?;
+    // Prints a fingerprint of the event log to compare a seeded run between machines, on
+    // stdout with the other reports.
+    if args.iter().any(|arg| arg == "--event-hash") {
+        let Some(ticks) = ticks else {
+            return Err(SimError::Usage("--event-hash needs --ticks".to_string()));
+        };
+        let hash = hash_run(&config, ticks)?;
+        println!("Event log hash: {:016x} over {} events", hash.hash, hash.events);
+        return Ok(());
+    }
+    let remote_address = flag_value(&args, "--remote")?;
+    let metrics_address = flag_value(&args, "--metrics")?;
+    let metrics = metrics_address.map(MetricsServer::start).transpose()?;
+    if let Some(address) = metrics_address {
+        tracing::info!("serving metrics on http://{}/metrics", address);
+    }
+    let fcd_export = flag_value(&args, "--export-fcd")?;
+    let tape_path = flag_value(&args, "--record-states")?;
+    let traces = Traces {
+        fcd: fcd_export.map(|path| FcdWriter::create(Path::new(path))).transpose()?,
+        tape: tape_path.map(|path| TapeWriter::create(Path::new(path))).transpose()?,
+    };
+    let stats = if let Some(ticks) = ticks {
+        run_batch(&config, ticks, speed, metrics, traces)?
+    } else if speed.is_some() {
+        return Err(SimError::Usage("--speed only applies to batch runs with --ticks".to_string()));
+    } else {
+        let remote = remote_address.map(RemoteServer::start).transpose()?;
+        if let Some(address) = remote_address {
+            tracing::info!("accepting remote commands on {}", address);
         }
-        let mut rng = rand::thread_rng();
-        let route = match rng.gen_range(0..3) {
-            0 => Route::Straight,
-            1 => Route::Left,
-            _ => Route::Right,
+        if args.iter().any(|arg| arg == "--tui") {
+            run_tui(&config, remote, metrics, traces)?
+        } else {
+            let record_target = flag_value(&args, "--record")?;
+            let fps = config.display.target_fps;
+            let recorder = record_target
+                .map(|target| FrameRecorder::new(target, fps))
+                .transpose()?;
+            let config_path = Path::new(config_path.unwrap_or(DEFAULT_CONFIG_PATH));
+            let map_path = Path::new(map_path.unwrap_or(DEFAULT_MAP_PATH));
+            run_sdl(&config, config_path, map_path, recorder, remote, metrics, traces)?
+        }
+    };
+    print_stats(&stats);
+    if let Some(path) = fcd_export {
+        tracing::info!("floating car data written to {}", path);
+    }
+    if let Some(path) = speed_export {
+        stats.export_speeds(Path::new(path))?;
+        tracing::info!("speed measurements written to {}", path);
+    }
+    if let Some(path) = travel_time_export {
+        stats.export_travel_times(Path::new(path))?;
+        tracing::info!("travel times written to {}", path);
+    }
+    if let Some(path) = turning_count_export {
+        let interval = Duration::from_secs_f32(config.turning_counts.interval_secs);
+        stats.export_turning_counts(Path::new(path), interval)?;
+        tracing::info!("turning movement counts written to {}", path);
+    }
+    if let Some(path) = level_of_service_export {
+        stats.export_level_of_service(Path::new(path))?;
+        tracing::info!("level of service written to {}", path);
+    }
+    if let Some(path) = saturation_flow_export {
+        stats.export_saturation_flows(Path::new(path))?;
+        tracing::info!("saturation flows written to {}", path);
+    }
+    if let Some(path) = stops_export {
+        stats.export_stops(Path::new(path))?;
+        tracing::info!("stops and idle times written to {}", path);
+    }
+    if let Some(path) = noise_export {
+        stats.export_noise(Path::new(path))?;
+        tracing::info!("corner noise levels written to {}", path);
+    }
+    if let Some(path) = arrival_export {
+        stats.export_arrivals(Path::new(path))?;
+        tracing::info!("arrivals at the stop lines written to {}", path);
+    }
+    Ok(())
+}
+
+// `sweep` runs every combination of the listed cycle lengths, north-south green splits and
+// demand levels headless, with several seeds each, and writes one CSV row per combination.
+// With `--compare <controller>` each run is repeated under that light controller, and the
+// paired difference in delay is reported for every combination.
+fn run_sweep(config: &Config, args: &[String]) -> Result<(), SimError> {
+    let mut sweep = Sweep::default();
+    if let Some(list) = flag_value(args, "--cycles")? {
+        sweep.cycles_secs = parse_list(list)?;
+    }
+    if let Some(list) = flag_value(args, "--splits")? {
+        sweep.north_south_splits = parse_list(list)?;
+    }
+    if let Some(list) = flag_value(args, "--demand")? {
+        sweep.vehicles_per_minute = parse_list(list)?;
+    }
+    if let Some(seeds) = flag_value(args, "--seeds")? {
+        sweep.seeds = seeds.parse().map_err(|_| invalid("seed count", seeds))?;
+    }
+    if let Some(ticks) = flag_value(args, "--ticks")? {
+        sweep.ticks = ticks.parse().map_err(|_| invalid("tick count", ticks))?;
+    }
+    sweep.compare = flag_value(args, "--compare")?.map(str::to_string);
+    let path = flag_value(args, "--out")?.unwrap_or("sweep.csv");
+    let started = Instant::now();
+    let results = sweep.run(config)?;
+    sweep::write_csv(&results, Path::new(path))?;
+    if let Some(controller) = &sweep.compare {
+        print_comparison(&results, &config.plugins.light_controller, controller);
+    }
+    tracing::info!(
+        "ran {} configurations in {:.1}s, summary written to {}",
+        results.len(),
+        started.elapsed().as_secs_f32(),
+        path
+    );
+    Ok(())
+}
+
+// The paired delay difference at each sweep point, and whether it is significant. A report,
+// so on stdout rather than in the logs.
+fn print_comparison(results: &[SweepResult], baseline: &str, controller: &str) {
+    println!("Average delay, {} against {} on the same seeds:", controller, baseline);
+    for result in results {
+        let Some(comparison) = &result.comparison else {
+            continue;
         };
-        let color = get_route_color(route);
-        let (x, y) = self.get_spawn_position();
-        let vehicle = Vehicle {
-            x,
-            y,
-            direction: self.direction,
-            route,
-            color,
-            has_turned: false,
+        let difference = comparison.delay_difference;
+        let interval = match difference.confidence_interval {
+            Some((low, high)) => format!("95% CI {:+.2}s to {:+.2}s", low, high),
+            None => "no CI from one seed".to_string(),
         };
-        self.vehicles.push_back(vehicle);
-        self.last_spawn = Instant::now();
-    }
-    fn get_spawn_position(&self) -> (f32, f32) {
-        let center_x = (WINDOW_WIDTH as f32) / 2.0;
-        let center_y = (WINDOW_HEIGHT as f32) / 2.0;
-        match self.direction {
-            Direction::North =>
-                (center_x + (LANE_WIDTH as f32) / 2.0, (WINDOW_HEIGHT as f32) - 30.0),
-            Direction::South => (center_x - (LANE_WIDTH as f32) / 2.0, 30.0),
-            Direction::East => (30.0, center_y + (LANE_WIDTH as f32) / 2.0),
-            Direction::West => ((WINDOW_WIDTH as f32) - 30.0, center_y - (LANE_WIDTH as f32) / 2.0),
-        }
-    }
-
-    fn update(&mut self) {
-        let mut to_remove = Vec::new();
-        let mut movements = Vec::new();
-        for (i, vehicle) in self.vehicles.iter().enumerate() {
-            let mut can_move = true;
-            if i > 0 {
-                let front_vehicle = &self.vehicles[i - 1];
-                let distance = calculate_distance(*vehicle, *front_vehicle);
-                if distance < (SAFETY_GAP as f32) + (VEHICLE_SIZE as f32) {
-                    can_move = false;
-                }
-            }
-            movements.push(can_move);
+        let verdict = match difference.is_significant() {
+            true if difference.mean < 0.0 => "better",
+            true => "worse",
+            false => "no significant difference",
+        };
+        println!(
+            "  cycle {:>4}s  split {:.2}  {:>4}/min  {:>6.2}s vs {:>6.2}s  {:+.2}s ({}), {}",
+            result.point.cycle_secs,
+            result.point.north_south_split,
+            result.point.vehicles_per_minute,
+            comparison.average_delay.as_secs_f32(),
+            result.average_delay.as_secs_f32(),
+            difference.mean,
+            interval,
+            verdict
+        );
+    }
+}
+
+// Comma-separated numbers such as "12,16,24".
+fn parse_list(text: &str) -> Result<Vec<f32>, SimError> {
+    text.split(',')
+        .map(|value| value.trim().parse::<f32>().map_err(|_| invalid("number", value)))
+        .collect()
+}
+
+// Logs go to stderr at `--log-level` (info by default), as JSON lines with `--log-json`.
+// Stdout is kept for the reports a run prints on purpose: the controls, the event log
+// hash, the controller comparison and the closing stats. They stay plain text there, so
+// they can be piped or diffed apart from the logs.
+fn init_logging(args: &[String]) -> Result<(), SimError> {
+    let level = flag_value(args, "--log-level")?.unwrap_or("info");
+    let level = level.parse::<Level>().map_err(|_| invalid("log level", level))?;
+    let logs = tracing_subscriber::fmt().with_max_level(level).with_writer(std::io::stderr);
+    if args.iter().any(|arg| arg == "--log-json") {
+        logs.json().init();
+    } else {
+        logs.init();
+    }
+    Ok(())
+}
+
+// A speed-up over real time such as "100x".
+fn parse_speed(text: &str) -> Result<f32, SimError> {
+    match text.strip_suffix('x').unwrap_or(text).parse::<f32>() {
+        Ok(speed) if speed > 0.0 => Ok(speed),
+        _ => Err(invalid("speed", text)),
+    }
+}
+
+fn invalid(what: &str, value: &str) -> SimError {
+    SimError::Usage(format!("invalid {}: {}", what, value))
+}
+
+// The argument following `flag`, if the flag was given.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, SimError> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => {
+            let value = args
+                .get(i + 1)
+                .ok_or_else(|| SimError::Usage(format!("{} requires a value", flag)))?;
+            Ok(Some(value.as_str()))
         }
-        for (i, vehicle) in self.vehicles.iter_mut().enumerate() {
-            if movements[i] {
-                move_vehicle(vehicle);
+        None => Ok(None),
+    }
+}
+
+fn run_sdl(
+    config: &Config,
+    config_path: &Path,
+    map_path: &Path,
+    mut recorder: Option<FrameRecorder>,
+    remote: Option<RemoteServer>,
+    metrics: Option<MetricsServer>,
+    mut traces: Traces
+) -> Result<Stats, SimError> {
+    let sdl_context = sdl2::init().map_err(SimError::Video)?;
+    let video_subsystem = sdl_context.video().map_err(SimError::Video)?;
+    // Recordings need frames at a known rate, which vsync leaves up to the display.
+    let vsync = config.display.vsync && recorder.is_none();
+    let (canvas, vsync) = open_canvas(&video_subsystem, vsync)?;
+    let mut renderer = SdlRenderer::new(canvas)?;
+    let mut pacer = FramePacer::new(config.display.target_fps, vsync, config.clock.tick());
+    let mut event_pump = sdl_context.event_pump().map_err(SimError::Video)?;
+    let mut audio = AudioPlayer::new(&sdl_context);
+    let mut gamepads = Gamepads::new(&sdl_context);
+    let mut simulation = TrafficSimulation::with_config(config)?;
+    println!("Traffic Intersection Simulation");
+    println!("Controls:");
+    for (action, key) in config.keymap.bindings() {
+        println!("{} - {}", key, action.description());
+    }
+    println!("Shift + spawn arrow, twice - Spawn a trip between two road ends");
+    println!("Ctrl + spawn arrow - Spawn a right-turner travelling that way");
+    println!("Alt + spawn arrow - Spawn a left-turner travelling that way");
+    println!("Ctrl + Alt + spawn arrow - Spawn a vehicle going straight that way");
+    println!("Click a corner's walk button - Call the pedestrian phase there");
+    println!("Gamepad D-pad - Spawn from that direction");
+    println!("Gamepad shoulder buttons - Slow down or speed up the simulation");
+    println!("Gamepad Start - Pause or resume");
+    println!("\nVehicle Colors:");
+    println!("Green - Going Straight");
+    println!("Yellow - Turning Left");
+    println!("Orange - Turning Right");
+    println!("Cyan - Cyclist");
+    println!("Blue - Bus");
+    let mut controls = Controls::new(config.platoons.size);
+    let mut console = Console::default();
+    let mut timing_editor = TimingEditor::default();
+    let mut mouse = Mouse::default();
+    let mut screenshot_requested = false;
+    // While the map editor is open the simulation stands still, and the mouse drags stop
+    // lines and lights instead of acting on the scene.
+    let mut editing = false;
+    let mut dragging: Option<Handle> = None;
+    let mut saved_layout = config.map;
+    // Closing the window skips the summary shown when the run is ended from the keyboard
+    // or by the scenario; dismissing the summary closes it too.
+    let mut closed = false;
+    let mut attract = AttractMode::new(config.attract.idle_secs);
 
-                if vehicle_off_screen(*vehicle) {
-                    to_remove.push(i);
+    'running: loop {
+        for event in event_pump.poll_iter() {
+            let input = matches!(
+                event,
+                Event::KeyDown { .. } |
+                    Event::MouseButtonDown { .. } |
+                    Event::MouseMotion { .. } |
+                    Event::ControllerButtonDown { .. }
+            );
+            // The input that ends the demo only wakes the window up.
+            if input && attract.input(&mut simulation) {
+                continue;
+            }
+            let action = match event {
+                Event::Quit { .. } => {
+                    closed = true;
+                    break 'running;
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    (mouse.x, mouse.y) = (x, y);
+                    if let Some(handle) = dragging {
+                        let (x, y) = simulation.view().to_world(x, y);
+                        let mut layout = *simulation.layout();
+                        layout.drag(handle, x, y);
+                        if let Err(e) = simulation.set_layout(layout) {
+                            tracing::warn!("{}", e);
+                        }
+                    }
+                    None
                 }
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    mouse = Mouse { x, y, down: true, clicked: true };
+                    // Clicks on the scene rather than the panel press a walk button or pick
+                    // a vehicle to trace, or in the editor grab what is under them. Those on
+                    // the minimap pan the camera there instead.
+                    let view = simulation.view();
+                    let (world_x, world_y) = view.to_world(x, y);
+                    let on_minimap = minimap::shows_more(&view).then(|| minimap::to_world(x, y));
+                    if let Some((map_x, map_y)) = on_minimap.flatten() {
+                        simulation.pan_to(map_x, map_y);
+                    } else if editing {
+                        dragging = simulation.layout().handle_at(world_x, world_y);
+                    } else if let Some(corner) = button_at(world_x, world_y) {
+                        simulation.press_walk_button(corner);
+                    } else if !over_panels(x, y) && !timing_editor.covers(x, y) {
+                        simulation.select_at(world_x, world_y);
+                    }
+                    None
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                    mouse.down = false;
+                    dragging = None;
+                    None
+                }
+                Event::TextInput { text, .. } => {
+                    if console.open {
+                        console.type_text(&text);
+                    }
+                    None
+                }
+                // The open console has the keyboard to itself, bar the key that closes it.
+                Event::KeyDown { keycode: Some(keycode), repeat, .. } if console.open => {
+                    let action = config.keymap.action_for(&keycode.name());
+                    match keycode {
+                        Keycode::Return | Keycode::KpEnter => {
+                            console.submit(&mut simulation, &mut controls.speed);
+                        }
+                        Keycode::Backspace => console.backspace(),
+                        Keycode::Escape => console.open = false,
+                        _ if !repeat && action == Some(Action::ToggleConsole) => {
+                            console.open = false;
+                        }
+                        _ => {}
+                    }
+                    None
+                }
+                // The open timing editor takes the arrows, Enter and Escape; other keys work
+                // as usual, so the lights can be watched while they are retimed.
+                Event::KeyDown { keycode: Some(keycode), .. } if
+                    timing_editor.open && is_timing_editor_key(keycode)
+                => {
+                    match keycode {
+                        Keycode::Up => timing_editor.select(-1),
+                        Keycode::Down => timing_editor.select(1),
+                        Keycode::Left => timing_editor.adjust(&mut simulation, -1.0),
+                        Keycode::Right => timing_editor.adjust(&mut simulation, 1.0),
+                        Keycode::Escape => timing_editor.open = false,
+                        _ => save_timing(&simulation, config_path)?,
+                    }
+                    None
+                }
+                Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. } => {
+                    let action = config.keymap.action_for(&keycode.name());
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let route = turn_route(
+                        keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+                        keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+                    );
+                    let picked = action.is_some_and(|a| {
+                        route.is_some_and(|route| spawn_on_route(a, route, &mut simulation)) ||
+                            (shift && controls.pick_trip_end(a, &mut simulation)) ||
+                            controls.pick_platoon_approach(a, &mut simulation)
+                    });
+                    if picked {
+                        continue;
+                    }
+                    action
+                }
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(action) = config.keymap.action_for(&keycode.name()) {
+                        controls.release(action, &mut simulation);
+                    }
+                    None
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    gamepads.connected(which);
+                    None
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    gamepads.disconnected(which);
+                    None
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    match gamepad::input_for(button) {
+                        Some(GamepadInput::Action(action)) => Some(action),
+                        Some(GamepadInput::Faster) => {
+                            controls.change_speed(true);
+                            None
+                        }
+                        Some(GamepadInput::Slower) => {
+                            controls.change_speed(false);
+                            None
+                        }
+                        None => None,
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(GamepadInput::Action(action)) = gamepad::input_for(button) {
+                        controls.release(action, &mut simulation);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            match action {
+                Some(Action::Quit) => {
+                    break 'running;
+                }
+                Some(Action::Screenshot) => {
+                    screenshot_requested = true;
+                }
+                Some(Action::ToggleSound) => {
+                    let muted = audio.toggle_mute();
+                    tracing::info!("sound {}", if muted { "off" } else { "on" });
+                }
+                Some(Action::ToggleConsole) => console.toggle(),
+                Some(Action::ToggleTimingEditor) => timing_editor.toggle(),
+                Some(Action::ToggleEditor) => {
+                    editing = !editing;
+                    dragging = None;
+                    if editing {
+                        tracing::info!("map editor open, drag stop lines and lights");
+                    } else if *simulation.layout() != saved_layout {
+                        saved_layout = *simulation.layout();
+                        saved_layout.save(map_path)?;
+                        tracing::info!("map saved to {}", map_path.display());
+                    }
+                }
+                Some(action) => controls.press(action, &mut simulation),
+                None => {}
+            }
+        }
+        if let Some(remote) = &remote {
+            remote.poll(&mut simulation);
+        }
+        if attract.update(&mut simulation) {
+            controls.paused = false;
+            controls.speed = 1;
+            editing = false;
+            console.open = false;
+            timing_editor.open = false;
+        }
+        let ticks = pacer.ticks();
+        if !editing {
+            controls.step(&mut simulation, ticks, &mut traces)?;
+        }
+        if simulation.scenario_ended() {
+            tracing::info!("scenario ended");
+            break 'running;
+        }
+        if let Some(metrics) = &metrics {
+            metrics.update(&simulation);
+        }
+        let events = simulation.drain_events();
+        audio.handle_events(&events);
+        simulation.render(&mut renderer)?;
+        if screenshot_requested || recorder.is_some() {
+            let frame = renderer.capture()?;
+            if screenshot_requested {
+                let path = capture::save_screenshot(&frame)?;
+                tracing::info!("saved screenshot to {}", path.display());
+                screenshot_requested = false;
             }
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(&frame)?;
+            }
+        }
+        // Drawn after any capture so screenshots and recordings show only the scene.
+        if editing {
+            simulation.layout().draw_handles(&mut renderer, &simulation.view(), dragging)?;
+        }
+        if draw_control_panel(&mut renderer, mouse, &mut controls, &mut simulation)? {
+            controls.apply(Action::Reset, &mut simulation);
+        }
+        draw_demand_panel(&mut renderer, mouse, &mut simulation)?;
+        draw_level_of_service(&mut renderer, mouse, &simulation)?;
+        if timing_editor.open && timing_editor.draw(&mut renderer, mouse, &mut simulation)? {
+            save_timing(&simulation, config_path)?;
         }
-        for &i in to_remove.iter().rev() {
-            self.vehicles.remove(i);
+        if console.open {
+            console.draw(&mut renderer, simulation.theme().palette())?;
         }
+        attract.draw(&mut renderer, &simulation)?;
+        mouse.clicked = false;
+        renderer.present()?;
+        pacer.wait();
     }
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.finish()?;
+    }
+    // The summary stays up over the last frame until a key or click, or the window closes.
+    while !closed {
+        for event in event_pump.poll_iter() {
+            if matches!(
+                event,
+                Event::Quit { .. } | Event::KeyDown { .. } | Event::MouseButtonDown { .. }
+            ) {
+                closed = true;
+            }
+        }
+        simulation.render(&mut renderer)?;
+        draw_run_summary(&mut renderer, &simulation)?;
+        renderer.present()?;
+        pacer.wait();
+    }
+    traces.finish()?;
+    // Only what was set by hand is kept, not the demo's traffic.
+    attract.stop(&mut simulation);
+    // Demand set on the sliders carries over to the next run.
+    let demand = &simulation.demand;
+    if demand.vehicles_per_minute != config.demand.vehicles_per_minute ||
+        demand.approaches != config.demand.approaches
+    {
+        demand.save(config_path)?;
+        tracing::info!("demand saved to {}", config_path.display());
+    }
+    Ok(simulation.stats)
 }
 
-fn calculate_distance(v1: Vehicle, v2: Vehicle) -> f32 {
-    ((v1.x - v2.x).powi(2) + (v1.y - v2.y).powi(2)).sqrt()
-}
+// The configured light controller and `controller` side by side on the same seed and
+// demand, until the window is closed or the scenario ends. Only pausing, speed, reset and
+// screenshots are taken from the keyboard, so both runs see the same traffic.
+fn run_compare(config: &Config, controller: &str) -> Result<(), SimError> {
+    let mut comparison = Comparison::new(config, controller)?;
+    let sdl_context = sdl2::init().map_err(SimError::Video)?;
+    let video_subsystem = sdl_context.video().map_err(SimError::Video)?;
+    let (canvas, vsync) = open_canvas(&video_subsystem, config.display.vsync)?;
+    let mut renderer = SdlRenderer::new(canvas)?;
+    let mut pacer = FramePacer::new(config.display.target_fps, vsync, config.clock.tick());
+    let mut event_pump = sdl_context.event_pump().map_err(SimError::Video)?;
+    let mut paused = false;
+    let mut speed = 1;
+    tracing::info!(
+        "comparing {} with {}",
+        comparison.controllers[0],
+        comparison.controllers[1]
+    );
 
-fn move_vehicle(vehicle: &mut Vehicle) {
-    match vehicle.direction {
-        Direction::North => {
-            vehicle.y -= VEHICLE_SPEED as f32;
+    'running: loop {
+        let mut screenshot_requested = false;
+        for event in event_pump.poll_iter() {
+            let action = match event {
+                Event::Quit { .. } => {
+                    break 'running;
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    config.keymap.action_for(&keycode.name())
+                }
+                _ => None,
+            };
+            match action {
+                Some(Action::Quit) => {
+                    break 'running;
+                }
+                Some(Action::Pause) => paused = !paused,
+                Some(Action::SpeedUp) => {
+                    speed = if speed >= 4 { 1 } else { speed * 2 };
+                    tracing::info!("simulation speed {}x", speed);
+                }
+                Some(Action::Reset) => comparison.reset(),
+                Some(Action::Screenshot) => screenshot_requested = true,
+                _ => {}
+            }
         }
-        Direction::South => {
-            vehicle.y += VEHICLE_SPEED as f32;
+        let ticks = pacer.ticks();
+        if !paused {
+            for _ in 0..ticks * speed {
+                comparison.update();
+            }
         }
-        Direction::East => {
-            vehicle.x += VEHICLE_SPEED as f32;
+        if comparison.scenario_ended() {
+            tracing::info!("scenario ended");
+            break 'running;
         }
-        Direction::West => {
-            vehicle.x -= VEHICLE_SPEED as f32;
+        comparison.render(&mut renderer)?;
+        if screenshot_requested {
+            let path = capture::save_screenshot(&renderer.capture()?)?;
+            tracing::info!("saved screenshot to {}", path.display());
         }
+        renderer.present()?;
+        pacer.wait();
     }
-
-    handle_route_change(vehicle);
+    println!();
+    for (run, controller) in comparison.runs.iter().zip(&comparison.controllers) {
+        println!(
+            "{}: {} vehicles served, average delay {:.1}s, longest queue {}",
+            controller,
+            run.stats.vehicles_completed,
+            run.stats.average_vehicle_delay().as_secs_f32(),
+            run.stats.max_queue
+        );
+    }
+    Ok(())
 }
 
-fn handle_route_change(vehicle: &mut Vehicle) {
-    let center_x = (WINDOW_WIDTH as f32) / 2.0;
-    let center_y = (WINDOW_HEIGHT as f32) / 2.0;
-
-    if vehicle.route != Route::Straight && !vehicle.has_turned {
-        let should_turn = match (vehicle.direction, vehicle.route) {
-            (Direction::North, Route::Left) => vehicle.y <= center_y,
-            (Direction::South, Route::Left) => vehicle.y >= center_y,
-            (Direction::East, Route::Left) => vehicle.x >= center_x,
-            (Direction::West, Route::Left) => vehicle.x <= center_x,
-
-            (Direction::North, Route::Right) => vehicle.y <= center_y + (VEHICLE_SIZE as f32),
-            (Direction::South, Route::Right) => vehicle.y >= center_y - (VEHICLE_SIZE as f32),
-            (Direction::East, Route::Right) => vehicle.x >= center_x - (VEHICLE_SIZE as f32),
-            (Direction::West, Route::Right) => vehicle.x <= center_x + (VEHICLE_SIZE as f32),
+// Steps through a `--record-states` recording over the road of the config and map given,
+// with a scrub bar to seek and the ticks with collisions and gridlocks marked on it.
+fn run_playback(config: &Config, tape: Tape) -> Result<(), SimError> {
+    let sdl_context = sdl2::init().map_err(SimError::Video)?;
+    let video_subsystem = sdl_context.video().map_err(SimError::Video)?;
+    let (canvas, vsync) = open_canvas(&video_subsystem, config.display.vsync)?;
+    let mut renderer = SdlRenderer::new(canvas)?;
+    let mut pacer = FramePacer::new(config.display.target_fps, vsync, tape.tick());
+    let mut event_pump = sdl_context.event_pump().map_err(SimError::Video)?;
+    let mut simulation = TrafficSimulation::with_config(config)?;
+    let mut playback = Playback::new(tape);
+    let mut mouse = Mouse::default();
+    tracing::info!("playing back {} ticks", playback.tape.ticks.len());
+    println!("Playback controls:");
+    println!("{} - Pause or resume", config.keymap.pause);
+    println!("Right / Left - Fast-forward / rewind, faster with each press");
+    println!(", / . - Step back / forward one tick");
+    println!("Page Up / Page Down - Previous / next collision or gridlock");
+    println!("Home / End - Start / end of the recording");
+    println!("{} - Quit", config.keymap.quit);
 
-            _ => false,
-        };
-
-        if should_turn {
-            match vehicle.route {
-                Route::Left => {
-                    vehicle.direction = match vehicle.direction {
-                        Direction::North => Direction::West,
-                        Direction::South => Direction::East,
-                        Direction::East => Direction::North,
-                        Direction::West => Direction::South,
-                    };
-                }
-                Route::Right => {
-                    vehicle.direction = match vehicle.direction {
-                        Direction::North => Direction::East,
-                        Direction::South => Direction::West,
-                        Direction::East => Direction::South,
-                        Direction::West => Direction::North,
-                    };
+    'running: loop {
+        let mut screenshot_requested = false;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    break 'running;
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    (mouse.x, mouse.y) = (x, y);
+                }
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    (mouse.x, mouse.y) = (x, y);
+                    mouse.down = true;
+                    mouse.clicked = true;
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                    mouse.down = false;
+                }
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    match keycode {
+                        Keycode::Right => playback.fast_forward(),
+                        Keycode::Left => playback.rewind(),
+                        Keycode::Period => playback.step(true),
+                        Keycode::Comma => playback.step(false),
+                        Keycode::Home => playback.seek(0),
+                        Keycode::End => playback.seek(usize::MAX),
+                        Keycode::PageDown | Keycode::PageUp => {
+                            if !playback.jump_to_mark(keycode == Keycode::PageDown) {
+                                tracing::info!("no more collisions or gridlocks that way");
+                            }
+                        }
+                        _ => {
+                            match config.keymap.action_for(&keycode.name()) {
+                                Some(Action::Quit) => {
+                                    break 'running;
+                                }
+                                Some(Action::Pause) => playback.paused = !playback.paused,
+                                Some(Action::Screenshot) => screenshot_requested = true,
+                                _ => {}
+                            }
+                        }
+                    }
                 }
                 _ => {}
             }
-            vehicle.has_turned = true;
         }
+        playback.advance(pacer.ticks());
+        playback.pose(&mut simulation);
+        simulation.render(&mut renderer)?;
+        if screenshot_requested {
+            let path = capture::save_screenshot(&renderer.capture()?)?;
+            tracing::info!("saved screenshot to {}", path.display());
+        }
+        playback.draw(&mut renderer, simulation.theme().palette(), mouse)?;
+        mouse.clicked = false;
+        renderer.present()?;
+        pacer.wait();
     }
+    Ok(())
 }
 
-fn vehicle_off_screen(vehicle: Vehicle) -> bool {
-    vehicle.x < -(VEHICLE_SIZE as f32) ||
-        vehicle.x > (WINDOW_WIDTH as f32) + (VEHICLE_SIZE as f32) ||
-        vehicle.y < -(VEHICLE_SIZE as f32) ||
-        vehicle.y > (WINDOW_HEIGHT as f32) + (VEHICLE_SIZE as f32)
+// The window with an accelerated renderer, or a software one where there is no GPU to
+// draw with, presenting in step with the display if `vsync` is asked for. Also returns
+// whether the renderer actually waits for vsync, which not all of them can.
+fn open_canvas(video: &VideoSubsystem, vsync: bool) -> Result<(Canvas<Window>, bool), SimError> {
+    let window = || {
+        video
+            .window("Traffic Intersection Simulation", WINDOW_WIDTH, WINDOW_HEIGHT)
+            .position_centered()
+            .allow_highdpi()
+            .resizable()
+            .build()
+            .map_err(SimError::Window)
+    };
+    let canvas = |builder: CanvasBuilder| {
+        if vsync { builder.present_vsync() } else { builder }
+    };
+    let canvas = match canvas(window()?.into_canvas().accelerated()).build() {
+        Ok(canvas) => canvas,
+        Err(e) => {
+            tracing::warn!("no accelerated renderer ({}), drawing in software", e);
+            canvas(window()?.into_canvas().software()).build().map_err(SimError::Canvas)?
+        }
+    };
+    let vsync_flag = SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32;
+    let synced = canvas.info().flags & vsync_flag != 0;
+    if vsync && !synced {
+        tracing::info!("no vsync on this renderer, limiting the frame rate instead");
+    }
+    Ok((canvas, synced))
 }
 
-fn get_route_color(route: Route) -> Color {
-    match route {
-        Route::Straight => Color::RGB(0, 255, 0),
-        Route::Left => Color::RGB(255, 255, 0),
-        Route::Right => Color::RGB(255, 165, 0),
+// Runs `ticks` updates with no window, as fast as the CPU allows or at `speed` times real
+// time, for traffic driven by the configured demand and scenario.
+fn run_batch(
+    config: &Config,
+    ticks: u64,
+    speed: Option<f32>,
+    metrics: Option<MetricsServer>,
+    mut traces: Traces
+) -> Result<Stats, SimError> {
+    let mut simulation = TrafficSimulation::with_config(config)?;
+    let metrics_every = BATCH_METRICS_INTERVAL.as_nanos() / simulation.tick().as_nanos();
+    let metrics_every = (metrics_every as u64).max(1);
+    let started = Instant::now();
+    for tick in 1..=ticks {
+        if simulation.scenario_ended() {
+            break;
+        }
+        simulation.update();
+        simulation.drain_events();
+        traces.record(&simulation)?;
+        if tick.is_multiple_of(metrics_every) {
+            if let Some(metrics) = &metrics {
+                metrics.update(&simulation);
+            }
+        }
+        if let Some(speed) = speed {
+            let due = simulation.tick().mul_f64((tick as f64) / (speed as f64));
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
     }
+    tracing::info!(
+        "simulated {:.0}s of traffic in {:.1}s",
+        simulation.time.now().as_secs_f32(),
+        started.elapsed().as_secs_f32()
+    );
+    traces.finish()?;
+    Ok(simulation.stats)
 }
 
-struct TrafficSimulation {
-    lanes: [Lane; 4],
+// Files written from the state of the road as the simulation runs, rather than from its
+// stats at the end.
+struct Traces {
+    fcd: Option<FcdWriter>,
+    tape: Option<TapeWriter>,
 }
 
-impl TrafficSimulation {
-    fn new() -> Self {
-        Self {
-            lanes: [
-                Lane::new(Direction::North),
-                Lane::new(Direction::South),
-                Lane::new(Direction::East),
-                Lane::new(Direction::West),
-            ],
+impl Traces {
+    fn record(&mut self, simulation: &TrafficSimulation) -> Result<(), SimError> {
+        if let Some(fcd) = self.fcd.as_mut() {
+            fcd.record(simulation)?;
+        }
+        if let Some(tape) = self.tape.as_mut() {
+            tape.record(simulation)?;
         }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SimError> {
+        if let Some(fcd) = self.fcd {
+            fcd.finish()?;
+        }
+        if let Some(tape) = self.tape {
+            tape.finish()?;
+        }
+        Ok(())
     }
-    fn update(&mut self) {
-        for lane in &mut self.lanes {
-            lane.update();
+}
+
+// Key-driven state shared by both front ends.
+struct Controls {
+    last_spawn_time: Instant,
+    // Spawn actions whose key is down; they keep spawning at the cooldown rate.
+    held: Vec<Action>,
+    paused: bool,
+    // Simulated time per real time.
+    speed: u32,
+    // Road end picked as the origin of a trip, waiting for its destination.
+    trip_from: Option<Direction>,
+    // The platoon key was pressed and the next spawn arrow picks the platoon's approach.
+    platoon_armed: bool,
+    platoon_size: u32,
+}
+
+impl Controls {
+    fn new(platoon_size: u32) -> Self {
+        Self {
+            last_spawn_time: Instant::now(),
+            held: Vec::new(),
+            paused: false,
+            speed: 1,
+            trip_from: None,
+            platoon_armed: false,
+            platoon_size,
         }
     }
-    fn spawn_vehicle(&mut self, direction: Direction) {
-        let lane_index = match direction {
-            Direction::North => 0,
-            Direction::South => 1,
-            Direction::East => 2,
-            Direction::West => 3,
+
+    // With Shift held, the spawn arrows pick road ends by the way they point: the first
+    // press picks a trip's origin and the second its destination. Returns whether `action`
+    // was one of those arrows.
+    fn pick_trip_end(&mut self, action: Action, simulation: &mut TrafficSimulation) -> bool {
+        let Some(end) = arrow_direction(action) else {
+            return false;
+        };
+        let name = node_name(node_id(end));
+        let Some(from) = self.trip_from.take() else {
+            self.trip_from = Some(end);
+            tracing::info!("trip from the {} end, pick its destination", name);
+            return true;
         };
-        self.lanes[lane_index].spawn_vehicle();
+        let from_name = node_name(node_id(from));
+        match simulation.spawn_trip(from, end) {
+            Ok(true) => tracing::info!("trip from the {} end to the {} end", from_name, name),
+            Ok(false) => {
+                tracing::info!("road from the {} end is full, trip queued upstream", from_name);
+            }
+            Err(e) => tracing::warn!("{}", e),
+        }
+        true
     }
 
-    fn spawn_random_vehicle(&mut self) {
-        let mut rng = rand::thread_rng();
-        let direction = match rng.gen_range(0..4) {
-            0 => Direction::North,
-            1 => Direction::South,
-            2 => Direction::East,
-            _ => Direction::West,
+    // Once the platoon key has been pressed, the next spawn arrow injects the platoon on
+    // its approach. Returns whether `action` was that arrow.
+    fn pick_platoon_approach(
+        &mut self,
+        action: Action,
+        simulation: &mut TrafficSimulation
+    ) -> bool {
+        let Some(direction) = arrow_direction(action).filter(|_| self.platoon_armed) else {
+            return false;
         };
-        self.spawn_vehicle(direction);
+        self.platoon_armed = false;
+        simulation.spawn_platoon(direction, self.platoon_size);
+        tracing::info!("platoon of {} travelling {:?}", self.platoon_size, direction);
+        true
     }
 
-    fn render(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
-        canvas.set_draw_color(Color::RGB(50, 50, 50));
-        canvas.clear();
-        self.draw_roads(canvas)?;
-        self.draw_vehicles(canvas)?;
-        canvas.present();
-        Ok(())
+    fn press(&mut self, action: Action, simulation: &mut TrafficSimulation) {
+        if action.is_spawn() && !self.held.contains(&action) {
+            self.held.push(action);
+        }
+        self.apply(action, simulation);
     }
 
-    fn draw_roads(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
-        canvas.set_draw_color(Color::RGB(100, 100, 100));
-        let center_x = (WINDOW_WIDTH as i32) / 2;
-        let center_y = (WINDOW_HEIGHT as i32) / 2;
-        let h_road = Rect::new(0, center_y - ROAD_WIDTH / 2, WINDOW_WIDTH, ROAD_WIDTH as u32);
-        canvas.fill_rect(h_road)?;
-        let v_road = Rect::new(center_x - ROAD_WIDTH / 2, 0, ROAD_WIDTH as u32, WINDOW_HEIGHT);
-        canvas.fill_rect(v_road)?;
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        let intersection_half_size = ROAD_WIDTH / 2 + 10;
-        for x in (0..WINDOW_WIDTH as i32).step_by(20) {
-            if !(x > center_x - intersection_half_size && x < center_x + intersection_half_size) {
-                let rect = Rect::new(x, center_y - 1, 10, 2);
-                canvas.fill_rect(rect)?;
+    // Doubles or halves the simulation speed, between 1x and 4x.
+    fn change_speed(&mut self, faster: bool) {
+        self.speed = if faster { (self.speed * 2).min(4) } else { (self.speed / 2).max(1) };
+        tracing::info!("simulation speed {}x", self.speed);
+    }
+
+    // Letting go of a manual phase key gives that road its green.
+    fn release(&mut self, action: Action, simulation: &mut TrafficSimulation) {
+        self.held.retain(|&held| held != action);
+        if let Some(phase) = manual_phase(action) {
+            simulation.override_lights(Some(phase));
+        }
+    }
+
+    // Handles the actions that only touch the simulation.
+    fn apply(&mut self, action: Action, simulation: &mut TrafficSimulation) {
+        if action.is_spawn() {
+            if self.last_spawn_time.elapsed() < SPAWN_KEY_COOLDOWN {
+                return;
             }
+            self.last_spawn_time = Instant::now();
         }
-        for y in (0..WINDOW_HEIGHT as i32).step_by(20) {
-            if !(y > center_y - intersection_half_size && y < center_y + intersection_half_size) {
-                let rect = Rect::new(center_x - 1, y, 2, 10);
-                canvas.fill_rect(rect)?;
+        match action {
+            Action::SpawnNorth => simulation.spawn_vehicle(Direction::North),
+            Action::SpawnSouth => simulation.spawn_vehicle(Direction::South),
+            Action::SpawnEast => simulation.spawn_vehicle(Direction::East),
+            Action::SpawnWest => simulation.spawn_vehicle(Direction::West),
+            Action::SpawnRandom => simulation.spawn_random_vehicle(),
+            Action::SpawnCyclist => simulation.spawn_random_cyclist(),
+            Action::SpawnBus => simulation.spawn_bus(),
+            Action::SpawnPlatoon => {
+                self.platoon_armed = !self.platoon_armed;
+                if self.platoon_armed {
+                    tracing::info!("pick the platoon's approach with a spawn arrow");
+                }
+            }
+            Action::CycleWeather => simulation.set_weather(simulation.weather.next()),
+            Action::ToggleHeatmap => simulation.show_heatmap = !simulation.show_heatmap,
+            Action::ToggleCounts => simulation.show_counts = !simulation.show_counts,
+            Action::ToggleNoise => simulation.show_noise = !simulation.show_noise,
+            Action::SelectNext => simulation.select_next_vehicle(),
+            Action::CallWalk => simulation.press_random_walk_button(),
+            Action::SendTrain => simulation.send_train(),
+            Action::ToggleClosure => simulation.toggle_random_closure(),
+            Action::CauseIncident => {
+                simulation.cause_incident(None);
             }
+            Action::ToggleWebster => {
+                simulation.toggle_webster();
+                let measuring = simulation.webster.is_some();
+                tracing::info!("Webster analysis {}", if measuring { "started" } else { "off" });
+            }
+            Action::ApplyWebster => {
+                if !simulation.apply_webster_timing() {
+                    tracing::info!("no Webster timing yet, every road needs measured flows");
+                }
+            }
+            Action::ToggleManual => simulation.toggle_manual_control(),
+            Action::ManualNorthSouth | Action::ManualEastWest => {
+                if !simulation.traffic_light().is_manual() {
+                    tracing::info!("take manual control first to set the lights by hand");
+                }
+                simulation.override_lights(None);
+            }
+            Action::Pause => {
+                self.paused = !self.paused;
+                tracing::info!("{}", if self.paused { "paused" } else { "resumed" });
+            }
+            Action::SpeedUp => {
+                self.speed = if self.speed >= 4 { 1 } else { self.speed * 2 };
+                tracing::info!("simulation speed {}x", self.speed);
+            }
+            Action::Reset => {
+                simulation.reset(None);
+                tracing::info!("simulation reset");
+            }
+            Action::ToggleEditor |
+            Action::ToggleTimingEditor |
+            Action::ToggleConsole |
+            Action::Screenshot |
+            Action::ToggleSound |
+            Action::Quit => {}
         }
+    }
 
+    // Runs `ticks` of simulated time at normal speed, more when sped up, writing the traces
+    // after each.
+    fn step(
+        &mut self,
+        simulation: &mut TrafficSimulation,
+        ticks: u32,
+        traces: &mut Traces
+    ) -> Result<(), SimError> {
+        for action in self.held.clone() {
+            self.apply(action, simulation);
+        }
+        if !self.paused {
+            for _ in 0..ticks * self.speed {
+                simulation.update();
+                traces.record(simulation)?;
+            }
+        }
         Ok(())
     }
+}
+
+// The road a manual phase key gives the green to.
+fn manual_phase(action: Action) -> Option<Phase> {
+    match action {
+        Action::ManualNorthSouth => Some(Phase::NorthSouth),
+        Action::ManualEastWest => Some(Phase::EastWest),
+        _ => None,
+    }
+}
+
+// The road direction a spawn arrow points, for the actions that are spawn arrows.
+fn arrow_direction(action: Action) -> Option<Direction> {
+    match action {
+        Action::SpawnNorth => Some(Direction::North),
+        Action::SpawnSouth => Some(Direction::South),
+        Action::SpawnEast => Some(Direction::East),
+        Action::SpawnWest => Some(Direction::West),
+        _ => None,
+    }
+}
+
+// The route a spawn arrow's vehicle takes with Ctrl and Alt held: Ctrl turns it right, Alt
+// left and both send it straight on.
+fn turn_route(ctrl: bool, alt: bool) -> Option<Route> {
+    match (ctrl, alt) {
+        (true, false) => Some(Route::Right),
+        (false, true) => Some(Route::Left),
+        (true, true) => Some(Route::Straight),
+        (false, false) => None,
+    }
+}
+
+// Spawns a vehicle travelling the way a spawn arrow points that takes `route`. Returns
+// whether `action` was a spawn arrow.
+fn spawn_on_route(action: Action, route: Route, simulation: &mut TrafficSimulation) -> bool {
+    let Some(direction) = arrow_direction(action) else {
+        return false;
+    };
+    match simulation.spawn_vehicle_with_route(direction, route) {
+        Ok(true) => tracing::info!("{:?} vehicle travelling {:?}", route, direction),
+        Ok(false) => tracing::info!("{:?} approach is full, vehicle queued upstream", direction),
+        Err(e) => tracing::warn!("{}", e),
+    }
+    true
+}
+
+fn panel_area() -> Rect {
+    Rect::new((WINDOW_WIDTH as i32) - 260, 10, 250, 200)
+}
+
+// Below the weather and clock in the top left corner.
+fn demand_panel_area() -> Rect {
+    Rect::new(10, 56, 250, 164)
+}
+
+// In the bottom left corner.
+fn level_of_service_area() -> Rect {
+    Rect::new(10, (WINDOW_HEIGHT as i32) - 132, 250, 122)
+}
+
+fn is_timing_editor_key(keycode: Keycode) -> bool {
+    matches!(
+        keycode,
+        Keycode::Up |
+            Keycode::Down |
+            Keycode::Left |
+            Keycode::Right |
+            Keycode::Return |
+            Keycode::KpEnter |
+            Keycode::Escape
+    )
+}
+
+// Writes the lights' current timing into the config file, for the next run.
+fn save_timing(simulation: &TrafficSimulation, config_path: &Path) -> Result<(), SimError> {
+    LightsConfig::from_light(&simulation.traffic_light()).save(config_path)?;
+    tracing::info!("signal timing saved to {}", config_path.display());
+    Ok(())
+}
+
+fn over_panels(x: i32, y: i32) -> bool {
+    let point = Rect::new(x, y, 1, 1);
+    [panel_area(), demand_panel_area(), level_of_service_area(), minimap::minimap_rect()]
+        .iter()
+        .any(|area| area.intersects(&point))
+}
+
+// Mouse-driven sliders and buttons in the top right corner; returns whether a reset was
+// asked for.
+fn draw_control_panel(
+    renderer: &mut dyn Renderer,
+    mouse: Mouse,
+    controls: &mut Controls,
+    simulation: &mut TrafficSimulation
+) -> Result<bool, RenderError> {
+    let mut panel = Panel::begin(renderer, simulation.theme().palette(), mouse, panel_area())?;
+    let rate = simulation.demand.vehicles_per_minute;
+    let label = format!("SPAWN RATE {:.0}/MIN", rate);
+    if let Some(rate) = panel.slider(&label, rate, 0.0..=120.0)? {
+        simulation.demand.vehicles_per_minute = rate.round();
+    }
+    let light = simulation.traffic_light_mut();
+    let green = light.green_time.as_secs_f32();
+    if let Some(green) = panel.slider(&format!("GREEN {:.0}S", green), green, 2.0..=30.0)? {
+        light.green_time = Duration::from_secs_f32(green.round());
+    }
+    let yellow = light.yellow_time.as_secs_f32();
+    if let Some(yellow) = panel.slider(&format!("YELLOW {:.0}S", yellow), yellow, 1.0..=6.0)? {
+        light.yellow_time = Duration::from_secs_f32(yellow.round());
+    }
+    let speed = controls.speed;
+    if let Some(speed) = panel.slider(&format!("SPEED {}X", speed), speed as f32, 1.0..=4.0)? {
+        controls.speed = speed.round() as u32;
+    }
+    match panel.buttons(&[if controls.paused { "RESUME" } else { "PAUSE" }, "RESET"])? {
+        Some(0) => controls.paused = !controls.paused,
+        Some(_) => return Ok(true),
+        None => {}
+    }
+    Ok(false)
+}
+
+// A slider per approach for its own arrival rate.
+fn draw_demand_panel(
+    renderer: &mut dyn Renderer,
+    mouse: Mouse,
+    simulation: &mut TrafficSimulation
+) -> Result<(), RenderError> {
+    let palette = simulation.theme().palette();
+    let mut panel = Panel::begin(renderer, palette, mouse, demand_panel_area())?;
+    for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+        let rate = simulation.demand.approaches.rate_mut(direction);
+        let label = format!("{}BOUND {:.0}/MIN", format!("{:?}", direction).to_uppercase(), rate);
+        if let Some(value) = panel.slider(&label, *rate, 0.0..=60.0)? {
+            *rate = value.round();
+        }
+    }
+    Ok(())
+}
 
-    fn draw_vehicles(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
-        let center_x = (WINDOW_WIDTH as i32) / 2;
-        let center_y = (WINDOW_HEIGHT as i32) / 2;
-        let vehicle_half = VEHICLE_SIZE / 2;
-
-        for lane in &self.lanes {
-            for vehicle in &lane.vehicles {
-                canvas.set_draw_color(vehicle.color);
-
-                let (x, y) = match vehicle.direction {
-                    Direction::North => (center_x + vehicle_half + 10, vehicle.y as i32),
-                    Direction::South => (center_x - vehicle_half - 10, vehicle.y as i32),
-                    Direction::East => (vehicle.x as i32, center_y + vehicle_half + 10),
-                    Direction::West => (vehicle.x as i32, center_y - vehicle_half - 10),
-                };
-
-                let rect = Rect::new(
-                    x - vehicle_half,
-                    y - vehicle_half,
-                    VEHICLE_SIZE as u32,
-                    VEHICLE_SIZE as u32
-                );
-                canvas.fill_rect(rect)?;
+// Each approach's mean control delay so far and its grade.
+fn draw_level_of_service(
+    renderer: &mut dyn Renderer,
+    mouse: Mouse,
+    simulation: &TrafficSimulation
+) -> Result<(), RenderError> {
+    let stats = &simulation.stats;
+    let palette = simulation.theme().palette();
+    let mut panel = Panel::begin(renderer, palette, mouse, level_of_service_area())?;
+    panel.label("LEVEL OF SERVICE")?;
+    for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+        let name = format!("{:?}", direction).to_uppercase();
+        let grade = match stats.average_control_delay(direction) {
+            Some(delay) => {
+                format!("{} {:.1}S", level_of_service(delay), delay.as_secs_f32())
             }
+            None => "-".to_string(),
+        };
+        panel.label(&format!("{}BOUND {}", name, grade))?;
+    }
+    Ok(())
+}
+
+// Totals for the run in the middle of the window, shown once it has ended.
+fn draw_run_summary(
+    renderer: &mut dyn Renderer,
+    simulation: &TrafficSimulation
+) -> Result<(), RenderError> {
+    let stats = &simulation.stats;
+    let (x, y) = ((WINDOW_WIDTH as i32) / 2 - 160, (WINDOW_HEIGHT as i32) / 2 - 88);
+    let area = Rect::new(x, y, 320, 176);
+    let palette = simulation.theme().palette();
+    let mut panel = Panel::begin(renderer, palette, Mouse::default(), area)?;
+    panel.label("RUN SUMMARY")?;
+    panel.label(&format!("ELAPSED {}", simulation.time.label()))?;
+    panel.label(&format!("VEHICLES SERVED {}", stats.vehicles_completed))?;
+    let delay = stats.average_vehicle_delay().as_secs_f32();
+    panel.label(&format!("AVERAGE DELAY {:.1}S", delay))?;
+    panel.label(&format!("COLLISIONS {}", stats.collisions))?;
+    panel.label(&format!("MAX QUEUE {}", stats.max_queue))?;
+    panel.label("PRESS ANY KEY TO EXIT")
+}
+
+// The closing report of a run, on stdout rather than in the logs.
+fn print_stats(stats: &Stats) {
+    println!("\nVehicles served: {}", stats.vehicles_completed);
+    println!("Average vehicle delay: {:.1}s", stats.average_vehicle_delay().as_secs_f32());
+    println!("Buses served: {}", stats.buses_completed);
+    println!("Average bus delay: {:.1}s", stats.average_bus_delay().as_secs_f32());
+    println!(
+        "Pedestrians served: {} (average wait {:.1}s)",
+        stats.pedestrians_served,
+        stats.average_pedestrian_wait().as_secs_f32()
+    );
+    println!(
+        "Fuel used: {:.2} l, {:.2} kg CO2 ({:.0} ml and {:.0} g per vehicle)",
+        stats.fuel_used,
+        stats.co2_emitted,
+        stats.fuel_per_vehicle() * 1000.0,
+        stats.co2_per_vehicle() * 1000.0
+    );
+    if let Some(summary) = stats.stop_summary() {
+        println!(
+            "Stops per vehicle: {:.2}, at most {} ({:.0}% never stopped)",
+            summary.mean_stops,
+            summary.max_stops,
+            summary.no_stop_share * 100.0
+        );
+        println!(
+            "Idle time per vehicle (mean / median / 95th percentile): {:.1}s / {:.1}s / {:.1}s",
+            summary.mean_idle.as_secs_f32(),
+            summary.median_idle.as_secs_f32(),
+            summary.percentile_95_idle.as_secs_f32()
+        );
+    }
+    let vehicle_time: Duration = stats.state_time.iter().sum();
+    if !vehicle_time.is_zero() {
+        let shares: Vec<String> = VehicleState::ALL
+            .iter()
+            .map(|state| {
+                let share = stats.state_time[state.index()].as_secs_f32() /
+                    vehicle_time.as_secs_f32();
+                format!("{} {:.0}%", state.label(), share * 100.0)
+            })
+            .collect();
+        println!("Vehicle time by state: {}", shares.join(", "));
+    }
+    if let Some([ne, nw, se, sw]) = stats.noise_levels() {
+        println!(
+            "Noise at the corners (NE / NW / SE / SW): {:.1} / {:.1} / {:.1} / {:.1} dB(A)",
+            ne,
+            nw,
+            se,
+            sw
+        );
+    }
+    println!("Collisions: {}", stats.collisions);
+    println!("Longest queue: {} vehicles", stats.max_queue);
+    println!("Trains: {}", stats.trains);
+    println!("Wrecks: {}", stats.wrecks);
+    if stats.bay_overflows > 0 {
+        println!(
+            "Turn bay overflows: {} (through lanes blocked for {:.1}s)",
+            stats.bay_overflows,
+            stats.bay_overflow_time.as_secs_f32()
+        );
+    }
+    if stats.lane_change_violations > 0 {
+        println!("Lane changes wanted in no-change zones: {}", stats.lane_change_violations);
+    }
+    if stats.route_reassignments > 0 {
+        println!("Routes changed to avoid saturated exits: {}", stats.route_reassignments);
+    }
+    if stats.jaywalkers > 0 || stats.emergency_brakes > 0 {
+        println!(
+            "Jaywalkers: {} ({} conflicts, {} of them near misses; {} emergency brakes)",
+            stats.jaywalkers,
+            stats.jaywalker_conflicts,
+            stats.jaywalker_near_misses,
+            stats.emergency_brakes
+        );
+    }
+    if stats.work_zone_vehicles > 0 {
+        println!(
+            "Vehicles through work zones: {} (average delay {:.1}s, against {:.1}s elsewhere)",
+            stats.work_zone_vehicles,
+            stats.average_work_zone_delay().as_secs_f32(),
+            stats.average_open_road_delay().as_secs_f32()
+        );
+    }
+    for (road, approaches) in [
+        ("north-south", [Direction::North, Direction::South]),
+        ("east-west", [Direction::East, Direction::West]),
+    ] {
+        if let Some(summary) = stats.speed_summary(&approaches) {
+            println!(
+                "Speeds on the {} road: mean {:.0}, 85th percentile {:.0} km/h, {:.0}% speeding",
+                road,
+                mps_to_kmh(summary.mean),
+                mps_to_kmh(summary.percentile_85),
+                summary.speeding_share * 100.0
+            );
+        }
+    }
+    println!("Travel times by movement (mean / median / 95th percentile):");
+    for (approach, route) in movements() {
+        if let Some(summary) = stats.travel_time_summary(approach, route) {
+            println!(
+                "  {:>5} {:<8} {:>4} vehicles  {:>5.1}s / {:>5.1}s / {:>5.1}s",
+                format!("{:?}", approach),
+                format!("{:?}", route),
+                summary.count,
+                summary.mean.as_secs_f32(),
+                summary.median.as_secs_f32(),
+                summary.percentile_95.as_secs_f32()
+            );
+        }
+    }
+    println!("Saturation flows by movement (mean queue discharge headway):");
+    for (approach, route) in movements() {
+        if let Some(summary) = stats.saturation_flow_summary(approach, route) {
+            println!(
+                "  {:>5} {:<8} {:>4} headways  {:>4.2}s  {:>5.0} veh/h per lane",
+                format!("{:?}", approach),
+                format!("{:?}", route),
+                summary.count,
+                summary.mean_headway.as_secs_f32(),
+                summary.saturation_flow
+            );
+        }
+    }
+    println!("Level of service by approach (mean control delay):");
+    for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+        if let Some(delay) = stats.average_control_delay(approach) {
+            println!(
+                "  {:>5}bound {}  {:>5.1}s",
+                format!("{:?}", approach),
+                level_of_service(delay),
+                delay.as_secs_f32()
+            );
         }
-        Ok(())
+    }
+    println!("Turning movement counts (left / through / right):");
+    let totals = stats.turning_totals();
+    for (approach, counts) in [Direction::North, Direction::South, Direction::East, Direction::West]
+        .iter()
+        .zip(totals.chunks(3))
+    {
+        println!(
+            "  {:>5}bound {:>5} / {:>5} / {:>5}",
+            format!("{:?}", approach),
+            counts[0],
+            counts[1],
+            counts[2]
+        );
+    }
+    println!(
+        "Arrivals held upstream of a full approach: {} ({} never got on)",
+        stats.arrivals_held_upstream,
+        stats.unserved_demand
+    );
+    println!(
+        "Vehicles clearing the intersection during the all-red: {} ({} still in it at the \
+         conflicting green)",
+        stats.clearance_conflicts_prevented,
+        stats.clearance_conflicts
+    );
+    println!("Gridlocks: {}", stats.gridlocks);
+    println!("Greens forced by the starvation watchdog: {}", stats.starvations);
+    if stats.manual_overrides > 0 {
+        println!("Manual light overrides: {}", stats.manual_overrides);
+    }
+    println!("Red-light violations: {}", stats.violations.len());
+    for violation in &stats.violations {
+        println!(
+            "  {:>7.1}s  vehicle {} from {:?}",
+            violation.time.as_secs_f32(),
+            violation.vehicle_id,
+            violation.approach
+        );
+    }
+    println!("Trips by origin (rows) and destination sink (columns):");
+    print!("{:>8}", "");
+    for destination in 0..SINK_COUNT {
+        print!("{:>8}", node_name(destination));
+    }
+    println!();
+    for (origin, row) in stats.od_matrix.iter().enumerate() {
+        print!("{:>8}", node_name(origin));
+        for trips in row {
+            print!("{:>8}", trips);
+        }
+        println!();
     }
 }
 
-fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-    let window = video_subsystem
-        .window("Traffic Intersection Simulation", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .build()
-        .expect("could not initialize video subsystem");
-    let mut canvas = window.into_canvas().build().expect("could not make a rendering context");
-    let mut event_pump = sdl_context.event_pump()?;
-    let mut simulation = TrafficSimulation::new();
-    println!("Traffic Intersection Simulation");
-    println!("Controls:");
-    println!("↑ - Spawn vehicle from South");
-    println!("↓ - Spawn vehicle from North");
-    println!("→ - Spawn vehicle from West");
-    println!("← - Spawn vehicle from East");
-    println!("R - Spawn random vehicle");
-    println!("ESC - Exit simulation");
-    println!("\nVehicle Colors:");
-    println!("Green - Going Straight");
-    println!("Yellow - Turning Left");
-    println!("Orange - Turning Right");
-    let mut last_spawn_time = Instant::now();
+#[cfg(feature = "tui")]
+fn run_tui(
+    config: &Config,
+    remote: Option<RemoteServer>,
+    metrics: Option<MetricsServer>,
+    mut traces: Traces
+) -> Result<Stats, SimError> {
+    use ratatui::crossterm::event::{ self, Event, KeyCode, KeyEventKind, KeyModifiers };
+    use road_intersection::render::TuiRenderer;
+
+    let mut renderer = TuiRenderer::new()?;
+    let mut simulation = TrafficSimulation::with_config(config)?;
+    let mut controls = Controls::new(config.platoons.size);
+    let mut console = Console::default();
+    // One tick per frame, which at normal speed keeps simulated time roughly in step with
+    // real time.
+    let frame_delay = simulation.tick();
 
     'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+        while event::poll(Duration::ZERO).map_err(RenderError::from)? {
+            let Event::Key(key) = event::read().map_err(RenderError::from)? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                break 'running;
+            }
+            if console.open {
+                match key.code {
+                    KeyCode::Enter => {
+                        console.submit(&mut simulation, &mut controls.speed);
+                    }
+                    KeyCode::Backspace => console.backspace(),
+                    KeyCode::Esc | KeyCode::Char('`') => console.open = false,
+                    KeyCode::Char(c) => console.type_text(&c.to_string()),
+                    _ => {}
+                }
+                continue;
+            }
+            // Key names as SDL spells them, so one keymap serves both front ends.
+            let name = match key.code {
+                KeyCode::Char(' ') => "Space".to_string(),
+                KeyCode::Char(c) => c.to_string(),
+                KeyCode::Up => "Up".to_string(),
+                KeyCode::Down => "Down".to_string(),
+                KeyCode::Left => "Left".to_string(),
+                KeyCode::Right => "Right".to_string(),
+                KeyCode::Esc => "Escape".to_string(),
+                KeyCode::Enter => "Return".to_string(),
+                KeyCode::Tab => "Tab".to_string(),
+                KeyCode::Backspace => "Backspace".to_string(),
+                _ => continue,
+            };
+            let action = config.keymap.action_for(&name);
+            let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+            let route = turn_route(
+                key.modifiers.contains(KeyModifiers::CONTROL),
+                key.modifiers.contains(KeyModifiers::ALT)
+            );
+            let picked = action.is_some_and(|action| {
+                route.is_some_and(|route| spawn_on_route(action, route, &mut simulation)) ||
+                    (shift && controls.pick_trip_end(action, &mut simulation)) ||
+                    controls.pick_platoon_approach(action, &mut simulation)
+            });
+            if picked {
+                continue;
+            }
+            match action {
+                Some(Action::Quit) => {
                     break 'running;
                 }
-                Event::KeyDown { keycode: Some(keycode), repeat, .. } => {
-                    if !repeat && last_spawn_time.elapsed() >= Duration::from_millis(700) {
-                        match keycode {
-                            Keycode::Up => simulation.spawn_vehicle(Direction::North),
-                            Keycode::Down => simulation.spawn_vehicle(Direction::South),
-                            Keycode::Right => simulation.spawn_vehicle(Direction::East),
-                            Keycode::Left => simulation.spawn_vehicle(Direction::West),
-                            Keycode::R => simulation.spawn_random_vehicle(),
-                            _ => {}
-                        }
-                        last_spawn_time = Instant::now();
-                    }
+                Some(Action::ToggleConsole) => console.toggle(),
+                // Terminals don't report keys being let go, so a manual phase key switches
+                // straight over.
+                Some(action) => {
+                    controls.apply(action, &mut simulation);
+                    controls.release(action, &mut simulation);
                 }
-                _ => {}
+                // `q` quits unless it has been bound to something else.
+                None if name == "q" => {
+                    break 'running;
+                }
+                None => {}
             }
         }
-        simulation.update();
-        simulation.render(&mut canvas)?;
-        std::thread::sleep(Duration::from_millis(10));
+        if let Some(remote) = &remote {
+            remote.poll(&mut simulation);
+        }
+        controls.step(&mut simulation, 1, &mut traces)?;
+        if simulation.scenario_ended() {
+            break 'running;
+        }
+        if let Some(metrics) = &metrics {
+            metrics.update(&simulation);
+        }
+        simulation.drain_events();
+        simulation.render(&mut renderer)?;
+        if console.open {
+            console.draw(&mut renderer, simulation.theme().palette())?;
+        }
+        renderer.present()?;
+        std::thread::sleep(frame_delay);
     }
-    Ok(())
+    loop {
+        simulation.render(&mut renderer)?;
+        draw_run_summary(&mut renderer, &simulation)?;
+        renderer.present()?;
+        let pressed = event::poll(frame_delay).map_err(RenderError::from)? &&
+            matches!(
+                event::read().map_err(RenderError::from)?,
+                Event::Key(key) if key.kind == KeyEventKind::Press
+            );
+        if pressed {
+            break;
+        }
+    }
+    traces.finish()?;
+    Ok(simulation.stats)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(
+    _config: &Config,
+    _remote: Option<RemoteServer>,
+    _metrics: Option<MetricsServer>,
+    _traces: Traces
+) -> Result<Stats, SimError> {
+    let message = "terminal renderer not available: rebuild with `--features tui`";
+    Err(SimError::Usage(message.to_string()))
 }
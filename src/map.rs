@@ -0,0 +1,288 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::error::{ MapError, RenderError };
+use crate::geometry::Geometry;
+use crate::map_file::MapFile;
+use crate::render::{ Color, Rect, Renderer };
+use crate::sink::node_id;
+use crate::units::{ Area, View };
+use crate::vehicle::{ heading, lane_center, opposite, turned_direction, Direction, Route };
+use crate::{
+    BIKE_LANE_WIDTH,
+    LANES_PER_DIRECTION,
+    LANE_WIDTH,
+    ROAD_WIDTH,
+    WINDOW_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+// Read from the working directory when no `--map` path is given, if it exists.
+pub const DEFAULT_MAP_PATH: &str = "map.toml";
+// Furthest a stop line may be moved back from the crossing road's bike lane.
+pub const MAX_STOP_LINE_SETBACK: f32 = 10.0;
+// A signal head's lamps and the margin of housing around each.
+pub const LAMP_SIZE: f32 = 1.0;
+pub const LAMP_MARGIN: f32 = 0.2;
+// The housing holds red, yellow and green lamps in a row across the approach.
+const HOUSING_LENGTH: f32 = LAMP_SIZE * 3.0 + LAMP_MARGIN * 4.0;
+const HOUSING_WIDTH: f32 = LAMP_SIZE + LAMP_MARGIN * 2.0;
+const STOP_LINE_WIDTH: f32 = 0.4;
+// Handles can be grabbed this far outside them.
+const HANDLE_SLACK: f32 = 0.4;
+const HANDLE_COLOR: Color = Color::rgb(0, 200, 255);
+const TEXT_COLOR: Color = Color::rgb(255, 255, 255);
+
+// Where each approach's stop line and signal head sit, keyed by the way its traffic
+// travels, and which way traffic runs on the road at each end of the cross. Edited in the
+// map editor and kept in a map file loaded with `--map <path>`. Version 1 map files held
+// just the stop lines and lights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapLayout {
+    pub north: ApproachLayout,
+    pub south: ApproachLayout,
+    pub east: ApproachLayout,
+    pub west: ApproachLayout,
+    // By road end, in north, south, east, west order.
+    #[serde(skip)]
+    pub roads: [RoadEnd; 4],
+}
+
+// The road at one end of the intersection.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RoadEnd {
+    #[default]
+    TwoWay,
+    // One-way toward the intersection, or away from it.
+    Inbound,
+    Outbound,
+    // No road on this side, which makes a T intersection.
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApproachLayout {
+    // Distance the stop line sits back from the crossing road's bike lane.
+    pub stop_line_setback: f32,
+    // Center of the signal head in the world; on the curb to the right just before the box
+    // if unset.
+    pub light: Option<(f32, f32)>,
+}
+
+// Something the editor can drag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    StopLine(Direction),
+    Light(Direction),
+}
+
+impl MapLayout {
+    // Reads the map file at `path`, which must describe the cross the simulator runs.
+    pub fn load(path: &Path) -> Result<Self, MapError> {
+        MapFile::load(path)?.layout().map_err(|e| e.in_file(path))
+    }
+
+    // An explicit path must exist; otherwise the default file is used if present.
+    pub fn load_or_default(path: Option<&str>) -> Result<Self, MapError> {
+        match path {
+            Some(path) => MapLayout::load(Path::new(path)),
+            None if Path::new(DEFAULT_MAP_PATH).exists() => {
+                MapLayout::load(Path::new(DEFAULT_MAP_PATH))
+            }
+            None => Ok(MapLayout::default()),
+        }
+    }
+
+    // Writes the whole network, at the current map file version.
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        MapFile::from_layout(self).save(path)
+    }
+
+    pub fn validate(&self) -> Result<(), MapError> {
+        for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let approach = self.approach(direction);
+            if !(0.0..=MAX_STOP_LINE_SETBACK).contains(&approach.stop_line_setback) {
+                return Err(MapError::Invalid(format!(
+                    "stop line setbacks must be between 0 and {}",
+                    MAX_STOP_LINE_SETBACK
+                )));
+            }
+            if let Some((x, y)) = approach.light {
+                let inside = (0.0..WORLD_WIDTH).contains(&x) && (0.0..WORLD_HEIGHT).contains(&y);
+                if !inside {
+                    return Err(MapError::Invalid("lights must be inside the world".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn road(&self, end: Direction) -> RoadEnd {
+        self.roads[node_id(end)]
+    }
+
+    pub fn has_road(&self, end: Direction) -> bool {
+        self.road(end) != RoadEnd::Missing
+    }
+
+    // Whether traffic arrives travelling `direction`, from the road end behind it.
+    pub fn has_approach(&self, direction: Direction) -> bool {
+        matches!(self.road(opposite(direction)), RoadEnd::TwoWay | RoadEnd::Inbound)
+    }
+
+    // Whether traffic can leave along the road at `end`.
+    pub fn has_exit(&self, end: Direction) -> bool {
+        matches!(self.road(end), RoadEnd::TwoWay | RoadEnd::Outbound)
+    }
+
+    // Whether traffic travelling `direction` can arrive and leave by `route`. On a T
+    // intersection the stem only turns, and nothing turns into the missing side or the
+    // wrong way into a one-way road.
+    pub fn serves(&self, direction: Direction, route: Route) -> bool {
+        self.has_approach(direction) && self.has_exit(turned_direction(direction, route))
+    }
+
+    pub fn approach(&self, direction: Direction) -> &ApproachLayout {
+        match direction {
+            Direction::North => &self.north,
+            Direction::South => &self.south,
+            Direction::East => &self.east,
+            Direction::West => &self.west,
+        }
+    }
+
+    pub fn approach_mut(&mut self, direction: Direction) -> &mut ApproachLayout {
+        match direction {
+            Direction::North => &mut self.north,
+            Direction::South => &mut self.south,
+            Direction::East => &mut self.east,
+            Direction::West => &mut self.west,
+        }
+    }
+
+    // The housing of the approach's signal head, lying across the road.
+    pub fn light_rect(&self, direction: Direction) -> Area {
+        let (x, y) = self.approach(direction).light.unwrap_or_else(|| curb_light(direction));
+        if heading(direction).0 == 0.0 {
+            Area::centered(x, y, HOUSING_LENGTH, HOUSING_WIDTH)
+        } else {
+            Area::centered(x, y, HOUSING_WIDTH, HOUSING_LENGTH)
+        }
+    }
+
+    // Across the approach's travel lanes, on the upstream side of where traffic stops.
+    pub fn stop_line_rect(&self, direction: Direction) -> Area {
+        let stop = Geometry::new(direction, self).stop_distance;
+        let across = lane_center(direction, (LANES_PER_DIRECTION as f32 - 1.0) / 2.0);
+        stop_bar_rect(direction, stop, across, LANE_WIDTH * (LANES_PER_DIRECTION as f32))
+    }
+
+    // The handle under the point (x, y) in the world, lights first as they are the smaller
+    // targets.
+    pub fn handle_at(&self, x: f32, y: f32) -> Option<Handle> {
+        let directions = [Direction::North, Direction::South, Direction::East, Direction::West]
+            .into_iter()
+            .filter(|&direction| self.has_approach(direction));
+        let under = |area: Area| area.grown(HANDLE_SLACK).contains(x, y);
+        directions
+            .clone()
+            .find(|&direction| under(self.light_rect(direction)))
+            .map(Handle::Light)
+            .or_else(|| {
+                directions
+                    .clone()
+                    .find(|&direction| under(self.stop_line_rect(direction)))
+                    .map(Handle::StopLine)
+            })
+    }
+
+    // Moves `handle` to follow the pointer at (x, y) in the world: lights anywhere in it,
+    // stop lines along their approach within the allowed setback, to the nearest 10 cm.
+    pub fn drag(&mut self, handle: Handle, x: f32, y: f32) {
+        match handle {
+            Handle::Light(direction) => {
+                let x = x.clamp(HOUSING_LENGTH / 2.0, WORLD_WIDTH - HOUSING_LENGTH / 2.0);
+                let y = y.clamp(HOUSING_LENGTH / 2.0, WORLD_HEIGHT - HOUSING_LENGTH / 2.0);
+                self.approach_mut(direction).light = Some((x, y));
+            }
+            Handle::StopLine(direction) => {
+                let (hx, hy) = heading(direction);
+                let center_x = WORLD_WIDTH / 2.0;
+                let center_y = WORLD_HEIGHT / 2.0;
+                // Distance upstream of the box along the approach.
+                let upstream = -((x - center_x) * hx + (y - center_y) * hy);
+                let setback = upstream - (ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH);
+                self.approach_mut(direction).stop_line_setback =
+                    ((setback * 10.0).round() / 10.0).clamp(0.0, MAX_STOP_LINE_SETBACK);
+            }
+        }
+    }
+
+    // Outlines every handle, the one being dragged filled in, with a hint at the top.
+    pub fn draw_handles(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View,
+        dragging: Option<Handle>
+    ) -> Result<(), RenderError> {
+        for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            if !self.has_approach(direction) {
+                continue;
+            }
+            for handle in [Handle::StopLine(direction), Handle::Light(direction)] {
+                let rect = view.rect(match handle {
+                    Handle::StopLine(direction) => self.stop_line_rect(direction),
+                    Handle::Light(direction) => self.light_rect(direction),
+                });
+                let outline = Rect::new(rect.x - 3, rect.y - 3, rect.w + 6, rect.h + 6);
+                if dragging == Some(handle) {
+                    renderer.draw_rect(outline, HANDLE_COLOR)?;
+                } else {
+                    draw_outline(renderer, outline)?;
+                }
+            }
+        }
+        let x = (WINDOW_WIDTH as i32) / 2 - 160;
+        renderer.draw_rect(Rect::new(x - 8, 2, 336, 24), Color::rgba(20, 20, 30, 200))?;
+        renderer.draw_text("MAP EDITOR  DRAG LINES AND LIGHTS", x, 6, TEXT_COLOR)
+    }
+}
+
+// A stop bar `width` wide centered `across` the road, whose near edge is where traffic
+// travelling `direction` stops, `stop` before the center of the intersection.
+pub fn stop_bar_rect(direction: Direction, stop: f32, across: f32, width: f32) -> Area {
+    let (hx, hy) = heading(direction);
+    let center_x = WORLD_WIDTH / 2.0;
+    let center_y = WORLD_HEIGHT / 2.0;
+    // The bar extends back from the stop position against the heading.
+    let near = if hx == 0.0 { center_y - hy * stop } else { center_x - hx * stop };
+    let start = if hx + hy < 0.0 { near } else { near - STOP_LINE_WIDTH };
+    let across = across - width / 2.0;
+    if hx == 0.0 {
+        Area::new(across, start, width, STOP_LINE_WIDTH)
+    } else {
+        Area::new(start, across, STOP_LINE_WIDTH, width)
+    }
+}
+
+// On the curb to the right of the approach, just before the box and clear of the corner's
+// pedestrian signal.
+fn curb_light(direction: Direction) -> (f32, f32) {
+    let back = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 0.8;
+    let out = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + LAMP_MARGIN + HOUSING_LENGTH / 2.0;
+    let (hx, hy) = heading(direction);
+    let x = WORLD_WIDTH / 2.0 - hx * back - hy * out;
+    let y = WORLD_HEIGHT / 2.0 - hy * back + hx * out;
+    (x, y)
+}
+
+fn draw_outline(renderer: &mut dyn Renderer, rect: Rect) -> Result<(), RenderError> {
+    let (x, y, w, h) = (rect.x, rect.y, rect.w, rect.h);
+    renderer.draw_rect(Rect::new(x, y, w, 2), HANDLE_COLOR)?;
+    renderer.draw_rect(Rect::new(x, y + (h as i32) - 2, w, 2), HANDLE_COLOR)?;
+    renderer.draw_rect(Rect::new(x, y, 2, h), HANDLE_COLOR)?;
+    renderer.draw_rect(Rect::new(x + (w as i32) - 2, y, 2, h), HANDLE_COLOR)
+}
@@ -0,0 +1,574 @@
+use serde::{ Deserialize, Serialize };
+use std::fs;
+use std::path::Path;
+
+use crate::error::MapError;
+use crate::map::{ ApproachLayout, MapLayout, RoadEnd };
+use crate::sink::{ node_id, node_name };
+use crate::vehicle::{ opposite, turn_lane, turned_direction, Direction, Route };
+use crate::{ LANES_PER_DIRECTION, WORLD_HEIGHT, WORLD_WIDTH };
+
+// Written into every map file. Older files are read and brought up to date; newer ones are
+// refused rather than half understood.
+pub const MAP_VERSION: u32 = 3;
+// Versions 1 and 2 placed everything in window pixels, which the world spans at 10 cm each.
+const OLD_METERS_PER_PIXEL: f32 = 0.1;
+const CENTER_NODE: &str = "center";
+// Roads leaving a shared node closer together than this lie on top of each other.
+const MIN_ROAD_ANGLE_DEGREES: f32 = 10.0;
+// Nodes closer than this are in the same place.
+const NODE_SLACK: f32 = 0.1;
+const SIDES: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+// A road network as kept on disk: junctions and road ends as nodes, the roads between them
+// with their lanes each way, where each lane may carry on through a junction, and the stop
+// line and signal head on each road into one, all in meters. Version 1 files, from before
+// the network was written out, held only the stop lines and lights.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapFile {
+    pub version: u32,
+    #[serde(default, rename = "node")]
+    pub nodes: Vec<Node>,
+    #[serde(default, rename = "road")]
+    pub roads: Vec<Road>,
+    #[serde(default, rename = "connection")]
+    pub connections: Vec<Connection>,
+    #[serde(default, rename = "signal")]
+    pub signals: Vec<Signal>,
+}
+
+// Placed in the world.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Node {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+// A straight road between two nodes, with `lanes_forward` travel lanes from `from` to `to`
+// and `lanes_backward` the other way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Road {
+    pub from: String,
+    pub to: String,
+    pub lanes_forward: usize,
+    pub lanes_backward: usize,
+}
+
+// Traffic in lane `lane` (0 next to the center line) of the road into junction `at` from
+// node `from` may carry on along the road out of it to node `to`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Connection {
+    pub at: String,
+    pub from: String,
+    pub lane: usize,
+    pub to: String,
+}
+
+// The stop line and signal head for traffic into junction `at` on the road from `from`,
+// placed as in the map layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signal {
+    pub at: String,
+    pub from: String,
+    #[serde(default)]
+    pub stop_line_setback: f32,
+    pub light: Option<(f32, f32)>,
+}
+
+impl MapFile {
+    pub fn load(path: &Path) -> Result<Self, MapError> {
+        fs::read_to_string(path)
+            .map_err(MapError::from)
+            .and_then(|text| Self::parse(&text))
+            .map_err(|e| e.in_file(path))
+    }
+
+    // Reads a map file of any version up to MAP_VERSION and checks its network.
+    pub fn parse(text: &str) -> Result<Self, MapError> {
+        let table: toml::Table = text.parse()?;
+        let version = match table.get("version") {
+            None => 1,
+            Some(version) => {
+                version
+                    .as_integer()
+                    .and_then(|version| u32::try_from(version).ok())
+                    .filter(|&version| version >= 1)
+                    .ok_or_else(|| {
+                        MapError::Invalid("version must be a whole number from 1".to_string())
+                    })?
+            }
+        };
+        let map = match version {
+            1 => {
+                let mut layout: MapLayout = toml::from_str(text)?;
+                for side in SIDES {
+                    let approach = layout.approach_mut(side);
+                    approach.stop_line_setback *= OLD_METERS_PER_PIXEL;
+                    approach.light = approach.light.map(point_in_meters);
+                }
+                layout.validate()?;
+                Self::from_layout(&layout)
+            }
+            2 => {
+                let map: Self = toml::from_str(text)?;
+                map.in_meters()
+            }
+            MAP_VERSION => toml::from_str(text)?,
+            _ => {
+                return Err(MapError::NewerVersion { found: version, supported: MAP_VERSION });
+            }
+        };
+        map.validate()?;
+        Ok(map)
+    }
+
+    // A version 2 file's network, placed in pixels, brought up to date.
+    fn in_meters(mut self) -> Self {
+        for node in &mut self.nodes {
+            node.x *= OLD_METERS_PER_PIXEL;
+            node.y *= OLD_METERS_PER_PIXEL;
+        }
+        for signal in &mut self.signals {
+            signal.stop_line_setback *= OLD_METERS_PER_PIXEL;
+            signal.light = signal.light.map(point_in_meters);
+        }
+        self.version = MAP_VERSION;
+        self
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        toml::to_string(self)
+            .map_err(MapError::from)
+            .and_then(|text| fs::write(path, text).map_err(MapError::from))
+            .map_err(|e| e.in_file(path))
+    }
+
+    // The four-way cross or T intersection the simulator runs, with its one-way roads and
+    // its stop lines and lights where `layout` has them. Road ends sit on the world's edges
+    // and are named after their side.
+    pub fn from_layout(layout: &MapLayout) -> Self {
+        let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        let mut nodes = vec![Node { id: CENTER_NODE.to_string(), x: center.0, y: center.1 }];
+        let mut roads = Vec::new();
+        let mut connections = Vec::new();
+        let mut signals = Vec::new();
+        for side in SIDES.into_iter().filter(|&side| layout.has_road(side)) {
+            let (x, y) = match side {
+                Direction::North => (center.0, 0.0),
+                Direction::South => (center.0, WORLD_HEIGHT),
+                Direction::East => (WORLD_WIDTH, center.1),
+                Direction::West => (0.0, center.1),
+            };
+            let end = end_name(side);
+            nodes.push(Node { id: end.clone(), x, y });
+            let lanes = |used: bool| if used { LANES_PER_DIRECTION } else { 0 };
+            let inbound = layout.has_approach(opposite(side));
+            roads.push(Road {
+                from: end.clone(),
+                to: CENTER_NODE.to_string(),
+                lanes_forward: lanes(inbound),
+                lanes_backward: lanes(layout.has_exit(side)),
+            });
+            if !inbound {
+                continue;
+            }
+            for (lane, exit) in driven_connections(side, layout) {
+                connections.push(Connection {
+                    at: CENTER_NODE.to_string(),
+                    from: end.clone(),
+                    lane,
+                    to: end_name(exit),
+                });
+            }
+            // Approaches are laid out by the way their traffic travels, away from its end.
+            let approach = layout.approach(opposite(side));
+            signals.push(Signal {
+                at: CENTER_NODE.to_string(),
+                from: end,
+                stop_line_setback: approach.stop_line_setback,
+                light: approach.light,
+            });
+        }
+        Self { version: MAP_VERSION, nodes, roads, connections, signals }
+    }
+
+    // Checks the network makes sense as a road network of any shape: every node is placed
+    // once and on a road, roads don't run over each other, everything is connected, and
+    // every lane into a junction leads on and every lane out of one is led into.
+    pub fn validate(&self) -> Result<(), MapError> {
+        for (i, node) in self.nodes.iter().enumerate() {
+            for other in &self.nodes[..i] {
+                if other.id == node.id {
+                    let message = format!("node \"{}\" is defined twice", node.id);
+                    return Err(MapError::Invalid(message));
+                }
+                if (other.x - node.x).hypot(other.y - node.y) < NODE_SLACK {
+                    return Err(MapError::Invalid(format!(
+                        "nodes \"{}\" and \"{}\" overlap",
+                        other.id,
+                        node.id
+                    )));
+                }
+            }
+            if !self.roads.iter().any(|road| road.from == node.id || road.to == node.id) {
+                return Err(MapError::Invalid(format!("node \"{}\" is on no road", node.id)));
+            }
+        }
+        for (i, road) in self.roads.iter().enumerate() {
+            let (from, to) = (self.position(&road.from)?, self.position(&road.to)?);
+            if road.from == road.to {
+                return Err(MapError::Invalid(format!(
+                    "road from \"{}\" back to itself",
+                    road.from
+                )));
+            }
+            if road.lanes_forward + road.lanes_backward == 0 {
+                return Err(MapError::Invalid(format!("road {} has no lanes", road_name(road))));
+            }
+            for other in &self.roads[..i] {
+                let (a, b) = (self.position(&other.from)?, self.position(&other.to)?);
+                let same_ends =
+                    (other.from == road.from && other.to == road.to) ||
+                    (other.from == road.to && other.to == road.from);
+                let shared = [&other.from, &other.to]
+                    .into_iter()
+                    .find(|&id| *id == road.from || *id == road.to);
+                let overlap = match shared {
+                    _ if same_ends => true,
+                    Some(shared) => {
+                        let node = self.position(shared)?;
+                        let far = |start: (f32, f32), end: (f32, f32)| {
+                            if start == node { end } else { start }
+                        };
+                        angle_between(node, far(from, to), far(a, b)) < MIN_ROAD_ANGLE_DEGREES
+                    }
+                    None => segments_touch((from, to), (a, b)),
+                };
+                if overlap {
+                    return Err(MapError::Invalid(
+                        format!("roads {} and {} overlap", road_name(other), road_name(road))
+                    ));
+                }
+            }
+        }
+        self.check_connected()?;
+        for connection in &self.connections {
+            let lanes_in = self.lanes(&connection.from, &connection.at).unwrap_or(0);
+            if connection.lane >= lanes_in {
+                return Err(MapError::Invalid(format!(
+                    "no lane {} into \"{}\" from \"{}\" to connect",
+                    connection.lane,
+                    connection.at,
+                    connection.from
+                )));
+            }
+            if self.lanes(&connection.at, &connection.to).unwrap_or(0) == 0 {
+                return Err(MapError::Invalid(format!(
+                    "no lane out of \"{}\" to \"{}\" to connect to",
+                    connection.at,
+                    connection.to
+                )));
+            }
+        }
+        for junction in self.nodes.iter().filter(|node| self.neighbours(&node.id).len() > 1) {
+            let at = &junction.id;
+            for neighbour in self.neighbours(at) {
+                let connected = |c: &&Connection| c.at == *at;
+                let lanes_in = self.lanes(neighbour, at).unwrap_or(0);
+                for lane in 0..lanes_in {
+                    let leads_on = self.connections
+                        .iter()
+                        .filter(connected)
+                        .any(|c| c.from == *neighbour && c.lane == lane);
+                    if !leads_on {
+                        return Err(MapError::Invalid(format!(
+                            "lane {} into \"{}\" from \"{}\" leads nowhere",
+                            lane,
+                            at,
+                            neighbour
+                        )));
+                    }
+                }
+                let led_into = self.connections
+                    .iter()
+                    .filter(connected)
+                    .any(|c| c.to == *neighbour);
+                if self.lanes(at, neighbour).unwrap_or(0) > 0 && !led_into {
+                    return Err(MapError::Invalid(
+                        format!("no lane leads out of \"{}\" to \"{}\"", at, neighbour)
+                    ));
+                }
+            }
+        }
+        for (i, signal) in self.signals.iter().enumerate() {
+            if self.lanes(&signal.from, &signal.at).unwrap_or(0) == 0 {
+                return Err(MapError::Invalid(format!(
+                    "signal for traffic into \"{}\" from \"{}\" is on no road",
+                    signal.at,
+                    signal.from
+                )));
+            }
+            let same_road = |other: &Signal| other.at == signal.at && other.from == signal.from;
+            if self.signals[..i].iter().any(same_road) {
+                return Err(MapError::Invalid(format!(
+                    "two signals for traffic into \"{}\" from \"{}\"",
+                    signal.at,
+                    signal.from
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // The layout of the intersection the simulator runs, if the network is one it can:
+    // one junction in the middle of the world, a straight road from it to every side or
+    // all but one, each with the simulator's lanes both ways or one way, and lanes
+    // connected as the simulator drives them.
+    pub fn layout(&self) -> Result<MapLayout, MapError> {
+        let junctions: Vec<&Node> = self.nodes
+            .iter()
+            .filter(|node| self.neighbours(&node.id).len() > 1)
+            .collect();
+        let [junction] = junctions[..] else {
+            return Err(MapError::Invalid(format!(
+                "the simulator runs a single intersection, not {}",
+                junctions.len()
+            )));
+        };
+        let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        if (junction.x - center.0).hypot(junction.y - center.1) >= NODE_SLACK {
+            return Err(MapError::Invalid(format!(
+                "the intersection must be in the middle of the world, at ({}, {})",
+                center.0,
+                center.1
+            )));
+        }
+        let mut ends: Vec<(Direction, &str, RoadEnd)> = Vec::new();
+        let slack = NODE_SLACK;
+        for end in self.neighbours(&junction.id) {
+            let (x, y) = self.position(end)?;
+            let (dx, dy) = (x - junction.x, y - junction.y);
+            // Road ends sit on the world's edge, where vehicles enter and leave.
+            let side = match (dx.abs() < slack, dy.abs() < slack) {
+                (true, false) if y < slack => Direction::North,
+                (true, false) if y > WORLD_HEIGHT - slack => Direction::South,
+                (false, true) if x > WORLD_WIDTH - slack => Direction::East,
+                (false, true) if x < slack => Direction::West,
+                _ => {
+                    return Err(MapError::Invalid(
+                        format!("road to \"{}\" must run straight to the world's edge", end)
+                    ));
+                }
+            };
+            let lanes = [self.lanes(end, &junction.id), self.lanes(&junction.id, end)];
+            let road = match lanes.map(|lanes| lanes.unwrap_or(0)) {
+                [LANES_PER_DIRECTION, LANES_PER_DIRECTION] => RoadEnd::TwoWay,
+                [LANES_PER_DIRECTION, 0] => RoadEnd::Inbound,
+                [0, LANES_PER_DIRECTION] => RoadEnd::Outbound,
+                _ => {
+                    return Err(MapError::Invalid(format!(
+                        "road to \"{}\" must have {} lanes each way or one way",
+                        end,
+                        LANES_PER_DIRECTION
+                    )));
+                }
+            };
+            ends.push((side, end, road));
+        }
+        let end_at = |side: Direction| {
+            ends.iter().find(|&&(end_side, _, _)| end_side == side).map(|&(_, end, _)| end)
+        };
+        let side_of = |end: &str| {
+            ends.iter().find(|&&(_, id, _)| id == end).map(|&(side, _, _)| side)
+        };
+        if ends.len() < 3 {
+            return Err(MapError::Invalid(format!(
+                "the simulator runs a four-way cross or a T intersection, not {} roads",
+                ends.len()
+            )));
+        }
+        let mut layout = MapLayout { roads: [RoadEnd::Missing; 4], ..MapLayout::default() };
+        for &(side, _, road) in &ends {
+            layout.roads[node_id(side)] = road;
+        }
+        for side in SIDES {
+            let Some(end) = end_at(side).filter(|_| layout.has_approach(opposite(side))) else {
+                continue;
+            };
+            let driven = driven_connections(side, &layout);
+            for &(lane, exit) in &driven {
+                let Some(to) = end_at(exit) else {
+                    continue;
+                };
+                let found = self.connections
+                    .iter()
+                    .any(|c| c.at == junction.id && c.from == end && c.lane == lane && c.to == to);
+                if !found {
+                    return Err(MapError::Invalid(format!(
+                        "no connection from lane {} in from \"{}\" to \"{}\", which the \
+                         simulator drives",
+                        lane,
+                        end,
+                        to
+                    )));
+                }
+            }
+            for connection in self.connections.iter().filter(|c| c.from == end) {
+                let exit = side_of(&connection.to);
+                if !exit.is_some_and(|exit| driven.contains(&(connection.lane, exit))) {
+                    return Err(MapError::Invalid(format!(
+                        "the simulator doesn't drive lane {} in from \"{}\" on to \"{}\"",
+                        connection.lane,
+                        end,
+                        connection.to
+                    )));
+                }
+            }
+            let approach = layout.approach_mut(opposite(side));
+            if let Some(signal) = self.signals.iter().find(|signal| signal.from == end) {
+                *approach = ApproachLayout {
+                    stop_line_setback: signal.stop_line_setback,
+                    light: signal.light,
+                };
+            }
+        }
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    fn position(&self, id: &str) -> Result<(f32, f32), MapError> {
+        self.nodes
+            .iter()
+            .find(|node| node.id == id)
+            .map(|node| (node.x, node.y))
+            .ok_or_else(|| MapError::Invalid(format!("no node \"{}\"", id)))
+    }
+
+    // Travel lanes running from node `from` to node `to`, if a road joins them.
+    fn lanes(&self, from: &str, to: &str) -> Option<usize> {
+        self.roads.iter().find_map(|road| {
+            if road.from == from && road.to == to {
+                Some(road.lanes_forward)
+            } else if road.from == to && road.to == from {
+                Some(road.lanes_backward)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn neighbours(&self, id: &str) -> Vec<&str> {
+        self.roads
+            .iter()
+            .filter_map(|road| {
+                if road.from == id {
+                    Some(road.to.as_str())
+                } else if road.to == id {
+                    Some(road.from.as_str())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Every node can be reached from the first along the roads.
+    fn check_connected(&self) -> Result<(), MapError> {
+        let Some(first) = self.nodes.first() else {
+            return Ok(());
+        };
+        let mut reached = vec![first.id.as_str()];
+        let mut next = 0;
+        while next < reached.len() {
+            for neighbour in self.neighbours(reached[next]) {
+                if !reached.contains(&neighbour) {
+                    reached.push(neighbour);
+                }
+            }
+            next += 1;
+        }
+        match self.nodes.iter().find(|node| !reached.contains(&node.id.as_str())) {
+            Some(node) => {
+                Err(MapError::Invalid(format!(
+                    "node \"{}\" can't be reached from \"{}\"",
+                    node.id,
+                    first.id
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// A point from a version 1 or 2 file, placed in pixels, in the world.
+fn point_in_meters((x, y): (f32, f32)) -> (f32, f32) {
+    (x * OLD_METERS_PER_PIXEL, y * OLD_METERS_PER_PIXEL)
+}
+
+fn end_name(side: Direction) -> String {
+    node_name(node_id(side)).to_string()
+}
+
+fn road_name(road: &Road) -> String {
+    format!("\"{}\"-\"{}\"", road.from, road.to)
+}
+
+// Lanes in from the road end on `side` and the ends they lead on to, as the simulator
+// drives them: turns from the lane on their side, straight on from any lane, and nowhere
+// toward a side with no road or with a one-way road into the intersection.
+fn driven_connections(side: Direction, layout: &MapLayout) -> Vec<(usize, Direction)> {
+    let direction = opposite(side);
+    let mut connections = Vec::new();
+    for route in [Route::Left, Route::Straight, Route::Right] {
+        let exit = turned_direction(direction, route);
+        if !layout.has_exit(exit) {
+            continue;
+        }
+        match turn_lane(route) {
+            Some(lane) => connections.push((lane, exit)),
+            None => connections.extend((0..LANES_PER_DIRECTION).map(|lane| (lane, exit))),
+        }
+    }
+    connections
+}
+
+// Angle in degrees between the directions from `node` to `a` and to `b`.
+fn angle_between(node: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (ax, ay) = (a.0 - node.0, a.1 - node.1);
+    let (bx, by) = (b.0 - node.0, b.1 - node.1);
+    (ax * by - ay * bx).atan2(ax * bx + ay * by).abs().to_degrees()
+}
+
+// Whether two roads with no node in common cross or touch.
+fn segments_touch(a: ((f32, f32), (f32, f32)), b: ((f32, f32), (f32, f32))) -> bool {
+    let cross = |o: (f32, f32), p: (f32, f32), q: (f32, f32)| {
+        (p.0 - o.0) * (q.1 - o.1) - (p.1 - o.1) * (q.0 - o.0)
+    };
+    // Whether `p`, known to be in line with the segment, lies within it.
+    let within = |segment: ((f32, f32), (f32, f32)), p: (f32, f32)| {
+        let ((x1, y1), (x2, y2)) = segment;
+        p.0 >= x1.min(x2) && p.0 <= x1.max(x2) && p.1 >= y1.min(y2) && p.1 <= y1.max(y2)
+    };
+    let d1 = cross(b.0, b.1, a.0);
+    let d2 = cross(b.0, b.1, a.1);
+    let d3 = cross(a.0, a.1, b.0);
+    let d4 = cross(a.0, a.1, b.1);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && within(b, a.0)) ||
+        (d2 == 0.0 && within(b, a.1)) ||
+        (d3 == 0.0 && within(a, b.0)) ||
+        (d4 == 0.0 && within(a, b.1))
+}
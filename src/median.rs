@@ -0,0 +1,56 @@
+use crate::render::Color;
+use crate::units::Area;
+use crate::vehicle::{ heading, lane_center, Direction };
+use crate::{ BIKE_LANE_WIDTH, LANE_WIDTH, ROAD_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// On a divided road the median takes the lane next to the center line of each approach,
+// which opens into the left-turn bay just before the stop line.
+pub const MEDIAN_LANE: usize = 0;
+// Left-turners need room to move over into the bay before lane changes stop.
+pub const MIN_TURN_BAY_LENGTH: f32 = 10.0;
+pub const MAX_TURN_BAY_LENGTH: f32 = 25.0;
+pub const MEDIAN_COLOR: Color = Color::rgb(90, 130, 80);
+const CURB_COLOR: Color = Color::rgb(200, 200, 200);
+const CURB_WIDTH: f32 = 0.2;
+const BAY_LINE_WIDTH: f32 = 0.2;
+
+// Stretch of `approach` between `from` and `to` upstream of the crossing road's bike lane,
+// `width` across centered on `across`.
+pub fn along_rect(approach: Direction, across: f32, width: f32, from: f32, to: f32) -> Area {
+    let (hx, hy) = heading(approach);
+    let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    let near = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + from;
+    let far = near + (to - from);
+    let across = across - width / 2.0;
+    // Upstream of the stop line is against the heading.
+    if hx == 0.0 {
+        let (a, b) = (center.1 - hy * near, center.1 - hy * far);
+        Area::new(across, a.min(b), width, to - from)
+    } else {
+        let (a, b) = (center.0 - hx * near, center.0 - hx * far);
+        Area::new(a.min(b), across, to - from, width)
+    }
+}
+
+// The raised strip from the world's edge to the start of the bay, `bay` before the stop
+// line set `setback` back, with its curbs.
+pub fn median_rects(approach: Direction, bay: f32, setback: f32) -> [(Area, Color); 2] {
+    let road_end = match approach {
+        Direction::North | Direction::South => WORLD_HEIGHT / 2.0,
+        Direction::East | Direction::West => WORLD_WIDTH / 2.0,
+    };
+    let across = lane_center(approach, MEDIAN_LANE as f32);
+    let from = bay + setback;
+    let width = LANE_WIDTH - 0.4;
+    let inner = width - CURB_WIDTH * 2.0;
+    [
+        (along_rect(approach, across, width, from, road_end), CURB_COLOR),
+        (along_rect(approach, across, inner, from + CURB_WIDTH, road_end), MEDIAN_COLOR),
+    ]
+}
+
+// Solid line between the bay and the through lane, which traffic doesn't cross once in it.
+pub fn bay_line_rect(approach: Direction, bay: f32, setback: f32) -> Area {
+    let across = lane_center(approach, (MEDIAN_LANE as f32) + 0.5);
+    along_rect(approach, across, BAY_LINE_WIDTH, setback, bay + setback)
+}
@@ -0,0 +1,211 @@
+use std::io::{ BufRead, BufReader, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::sync::{ Arc, Mutex };
+use std::thread;
+
+use crate::error::SimError;
+use crate::simulation::TrafficSimulation;
+use crate::stats::{ level_of_service, movements };
+use crate::traffic_light::Phase;
+use crate::vehicle::Direction;
+
+// Serves the latest snapshot in the Prometheus text format to any HTTP GET, from a
+// background thread. The front end refreshes the snapshot with `update` as the run goes.
+pub struct MetricsServer {
+    snapshot: Arc<Mutex<String>>,
+}
+
+impl MetricsServer {
+    pub fn start(address: &str) -> Result<Self, SimError> {
+        let listener = TcpListener::bind(address)
+            .map_err(|source| SimError::Listen { address: address.to_string(), source })?;
+        let snapshot = Arc::new(Mutex::new(String::new()));
+        let shared = Arc::clone(&snapshot);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let body = shared.lock().map(|body| body.clone()).unwrap_or_default();
+                // A scraper that hangs up early only costs it this scrape.
+                let _ = respond(stream, &body);
+            }
+        });
+        Ok(Self { snapshot })
+    }
+
+    pub fn update(&self, simulation: &TrafficSimulation) {
+        let text = render(simulation);
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = text;
+        }
+    }
+}
+
+// Any path answers with the metrics; the request itself is read and ignored.
+fn respond(stream: TcpStream, body: &str) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line.trim() != "" {
+        line.clear();
+    }
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+pub fn render(simulation: &TrafficSimulation) -> String {
+    let stats = &simulation.stats;
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, f64)]| {
+        out.push_str(&format!("# HELP road_intersection_{} {}\n", name, help));
+        out.push_str(&format!("# TYPE road_intersection_{} {}\n", name, kind));
+        for (labels, value) in samples {
+            out.push_str(&format!("road_intersection_{}{} {}\n", name, labels, value));
+        }
+    };
+    let single = |value: f64| [(String::new(), value)];
+    metric(
+        "simulated_seconds_total",
+        "counter",
+        "Simulated time since the run started.",
+        &single(simulation.time.now().as_secs_f64())
+    );
+    let active = simulation.vehicles().len();
+    metric("vehicles_active", "gauge", "Vehicles on the road.", &single(active as f64));
+    let queues: Vec<(String, f64)> = simulation.lanes
+        .iter()
+        .map(|lane| {
+            let approach = format!("{:?}", lane.direction).to_lowercase();
+            let queue = simulation.queue_length(lane.direction);
+            (format!("{{approach=\"{}\"}}", approach), queue as f64)
+        })
+        .collect();
+    metric(
+        "queue_length",
+        "gauge",
+        "Vehicles waiting on each approach, including those held upstream.",
+        &queues
+    );
+    metric(
+        "average_vehicle_delay_seconds",
+        "gauge",
+        "Mean time completed vehicles spent stopped in traffic.",
+        &single(stats.average_vehicle_delay().as_secs_f64())
+    );
+    let approaches = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let grades: Vec<(String, f64)> = approaches
+        .into_iter()
+        .filter_map(|approach| {
+            let delay = stats.average_control_delay(approach)?;
+            let approach = format!("{:?}", approach).to_lowercase();
+            let labels = format!(
+                "{{approach=\"{}\",los=\"{}\"}}",
+                approach,
+                level_of_service(delay)
+            );
+            Some((labels, delay.as_secs_f64()))
+        })
+        .collect();
+    metric(
+        "control_delay_seconds",
+        "gauge",
+        "Mean control delay of completed vehicles on each approach, labelled with its level \
+         of service.",
+        &grades
+    );
+    let saturation_flows: Vec<(String, f64)> = movements()
+        .filter_map(|(approach, route)| {
+            let summary = stats.saturation_flow_summary(approach, route)?;
+            let labels = format!(
+                "{{approach=\"{}\",route=\"{}\"}}",
+                format!("{:?}", approach).to_lowercase(),
+                format!("{:?}", route).to_lowercase()
+            );
+            Some((labels, summary.saturation_flow as f64))
+        })
+        .collect();
+    metric(
+        "saturation_flow_vehicles_per_hour",
+        "gauge",
+        "Saturation flow of each movement per lane of green, from measured queue discharge \
+         headways.",
+        &saturation_flows
+    );
+    let phase = match simulation.traffic_light().phase {
+        Phase::NorthSouth => 0.0,
+        Phase::EastWest => 1.0,
+    };
+    metric(
+        "phase",
+        "gauge",
+        "Road with right of way: 0 north-south, 1 east-west.",
+        &single(phase)
+    );
+    metric(
+        "vehicles_completed_total",
+        "counter",
+        "Vehicles that finished their trip.",
+        &single(stats.vehicles_completed as f64)
+    );
+    if let Some(summary) = stats.stop_summary() {
+        metric(
+            "stops_per_vehicle",
+            "gauge",
+            "Mean full stops made by vehicles that finished their trip.",
+            &single(summary.mean_stops as f64)
+        );
+        metric(
+            "idle_seconds_per_vehicle",
+            "gauge",
+            "Mean time vehicles that finished their trip stood still.",
+            &single(summary.mean_idle.as_secs_f64())
+        );
+    }
+    metric(
+        "fuel_litres_total",
+        "counter",
+        "Fuel burned by vehicles that finished their trip.",
+        &single(stats.fuel_used as f64)
+    );
+    metric(
+        "co2_kilograms_total",
+        "counter",
+        "CO2 given off by vehicles that finished their trip.",
+        &single(stats.co2_emitted as f64)
+    );
+    if let Some(sample) = stats.noise_samples.last() {
+        let corners = ["north_east", "north_west", "south_east", "south_west"];
+        let levels: Vec<(String, f64)> = corners
+            .into_iter()
+            .zip(sample.levels)
+            .map(|(corner, level)| (format!("{{corner=\"{}\"}}", corner), level as f64))
+            .collect();
+        metric(
+            "noise_level_decibels",
+            "gauge",
+            "Equivalent traffic noise level in dB(A) at each corner over the last second.",
+            &levels
+        );
+    }
+    metric(
+        "buses_completed_total",
+        "counter",
+        "Buses that finished their line.",
+        &single(stats.buses_completed as f64)
+    );
+    metric(
+        "gridlocks_total",
+        "counter",
+        "Gridlocks broken by removing a vehicle.",
+        &single(stats.gridlocks as f64)
+    );
+    metric(
+        "starvations_total",
+        "counter",
+        "Greens forced by the starvation watchdog.",
+        &single(stats.starvations as f64)
+    );
+    out
+}
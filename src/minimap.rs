@@ -0,0 +1,127 @@
+use crate::error::RenderError;
+use crate::render::{ Color, Rect, Renderer, Viewport };
+use crate::simulation::TrafficSimulation;
+use crate::theme::Palette;
+use crate::units::{ Area, View };
+use crate::vehicle::{ vehicle_rect, Direction };
+use crate::{ ROAD_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// In pixels; the height follows from the world's proportions.
+const MINIMAP_WIDTH: u32 = 200;
+const BORDER: u32 = 2;
+// Queues at least this long, in vehicles, tint their approach red on the minimap; any
+// shorter queue tints it yellow.
+const CONGESTED_QUEUE: usize = 8;
+const TINT_ALPHA: u8 = 150;
+
+// On the left under the demand panel, clear of the timing editor on the right.
+pub fn minimap_rect() -> Rect {
+    let height = ((MINIMAP_WIDTH as f32) * WORLD_HEIGHT / WORLD_WIDTH).round() as u32;
+    Rect::new(10 + (BORDER as i32), 230 + (BORDER as i32), MINIMAP_WIDTH, height)
+}
+
+// Whether `view` leaves some of the world out of the window, so the minimap has more to
+// show than the window does.
+pub fn shows_more(view: &View) -> bool {
+    let (left, top) = view.to_world(0, 0);
+    let (right, bottom) = view.to_world(WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32);
+    left > 0.0 || top > 0.0 || right < WORLD_WIDTH || bottom < WORLD_HEIGHT
+}
+
+// The point in the world under window pixel (x, y), if it is on the minimap.
+pub fn to_world(x: i32, y: i32) -> Option<(f32, f32)> {
+    let area = minimap_rect();
+    if !area.intersects(&Rect::new(x, y, 1, 1)) {
+        return None;
+    }
+    let across = ((x - area.x) as f32) / (area.w as f32);
+    let down = ((y - area.y) as f32) / (area.h as f32);
+    Some((across * WORLD_WIDTH, down * WORLD_HEIGHT))
+}
+
+// The whole of `simulation`'s world scaled down into the inset: the roads, each approach
+// tinted by how far its queue backs up, every vehicle as a plain block, and a frame around
+// the part `view` shows.
+pub fn draw(
+    renderer: &mut dyn Renderer,
+    view: &View,
+    simulation: &TrafficSimulation,
+    palette: &Palette
+) -> Result<(), RenderError> {
+    let map = simulation.layout();
+    let area = minimap_rect();
+    let border = Rect::new(
+        area.x - (BORDER as i32),
+        area.y - (BORDER as i32),
+        area.w + 2 * BORDER,
+        area.h + 2 * BORDER
+    );
+    renderer.draw_rect(border, palette.panel)?;
+    let mut inset = Viewport::new(renderer, area);
+    // At this scale the world fills the window, which the inset scales down in turn.
+    let whole = View::new(WORLD_WIDTH / (WINDOW_WIDTH as f32));
+    inset.clear(palette.ground)?;
+    let (center_x, center_y) = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    let junction = Area::centered(center_x, center_y, ROAD_WIDTH, ROAD_WIDTH);
+    inset.draw_rect(whole.rect(junction), palette.road)?;
+    let ends = [Direction::North, Direction::South, Direction::East, Direction::West];
+    for end in ends.into_iter().filter(|&end| map.has_road(end)) {
+        inset.draw_rect(whole.rect(arm_area(end)), palette.road)?;
+    }
+    for lane in simulation.lanes.iter().filter(|lane| map.has_approach(lane.direction)) {
+        let Color { r, g, b, .. } = match simulation.queue_length(lane.direction) {
+            0 => palette.green,
+            queue if queue < CONGESTED_QUEUE => palette.yellow,
+            _ => palette.red,
+        };
+        let tint = Color::rgba(r, g, b, TINT_ALPHA);
+        inset.draw_rect(whole.rect(approach_area(lane.direction)), tint)?;
+    }
+    let vehicles: Vec<Rect> = simulation
+        .vehicles()
+        .iter()
+        .map(|vehicle| whole.rect(vehicle_rect(vehicle)))
+        .collect();
+    inset.draw_rects(&vehicles, palette.marking)?;
+    let (left, top) = view.to_world(0, 0);
+    let (right, bottom) = view.to_world(WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32);
+    let (width, height, line) = (right - left, bottom - top, whole.meters_per_pixel);
+    for edge in [
+        Area::new(left, top, width, line),
+        Area::new(left, bottom - line, width, line),
+        Area::new(left, top, line, height),
+        Area::new(right - line, top, line, height),
+    ] {
+        inset.draw_rect(whole.rect(edge), palette.text)?;
+    }
+    Ok(())
+}
+
+// The road out to `end`, from the edge of the junction to the edge of the world.
+fn arm_area(end: Direction) -> Area {
+    let half = ROAD_WIDTH / 2.0;
+    let (center_x, center_y) = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    match end {
+        Direction::North => Area::new(center_x - half, 0.0, ROAD_WIDTH, center_y - half),
+        Direction::South => {
+            Area::new(center_x - half, center_y + half, ROAD_WIDTH, center_y - half)
+        }
+        Direction::East => {
+            Area::new(center_x + half, center_y - half, center_x - half, ROAD_WIDTH)
+        }
+        Direction::West => Area::new(0.0, center_y - half, center_x - half, ROAD_WIDTH),
+    }
+}
+
+// The half of the road traffic travelling `direction` comes up to the junction on, to the
+// right of the center line.
+fn approach_area(direction: Direction) -> Area {
+    let half = ROAD_WIDTH / 2.0;
+    let (center_x, center_y) = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    match direction {
+        Direction::North => Area::new(center_x, center_y + half, half, center_y - half),
+        Direction::South => Area::new(center_x - half, 0.0, half, center_y - half),
+        Direction::East => Area::new(0.0, center_y, center_x - half, half),
+        Direction::West => Area::new(center_x + half, center_y - half, center_x - half, half),
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use crate::portable_math::hypot;
+use crate::units::per_tick;
+use crate::vehicle::{ Vehicle, VehicleId };
+
+// Slack for rounding in the path arithmetic, in meters.
+const TOLERANCE: f32 = 0.001;
+
+// Where each vehicle was at the end of the last tick and the fastest it could have gone on
+// from there, to check that none covered more ground in a tick than its top speed allows.
+// A vehicle that jumps is a bug in the motion code, not traffic.
+#[derive(Debug, Clone, Default)]
+pub struct MotionGuard {
+    // Position and top speed, by vehicle.
+    last: HashMap<VehicleId, (f32, f32, f32)>,
+}
+
+impl MotionGuard {
+    // Of `vehicles`, those that moved further than their top speed since the last check,
+    // each logged with its full state. Those just entered are only remembered.
+    pub fn check(&mut self, vehicles: &[&Vehicle]) -> Vec<VehicleId> {
+        let mut teleported = Vec::new();
+        let mut last = HashMap::with_capacity(self.last.len());
+        for vehicle in vehicles {
+            if let Some(&(x, y, top_speed)) = self.last.get(&vehicle.id) {
+                let moved = hypot(vehicle.x - x, vehicle.y - y);
+                if moved > per_tick(top_speed) + TOLERANCE {
+                    tracing::error!(
+                        moved,
+                        top_speed,
+                        from = ?(x, y),
+                        ?vehicle,
+                        "vehicle moved further in a tick than its top speed"
+                    );
+                    teleported.push(vehicle.id);
+                }
+            }
+            // Vehicles never speed up past the speed their driver wants, but may still be
+            // slowing down to it.
+            let top_speed = vehicle.desired_speed.max(vehicle.speed);
+            last.insert(vehicle.id, (vehicle.x, vehicle.y, top_speed));
+        }
+        self.last = last;
+        teleported
+    }
+}
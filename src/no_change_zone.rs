@@ -0,0 +1,20 @@
+use crate::median::along_rect;
+use crate::units::Area;
+use crate::vehicle::{ lane_center, Direction };
+use crate::LANES_PER_DIRECTION;
+
+// Longest stretch before the stop line over which lane changes can be forbidden, which
+// leaves room to change lanes between the spawn point and the zone on every approach.
+pub const MAX_NO_CHANGE_ZONE: f32 = 20.0;
+const LINE_WIDTH: f32 = 0.2;
+
+// Solid lines over the dashed dividers between `approach`'s lanes, `zone` back from its stop
+// line set `setback` back.
+pub fn solid_divider_rects(approach: Direction, zone: f32, setback: f32) -> Vec<Area> {
+    (1..LANES_PER_DIRECTION)
+        .map(|divider| {
+            let across = lane_center(approach, (divider as f32) - 0.5);
+            along_rect(approach, across, LINE_WIDTH, setback, setback + zone)
+        })
+        .collect()
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use crate::clock::TICK;
+use crate::error::RenderError;
+use crate::pedestrian::{ Corner, CORNERS };
+use crate::portable_math::{ exp10, hypot, log10 };
+use crate::render::{ font, Color, Rect, Renderer };
+use crate::units::{ mps_to_kmh, Area, View };
+use crate::vehicle::{ Vehicle, VehicleKind };
+use crate::{ BIKE_LANE_WIDTH, ROAD_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// Receivers stand this far from the center of the intersection along both axes, just back
+// from the curb at each corner.
+const RECEIVER_OFFSET: f32 = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 4.0;
+const RECEIVER_SIZE: u32 = 8;
+// Receiver levels are averaged over this long.
+const PERIOD: Duration = Duration::from_secs(1);
+// Sound power of a car at the reference speed, and its rise with speed; below the floor
+// speed the engine drowns out the tyres and the level stays put. Buses are louder.
+const CAR_SOUND_POWER_DB: f32 = 95.0;
+const REFERENCE_SPEED_KMH: f32 = 70.0;
+const SPEED_COEFFICIENT: f32 = 30.0;
+const FLOOR_SPEED_KMH: f32 = 20.0;
+const IDLE_SOUND_POWER_DB: f32 = 75.0;
+const BUS_EXTRA_DB: f32 = 8.0;
+// The town around the intersection when no traffic is near.
+const AMBIENT_DB: f32 = 40.0;
+// Closer than this a vehicle counts as this close.
+const MIN_DISTANCE_M: f32 = 1.0;
+// The overlay shades levels in bands of BAND_DB from QUIET_DB to LOUD_DB, over squares of
+// CELL_SIZE meters.
+const CELL_SIZE: f32 = 2.0;
+const BAND_DB: f32 = 5.0;
+const QUIET_DB: f32 = 45.0;
+const LOUD_DB: f32 = 80.0;
+
+// A-weighted sound power of `vehicle` in dB, from its kind and speed.
+pub fn sound_power(vehicle: &Vehicle) -> f32 {
+    let speed_kmh = mps_to_kmh(vehicle.speed);
+    let level = if speed_kmh <= 0.0 {
+        IDLE_SOUND_POWER_DB
+    } else {
+        let speed = speed_kmh.max(FLOOR_SPEED_KMH) / REFERENCE_SPEED_KMH;
+        CAR_SOUND_POWER_DB + SPEED_COEFFICIENT * (log10(speed as f64) as f32)
+    };
+    match vehicle.kind {
+        VehicleKind::Car => level,
+        VehicleKind::Bus => level + BUS_EXTRA_DB,
+    }
+}
+
+// Sound pressure level in dB(A) at (x, y) in the world: every vehicle a point source
+// over hard ground, added up on top of the ambient level.
+pub fn level_at<'a>(vehicles: impl Iterator<Item = &'a Vehicle>, x: f32, y: f32) -> f32 {
+    let mut total = energy(AMBIENT_DB);
+    for vehicle in vehicles {
+        let distance = hypot(vehicle.x - x, vehicle.y - y).max(MIN_DISTANCE_M);
+        total += energy(sound_power(vehicle) - 20.0 * (log10(distance as f64) as f32) - 8.0);
+    }
+    decibels(total)
+}
+
+pub fn receiver_position(corner: Corner) -> (f32, f32) {
+    let (sx, sy) = corner.signs();
+    let x = WORLD_WIDTH / 2.0 + (sx as f32) * RECEIVER_OFFSET;
+    let y = WORLD_HEIGHT / 2.0 + (sy as f32) * RECEIVER_OFFSET;
+    (x, y)
+}
+
+// The steady level with the same sound energy as `levels` taken over equal periods; None
+// without any.
+pub fn equivalent_level(levels: impl Iterator<Item = f32>) -> Option<f32> {
+    let (total, count) = levels.fold((0.0, 0), |(total, count), level| {
+        (total + energy(level), count + 1)
+    });
+    (count > 0).then(|| decibels(total / (count as f64)))
+}
+
+fn energy(level: f32) -> f64 {
+    exp10((level as f64) / 10.0)
+}
+
+fn decibels(energy: f64) -> f32 {
+    (10.0 * log10(energy)) as f32
+}
+
+// Energy-averages the level at each corner's receiver, in `CORNERS` order, over every
+// PERIOD of the run.
+#[derive(Debug, Clone, Default)]
+pub struct NoiseMeter {
+    energy: [f64; 4],
+    ticks: u32,
+}
+
+impl NoiseMeter {
+    // Adds this tick's traffic; returns the equivalent levels once a period is complete.
+    pub fn record<'a>(
+        &mut self,
+        vehicles: impl Iterator<Item = &'a Vehicle> + Clone
+    ) -> Option<[f32; 4]> {
+        for (i, &corner) in CORNERS.iter().enumerate() {
+            let (x, y) = receiver_position(corner);
+            self.energy[i] += energy(level_at(vehicles.clone(), x, y));
+        }
+        self.ticks += 1;
+        if TICK * self.ticks < PERIOD {
+            return None;
+        }
+        let ticks = std::mem::take(&mut self.ticks) as f64;
+        let energy = std::mem::take(&mut self.energy);
+        Some(energy.map(|energy| decibels(energy / ticks)))
+    }
+}
+
+// The level across the world right now in bands, green through red, with each receiver
+// marked and labelled with its level.
+pub fn draw<'a>(
+    renderer: &mut dyn Renderer,
+    view: &View,
+    vehicles: impl Iterator<Item = &'a Vehicle> + Clone,
+    text: Color
+) -> Result<(), RenderError> {
+    let columns = (WORLD_WIDTH / CELL_SIZE).ceil() as usize;
+    let rows = (WORLD_HEIGHT / CELL_SIZE).ceil() as usize;
+    let half = CELL_SIZE / 2.0;
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = ((column as f32) * CELL_SIZE, (row as f32) * CELL_SIZE);
+            let level = level_at(vehicles.clone(), x + half, y + half);
+            if level < QUIET_DB {
+                continue;
+            }
+            let band = ((level - QUIET_DB) / BAND_DB).floor() * BAND_DB;
+            let loudness = (band / (LOUD_DB - QUIET_DB)).min(1.0);
+            let color = Color::rgba(
+                (255.0 * loudness.min(0.5) * 2.0) as u8,
+                (255.0 * (1.0 - loudness).min(0.5) * 2.0) as u8,
+                0,
+                (50.0 + 90.0 * loudness) as u8
+            );
+            renderer.draw_rect(view.rect(Area::new(x, y, CELL_SIZE, CELL_SIZE)), color)?;
+        }
+    }
+    for corner in CORNERS {
+        let (x, y) = receiver_position(corner);
+        let label = format!("{:.0} DB", level_at(vehicles.clone(), x, y));
+        let (x, y) = view.point(x, y);
+        let marker = (RECEIVER_SIZE as i32) / 2;
+        let rect = Rect::new(x - marker, y - marker, RECEIVER_SIZE, RECEIVER_SIZE);
+        renderer.draw_rect(rect, text)?;
+        // Labels go on the side away from the road, clear of the walk buttons.
+        let width = font::GLYPH_ADVANCE * (label.len() as i32);
+        let left = if corner.signs().0 > 0 { x + marker + 4 } else { x - marker - 4 - width };
+        renderer.draw_text(&label, left, y - marker, text)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::MapError;
+use crate::vehicle::Direction;
+use crate::LANES_PER_DIRECTION;
+
+// Arms more than this far off the nearest compass direction don't fit the cross layout.
+const MAX_SKEW_DEGREES: f32 = 30.0;
+
+// One road leaving the intersection node, as read from an OpenStreetMap extract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    // The way the arm leaves the intersection.
+    pub side: Direction,
+    // Degrees clockwise from north.
+    pub bearing: f32,
+    pub name: Option<String>,
+    // Travel lanes toward and away from the intersection; zero one way on a one-way arm.
+    pub lanes_in: Option<u32>,
+    pub lanes_out: Option<u32>,
+    pub max_speed_kmh: Option<f32>,
+}
+
+// A four-way intersection found in an extract: its node and one arm per compass side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmIntersection {
+    pub node: i64,
+    pub arms: Vec<Arm>,
+}
+
+struct Way {
+    nodes: Vec<i64>,
+    tags: HashMap<String, String>,
+}
+
+struct Extract {
+    // (latitude, longitude) by node id.
+    positions: HashMap<i64, (f64, f64)>,
+    ways: Vec<Way>,
+}
+
+impl OsmIntersection {
+    // Reads the OSM XML extract at `path` and finds the intersection at `node`, or else
+    // the one node where four roads meet.
+    pub fn load(path: &Path, node: Option<i64>) -> Result<Self, MapError> {
+        fs::read_to_string(path)
+            .map_err(MapError::from)
+            .and_then(|text| Self::parse(&text, node))
+            .map_err(|e| e.in_file(path))
+    }
+
+    pub fn parse(text: &str, node: Option<i64>) -> Result<Self, MapError> {
+        let Extract { positions, ways } = read_osm(text)?;
+        let roads: Vec<&Way> = ways
+            .iter()
+            .filter(|way| way.tags.get("highway").is_some_and(|kind| is_road(kind)))
+            .collect();
+        let mut arm_counts: HashMap<i64, usize> = HashMap::new();
+        for way in &roads {
+            for (i, &id) in way.nodes.iter().enumerate() {
+                let arms = usize::from(i > 0) + usize::from(i + 1 < way.nodes.len());
+                *arm_counts.entry(id).or_default() += arms;
+            }
+        }
+        let node = match node {
+            Some(node) => node,
+            None => {
+                let four_way: Vec<i64> = arm_counts
+                    .iter()
+                    .filter(|&(_, &arms)| arms == 4)
+                    .map(|(&id, _)| id)
+                    .collect();
+                match four_way[..] {
+                    [node] => node,
+                    [] => {
+                        let message = "no node where four roads meet".to_string();
+                        return Err(MapError::Invalid(message));
+                    }
+                    _ => {
+                        return Err(MapError::Invalid(format!(
+                            "{} nodes where four roads meet, pick one by id",
+                            four_way.len()
+                        )));
+                    }
+                }
+            }
+        };
+        let center = *positions.get(&node).ok_or_else(|| no_node(node))?;
+        let mut arms: Vec<Arm> = Vec::new();
+        for way in &roads {
+            for (i, _) in way.nodes.iter().enumerate().filter(|&(_, &id)| id == node) {
+                let neighbours = [
+                    (i.checked_sub(1), false),
+                    ((i + 1 < way.nodes.len()).then_some(i + 1), true),
+                ];
+                for (neighbour, forward) in neighbours {
+                    let Some(neighbour) = neighbour else {
+                        continue;
+                    };
+                    let id = way.nodes[neighbour];
+                    let position = *positions.get(&id).ok_or_else(|| no_node(id))?;
+                    arms.push(arm(way, bearing(center, position), forward)?);
+                }
+            }
+        }
+        if arms.len() != 4 {
+            return Err(MapError::Invalid(format!(
+                "{} roads meet at node {}, not four",
+                arms.len(),
+                node
+            )));
+        }
+        for side in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            if !arms.iter().any(|arm| arm.side == side) {
+                return Err(MapError::Invalid(format!(
+                    "node {} has no road to the {:?}",
+                    node,
+                    side
+                )));
+            }
+        }
+        Ok(Self { node, arms })
+    }
+
+    // Takes over what the cross layout can represent, the speed limits, into `config`:
+    // each road gets the lower limit of its two arms. Anything else that differs from the
+    // layout is logged.
+    pub fn apply(&self, config: &mut Config) {
+        let limit = |sides: [Direction; 2]| {
+            self.arms
+                .iter()
+                .filter(|arm| sides.contains(&arm.side))
+                .filter_map(|arm| arm.max_speed_kmh)
+                .min_by(f32::total_cmp)
+        };
+        if let Some(limit) = limit([Direction::North, Direction::South]) {
+            config.speed_limits.north_south_kmh = limit;
+        }
+        if let Some(limit) = limit([Direction::East, Direction::West]) {
+            config.speed_limits.east_west_kmh = limit;
+        }
+        for arm in &self.arms {
+            let name = arm.name.as_deref().unwrap_or("unnamed road");
+            tracing::info!(
+                side = ?arm.side,
+                bearing = arm.bearing.round(),
+                lanes_in = arm.lanes_in,
+                lanes_out = arm.lanes_out,
+                max_speed_kmh = arm.max_speed_kmh,
+                "{}",
+                name
+            );
+            let lanes = [arm.lanes_in, arm.lanes_out];
+            if lanes.iter().flatten().any(|&lanes| lanes as usize != LANES_PER_DIRECTION) {
+                tracing::warn!(
+                    "{} to the {:?} keeps {} lanes each way, the only layout supported",
+                    name,
+                    arm.side,
+                    LANES_PER_DIRECTION
+                );
+            }
+        }
+    }
+}
+
+// The arm of `way` leaving the center at `bearing`, toward the end of the way's node list
+// if `forward`.
+fn arm(way: &Way, bearing: f32, forward: bool) -> Result<Arm, MapError> {
+    let side = match bearing {
+        b if b.min(360.0 - b) <= MAX_SKEW_DEGREES => Direction::North,
+        b if (b - 90.0).abs() <= MAX_SKEW_DEGREES => Direction::East,
+        b if (b - 180.0).abs() <= MAX_SKEW_DEGREES => Direction::South,
+        b if (b - 270.0).abs() <= MAX_SKEW_DEGREES => Direction::West,
+        b => {
+            return Err(MapError::Invalid(format!(
+                "a road leaves at {:.0} degrees, too far off the compass",
+                b
+            )));
+        }
+    };
+    let tag = |key: &str| way.tags.get(key);
+    let count = |key: &str| tag(key).and_then(|value| value.trim().parse::<u32>().ok());
+    let oneway = tag("oneway").map(String::as_str);
+    let (lanes_forward, lanes_backward) = match oneway {
+        Some("yes" | "true" | "1") => (count("lanes"), Some(0)),
+        Some("-1" | "reverse") => (Some(0), count("lanes")),
+        // Without per-direction counts, a two-way road's lanes are split evenly.
+        _ => (
+            count("lanes:forward").or(count("lanes").map(|lanes| lanes / 2)),
+            count("lanes:backward").or(count("lanes").map(|lanes| lanes.div_ceil(2)))
+        ),
+    };
+    // Traffic in the way's forward direction leaves along arms that run forward.
+    let (lanes_out, lanes_in) = if forward {
+        (lanes_forward, lanes_backward)
+    } else {
+        (lanes_backward, lanes_forward)
+    };
+    Ok(Arm {
+        side,
+        bearing,
+        name: tag("name").cloned(),
+        lanes_in,
+        lanes_out,
+        max_speed_kmh: tag("maxspeed").and_then(|value| parse_speed(value)),
+    })
+}
+
+fn is_road(kind: &str) -> bool {
+    matches!(
+        kind,
+        "motorway" |
+            "trunk" |
+            "primary" |
+            "secondary" |
+            "tertiary" |
+            "unclassified" |
+            "residential" |
+            "living_street" |
+            "service" |
+            "motorway_link" |
+            "trunk_link" |
+            "primary_link" |
+            "secondary_link" |
+            "tertiary_link"
+    )
+}
+
+// "50", "50 km/h" and "30 mph" are understood; words such as "signals" or "none" are not.
+fn parse_speed(value: &str) -> Option<f32> {
+    let value = value.trim();
+    if let Some(mph) = value.strip_suffix("mph") {
+        return mph.trim().parse::<f32>().ok().filter(|&mph| mph > 0.0).map(|mph| mph * 1.609_344);
+    }
+    value.trim_end_matches("km/h").trim().parse().ok().filter(|&kmh: &f32| kmh > 0.0)
+}
+
+// Compass bearing in degrees from `from` to `to`, both (latitude, longitude), over the
+// short distances within an intersection.
+fn bearing(from: (f64, f64), to: (f64, f64)) -> f32 {
+    let north = to.0 - from.0;
+    let east = (to.1 - from.1) * from.0.to_radians().cos();
+    (east.atan2(north).to_degrees().rem_euclid(360.0)) as f32
+}
+
+// Only what the importer needs is read from the OSM XML: the elements' attributes and the
+// ways' node references and tags.
+fn read_osm(text: &str) -> Result<Extract, MapError> {
+    let mut positions = HashMap::new();
+    let mut ways = Vec::new();
+    let mut way: Option<Way> = None;
+    for element in text.split('<').skip(1) {
+        let Some(end) = element.find('>') else {
+            return Err(MapError::Invalid("unterminated element".to_string()));
+        };
+        let element = element[..end].trim_end_matches('/');
+        let (name, rest) = element.split_once(char::is_whitespace).unwrap_or((element, ""));
+        let attributes = attributes(rest);
+        let attribute = |key: &str| {
+            attributes
+                .get(key)
+                .ok_or_else(|| MapError::Invalid(format!("<{}> without {}", name, key)))
+        };
+        let number = |key: &str| -> Result<f64, MapError> {
+            let value = attribute(key)?;
+            value.parse().map_err(|_| invalid(key, value))
+        };
+        let id = |key: &str| -> Result<i64, MapError> {
+            let value = attribute(key)?;
+            value.parse().map_err(|_| invalid(key, value))
+        };
+        match name {
+            "node" => {
+                positions.insert(id("id")?, (number("lat")?, number("lon")?));
+            }
+            "way" => {
+                way = Some(Way { nodes: Vec::new(), tags: HashMap::new() });
+            }
+            "nd" => {
+                if let Some(way) = way.as_mut() {
+                    way.nodes.push(id("ref")?);
+                }
+            }
+            "tag" => {
+                if let Some(way) = way.as_mut() {
+                    way.tags.insert(attribute("k")?.clone(), attribute("v")?.clone());
+                }
+            }
+            "/way" => ways.extend(way.take()),
+            _ => {}
+        }
+    }
+    Ok(Extract { positions, ways })
+}
+
+// key="value" pairs, with either quote, and XML's predefined entities in values.
+fn no_node(id: i64) -> MapError {
+    MapError::Invalid(format!("no node {}", id))
+}
+
+fn invalid(key: &str, value: &str) -> MapError {
+    MapError::Invalid(format!("invalid {} \"{}\"", key, value))
+}
+
+fn attributes(text: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = text;
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let Some(quote) = after.chars().next().filter(|&c| c == '"' || c == '\'') else {
+            break;
+        };
+        let Some((value, next)) = after[1..].split_once(quote) else {
+            break;
+        };
+        let value = value
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&");
+        attributes.insert(key.trim().to_string(), value);
+        rest = next;
+    }
+    attributes
+}
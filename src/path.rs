@@ -0,0 +1,132 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::portable_math::{ hypot, sin_cos };
+use crate::vehicle::{ heading, lane_center, turned_direction, Direction, Route };
+use crate::{ LANE_CHANGE_LENGTH, ROAD_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+pub const MAX_PATH_POINTS: usize = 12;
+// Straight segments used to approximate the quarter circle of a turn.
+const ARC_SEGMENTS: usize = 6;
+
+// Polyline a vehicle follows, stored inline so vehicles stay `Copy`. `next` is the index
+// of the waypoint currently being driven towards.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path {
+    points: [(f32, f32); MAX_PATH_POINTS],
+    len: usize,
+    next: usize,
+}
+
+impl Path {
+    pub fn push(&mut self, point: (f32, f32)) {
+        assert!(self.len < MAX_PATH_POINTS, "path has more than {} waypoints", MAX_PATH_POINTS);
+        self.points[self.len] = point;
+        self.len += 1;
+    }
+
+    pub fn waypoints(&self) -> &[(f32, f32)] {
+        &self.points[self.next..self.len]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.len
+    }
+
+    // Length of what is left of the path, following it from `position`.
+    pub fn length_from(&self, position: (f32, f32)) -> f32 {
+        let mut length = 0.0;
+        let mut last = position;
+        for &point in self.waypoints() {
+            length += hypot(point.0 - last.0, point.1 - last.1);
+            last = point;
+        }
+        length
+    }
+
+    // Moves `position` up to `distance` along the remaining waypoints and returns the
+    // heading of the last segment travelled.
+    pub fn advance(
+        &mut self,
+        position: &mut (f32, f32),
+        mut distance: f32
+    ) -> Option<(f32, f32)> {
+        let mut heading = None;
+        while distance > 0.0 && !self.is_finished() {
+            let target = self.points[self.next];
+            let (dx, dy) = (target.0 - position.0, target.1 - position.1);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > f32::EPSILON {
+                heading = Some((dx / length, dy / length));
+            }
+            if length <= distance {
+                *position = target;
+                distance -= length;
+                self.next += 1;
+            } else {
+                position.0 += (dx / length) * distance;
+                position.1 += (dy / length) * distance;
+                distance = 0.0;
+            }
+        }
+        heading
+    }
+}
+
+// Path from `from` on the `approach` road: over to the center of `lane` if not already
+// there, through the intersection along a quarter circle for turns, and out along the exit
+// lane until `margin` past the edge of the world, where its sink is. Turns keep to the
+// same lane number, so two turning side by side follow concentric arcs.
+pub fn plan_path(
+    approach: Direction,
+    route: Route,
+    lane: usize,
+    from: (f32, f32),
+    margin: f32
+) -> Path {
+    let mut path = Path::default();
+    let across = lane_center(approach, lane as f32);
+    let (hx, hy) = heading(approach);
+    let mut last = on_lane(approach, across, from);
+    if last != from {
+        last = (last.0 + hx * LANE_CHANGE_LENGTH, last.1 + hy * LANE_CHANGE_LENGTH);
+        path.push(last);
+    }
+
+    let exit_direction = turned_direction(approach, route);
+    if route != Route::Straight {
+        let exit_across = lane_center(exit_direction, lane as f32);
+        let corner = match approach {
+            Direction::North | Direction::South => (across, exit_across),
+            Direction::East | Direction::West => (exit_across, across),
+        };
+        // The arc starts where the approach lane enters the intersection box.
+        let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        let corner_ahead = (corner.0 - center.0) * hx + (corner.1 - center.1) * hy;
+        let radius = ROAD_WIDTH / 2.0 + corner_ahead;
+        let (ex, ey) = heading(exit_direction);
+        let pivot = (corner.0 + (ex - hx) * radius, corner.1 + (ey - hy) * radius);
+        for k in 0..=ARC_SEGMENTS {
+            let angle = ((k as f32) / (ARC_SEGMENTS as f32)) * FRAC_PI_2;
+            let (sin, cos) = sin_cos(angle as f64);
+            let (cos, sin) = ((cos as f32) * radius, (sin as f32) * radius);
+            last = (pivot.0 - ex * cos + hx * sin, pivot.1 - ey * cos + hy * sin);
+            path.push(last);
+        }
+    }
+
+    path.push(match exit_direction {
+        Direction::North => (last.0, -margin),
+        Direction::South => (last.0, WORLD_HEIGHT + margin),
+        Direction::East => (WORLD_WIDTH + margin, last.1),
+        Direction::West => (-margin, last.1),
+    });
+    path
+}
+
+// `point` moved sideways onto the lane whose center is at `across` for `direction`.
+fn on_lane(direction: Direction, across: f32, point: (f32, f32)) -> (f32, f32) {
+    match direction {
+        Direction::North | Direction::South => (across, point.1),
+        Direction::East | Direction::West => (point.0, across),
+    }
+}
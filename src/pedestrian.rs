@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use crate::units::{ per_tick, Area };
+use crate::vehicle::{ heading, relative_offset, Direction, Vehicle };
+use crate::{ BIKE_LANE_WIDTH, ROAD_WIDTH, VEHICLE_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+pub const PEDESTRIAN_SIZE: f32 = 0.6;
+// Walking speeds in m/s; the slowest take about as long as the clearance interval to cross.
+pub const MIN_WALKING_SPEED: f32 = 4.0;
+pub const MAX_WALKING_SPEED: f32 = 6.0;
+pub const BUTTON_SIZE: f32 = 1.0;
+// Clicks this close to a button still press it.
+const BUTTON_SLACK: f32 = 0.6;
+// Distance of each corner's button from the center of the intersection along both axes,
+// out on the sidewalk past the corner's traffic light.
+const BUTTON_OFFSET: f32 = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 4.0;
+// Crosswalks run from curb to curb, over the bike lanes.
+const CROSSWALK_LENGTH: f32 = ROAD_WIDTH + BIKE_LANE_WIDTH * 2.0;
+// Jaywalkers crossing between the crosswalks step out this far from the center of the
+// intersection along the road, from just past the crosswalk to well short of the edge.
+pub const MID_BLOCK_NEAREST: f32 = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 3.0;
+pub const MID_BLOCK_FARTHEST: f32 = 30.0;
+// A vehicle bearing down on a jaywalker with less than this long to go at its speed is a
+// conflict; one whose front comes within NEAR_MISS_GAP of them is a near miss.
+const CONFLICT_TIME: f32 = 1.5;
+const NEAR_MISS_GAP: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+pub const CORNERS: [Corner; 4] = [
+    Corner::NorthEast,
+    Corner::NorthWest,
+    Corner::SouthEast,
+    Corner::SouthWest,
+];
+
+impl Corner {
+    // Which way the corner lies from the center of the intersection on screen.
+    pub fn signs(self) -> (i32, i32) {
+        match self {
+            Corner::NorthEast => (1, -1),
+            Corner::NorthWest => (-1, -1),
+            Corner::SouthEast => (1, 1),
+            Corner::SouthWest => (-1, 1),
+        }
+    }
+
+    // The two crosswalks that start at this corner, by the approach whose stop line they
+    // lie past: one across each road.
+    pub fn crosswalks(self) -> [Direction; 2] {
+        let (sx, sy) = self.signs();
+        let across_north_south = if sy < 0 { Direction::South } else { Direction::North };
+        let across_east_west = if sx < 0 { Direction::East } else { Direction::West };
+        [across_north_south, across_east_west]
+    }
+}
+
+// How close a vehicle came to hitting a jaywalker, ordered from least to most serious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CloseCall {
+    Conflict,
+    NearMiss,
+}
+
+// Someone who pressed the button at `corner` to use the `crosswalk` past that approach's
+// stop line. They wait at the corner until the walk signal, then cross at `speed`.
+// Jaywalkers don't wait, and drivers don't hold back for them; they only brake once one is
+// in their path.
+#[derive(Debug, Clone, Copy)]
+pub struct Pedestrian {
+    pub corner: Corner,
+    pub crosswalk: Direction,
+    pub waiting_since: Duration,
+    pub speed: f32,
+    pub x: f32,
+    pub y: f32,
+    pub jaywalking: bool,
+    // The closest call a vehicle has given them so far.
+    pub close_call: Option<CloseCall>,
+}
+
+impl Pedestrian {
+    pub fn new(corner: Corner, crosswalk: Direction, now: Duration, speed: f32) -> Self {
+        let (x, y) = crosswalk_end(crosswalk, corner);
+        Self {
+            corner,
+            crosswalk,
+            waiting_since: now,
+            speed,
+            x,
+            y,
+            jaywalking: false,
+            close_call: None,
+        }
+    }
+
+    // A jaywalker setting off from the curb at `corner` over the same road as `crosswalk`,
+    // but `distance` from the center of the intersection rather than on the crosswalk.
+    pub fn mid_block(
+        corner: Corner,
+        crosswalk: Direction,
+        distance: f32,
+        now: Duration,
+        speed: f32
+    ) -> Self {
+        let (hx, hy) = heading(crosswalk);
+        let (x, y) = crosswalk_end(crosswalk, corner);
+        let (x, y) = if hx == 0.0 {
+            (x, WORLD_HEIGHT / 2.0 - hy * distance)
+        } else {
+            (WORLD_WIDTH / 2.0 - hx * distance, y)
+        };
+        Self { x, y, jaywalking: true, ..Self::new(corner, crosswalk, now, speed) }
+    }
+
+    // Steps along the crosswalk, away from the corner they started at.
+    pub fn walk(&mut self) {
+        let (sx, sy) = self.corner.signs();
+        let (hx, _) = heading(self.crosswalk);
+        let step = per_tick(self.speed);
+        if hx == 0.0 {
+            self.x -= (sx as f32) * step;
+        } else {
+            self.y -= (sy as f32) * step;
+        }
+    }
+
+    // Whether they have reached the curb on the far side.
+    pub fn has_crossed(&self) -> bool {
+        let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        let (sx, sy) = self.corner.signs();
+        let (hx, _) = heading(self.crosswalk);
+        let half = CROSSWALK_LENGTH / 2.0;
+        if hx == 0.0 {
+            (self.x - center.0) * (sx as f32) < -half
+        } else {
+            (self.y - center.1) * (sy as f32) < -half
+        }
+    }
+}
+
+// How close `vehicle` is to hitting `pedestrian`, if it is moving and they are in its path
+// or walking into its side.
+pub fn close_call(vehicle: &Vehicle, pedestrian: &Pedestrian) -> Option<CloseCall> {
+    let position = (pedestrian.x, pedestrian.y);
+    let (ahead, sideways) = relative_offset(vehicle.direction, (vehicle.x, vehicle.y), position);
+    let reach = (VEHICLE_WIDTH + PEDESTRIAN_SIZE) / 2.0;
+    let clearance = (vehicle.length() + PEDESTRIAN_SIZE) / 2.0;
+    if vehicle.speed == 0.0 || ahead <= -clearance || sideways.abs() >= reach {
+        return None;
+    }
+    let gap = ahead - clearance;
+    if gap < NEAR_MISS_GAP {
+        Some(CloseCall::NearMiss)
+    } else if gap < vehicle.speed * CONFLICT_TIME {
+        Some(CloseCall::Conflict)
+    } else {
+        None
+    }
+}
+
+pub fn button_rect(corner: Corner) -> Area {
+    let (sx, sy) = corner.signs();
+    let x = WORLD_WIDTH / 2.0 + (sx as f32) * BUTTON_OFFSET;
+    let y = WORLD_HEIGHT / 2.0 + (sy as f32) * BUTTON_OFFSET;
+    Area::centered(x, y, BUTTON_SIZE, BUTTON_SIZE)
+}
+
+// The pedestrian signal head, just toward the road from the button.
+pub fn signal_rect(corner: Corner) -> Area {
+    let (sx, _) = corner.signs();
+    let button = button_rect(corner);
+    let x = button.x - (sx as f32) * (BUTTON_SIZE + 0.4);
+    Area::new(x, button.y, BUTTON_SIZE, BUTTON_SIZE)
+}
+
+// Where the clearance countdown is written, on the side of the signal away from the road.
+pub fn countdown_position(corner: Corner) -> (f32, f32) {
+    let (_, sy) = corner.signs();
+    let signal = signal_rect(corner);
+    (signal.x, signal.y + if sy < 0 { -1.6 } else { BUTTON_SIZE + 0.4 })
+}
+
+// The corner whose button is at (x, y), if any.
+pub fn button_at(x: f32, y: f32) -> Option<Corner> {
+    CORNERS.into_iter().find(|&corner| button_rect(corner).grown(BUTTON_SLACK).contains(x, y))
+}
+
+// The crosswalk over the road `approach` traffic arrives on, between its stop line and
+// the box where the travel lanes cross.
+pub fn crosswalk_rect(approach: Direction) -> Area {
+    let (x, y) = crosswalk_center(approach);
+    let (hx, _) = heading(approach);
+    let (w, h) = if hx == 0.0 {
+        (CROSSWALK_LENGTH, BIKE_LANE_WIDTH)
+    } else {
+        (BIKE_LANE_WIDTH, CROSSWALK_LENGTH)
+    };
+    Area::centered(x, y, w, h)
+}
+
+fn crosswalk_center(approach: Direction) -> (f32, f32) {
+    let (hx, hy) = heading(approach);
+    let setback = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH / 2.0;
+    (WORLD_WIDTH / 2.0 - hx * setback, WORLD_HEIGHT / 2.0 - hy * setback)
+}
+
+// Where the crosswalk's center line meets the curb at `corner`.
+fn crosswalk_end(crosswalk: Direction, corner: Corner) -> (f32, f32) {
+    let (x, y) = crosswalk_center(crosswalk);
+    let (sx, sy) = corner.signs();
+    let (hx, _) = heading(crosswalk);
+    let half = CROSSWALK_LENGTH / 2.0;
+    if hx == 0.0 { (x + (sx as f32) * half, y) } else { (x, y + (sy as f32) * half) }
+}
+
+// The `index`th pedestrian waiting at `corner`, standing in a row behind the button.
+pub fn waiting_rect(corner: Corner, index: usize) -> Area {
+    let (sx, sy) = corner.signs();
+    let button = button_rect(corner);
+    let step = (PEDESTRIAN_SIZE + 0.2) * ((index as f32) + 1.0);
+    let x = button.x + (BUTTON_SIZE - PEDESTRIAN_SIZE) / 2.0 + (sx as f32) * step;
+    let y = button.y + (BUTTON_SIZE - PEDESTRIAN_SIZE) / 2.0 + (sy as f32) * 0.2;
+    Area::new(x, y, PEDESTRIAN_SIZE, PEDESTRIAN_SIZE)
+}
+
+pub fn pedestrian_rect(pedestrian: &Pedestrian) -> Area {
+    Area::centered(pedestrian.x, pedestrian.y, PEDESTRIAN_SIZE, PEDESTRIAN_SIZE)
+}
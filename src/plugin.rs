@@ -0,0 +1,321 @@
+use rand::{ Rng, RngCore };
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::agents::Detector;
+use crate::clock::TICK;
+use crate::config::DemandConfig;
+use crate::driver::DriverProfile;
+use crate::error::ConfigError;
+use crate::lane::Lane;
+use crate::sink::node_id;
+use crate::traffic_light::{ LightState, TrafficLight };
+use crate::vehicle::Direction;
+use crate::LANES_PER_DIRECTION;
+
+// The target-density policy's rate on an approach, in vehicles per minute, rises by the
+// proportional gain for each vehicle short of its target and by the integral gain for each
+// second it stays short, up to the maximum.
+const TARGET_PROPORTIONAL_GAIN: f32 = 4.0;
+const TARGET_INTEGRAL_GAIN: f32 = 0.5;
+const MAX_TARGET_RATE: f32 = 120.0;
+
+// Decides when cars arrive under the demand the config and the sliders set.
+pub trait SpawnPolicy: Send {
+    // Cars arriving this tick at `hour` of the day with `lanes` and what their `detectors`
+    // read in view, each on its own approach, or on any approach for None.
+    fn arrivals(
+        &mut self,
+        demand: &DemandConfig,
+        hour: f32,
+        lanes: &[Lane],
+        detectors: &[Detector],
+        rng: &mut dyn RngCore
+    ) -> Vec<Option<Direction>>;
+}
+
+// Runs the signal, on top of the yellow, walk and clearance timing `TrafficLight` keeps.
+pub trait LightController: Send {
+    // Advances the signal one tick with what the approaches' `detectors` read in view,
+    // returning whether any light changed.
+    fn update(&mut self, light: &mut TrafficLight, detectors: &[Detector], now: Duration) -> bool;
+}
+
+// Picks who is driving each car as it enters the road.
+pub trait DriverModel: Send {
+    fn driver(&mut self, rng: &mut dyn RngCore) -> DriverProfile;
+}
+
+// Which registered implementation runs each extension point, by name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    pub spawn_policy: String,
+    pub light_controller: String,
+    pub driver_model: String,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            spawn_policy: "random".to_string(),
+            light_controller: "fixed-time".to_string(),
+            driver_model: "mixed".to_string(),
+        }
+    }
+}
+
+// Implementations of the extension points by name. The built-in ones are always there;
+// crates embedding the simulation register their own before building it with
+// `TrafficSimulation::with_registry`.
+#[derive(Clone)]
+pub struct Registry {
+    spawn_policies: BTreeMap<String, fn() -> Box<dyn SpawnPolicy>>,
+    light_controllers: BTreeMap<String, fn() -> Box<dyn LightController>>,
+    driver_models: BTreeMap<String, fn() -> Box<dyn DriverModel>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            spawn_policies: BTreeMap::new(),
+            light_controllers: BTreeMap::new(),
+            driver_models: BTreeMap::new(),
+        };
+        registry.register_spawn_policy("random", || Box::new(RandomArrivals));
+        registry.register_spawn_policy("regular", || Box::new(RegularArrivals::default()));
+        registry.register_spawn_policy("target-density", || Box::new(TargetDensity::default()));
+        registry.register_light_controller("fixed-time", || Box::new(FixedTime));
+        registry.register_light_controller("actuated", || Box::new(Actuated));
+        registry.register_driver_model("mixed", || Box::new(MixedDrivers));
+        registry.register_driver_model("cautious", || Box::new(CautiousDrivers));
+        registry
+    }
+}
+
+impl Registry {
+    // Registering a name again replaces the implementation, built-in ones included.
+    pub fn register_spawn_policy(&mut self, name: &str, make: fn() -> Box<dyn SpawnPolicy>) {
+        self.spawn_policies.insert(name.to_string(), make);
+    }
+
+    pub fn register_light_controller(
+        &mut self,
+        name: &str,
+        make: fn() -> Box<dyn LightController>
+    ) {
+        self.light_controllers.insert(name.to_string(), make);
+    }
+
+    pub fn register_driver_model(&mut self, name: &str, make: fn() -> Box<dyn DriverModel>) {
+        self.driver_models.insert(name.to_string(), make);
+    }
+
+    // Every name in `config` must be registered.
+    pub fn check(&self, config: &PluginConfig) -> Result<(), ConfigError> {
+        find(&self.spawn_policies, "spawn policy", &config.spawn_policy)?;
+        find(&self.light_controllers, "light controller", &config.light_controller)?;
+        find(&self.driver_models, "driver model", &config.driver_model)?;
+        Ok(())
+    }
+
+    // Fresh instances, falling back to the built-in defaults for names that aren't
+    // registered; `check` reports those.
+    pub fn spawn_policy(&self, name: &str) -> Box<dyn SpawnPolicy> {
+        self.spawn_policies.get(name).map_or_else(|| Box::new(RandomArrivals) as _, |make| make())
+    }
+
+    pub fn light_controller(&self, name: &str) -> Box<dyn LightController> {
+        self.light_controllers.get(name).map_or_else(|| Box::new(FixedTime) as _, |make| make())
+    }
+
+    pub fn driver_model(&self, name: &str) -> Box<dyn DriverModel> {
+        self.driver_models.get(name).map_or_else(|| Box::new(MixedDrivers) as _, |make| make())
+    }
+}
+
+fn find<T>(
+    implementations: &BTreeMap<String, T>,
+    kind: &str,
+    name: &str
+) -> Result<(), ConfigError> {
+    if implementations.contains_key(name) {
+        return Ok(());
+    }
+    let known: Vec<&str> = implementations.keys().map(String::as_str).collect();
+    Err(ConfigError::Invalid(format!(
+        "unknown {} \"{}\", expected one of {}",
+        kind,
+        name,
+        known.join(", ")
+    )))
+}
+
+// Each tick is an independent chance of an arrival at the demand rate.
+struct RandomArrivals;
+
+impl SpawnPolicy for RandomArrivals {
+    fn arrivals(
+        &mut self,
+        demand: &DemandConfig,
+        hour: f32,
+        _lanes: &[Lane],
+        _detectors: &[Detector],
+        rng: &mut dyn RngCore
+    ) -> Vec<Option<Direction>> {
+        let mut arrivals = Vec::new();
+        if arrives(demand.rate_at(hour), rng) {
+            arrivals.push(None);
+        }
+        for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            if arrives(demand.approaches.rate(direction), rng) {
+                arrivals.push(Some(direction));
+            }
+        }
+        arrivals
+    }
+}
+
+// Whether a vehicle arrives this tick at `vehicles_per_minute`.
+fn arrives(vehicles_per_minute: f32, rng: &mut dyn RngCore) -> bool {
+    let per_second = vehicles_per_minute / 60.0;
+    let chance = ((per_second * TICK.as_secs_f32()) as f64).min(1.0);
+    per_second > 0.0 && rng.gen_bool(chance)
+}
+
+// Arrivals evenly spaced at the demand rate, for repeatable queues.
+#[derive(Default)]
+struct RegularArrivals {
+    // Vehicles owed but not yet arrived: over all approaches, then by approach in north,
+    // south, east, west order.
+    owed: f32,
+    owed_by_approach: [f32; 4],
+}
+
+impl SpawnPolicy for RegularArrivals {
+    fn arrivals(
+        &mut self,
+        demand: &DemandConfig,
+        hour: f32,
+        _lanes: &[Lane],
+        _detectors: &[Detector],
+        _rng: &mut dyn RngCore
+    ) -> Vec<Option<Direction>> {
+        let per_tick = |vehicles_per_minute: f32| {
+            (vehicles_per_minute / 60.0) * TICK.as_secs_f32()
+        };
+        let mut arrivals = Vec::new();
+        self.owed += per_tick(demand.rate_at(hour));
+        while self.owed >= 1.0 {
+            self.owed -= 1.0;
+            arrivals.push(None);
+        }
+        for direction in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let owed = &mut self.owed_by_approach[node_id(direction)];
+            *owed += per_tick(demand.approaches.rate(direction));
+            while *owed >= 1.0 {
+                *owed -= 1.0;
+                arrivals.push(Some(direction));
+            }
+        }
+        arrivals
+    }
+}
+
+// Random arrivals on each approach at a rate a proportional-integral loop keeps adjusting,
+// so the vehicles on the approach, queued upstream included, settle at its share of the
+// demand target whatever the light controller gets through.
+#[derive(Default)]
+struct TargetDensity {
+    // The integral term of each approach's rate in vehicles per minute, in north, south,
+    // east, west order.
+    base_rates: [f32; 4],
+}
+
+impl SpawnPolicy for TargetDensity {
+    fn arrivals(
+        &mut self,
+        demand: &DemandConfig,
+        _hour: f32,
+        lanes: &[Lane],
+        detectors: &[Detector],
+        rng: &mut dyn RngCore
+    ) -> Vec<Option<Direction>> {
+        let lane_km = |lane: &Lane| {
+            lane.geometry.approach_length * (LANES_PER_DIRECTION as f32) / 1000.0
+        };
+        let target = demand.target;
+        let density = if target.vehicles > 0 {
+            (target.vehicles as f32) / lanes.iter().map(lane_km).sum::<f32>()
+        } else {
+            target.vehicles_per_km
+        };
+        let mut arrivals = Vec::new();
+        for lane in lanes {
+            let detector = detectors.iter().find(|detector| detector.approach == lane.direction);
+            let present = detector.map_or(0, |detector| detector.vehicles) + lane.upstream.len();
+            let error = density * lane_km(lane) - (present as f32);
+            let base = &mut self.base_rates[node_id(lane.direction)];
+            *base = (*base + TARGET_INTEGRAL_GAIN * error * TICK.as_secs_f32())
+                .clamp(0.0, MAX_TARGET_RATE);
+            let rate = (*base + TARGET_PROPORTIONAL_GAIN * error).clamp(0.0, MAX_TARGET_RATE);
+            if arrives(rate, rng) {
+                arrivals.push(Some(lane.direction));
+            }
+        }
+        arrivals
+    }
+}
+
+// Each road's green runs its full time.
+struct FixedTime;
+
+impl LightController for FixedTime {
+    fn update(&mut self, light: &mut TrafficLight, _detectors: &[Detector], now: Duration) -> bool {
+        light.update(now)
+    }
+}
+
+// Ends a green early once the detectors on the road being served are clear while traffic
+// waits on the other, after a minimum green.
+struct Actuated;
+
+impl LightController for Actuated {
+    fn update(&mut self, light: &mut TrafficLight, detectors: &[Detector], now: Duration) -> bool {
+        if light.update(now) {
+            return true;
+        }
+        let other_road = match light.phase.serves(Direction::North) {
+            true => Direction::East,
+            false => Direction::North,
+        };
+        let min_green_reached = light.red_time(other_road, now) >= light.min_green_time;
+        if light.state != LightState::Green || !min_green_reached {
+            return false;
+        }
+        let (served, waiting): (Vec<&Detector>, Vec<&Detector>) =
+            detectors.iter().partition(|detector| light.phase.serves(detector.approach));
+        let approaching = served.iter().any(|detector| detector.occupied);
+        let demand = waiting.iter().any(|detector| detector.queue > 0);
+        !approaching && demand && light.end_green(now)
+    }
+}
+
+// Cautious, normal and aggressive drivers in the usual mix.
+struct MixedDrivers;
+
+impl DriverModel for MixedDrivers {
+    fn driver(&mut self, rng: &mut dyn RngCore) -> DriverProfile {
+        DriverProfile::random(rng)
+    }
+}
+
+// Nobody speeds or runs a light.
+struct CautiousDrivers;
+
+impl DriverModel for CautiousDrivers {
+    fn driver(&mut self, _rng: &mut dyn RngCore) -> DriverProfile {
+        DriverProfile::Cautious
+    }
+}
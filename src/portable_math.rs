@@ -0,0 +1,103 @@
+use std::f64::consts::{ FRAC_PI_2, LN_10, LN_2, SQRT_2 };
+
+// Elementary functions worked out with nothing but IEEE 754 arithmetic and square roots,
+// which round the same on every platform. The standard library's call into the platform's
+// math library, whose last bits differ between Linux, Windows and macOS, so anything that
+// feeds the simulation's state uses these instead and a seeded run plays out to the bit
+// the same everywhere.
+
+// Terms of each series: enough that the next would not change an f64 over the reduced
+// ranges below.
+const EXP_TERMS: u32 = 18;
+const LN_TERMS: u32 = 12;
+const SIN_COS_TERMS: u32 = 10;
+// 2^54, to bring subnormals into the normal range.
+const SUBNORMAL_SCALE: f64 = 18_014_398_509_481_984.0;
+
+// e^x
+pub fn exp(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    if x > 709.0 {
+        return f64::INFINITY;
+    }
+    if x < -708.0 {
+        return 0.0;
+    }
+    // e^x = 2^k e^r, with r within half of ln 2 of zero.
+    let k = (x / LN_2).round();
+    let r = x - k * LN_2;
+    let (mut term, mut sum) = (1.0, 1.0);
+    for n in 1..=EXP_TERMS {
+        term *= r / (n as f64);
+        sum += term;
+    }
+    sum * f64::from_bits(((k as i64 + 1023) as u64) << 52)
+}
+
+// The natural logarithm.
+pub fn ln(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+    let (x, shift) = if x < f64::MIN_POSITIVE { (x * SUBNORMAL_SCALE, -54) } else { (x, 0) };
+    // x = m 2^e, with m between 1/√2 and √2.
+    let bits = x.to_bits();
+    let mut e = ((bits >> 52) & 0x7ff) as i64 - 1023 + shift;
+    let mut m = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | 0x3ff0_0000_0000_0000);
+    if m > SQRT_2 {
+        m /= 2.0;
+        e += 1;
+    }
+    // ln m = 2 atanh(s), for s = (m - 1) / (m + 1).
+    let s = (m - 1.0) / (m + 1.0);
+    let (mut power, mut sum) = (s, 0.0);
+    for n in 0..LN_TERMS {
+        sum += power / ((2 * n + 1) as f64);
+        power *= s * s;
+    }
+    2.0 * sum + (e as f64) * LN_2
+}
+
+pub fn log10(x: f64) -> f64 {
+    ln(x) / LN_10
+}
+
+// 10^x
+pub fn exp10(x: f64) -> f64 {
+    exp(x * LN_10)
+}
+
+// (sin x, cos x) for x in radians.
+pub fn sin_cos(x: f64) -> (f64, f64) {
+    // x = q π/2 + r, with r within π/4 of zero.
+    let q = (x / FRAC_PI_2).round();
+    let r = x - q * FRAC_PI_2;
+    let (mut sin_term, mut cos_term) = (r, 1.0);
+    let (mut sin, mut cos) = (0.0, 0.0);
+    for n in 0..SIN_COS_TERMS {
+        sin += sin_term;
+        cos += cos_term;
+        let n = n as f64;
+        sin_term *= -r * r / ((2.0 * n + 2.0) * (2.0 * n + 3.0));
+        cos_term *= -r * r / ((2.0 * n + 1.0) * (2.0 * n + 2.0));
+    }
+    match (q as i64).rem_euclid(4) {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+// Length of the vector (x, y).
+pub fn hypot(x: f32, y: f32) -> f32 {
+    (x * x + y * y).sqrt()
+}
@@ -0,0 +1,186 @@
+use rand::Rng;
+use std::time::Duration;
+
+use crate::config::RailConfig;
+use crate::error::RenderError;
+use crate::portable_math::ln;
+use crate::render::{ Color, Renderer };
+use crate::simulation::SimEvent;
+use crate::units::{ kmh_to_mps, Area, View };
+use crate::vehicle::Direction;
+use crate::{ BIKE_LANE_WIDTH, ROAD_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// The rail line runs north-south over the east arm, far enough out to leave room for a
+// short queue between the gates and the intersection.
+pub const RAIL_X: f32 = WORLD_WIDTH / 2.0 + 26.0;
+// Traffic waits at a gate this far from the middle of the tracks on either side.
+const GATE_SETBACK: f32 = 2.4;
+// Traffic leaving the intersection this way goes over the tracks.
+pub const RAIL_EXIT: Direction = Direction::East;
+const TRACK_GAUGE: f32 = 1.4;
+const TIE_SPACING: f32 = 1.2;
+const GATE_STRIPE: f32 = 1.0;
+const TRAIN_WIDTH: f32 = 2.2;
+const TRAIN_COLOR: Color = Color::rgb(70, 40, 110);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrossingState {
+    Open,
+    // Gates down from the warning until the tail of the train has left the road; its
+    // front reaches the road at `arrival`.
+    Closed { arrival: Duration },
+    // The train has gone and the gates are going back up until `until`.
+    Raising { until: Duration },
+}
+
+// Level crossing with trains arriving at random at the configured mean interval, or when
+// sent. The gates are down, for road users and for the lights' preemption, from the
+// warning until they are fully up again.
+pub struct RailCrossing {
+    pub state: CrossingState,
+    config: RailConfig,
+    // When the next random train's warning starts, if trains run on their own.
+    next_train: Option<Duration>,
+}
+
+impl RailCrossing {
+    pub fn new(config: RailConfig, rng: &mut impl Rng) -> Self {
+        let mut crossing = Self { state: CrossingState::Open, config, next_train: None };
+        crossing.schedule(Duration::ZERO, rng);
+        crossing
+    }
+
+    // Draws the time to the next train from an exponential distribution.
+    fn schedule(&mut self, now: Duration, rng: &mut impl Rng) {
+        let mean = self.config.mean_interval_secs;
+        self.next_train = (mean > 0.0).then(|| {
+            let wait = -mean * (ln((1.0 - rng.gen::<f32>()) as f64) as f32);
+            now + Duration::from_secs_f32(wait)
+        });
+    }
+
+    pub fn gates_down(&self) -> bool {
+        self.state != CrossingState::Open
+    }
+
+    // Starts the warning for a train now; returns false while one is already crossing.
+    pub fn send_train(&mut self, now: Duration) -> bool {
+        if self.gates_down() {
+            return false;
+        }
+        let warning = Duration::from_secs_f32(self.config.warning_secs);
+        self.state = CrossingState::Closed { arrival: now + warning };
+        true
+    }
+
+    // Moves the crossing on, returning the event if the gates started coming down or
+    // finished going up.
+    pub fn update(&mut self, now: Duration, rng: &mut impl Rng) -> Option<SimEvent> {
+        match self.state {
+            CrossingState::Open if self.next_train.is_some_and(|at| now >= at) => {
+                self.send_train(now);
+                Some(SimEvent::CrossingClosed)
+            }
+            CrossingState::Closed { .. } if self.train_cleared(now) => {
+                let raise = Duration::from_secs_f32(self.config.raise_secs);
+                self.state = CrossingState::Raising { until: now + raise };
+                None
+            }
+            CrossingState::Raising { until } if now >= until => {
+                self.state = CrossingState::Open;
+                self.schedule(now, rng);
+                Some(SimEvent::CrossingOpened)
+            }
+            _ => None,
+        }
+    }
+
+    // Where the front of the train is, in meters from the top of the world, while one is
+    // due or passing.
+    pub fn train_front(&self, now: Duration) -> Option<f32> {
+        let CrossingState::Closed { arrival } = self.state else {
+            return None;
+        };
+        let road_top = WORLD_HEIGHT / 2.0 - (ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH);
+        let elapsed = now.as_secs_f32() - arrival.as_secs_f32();
+        Some(road_top + elapsed * kmh_to_mps(self.config.train_speed_kmh))
+    }
+
+    fn train_cleared(&self, now: Duration) -> bool {
+        let road_bottom = WORLD_HEIGHT / 2.0 + (ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH);
+        self.train_front(now).is_some_and(|front| front - self.config.train_length > road_bottom)
+    }
+
+    // Gates across the road on both sides while they are down, with their lights flashing,
+    // and the train, over the tracks `draw_track` laid.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View,
+        now: Duration
+    ) -> Result<(), RenderError> {
+        if self.gates_down() {
+            let center_y = WORLD_HEIGHT / 2.0;
+            let paved_half = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH;
+            // On for the first half of every second.
+            let lit = now.as_millis() % 1000 < 500;
+            for side in [-1.0, 1.0] {
+                let gate_x = RAIL_X + side * GATE_SETBACK;
+                // Red and white stripes, each gate spanning the half of the road that
+                // arrives at it.
+                let from = if side > 0.0 { center_y - paved_half } else { center_y };
+                let stripes = (paved_half / GATE_STRIPE).ceil() as usize;
+                for i in 0..stripes {
+                    let color = if i % 2 == 0 {
+                        Color::rgb(220, 0, 0)
+                    } else {
+                        Color::rgb(255, 255, 255)
+                    };
+                    let top = from + (i as f32) * GATE_STRIPE;
+                    let height = GATE_STRIPE.min(from + paved_half - top);
+                    let stripe = Area::new(gate_x - 0.2, top, 0.4, height);
+                    renderer.draw_rect(view.rect(stripe), color)?;
+                }
+                let post_y = if side > 0.0 { from - 1.2 } else { from + paved_half + 0.4 };
+                let light = if lit { Color::rgb(255, 0, 0) } else { Color::rgb(60, 0, 0) };
+                renderer.draw_rect(view.rect(Area::new(gate_x - 0.4, post_y, 0.8, 0.8)), light)?;
+            }
+        }
+        if let Some(front) = self.train_front(now) {
+            let length = self.config.train_length;
+            let train = Area::new(RAIL_X - TRAIN_WIDTH / 2.0, front - length, TRAIN_WIDTH, length);
+            renderer.draw_rect(view.rect(train), TRAIN_COLOR)?;
+        }
+        Ok(())
+    }
+}
+
+// Tracks across the whole world, which never change.
+pub fn draw_track(renderer: &mut dyn Renderer, view: &View) -> Result<(), RenderError> {
+    let rail_color = Color::rgb(150, 150, 160);
+    for side in [-1.0, 1.0] {
+        let rail = Area::new(RAIL_X + side * TRACK_GAUGE / 2.0 - 0.15, 0.0, 0.3, WORLD_HEIGHT);
+        renderer.draw_rect(view.rect(rail), rail_color)?;
+    }
+    let ties = (WORLD_HEIGHT / TIE_SPACING).ceil() as usize;
+    for i in 0..ties {
+        let y = (i as f32) * TIE_SPACING;
+        let tie = Area::new(RAIL_X - TRACK_GAUGE, y, TRACK_GAUGE * 2.0, 0.3);
+        renderer.draw_rect(view.rect(tie), Color::rgb(110, 90, 70))?;
+    }
+    Ok(())
+}
+
+// Room left for something heading `direction` with its center at `x` and half its length
+// `half_length` before it reaches the gate on its side of the tracks. None for traffic on
+// the north-south road or already past the gate.
+pub fn distance_to_gate(direction: Direction, x: f32, half_length: f32) -> Option<f32> {
+    let distance = match direction {
+        Direction::East => RAIL_X - GATE_SETBACK - (x + half_length),
+        Direction::West => x - half_length - (RAIL_X + GATE_SETBACK),
+        Direction::North | Direction::South => {
+            return None;
+        }
+    };
+    (distance >= 0.0).then_some(distance)
+}
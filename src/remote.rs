@@ -0,0 +1,171 @@
+#[cfg(feature = "remote")]
+pub use enabled::RemoteServer;
+#[cfg(not(feature = "remote"))]
+pub use disabled::RemoteServer;
+
+#[cfg(feature = "remote")]
+mod enabled {
+    use serde::Deserialize;
+    use serde_json::{ json, Value };
+    use std::io::{ BufRead, BufReader, Write };
+    use std::net::{ TcpListener, TcpStream };
+    use std::sync::mpsc::{ self, Receiver, Sender };
+    use std::thread;
+
+    use crate::error::SimError;
+    use crate::lane::MAX_PLATOON_SIZE;
+    use crate::simulation::TrafficSimulation;
+    use crate::traffic_light::Phase;
+    use crate::vehicle::Direction;
+
+    // One JSON object per line, such as {"command": "spawn", "approach": "north"}.
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+    enum Command {
+        Spawn {
+            approach: Direction,
+        },
+        SpawnPlatoon {
+            approach: Direction,
+            size: u32,
+        },
+        SetPhase {
+            phase: Phase,
+        },
+        SetDemand {
+            vehicles_per_minute: f32,
+        },
+        GetStats,
+    }
+
+    // Accepts clients on a background thread. Their commands wait on a channel until the
+    // front end's loop calls `poll`, so the simulation is only touched between frames.
+    pub struct RemoteServer {
+        commands: Receiver<(String, Sender<Value>)>,
+    }
+
+    impl RemoteServer {
+        pub fn start(address: &str) -> Result<Self, SimError> {
+            let listener = TcpListener::bind(address)
+                .map_err(|source| SimError::Listen { address: address.to_string(), source })?;
+            let (sender, commands) = mpsc::channel();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let sender = sender.clone();
+                    thread::spawn(move || serve(stream, sender));
+                }
+            });
+            Ok(Self { commands })
+        }
+
+        // Runs every command received since the last call and replies to each.
+        pub fn poll(&self, simulation: &mut TrafficSimulation) {
+            while let Ok((line, reply)) = self.commands.try_recv() {
+                let response = match serde_json::from_str::<Command>(&line) {
+                    Ok(command) => execute(command, simulation),
+                    Err(e) => json!({ "ok": false, "error": e.to_string() }),
+                };
+                // The client may have gone away; there is nobody left to tell.
+                let _ = reply.send(response);
+            }
+        }
+    }
+
+    // Forwards each line from the client and writes back the reply before reading the next.
+    fn serve(stream: TcpStream, commands: Sender<(String, Sender<Value>)>) {
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (reply, response) = mpsc::channel();
+            if commands.send((line, reply)).is_err() {
+                return;
+            }
+            let Ok(response) = response.recv() else {
+                return;
+            };
+            if writeln!(writer, "{}", response).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn execute(command: Command, simulation: &mut TrafficSimulation) -> Value {
+        match command {
+            Command::Spawn { approach } => {
+                simulation.spawn_vehicle(approach);
+                json!({ "ok": true })
+            }
+            Command::SpawnPlatoon { approach, size } => {
+                if !(1..=MAX_PLATOON_SIZE).contains(&size) {
+                    let error = format!("platoons must be between 1 and {} cars", MAX_PLATOON_SIZE);
+                    return json!({ "ok": false, "error": error });
+                }
+                simulation.spawn_platoon(approach, size);
+                json!({ "ok": true })
+            }
+            Command::SetPhase { phase } => {
+                let now = simulation.time.now();
+                let switching = simulation.traffic_light_mut().request_phase(phase, now);
+                json!({ "ok": true, "switching": switching })
+            }
+            Command::SetDemand { vehicles_per_minute } => {
+                if vehicles_per_minute < 0.0 {
+                    return json!({ "ok": false, "error": "demand must not be negative" });
+                }
+                simulation.demand.vehicles_per_minute = vehicles_per_minute;
+                json!({ "ok": true })
+            }
+            Command::GetStats => {
+                let stats = &simulation.stats;
+                let queues: Vec<Value> = simulation.lanes
+                    .iter()
+                    .map(|lane| {
+                        json!({
+                            "approach": lane.direction,
+                            "vehicles": simulation.vehicles_on(lane.direction).len(),
+                            "upstream": lane.upstream.len(),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "ok": true,
+                    "time_secs": simulation.time.now().as_secs_f32(),
+                    "phase": simulation.traffic_light().phase,
+                    "vehicles_completed": stats.vehicles_completed,
+                    "average_vehicle_delay_secs": stats.average_vehicle_delay().as_secs_f32(),
+                    "buses_completed": stats.buses_completed,
+                    "average_bus_delay_secs": stats.average_bus_delay().as_secs_f32(),
+                    "fuel_litres": stats.fuel_used,
+                    "co2_kg": stats.co2_emitted,
+                    "starvations": stats.starvations,
+                    "gridlocks": stats.gridlocks,
+                    "queues": queues,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+mod disabled {
+    use crate::error::SimError;
+    use crate::simulation::TrafficSimulation;
+
+    pub struct RemoteServer;
+
+    impl RemoteServer {
+        pub fn start(_address: &str) -> Result<Self, SimError> {
+            let message = "remote control not available: rebuild with `--features remote`";
+            Err(SimError::Usage(message.to_string()))
+        }
+
+        pub fn poll(&self, _simulation: &mut TrafficSimulation) {}
+    }
+}
@@ -0,0 +1,223 @@
+pub mod font;
+pub mod sdl;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+pub use sdl::SdlRenderer;
+#[cfg(feature = "tui")]
+pub use tui::TuiRenderer;
+
+use crate::error::RenderError;
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + (other.w as i32) &&
+            other.x < self.x + (self.w as i32) &&
+            self.y < other.y + (other.h as i32) &&
+            other.y < self.y + (self.h as i32)
+    }
+}
+
+// RGBA8 pixel data, row-major, uploaded by the backend on draw.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self { width, height, pixels }
+    }
+
+    // Scaled to `width` by `height`, each pixel taken from the nearest one.
+    pub fn resized(self, width: u32, height: u32) -> Self {
+        if (width, height) == (self.width, self.height) {
+            return self;
+        }
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let from_y = (((y as u64) * (self.height as u64)) / (height as u64)) as u32;
+            for x in 0..width {
+                let from_x = (((x as u64) * (self.width as u64)) / (width as u64)) as u32;
+                let i = ((from_y * self.width + from_x) * 4) as usize;
+                pixels.extend_from_slice(&self.pixels[i..i + 4]);
+            }
+        }
+        Self { width, height, pixels }
+    }
+}
+
+pub trait Renderer {
+    fn clear(&mut self, color: Color) -> Result<(), RenderError>;
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError>;
+    fn draw_texture(&mut self, texture: &Texture, dst: Rect) -> Result<(), RenderError>;
+    fn present(&mut self) -> Result<(), RenderError>;
+
+    // Every one of `rects` in `color`, in a single call to the backend where it has one.
+    fn draw_rects(&mut self, rects: &[Rect], color: Color) -> Result<(), RenderError> {
+        for &rect in rects {
+            self.draw_rect(rect, color)?;
+        }
+        Ok(())
+    }
+
+    // Draws a layer of the scene that only changes along with `key`, such as the roads under
+    // the traffic. A backend that can keep what was drawn draws it once per key and copies
+    // it onto each frame after; the others `draw` it every time.
+    fn draw_layer(&mut self, key: u64, draw: &mut DrawLayer) -> Result<(), RenderError>;
+
+    // Reads back the frame drawn so far; call before `present`.
+    fn capture(&mut self) -> Result<Texture, RenderError> {
+        Err(RenderError::CaptureUnsupported)
+    }
+
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Color) -> Result<(), RenderError> {
+        let scale = font::GLYPH_SCALE;
+        for (i, c) in text.chars().enumerate() {
+            let origin_x = x + (i as i32) * font::GLYPH_ADVANCE;
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for col in 0..font::GLYPH_WIDTH {
+                    if (bits >> (font::GLYPH_WIDTH - 1 - col)) & 1 == 1 {
+                        let rect = Rect::new(
+                            origin_x + col * scale,
+                            y + (row as i32) * scale,
+                            scale as u32,
+                            scale as u32
+                        );
+                        self.draw_rect(rect, color)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rects gathered by color, to be drawn with one `draw_rects` per color rather than a call
+// each. Colors are drawn in the order they were first added.
+#[derive(Default)]
+pub struct RectBatch {
+    batches: Vec<(Color, Vec<Rect>)>,
+}
+
+impl RectBatch {
+    pub fn add(&mut self, rect: Rect, color: Color) {
+        match self.batches.iter_mut().find(|(batch_color, _)| *batch_color == color) {
+            Some((_, rects)) => rects.push(rect),
+            None => self.batches.push((color, vec![rect])),
+        }
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer) -> Result<(), RenderError> {
+        for (color, rects) in &self.batches {
+            renderer.draw_rects(rects, *color)?;
+        }
+        Ok(())
+    }
+}
+
+// Draws one layer for `Renderer::draw_layer`, onto whatever renderer it is handed.
+pub type DrawLayer<'a> = dyn FnMut(&mut dyn Renderer) -> Result<(), RenderError> + 'a;
+
+// Draws a whole window's worth of drawing calls scaled down into `area` of another
+// renderer, for showing several scenes in one window. Presenting is left to the target.
+pub struct Viewport<'a> {
+    target: &'a mut dyn Renderer,
+    area: Rect,
+    scale_x: f32,
+    scale_y: f32,
+}
+
+impl<'a> Viewport<'a> {
+    pub fn new(target: &'a mut dyn Renderer, area: Rect) -> Self {
+        let scale_x = (area.w as f32) / (WINDOW_WIDTH as f32);
+        let scale_y = (area.h as f32) / (WINDOW_HEIGHT as f32);
+        Self { target, area, scale_x, scale_y }
+    }
+
+    // Edges are scaled rather than sizes, so neighbouring rects still meet, and anything
+    // with an area keeps at least a pixel.
+    fn map(&self, rect: Rect) -> Rect {
+        let x0 = ((rect.x as f32) * self.scale_x).floor() as i32;
+        let y0 = ((rect.y as f32) * self.scale_y).floor() as i32;
+        let x1 = (((rect.x + (rect.w as i32)) as f32) * self.scale_x).floor() as i32;
+        let y1 = (((rect.y + (rect.h as i32)) as f32) * self.scale_y).floor() as i32;
+        let w = if rect.w == 0 { 0 } else { (x1 - x0).max(1) as u32 };
+        let h = if rect.h == 0 { 0 } else { (y1 - y0).max(1) as u32 };
+        Rect::new(self.area.x + x0, self.area.y + y0, w, h)
+    }
+
+    // Clipped to the area so scenes drawn side by side don't spill into each other; None
+    // if nothing is left.
+    fn clip(&self, rect: Rect) -> Option<Rect> {
+        let left = rect.x.max(self.area.x);
+        let top = rect.y.max(self.area.y);
+        let right = (rect.x + (rect.w as i32)).min(self.area.x + (self.area.w as i32));
+        let bottom = (rect.y + (rect.h as i32)).min(self.area.y + (self.area.h as i32));
+        (right > left && bottom > top)
+            .then(|| Rect::new(left, top, (right - left) as u32, (bottom - top) as u32))
+    }
+}
+
+impl Renderer for Viewport<'_> {
+    fn clear(&mut self, color: Color) -> Result<(), RenderError> {
+        self.target.draw_rect(self.area, color)
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError> {
+        match self.clip(self.map(rect)) {
+            Some(rect) => self.target.draw_rect(rect, color),
+            None => Ok(()),
+        }
+    }
+
+    fn draw_rects(&mut self, rects: &[Rect], color: Color) -> Result<(), RenderError> {
+        let rects: Vec<Rect> = rects.iter().filter_map(|&rect| self.clip(self.map(rect))).collect();
+        self.target.draw_rects(&rects, color)
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, dst: Rect) -> Result<(), RenderError> {
+        let dst = self.map(dst);
+        self.target.draw_texture(texture, dst)
+    }
+
+    // Scaled down alongside other scenes, the layer isn't worth keeping.
+    fn draw_layer(&mut self, _key: u64, draw: &mut DrawLayer) -> Result<(), RenderError> {
+        draw(self)
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use sdl2::pixels::{ self, PixelFormatEnum };
+use sdl2::rect;
+use sdl2::render::{ self, BlendMode, TextureCreator, WindowCanvas };
+use sdl2::video::WindowContext;
+
+use super::{ Color, DrawLayer, Rect, Renderer, Texture };
+use crate::error::RenderError;
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+pub struct SdlRenderer {
+    canvas: WindowCanvas,
+    // Leaked, so the layer's texture can be kept from frame to frame; there is one per
+    // window.
+    texture_creator: &'static TextureCreator<WindowContext>,
+    // The last layer `draw_layer` drew, with its key.
+    layer: Option<(u64, render::Texture<'static>)>,
+}
+
+impl SdlRenderer {
+    // Everything is drawn at the window's logical size, which SDL scales to fit however many
+    // pixels the window really has on a high-DPI display or after a resize, letterboxing
+    // to keep the shape. Mouse positions come back in the same logical units.
+    pub fn new(mut canvas: WindowCanvas) -> Result<Self, RenderError> {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas
+            .set_logical_size(WINDOW_WIDTH, WINDOW_HEIGHT)
+            .map_err(|e| RenderError::Sdl(e.to_string()))?;
+        let texture_creator = Box::leak(Box::new(canvas.texture_creator()));
+        Ok(Self { canvas, texture_creator, layer: None })
+    }
+
+    // Where the scene is shown, in the window's real pixels, inside any letterbox bars.
+    fn scene_area(&self) -> rect::Rect {
+        let viewport = self.canvas.viewport();
+        let (scale_x, scale_y) = self.canvas.scale();
+        let physical = |value: i32, scale: f32| ((value as f32) * scale).round() as i32;
+        rect::Rect::new(
+            physical(viewport.x(), scale_x),
+            physical(viewport.y(), scale_y),
+            physical(viewport.width() as i32, scale_x).max(1) as u32,
+            physical(viewport.height() as i32, scale_y).max(1) as u32
+        )
+    }
+
+    // A texture the size of the scene on screen with `draw` drawn on it, at the logical
+    // size scaled up to it like the window.
+    fn draw_to_texture(
+        &mut self,
+        draw: &mut DrawLayer
+    ) -> Result<render::Texture<'static>, RenderError> {
+        let area = self.scene_area();
+        let mut texture = self.texture_creator
+            .create_texture_target(PixelFormatEnum::RGBA32, area.width(), area.height())
+            .map_err(|e| RenderError::Sdl(e.to_string()))?;
+        // The layer is opaque, so copying it needn't blend.
+        texture.set_blend_mode(BlendMode::None);
+        let scale_x = (area.width() as f32) / (WINDOW_WIDTH as f32);
+        let scale_y = (area.height() as f32) / (WINDOW_HEIGHT as f32);
+        let texture_creator = self.texture_creator;
+        let mut drawn = Ok(());
+        self.canvas
+            .with_texture_canvas(&mut texture, |canvas| {
+                drawn = match canvas.set_scale(scale_x, scale_y) {
+                    Ok(()) => draw(&mut CanvasTarget { canvas, texture_creator }),
+                    Err(e) => Err(RenderError::Sdl(e)),
+                };
+            })
+            .map_err(|e| RenderError::Sdl(e.to_string()))?;
+        drawn?;
+        Ok(texture)
+    }
+
+    fn target(&mut self) -> CanvasTarget<'_> {
+        CanvasTarget { canvas: &mut self.canvas, texture_creator: self.texture_creator }
+    }
+}
+
+fn to_sdl_color(color: Color) -> pixels::Color {
+    pixels::Color::RGBA(color.r, color.g, color.b, color.a)
+}
+
+fn to_sdl_rect(rect: Rect) -> rect::Rect {
+    rect::Rect::new(rect.x, rect.y, rect.w, rect.h)
+}
+
+impl Renderer for SdlRenderer {
+    fn clear(&mut self, color: Color) -> Result<(), RenderError> {
+        self.target().clear(color)
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError> {
+        self.target().draw_rect(rect, color)
+    }
+
+    fn draw_rects(&mut self, rects: &[Rect], color: Color) -> Result<(), RenderError> {
+        self.target().draw_rects(rects, color)
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, dst: Rect) -> Result<(), RenderError> {
+        self.target().draw_texture(texture, dst)
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        self.canvas.present();
+        Ok(())
+    }
+
+    // Kept in a texture at the size it is shown, redrawn when the key or the window's size
+    // changes.
+    fn draw_layer(&mut self, key: u64, draw: &mut DrawLayer) -> Result<(), RenderError> {
+        if !self.canvas.render_target_supported() {
+            return draw(self);
+        }
+        let area = self.scene_area();
+        let kept = self.layer.as_ref().is_some_and(|(kept_key, texture)| {
+            let query = texture.query();
+            *kept_key == key && (query.width, query.height) == (area.width(), area.height())
+        });
+        if !kept {
+            self.layer = Some((key, self.draw_to_texture(draw)?));
+        }
+        let Some((_, texture)) = &self.layer else {
+            return Ok(());
+        };
+        let screen = rect::Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+        self.canvas.copy(texture, None, screen).map_err(RenderError::Sdl)
+    }
+
+    // The scene is read back at the size it is shown, from inside any letterbox bars, and
+    // brought to the logical size, so screenshots and recordings come out the same size on
+    // any display.
+    fn capture(&mut self) -> Result<Texture, RenderError> {
+        let area = self.scene_area();
+        let pixels = self.canvas
+            .read_pixels(area, PixelFormatEnum::RGBA32)
+            .map_err(RenderError::Sdl)?;
+        let frame = Texture::new(area.width(), area.height(), pixels);
+        Ok(frame.resized(WINDOW_WIDTH, WINDOW_HEIGHT))
+    }
+}
+
+// Draws onto the window, or onto a texture while a layer is drawn.
+struct CanvasTarget<'a> {
+    canvas: &'a mut WindowCanvas,
+    texture_creator: &'static TextureCreator<WindowContext>,
+}
+
+impl Renderer for CanvasTarget<'_> {
+    fn clear(&mut self, color: Color) -> Result<(), RenderError> {
+        self.canvas.set_draw_color(to_sdl_color(color));
+        self.canvas.clear();
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError> {
+        self.canvas.set_draw_color(to_sdl_color(color));
+        self.canvas.fill_rect(to_sdl_rect(rect)).map_err(RenderError::Sdl)
+    }
+
+    fn draw_rects(&mut self, rects: &[Rect], color: Color) -> Result<(), RenderError> {
+        let rects: Vec<rect::Rect> = rects.iter().map(|&rect| to_sdl_rect(rect)).collect();
+        self.canvas.set_draw_color(to_sdl_color(color));
+        self.canvas.fill_rects(&rects).map_err(RenderError::Sdl)
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, dst: Rect) -> Result<(), RenderError> {
+        let mut sdl_texture = self.texture_creator
+            .create_texture_static(PixelFormatEnum::RGBA32, texture.width, texture.height)
+            .map_err(|e| RenderError::Sdl(e.to_string()))?;
+        sdl_texture.set_blend_mode(BlendMode::Blend);
+        sdl_texture
+            .update(None, &texture.pixels, (texture.width as usize) * 4)
+            .map_err(|e| RenderError::Sdl(e.to_string()))?;
+        self.canvas.copy(&sdl_texture, None, to_sdl_rect(dst)).map_err(RenderError::Sdl)
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    // Only reached while a layer is drawn, and layers don't nest.
+    fn draw_layer(&mut self, _key: u64, draw: &mut DrawLayer) -> Result<(), RenderError> {
+        draw(self)
+    }
+}
@@ -0,0 +1,195 @@
+use ratatui::style;
+use ratatui::DefaultTerminal;
+
+use super::{ Color, DrawLayer, Rect, Renderer, Texture };
+use crate::error::RenderError;
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    symbol: char,
+    fg: Color,
+    bg: Color,
+}
+
+const BLANK: Cell = Cell { symbol: ' ', fg: Color::rgb(255, 255, 255), bg: Color::rgb(0, 0, 0) };
+
+// Rasterizes the pixel-space draw calls onto a terminal cell grid. Rects thinner than a
+// cell become box-drawing lines, everything else fills the cell background.
+pub struct TuiRenderer {
+    terminal: DefaultTerminal,
+    cells: Vec<Cell>,
+    cols: u16,
+    rows: u16,
+}
+
+impl TuiRenderer {
+    pub fn new() -> Result<Self, RenderError> {
+        let terminal = ratatui::init();
+        let mut renderer = Self { terminal, cells: Vec::new(), cols: 0, rows: 0 };
+        renderer.resize()?;
+        Ok(renderer)
+    }
+
+    fn resize(&mut self) -> Result<(), RenderError> {
+        let size = self.terminal.size()?;
+        self.cols = size.width.max(1);
+        self.rows = size.height.max(1);
+        self.cells = vec![BLANK; (self.cols as usize) * (self.rows as usize)];
+        Ok(())
+    }
+
+    fn cell_width(&self) -> f32 {
+        (WINDOW_WIDTH as f32) / (self.cols as f32)
+    }
+
+    fn cell_height(&self) -> f32 {
+        (WINDOW_HEIGHT as f32) / (self.rows as f32)
+    }
+
+    fn to_cell(&self, x: i32, y: i32) -> (i32, i32) {
+        (((x as f32) / self.cell_width()) as i32, ((y as f32) / self.cell_height()) as i32)
+    }
+
+    fn cell_mut(&mut self, col: i32, row: i32) -> Option<&mut Cell> {
+        if col < 0 || row < 0 || col >= (self.cols as i32) || row >= (self.rows as i32) {
+            return None;
+        }
+        let index = (row as usize) * (self.cols as usize) + (col as usize);
+        self.cells.get_mut(index)
+    }
+}
+
+impl Drop for TuiRenderer {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+fn to_tui_color(color: Color) -> style::Color {
+    style::Color::Rgb(color.r, color.g, color.b)
+}
+
+impl Renderer for TuiRenderer {
+    fn clear(&mut self, color: Color) -> Result<(), RenderError> {
+        let size = self.terminal.size()?;
+        if size.width != self.cols || size.height != self.rows {
+            self.resize()?;
+        }
+        for cell in &mut self.cells {
+            *cell = Cell { symbol: ' ', fg: color, bg: color };
+        }
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError> {
+        if color.a == 0 {
+            return Ok(());
+        }
+        let thin_horizontal = (rect.h as f32) < self.cell_height() / 2.0;
+        let thin_vertical = (rect.w as f32) < self.cell_width() / 2.0;
+        let (left, top) = self.to_cell(rect.x, rect.y);
+        let (right, bottom) = self.to_cell(
+            rect.x + (rect.w as i32) - 1,
+            rect.y + (rect.h as i32) - 1
+        );
+        for row in top..=bottom {
+            for col in left..=right {
+                if let Some(cell) = self.cell_mut(col, row) {
+                    match (thin_horizontal, thin_vertical) {
+                        (true, false) => {
+                            cell.symbol = '─';
+                            cell.fg = color;
+                        }
+                        (false, true) => {
+                            cell.symbol = '│';
+                            cell.fg = color;
+                        }
+                        (true, true) => {
+                            cell.symbol = '·';
+                            cell.fg = color;
+                        }
+                        (false, false) => {
+                            cell.symbol = ' ';
+                            cell.bg = color;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, dst: Rect) -> Result<(), RenderError> {
+        if texture.width == 0 || texture.height == 0 || dst.w == 0 || dst.h == 0 {
+            return Ok(());
+        }
+        let (left, top) = self.to_cell(dst.x, dst.y);
+        let (right, bottom) = self.to_cell(
+            dst.x + (dst.w as i32) - 1,
+            dst.y + (dst.h as i32) - 1
+        );
+        let (cell_width, cell_height) = (self.cell_width(), self.cell_height());
+        for row in top..=bottom {
+            for col in left..=right {
+                let px = ((col as f32) + 0.5) * cell_width - (dst.x as f32);
+                let py = ((row as f32) + 0.5) * cell_height - (dst.y as f32);
+                let tx = ((px / (dst.w as f32)) * (texture.width as f32)) as i64;
+                let ty = ((py / (dst.h as f32)) * (texture.height as f32)) as i64;
+                if
+                    tx < 0 ||
+                    ty < 0 ||
+                    tx >= (texture.width as i64) ||
+                    ty >= (texture.height as i64)
+                {
+                    continue;
+                }
+                let offset = ((ty as usize) * (texture.width as usize) + (tx as usize)) * 4;
+                let pixel = &texture.pixels[offset..offset + 4];
+                if pixel[3] == 0 {
+                    continue;
+                }
+                if let Some(cell) = self.cell_mut(col, row) {
+                    cell.symbol = ' ';
+                    cell.bg = Color::rgb(pixel[0], pixel[1], pixel[2]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Color) -> Result<(), RenderError> {
+        let (col, row) = self.to_cell(x, y);
+        for (i, c) in text.chars().enumerate() {
+            if let Some(cell) = self.cell_mut(col + (i as i32), row) {
+                cell.symbol = c;
+                cell.fg = color;
+            }
+        }
+        Ok(())
+    }
+
+    // Redrawing a terminal's worth of cells costs about what copying them would.
+    fn draw_layer(&mut self, _key: u64, draw: &mut DrawLayer) -> Result<(), RenderError> {
+        draw(self)
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        let (cols, cells) = (self.cols, &self.cells);
+        self.terminal
+            .draw(|frame| {
+                let buffer = frame.buffer_mut();
+                for (i, cell) in cells.iter().enumerate() {
+                    let x = (i % (cols as usize)) as u16;
+                    let y = (i / (cols as usize)) as u16;
+                    if let Some(target) = buffer.cell_mut((x, y)) {
+                        target
+                            .set_char(cell.symbol)
+                            .set_fg(to_tui_color(cell.fg))
+                            .set_bg(to_tui_color(cell.bg));
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
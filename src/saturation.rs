@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::simulation::SimEvent;
+use crate::sink::node_id;
+use crate::traffic_light::{ LightState, TrafficLight };
+use crate::vehicle::{ Direction, Vehicle, VehicleId, VehicleState };
+use crate::LANES_PER_DIRECTION;
+
+// The first vehicles away from a queue lose time starting up, so only headways from the
+// fifth on count towards the saturation headway, as in the Highway Capacity Manual.
+const START_UP_VEHICLES: u32 = 4;
+
+// Where a travel lane's queue has got to discharging since its green started.
+#[derive(Debug, Clone, Copy)]
+struct Discharge {
+    // Queued vehicles that have entered the intersection, and when the last one did.
+    entered: u32,
+    last_entry: Option<Duration>,
+    // Cleared once an unqueued vehicle follows the queue in, ending saturated flow.
+    saturated: bool,
+}
+
+impl Default for Discharge {
+    fn default() -> Self {
+        Self { entered: 0, last_entry: None, saturated: true }
+    }
+}
+
+// Times queued vehicles entering the intersection on green, lane by lane, and reports each
+// headway behind the start-up vehicles with the movement of the vehicle that made it.
+#[derive(Debug, Clone, Default)]
+pub struct DischargeMeter {
+    // By approach in north, south, east, west order, then travel lane.
+    discharges: [[Discharge; LANES_PER_DIRECTION]; 4],
+    // Vehicles that have stopped on their approach and not yet entered the intersection,
+    // with the lane they are queued in.
+    queued: Vec<(VehicleId, usize)>,
+}
+
+impl DischargeMeter {
+    // Takes in one tick, after the lanes have moved, with the events it raised, and
+    // returns a `QueueHeadway` event for each saturation headway measured.
+    pub fn observe(
+        &mut self,
+        vehicles: &[&Vehicle],
+        light: &TrafficLight,
+        events: &[SimEvent],
+        now: Duration
+    ) -> Vec<SimEvent> {
+        let mut headways = Vec::new();
+        for event in events {
+            let SimEvent::VehicleEnteredIntersection { vehicle_id, approach, route, .. } = *event
+            else {
+                continue;
+            };
+            let green = light.state_for(approach) == LightState::Green;
+            let discharges = &mut self.discharges[node_id(approach)];
+            match self.queued.iter().position(|&(id, _)| id == vehicle_id) {
+                Some(i) => {
+                    let (_, lane) = self.queued.swap_remove(i);
+                    let discharge = &mut discharges[lane];
+                    if !green || !discharge.saturated {
+                        continue;
+                    }
+                    discharge.entered += 1;
+                    if discharge.entered > START_UP_VEHICLES {
+                        if let Some(last) = discharge.last_entry {
+                            let headway = now - last;
+                            headways.push(SimEvent::QueueHeadway { approach, route, headway });
+                        }
+                    }
+                    discharge.last_entry = Some(now);
+                }
+                None => {
+                    let lane = vehicles
+                        .iter()
+                        .find(|vehicle| vehicle.id == vehicle_id)
+                        .map(|vehicle| vehicle.lane);
+                    if let Some(lane) = lane {
+                        discharges[lane].saturated = false;
+                    }
+                }
+            }
+        }
+        let on_road = |id| vehicles.iter().any(|v| v.id == id);
+        // Towed or removed to break a gridlock rather than driven off.
+        self.queued.retain(|&(id, _)| on_road(id));
+        for vehicle in vehicles {
+            let waiting =
+                vehicle.state == VehicleState::QueuedAtLight && vehicle.wrecked_until.is_none();
+            if waiting && !self.queued.iter().any(|&(id, _)| id == vehicle.id) {
+                self.queued.push((vehicle.id, vehicle.lane));
+            }
+        }
+        // Each green starts a fresh discharge from the queue that built up on red.
+        for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            if light.state_for(approach) != LightState::Green {
+                self.discharges[node_id(approach)] = Default::default();
+            }
+        }
+        headways
+    }
+}
+
+// Saturation flow is one vehicle per saturation headway, in vehicles per hour of green
+// per lane.
+pub fn saturation_flow(headway: Duration) -> f32 {
+    3600.0 / headway.as_secs_f32()
+}
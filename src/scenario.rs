@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ConfigError;
+use crate::vehicle::{ opposite, route_between, Direction };
+use crate::LANES_PER_DIRECTION;
+
+// Trips released, lanes closed and incidents staged at set times into the run, loaded
+// with `--scenario <path>`. The run ends at `end_secs` if given.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(rename = "trip")]
+    pub trips: Vec<Trip>,
+    #[serde(rename = "closure")]
+    pub closures: Vec<Closure>,
+    #[serde(rename = "incident")]
+    pub incidents: Vec<Incident>,
+    pub end_secs: Option<f32>,
+}
+
+// `count` vehicles from the `from` road end to the `to` one, entering one after another as
+// soon as the approach has room.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trip {
+    pub at_secs: f32,
+    pub from: Direction,
+    pub to: Direction,
+    #[serde(default = "one")]
+    pub count: u32,
+}
+
+// Travel lane `lane` of `approach` (0 next to the center line) coned off from `at_secs`,
+// until `until_secs` if given.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Closure {
+    pub at_secs: f32,
+    pub approach: Direction,
+    pub lane: usize,
+    pub until_secs: Option<f32>,
+}
+
+// Wrecks the vehicle nearest the middle of the intersection at `at_secs`, out of those
+// from `approach` if given.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Incident {
+    pub at_secs: f32,
+    pub approach: Option<Direction>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::read(path).map_err(|e| e.in_file(path))
+    }
+
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let scenario: Scenario = toml::from_str(&fs::read_to_string(path)?)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        for trip in &self.trips {
+            if trip.at_secs < 0.0 {
+                return Err(ConfigError::Invalid("trip times must not be negative".to_string()));
+            }
+            if route_between(opposite(trip.from), trip.to).is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "no route from the {:?} end back to itself",
+                    trip.from
+                )));
+            }
+        }
+        for closure in &self.closures {
+            if closure.at_secs < 0.0 {
+                return Err(ConfigError::Invalid("closure times must not be negative".to_string()));
+            }
+            if closure.until_secs.is_some_and(|until| until <= closure.at_secs) {
+                return Err(ConfigError::Invalid("a closure must end after it starts".to_string()));
+            }
+            if closure.lane >= LANES_PER_DIRECTION {
+                return Err(ConfigError::Invalid(format!(
+                    "lanes are numbered 0 to {}",
+                    LANES_PER_DIRECTION - 1
+                )));
+            }
+        }
+        if self.incidents.iter().any(|incident| incident.at_secs < 0.0) {
+            return Err(ConfigError::Invalid("incident times must not be negative".to_string()));
+        }
+        if self.end_secs.is_some_and(|end| end <= 0.0) {
+            return Err(ConfigError::Invalid("the scenario must end after it starts".to_string()));
+        }
+        Ok(())
+    }
+}
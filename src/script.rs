@@ -0,0 +1,232 @@
+use crate::traffic_light::Phase;
+use crate::vehicle::Direction;
+
+#[cfg(feature = "scripting")]
+pub use enabled::{ Script, ScriptHooks };
+#[cfg(not(feature = "scripting"))]
+pub use disabled::{ Script, ScriptHooks };
+
+// What a script asked for from inside a hook, carried out at the start of the next tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptCommand {
+    Spawn(Direction),
+    SpawnTrip(Direction, Direction),
+    SpawnPlatoon(Direction, u32),
+    RequestPhase(Phase),
+}
+
+#[cfg(feature = "scripting")]
+mod enabled {
+    use rhai::{ CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST };
+    use serde::de::value::Error as NameError;
+    use serde::de::{ DeserializeOwned, IntoDeserializer };
+    use std::fs;
+    use std::path::Path;
+    use std::sync::{ Arc, Mutex };
+    use std::time::Duration;
+
+    use super::ScriptCommand;
+    use crate::error::ConfigError;
+    use crate::lane::MAX_PLATOON_SIZE;
+    use crate::simulation::SimEvent;
+    use crate::traffic_light::Phase;
+    use crate::vehicle::{ Direction, VehicleKind };
+
+    // Each hook a script may define, with the number of parameters it takes.
+    const HOOKS: [(&str, usize); 3] = [("on_tick", 1), ("on_spawn", 2), ("on_light_change", 1)];
+
+    // A compiled Rhai script, loaded with `--script`.
+    #[derive(Debug, Clone)]
+    pub struct Script {
+        ast: AST,
+    }
+
+    impl Script {
+        pub fn load(path: &Path) -> Result<Self, ConfigError> {
+            Self::read(path).map_err(|e| e.in_file(path))
+        }
+
+        fn read(path: &Path) -> Result<Self, ConfigError> {
+            let source = fs::read_to_string(path)?;
+            let ast = Engine::new()
+                .compile(source)
+                .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+            for function in ast.iter_functions() {
+                let hook = HOOKS.iter().find(|&&(name, _)| name == function.name);
+                if let Some(&(name, params)) = hook {
+                    if function.params.len() != params {
+                        return Err(
+                            ConfigError::Invalid(format!("{} takes {} parameters", name, params))
+                        );
+                    }
+                }
+            }
+            Ok(Self { ast })
+        }
+    }
+
+    // Runs a script's hooks for one simulation. `this` in every hook is a map the script
+    // keeps its own state in between calls. A hook that fails is logged and not called again.
+    pub struct ScriptHooks {
+        engine: Engine,
+        ast: AST,
+        state: Dynamic,
+        commands: Arc<Mutex<Vec<ScriptCommand>>>,
+        // The hooks the script defines that haven't failed yet.
+        hooks: Vec<&'static str>,
+    }
+
+    impl ScriptHooks {
+        pub fn new(script: &Script) -> Self {
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let mut engine = Engine::new();
+            let queue = Arc::clone(&commands);
+            engine.register_fn("spawn_vehicle", move |approach: &str| {
+                push(&queue, ScriptCommand::Spawn(parse(approach)?))
+            });
+            let queue = Arc::clone(&commands);
+            engine.register_fn("spawn_trip", move |from: &str, to: &str| {
+                push(&queue, ScriptCommand::SpawnTrip(parse(from)?, parse(to)?))
+            });
+            let queue = Arc::clone(&commands);
+            engine.register_fn("spawn_platoon", move |approach: &str, size: i64| {
+                if !(1..=(MAX_PLATOON_SIZE as i64)).contains(&size) {
+                    let error = format!("platoons must be between 1 and {} cars", MAX_PLATOON_SIZE);
+                    return Err(error.into());
+                }
+                push(&queue, ScriptCommand::SpawnPlatoon(parse(approach)?, size as u32))
+            });
+            let queue = Arc::clone(&commands);
+            engine.register_fn("request_phase", move |phase: &str| {
+                push(&queue, ScriptCommand::RequestPhase(parse(phase)?))
+            });
+            let hooks = HOOKS
+                .iter()
+                .map(|&(name, _)| name)
+                .filter(|&name| script.ast.iter_functions().any(|function| function.name == name))
+                .collect();
+            Self {
+                engine,
+                ast: script.ast.clone(),
+                state: Dynamic::from_map(Map::new()),
+                commands,
+                hooks,
+            }
+        }
+
+        // Calls `on_spawn` and `on_light_change` for `events`, then `on_tick` if `ends_tick`,
+        // and returns what they asked for. `phases` holds the phase each light change in
+        // `events` left the light in, in order.
+        pub fn run(
+            &mut self,
+            events: &[SimEvent],
+            phases: &[Phase],
+            now: Duration,
+            ends_tick: bool
+        ) -> Vec<ScriptCommand> {
+            let mut phases = phases.iter();
+            for event in events {
+                match *event {
+                    SimEvent::VehicleSpawned { approach, kind, .. } => {
+                        let kind = match kind {
+                            VehicleKind::Car => "car",
+                            VehicleKind::Bus => "bus",
+                        };
+                        self.call("on_spawn", (direction_name(approach), kind.to_string()));
+                    }
+                    SimEvent::LightChanged => {
+                        if let Some(&phase) = phases.next() {
+                            self.call("on_light_change", (phase_name(phase),));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if ends_tick {
+                self.call("on_tick", (now.as_secs_f64(),));
+            }
+            std::mem::take(&mut *self.commands.lock().unwrap())
+        }
+
+        fn call(&mut self, hook: &'static str, args: impl FuncArgs) {
+            if !self.hooks.contains(&hook) {
+                return;
+            }
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+            let (engine, ast) = (&self.engine, &self.ast);
+            let result =
+                engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, hook, args);
+            if let Err(e) = result {
+                tracing::warn!("script {} failed, not calling it again: {}", hook, e);
+                self.hooks.retain(|&name| name != hook);
+            }
+        }
+    }
+
+    fn push(
+        commands: &Mutex<Vec<ScriptCommand>>,
+        command: ScriptCommand
+    ) -> Result<(), Box<EvalAltResult>> {
+        commands.lock().unwrap().push(command);
+        Ok(())
+    }
+
+    // Approaches and phases go by the names config files use, such as "north" and
+    // "east_west".
+    fn parse<T: DeserializeOwned>(name: &str) -> Result<T, Box<EvalAltResult>> {
+        T::deserialize(name.into_deserializer()).map_err(|e: NameError| e.to_string().into())
+    }
+
+    fn direction_name(direction: Direction) -> String {
+        format!("{:?}", direction).to_lowercase()
+    }
+
+    fn phase_name(phase: Phase) -> String {
+        match phase {
+            Phase::NorthSouth => "north_south",
+            Phase::EastWest => "east_west",
+        }.to_string()
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+mod disabled {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::ScriptCommand;
+    use crate::error::ConfigError;
+    use crate::simulation::SimEvent;
+    use crate::traffic_light::Phase;
+
+    // Never loaded in a build without scripting.
+    #[derive(Debug, Clone)]
+    pub struct Script {
+        _unloadable: (),
+    }
+
+    impl Script {
+        pub fn load(_path: &Path) -> Result<Self, ConfigError> {
+            let message = "scripts not available: rebuild with `--features scripting`";
+            Err(ConfigError::Invalid(message.to_string()))
+        }
+    }
+
+    pub struct ScriptHooks;
+
+    impl ScriptHooks {
+        pub fn new(_script: &Script) -> Self {
+            Self
+        }
+
+        pub fn run(
+            &mut self,
+            _events: &[SimEvent],
+            _phases: &[Phase],
+            _now: Duration,
+            _ends_tick: bool
+        ) -> Vec<ScriptCommand> {
+            Vec::new()
+        }
+    }
+}
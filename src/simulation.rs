@@ -0,0 +1,2111 @@
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use hecs::Ref;
+use rayon::prelude::*;
+use std::sync::mpsc::{ self, Receiver, Sender };
+use std::time::Duration;
+
+use crate::agents::{ Agents, Detector, Roster };
+use crate::bus::{ bus_shelter_rect, bus_stop_rect, BUS_LINES };
+use crate::clock::{ SimClock, TICK };
+use crate::config::{ Config, DemandConfig, GridlockConfig, IncidentConfig, LightsConfig };
+use crate::conflicts::check_signal_plan;
+use crate::cyclist::{ bike_stop_line_rect, cyclist_rect, Cyclist };
+use crate::day_night::{ night_overlay, DayClock, LIGHTS_ON_DARKNESS };
+use crate::error::{ ConfigError, RenderError, SimError };
+use crate::geometry::Geometry;
+use crate::heatmap::Heatmap;
+use crate::motion_guard::MotionGuard;
+use crate::noise::{ self, NoiseMeter };
+use crate::lane::Lane;
+use crate::map::{ MapLayout, RoadEnd, LAMP_MARGIN, LAMP_SIZE };
+use crate::median::{ bay_line_rect, median_rects };
+use crate::minimap;
+use crate::no_change_zone::solid_divider_rects;
+use crate::pedestrian::{
+    button_rect,
+    countdown_position,
+    crosswalk_rect,
+    pedestrian_rect,
+    signal_rect,
+    waiting_rect,
+    CloseCall,
+    Corner,
+    Pedestrian,
+    CORNERS,
+    MAX_WALKING_SPEED,
+    MID_BLOCK_FARTHEST,
+    MID_BLOCK_NEAREST,
+    MIN_WALKING_SPEED,
+};
+use crate::plugin::{ LightController, Registry, SpawnPolicy };
+use crate::portable_math::hypot;
+use crate::render::{ font, Color, Rect, Renderer };
+use crate::rail::{ draw_track, RailCrossing, RAIL_EXIT };
+use crate::saturation::DischargeMeter;
+use crate::script::{ ScriptCommand, ScriptHooks };
+use crate::sink::node_id;
+use crate::stats::{ tmc_column, tmc_movements, Stats };
+use crate::systems::{ self, Conflicts, Room, Signals };
+use crate::theme::Theme;
+use crate::traffic_light::{ LightState, Phase, TrafficLight, WalkSignal };
+use crate::trail::draw_line;
+use crate::units::{ Area, View };
+use crate::vehicle::{
+    heading,
+    opposite,
+    route_between,
+    turned_direction,
+    vehicle_rect,
+    Direction,
+    Route,
+    Vehicle,
+    VehicleId,
+    VehicleKind,
+};
+use crate::weather::Weather;
+use crate::webster::WebsterAnalysis;
+use crate::work_zone::{ cone_rects, CONE_COLOR };
+use crate::{
+    BIKE_LANE_WIDTH,
+    LANES_PER_DIRECTION,
+    LANE_WIDTH,
+    ROAD_WIDTH,
+    WINDOW_HEIGHT,
+    WINDOW_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimEvent {
+    LightChanged,
+    VehicleWaiting,
+    Collision,
+    // A vehicle got onto its approach, whether straight away or after waiting upstream.
+    VehicleSpawned {
+        vehicle_id: VehicleId,
+        kind: VehicleKind,
+        approach: Direction,
+        route: Route,
+    },
+    // `entered_road` and `arrived` are when the vehicle got onto its approach and reached
+    // the stop line or the queue for it.
+    VehicleEnteredIntersection {
+        vehicle_id: VehicleId,
+        approach: Direction,
+        route: Route,
+        kind: VehicleKind,
+        entered_road: Duration,
+        arrived: Duration,
+    },
+    // A turning vehicle swung round to head closest to `exit`.
+    VehicleTurned {
+        vehicle_id: VehicleId,
+        exit: Direction,
+    },
+    // Sent alongside `Collision` for each vehicle involved.
+    VehicleCollided {
+        vehicle_id: VehicleId,
+    },
+    // The vehicle was left as a wreck where it stopped, after a collision or an incident.
+    VehicleWrecked {
+        vehicle_id: VehicleId,
+    },
+    // The wreck was towed off the road once the clearance time was up.
+    WreckCleared {
+        vehicle_id: VehicleId,
+    },
+    // `delay` is the time spent stopped and `control_delay` all the time lost against the
+    // free-flow speed. `origin` and `destination` are the road end nodes the trip started
+    // and ended at; `work_zone` is set if a lane of its approach was closed while it was on
+    // it.
+    VehicleExited {
+        vehicle_id: VehicleId,
+        kind: VehicleKind,
+        approach: Direction,
+        delay: Duration,
+        control_delay: Duration,
+        // Litres burned on the way.
+        fuel: f32,
+        stops: u32,
+        idle: Duration,
+        origin: usize,
+        destination: usize,
+        work_zone: bool,
+    },
+    RedLightViolation {
+        vehicle_id: VehicleId,
+        approach: Direction,
+    },
+    SpeedMeasured {
+        approach: Direction,
+        speed: f32,
+        limit: f32,
+    },
+    // A vehicle took `time` between the travel time entry and exit lines of its movement.
+    TravelTimeMeasured {
+        approach: Direction,
+        route: Route,
+        time: Duration,
+    },
+    // A vehicle found its approach full up to the spawn point and joined the queue upstream.
+    ArrivalQueued {
+        approach: Direction,
+    },
+    // The vehicle at the head of the upstream queue got onto its approach.
+    ArrivalReleased {
+        approach: Direction,
+    },
+    // Traffic on `approach` had waited out the maximum red time, so its green was forced.
+    Starvation {
+        approach: Direction,
+    },
+    // Nothing had moved for the gridlock timeout with `stuck` vehicles in the intersection;
+    // `removed_vehicle` was taken off the road to break it up.
+    Gridlock {
+        stuck: usize,
+        removed_vehicle: VehicleId,
+    },
+    WeatherChanged {
+        weather: Weather,
+    },
+    // A pedestrian who had waited `wait` since pressing the button at `corner` got the walk.
+    PedestrianServed {
+        corner: Corner,
+        wait: Duration,
+    },
+    // A jaywalker reached the far curb of the road `crosswalk` lies over, with the closest
+    // call any vehicle gave them on the way.
+    JaywalkerCrossed {
+        crosswalk: Direction,
+        close_call: Option<CloseCall>,
+    },
+    // A vehicle started braking for a jaywalker in its path.
+    EmergencyBrake {
+        vehicle_id: VehicleId,
+        approach: Direction,
+    },
+    // The level crossing's gates started coming down for a train.
+    CrossingClosed,
+    // They are fully up again after it.
+    CrossingOpened,
+    LaneClosed {
+        approach: Direction,
+        lane: usize,
+    },
+    LaneReopened {
+        approach: Direction,
+    },
+    // A left-turner on a divided road found the turn bay full and stopped beside it,
+    // blocking the through lane, until the bay has room for it again.
+    TurnBayOverflowed {
+        approach: Direction,
+    },
+    TurnBayCleared {
+        approach: Direction,
+    },
+    // A queued vehicle entered the intersection `headway` after the one ahead of it in its
+    // lane, past the start-up vehicles of a green with the queue still discharging.
+    QueueHeadway {
+        approach: Direction,
+        route: Route,
+        headway: Duration,
+    },
+    // A driver wanted to change lanes inside the no-change zone and was kept in lane.
+    LaneChangeViolation {
+        vehicle_id: VehicleId,
+        approach: Direction,
+    },
+    // An arrival on `approach` whose route led onto a saturated exit took `to` instead.
+    RouteReassigned {
+        approach: Direction,
+        from: Route,
+        to: Route,
+    },
+    // The other road's green began. Of the vehicles from the road that lost it still in the
+    // intersection when its yellow ended, `cleared` had left during the all-red and
+    // `still_inside` had not.
+    ClearanceChecked {
+        cleared: u32,
+        still_inside: u32,
+    },
+    // The user took the lights over from the controller, or handed them back.
+    ManualControl {
+        on: bool,
+    },
+    // Under manual control the user gave `phase` its green, or turned every approach red.
+    ManualOverride {
+        phase: Option<Phase>,
+    },
+    // The equivalent sound level in dB(A) at each corner's receiver, in `CORNERS` order, over
+    // the second just ended.
+    NoiseMeasured {
+        levels: [f32; 4],
+    },
+}
+
+const RAIN_STREAKS: usize = 150;
+// Movement counts sit this far out from the center along both axes, past the walk buttons.
+const COUNT_OFFSET: f32 = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + 6.0;
+const COUNT_LINE_HEIGHT: i32 = 18;
+// Painted lines, and the dashes and zebra stripes they are broken into: a dash every
+// DASH_SPACING, half of it painted, and likewise a stripe every ZEBRA_SPACING.
+const MARKING_WIDTH: f32 = 0.2;
+const DASH_SPACING: f32 = 2.0;
+const ZEBRA_SPACING: f32 = 0.8;
+const HOUSING_COLOR: Color = Color::rgb(25, 25, 25);
+// Two road users' footprints must overlap by this much, in meters, to collide, so ones
+// only brushing past each other don't.
+const MIN_OVERLAP: f32 = 0.1;
+// Below this many vehicles, handing the approaches to worker threads costs more than
+// it saves; see benches/update.rs.
+pub const PARALLEL_VEHICLES: usize = 200;
+
+// Logs the events worth following a run by; the rest only feed the stats.
+fn trace_event(event: &SimEvent, light: &TrafficLight) {
+    match *event {
+        SimEvent::LightChanged => {
+            if light.is_flashing() {
+                tracing::debug!("lights flashing");
+            } else {
+                tracing::debug!(phase = ?light.phase, state = ?light.state, "light changed");
+            }
+        }
+        SimEvent::Collision => tracing::warn!("collision"),
+        SimEvent::RedLightViolation { vehicle_id, approach } => {
+            tracing::info!(%vehicle_id, ?approach, "red-light violation");
+        }
+        SimEvent::Starvation { approach } => {
+            tracing::info!(?approach, "starvation, forcing green");
+        }
+        SimEvent::Gridlock { stuck, removed_vehicle } => {
+            tracing::warn!(stuck, %removed_vehicle, "gridlock, removed a vehicle");
+        }
+        SimEvent::WeatherChanged { weather } => tracing::info!(?weather, "weather changed"),
+        SimEvent::VehicleSpawned { vehicle_id, kind, approach, route } => {
+            tracing::debug!(%vehicle_id, ?kind, ?approach, ?route, "vehicle spawned");
+        }
+        SimEvent::VehicleCollided { vehicle_id } => {
+            tracing::debug!(%vehicle_id, "vehicle collided");
+        }
+        SimEvent::VehicleWrecked { vehicle_id } => {
+            tracing::info!(%vehicle_id, "vehicle wrecked, blocking the road");
+        }
+        SimEvent::WreckCleared { vehicle_id } => tracing::info!(%vehicle_id, "wreck towed"),
+        SimEvent::VehicleExited { vehicle_id, kind, delay, .. } => {
+            tracing::debug!(%vehicle_id, ?kind, delay = delay.as_secs_f32(), "vehicle exited");
+        }
+        SimEvent::PedestrianServed { corner, wait } => {
+            tracing::debug!(?corner, wait = wait.as_secs_f32(), "pedestrian served");
+        }
+        SimEvent::JaywalkerCrossed { crosswalk, close_call } => {
+            tracing::debug!(?crosswalk, ?close_call, "jaywalker crossed");
+        }
+        SimEvent::EmergencyBrake { vehicle_id, approach } => {
+            tracing::info!(%vehicle_id, ?approach, "emergency brake for a jaywalker");
+        }
+        SimEvent::CrossingClosed => tracing::info!("train approaching, crossing gates down"),
+        SimEvent::CrossingOpened => tracing::info!("crossing gates up"),
+        SimEvent::LaneClosed { approach, lane } => {
+            tracing::info!(?approach, lane, "lane closed for a work zone");
+        }
+        SimEvent::LaneReopened { approach } => tracing::info!(?approach, "lane reopened"),
+        SimEvent::TurnBayOverflowed { approach } => {
+            tracing::info!(?approach, "turn bay full, blocking through traffic");
+        }
+        SimEvent::TurnBayCleared { approach } => tracing::debug!(?approach, "turn bay clear"),
+        SimEvent::LaneChangeViolation { vehicle_id, approach } => {
+            tracing::debug!(%vehicle_id, ?approach, "lane change over a solid line");
+        }
+        SimEvent::RouteReassigned { approach, from, to } => {
+            tracing::debug!(?approach, ?from, ?to, "route changed to avoid a saturated exit");
+        }
+        SimEvent::ManualControl { on: true } => {
+            tracing::info!("manual control on, the controller is suspended");
+        }
+        SimEvent::ManualControl { on: false } => tracing::info!("manual control off"),
+        SimEvent::ManualOverride { phase: Some(phase) } => {
+            tracing::info!(?phase, "manual override: green");
+        }
+        SimEvent::ManualOverride { phase: None } => tracing::info!("manual override: all red"),
+        SimEvent::ClearanceChecked { cleared, still_inside } => {
+            if still_inside > 0 {
+                tracing::debug!(cleared, still_inside, "green with the intersection not clear");
+            }
+        }
+        SimEvent::VehicleEnteredIntersection { .. } |
+        SimEvent::VehicleTurned { .. } |
+        SimEvent::VehicleWaiting |
+        SimEvent::SpeedMeasured { .. } |
+        SimEvent::NoiseMeasured { .. } |
+        SimEvent::TravelTimeMeasured { .. } |
+        SimEvent::QueueHeadway { .. } |
+        SimEvent::ArrivalQueued { .. } |
+        SimEvent::ArrivalReleased { .. } => {}
+    }
+}
+
+pub struct TrafficSimulation {
+    // The road on each approach, in north, south, east, west order. Everyone on it, and the
+    // light and detectors, are in `agents`.
+    pub lanes: [Lane; 4],
+    agents: Agents,
+    pub stats: Stats,
+    pub weather: Weather,
+    theme: Theme,
+    // Moves on whenever the background layer of roads, markings, tracks and signal housings
+    // would come out differently, so the renderer draws it again only then.
+    background_generation: u64,
+    // Every timer in the simulation runs on this, not on the wall clock.
+    pub time: SimClock,
+    // Pending scheduled changes as (time into the run, condition), soonest last.
+    weather_schedule: Vec<(Duration, Weather)>,
+    // Scenario trips not yet due as (due time, from, to), soonest last.
+    pending_trips: Vec<(Duration, Direction, Direction)>,
+    // Scenario lane closures and reopenings not yet due as (due time, approach, lane to
+    // close or None to reopen), soonest last.
+    pending_closures: Vec<(Duration, Direction, Option<usize>)>,
+    // Scenario incidents not yet due as (due time, approach if one is named), soonest last.
+    pending_incidents: Vec<(Duration, Option<Direction>)>,
+    pub clock: DayClock,
+    pub heatmap: Heatmap,
+    pub show_heatmap: bool,
+    // Vehicles counted on each movement, written at the corners.
+    pub show_counts: bool,
+    // Traffic noise at the corners, and its contours across the window.
+    noise: NoiseMeter,
+    pub show_noise: bool,
+    // Measuring flows for Webster's cycle length, from when the analysis was started.
+    pub webster: Option<WebsterAnalysis>,
+    discharge_meter: DischargeMeter,
+    // Checks each tick's vehicle movements in debug builds and strict runs.
+    motion_guard: Option<MotionGuard>,
+    // Id of the vehicle whose trail and planned path are drawn.
+    pub selected_vehicle: Option<VehicleId>,
+    // The point in the world the window is centered on, when zoomed in far enough to pan.
+    camera: (f32, f32),
+    pub rail: RailCrossing,
+    pub demand: DemandConfig,
+    // The approaches update on worker threads once this many vehicles are on the road.
+    pub parallel_vehicles: usize,
+    spawn_policy: Box<dyn SpawnPolicy>,
+    light_controller: Box<dyn LightController>,
+    // The `--script` hooks, started over on reset, and what they asked for last tick.
+    script: Option<ScriptHooks>,
+    script_commands: Vec<ScriptCommand>,
+    // The phase the light was in as each `LightChanged` not yet recorded was raised.
+    light_phases: Vec<Phase>,
+    // Where they came from, kept to build fresh ones on reset.
+    registry: Registry,
+    gridlock: GridlockConfig,
+    incidents: IncidentConfig,
+    // When a vehicle last moved, or the intersection was last clear.
+    last_progress: Duration,
+    // The phase whose yellow last ended and the vehicles it left in the intersection,
+    // followed until the next green.
+    clearing: Option<(Phase, Vec<VehicleId>)>,
+    // Drives every random decision in the simulation, so a seeded run can be repeated.
+    rng: StdRng,
+    // What `reset` goes back to.
+    config: Config,
+    events: Vec<SimEvent>,
+    // How many of `events` the stats have seen; key presses add events between updates.
+    recorded_events: usize,
+    // Receive a copy of every event as it is recorded; kept across resets.
+    subscribers: Vec<Sender<SimEvent>>,
+}
+
+// Parallel runs build and step simulations on worker threads, so no part of one may be
+// tied to the thread or shared with another run.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<TrafficSimulation>();
+};
+
+impl Default for TrafficSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrafficSimulation {
+    // The default lights are known to keep conflicting movements apart.
+    pub fn new() -> Self {
+        Self::build(&Config::default(), Registry::default())
+    }
+
+    // Plugins the built-in registry doesn't know are replaced by the defaults, with a
+    // warning. Lights that would give conflicting movements the right of way together are
+    // refused.
+    pub fn with_config(config: &Config) -> Result<Self, ConfigError> {
+        let registry = Registry::default();
+        if let Err(e) = registry.check(&config.plugins) {
+            tracing::warn!("{}, using the default", e);
+        }
+        check_signal_plan(&config.lights, &config.map)?;
+        Ok(Self::build(config, registry))
+    }
+
+    // Runs the plugins `config` names from `registry`, which may hold implementations from
+    // outside this crate alongside the built-in ones. Lights that would give conflicting
+    // movements the right of way together are refused.
+    pub fn with_registry(config: &Config, registry: Registry) -> Result<Self, ConfigError> {
+        registry.check(&config.plugins)?;
+        check_signal_plan(&config.lights, &config.map)?;
+        Ok(Self::build(config, registry))
+    }
+
+    fn build(config: &Config, registry: Registry) -> Self {
+        let plugins = &config.plugins;
+        let lane = |direction| {
+            let limit = config.speed_limits.for_direction(direction);
+            let geometry = Geometry::new(direction, &config.map);
+            let drivers = registry.driver_model(&plugins.driver_model);
+            let mut lane = Lane::new(
+                direction,
+                limit,
+                geometry,
+                config.sinks,
+                config.travel_times,
+                config.reactions,
+                drivers
+            );
+            lane.turn_bay = config.median.turn_bay();
+            lane.no_change_zone = config.lane_changes.no_change_zone();
+            lane
+        };
+        let mut weather_schedule: Vec<(Duration, Weather)> = config.weather.schedule
+            .iter()
+            .map(|change| (Duration::from_secs_f32(change.after_secs), change.condition))
+            .collect();
+        weather_schedule.sort_by_key(|&(after, _)| std::cmp::Reverse(after));
+        let mut traffic_light = TrafficLight::new();
+        config.lights.apply_to(&mut traffic_light);
+        let idle_phase = [Phase::NorthSouth, Phase::EastWest].into_iter().find(|&phase| {
+            let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+            !directions.into_iter().any(|d| phase.serves(d) && config.map.has_approach(d))
+        });
+        traffic_light.set_idle_phase(idle_phase);
+        let mut pending_trips: Vec<(Duration, Direction, Direction)> = config.scenario.trips
+            .iter()
+            .flat_map(|trip| {
+                let at = Duration::from_secs_f32(trip.at_secs);
+                (0..trip.count).map(move |_| (at, trip.from, trip.to))
+            })
+            .collect();
+        pending_trips.sort_by_key(|&(at, _, _)| std::cmp::Reverse(at));
+        let scenario = &config.scenario;
+        let mut pending_closures: Vec<(Duration, Direction, Option<usize>)> = scenario.closures
+            .iter()
+            .flat_map(|closure| {
+                let at = Duration::from_secs_f32(closure.at_secs);
+                let close = (at, closure.approach, Some(closure.lane));
+                let reopen = closure.until_secs
+                    .map(|until| (Duration::from_secs_f32(until), closure.approach, None));
+                std::iter::once(close).chain(reopen)
+            })
+            .collect();
+        pending_closures.sort_by_key(|&(at, _, _)| std::cmp::Reverse(at));
+        let mut pending_incidents: Vec<(Duration, Option<Direction>)> = scenario.incidents
+            .iter()
+            .map(|incident| (Duration::from_secs_f32(incident.at_secs), incident.approach))
+            .collect();
+        pending_incidents.sort_by_key(|&(at, _)| std::cmp::Reverse(at));
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let rail = RailCrossing::new(config.rail, &mut rng);
+        Self {
+            lanes: [
+                lane(Direction::North),
+                lane(Direction::South),
+                lane(Direction::East),
+                lane(Direction::West),
+            ],
+            agents: Agents::new(traffic_light),
+            stats: Stats::default(),
+            weather: config.weather.condition,
+            theme: config.theme,
+            background_generation: 0,
+            time: SimClock::default(),
+            weather_schedule,
+            pending_trips,
+            pending_closures,
+            pending_incidents,
+            clock: DayClock::new(
+                Duration::from_secs_f32(config.day_night.day_length_secs),
+                config.day_night.start_hour
+            ),
+            heatmap: Heatmap::new(),
+            show_heatmap: false,
+            show_counts: false,
+            noise: NoiseMeter::default(),
+            show_noise: false,
+            webster: None,
+            discharge_meter: DischargeMeter::default(),
+            motion_guard: (cfg!(debug_assertions) || config.strict).then(MotionGuard::default),
+            selected_vehicle: None,
+            camera: (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0),
+            rail,
+            demand: config.demand.clone(),
+            parallel_vehicles: PARALLEL_VEHICLES,
+            spawn_policy: registry.spawn_policy(&plugins.spawn_policy),
+            light_controller: registry.light_controller(&plugins.light_controller),
+            script: config.script.as_ref().map(ScriptHooks::new),
+            script_commands: Vec::new(),
+            light_phases: Vec::new(),
+            registry,
+            gridlock: config.gridlock,
+            incidents: config.incidents,
+            last_progress: Duration::ZERO,
+            clearing: None,
+            rng,
+            config: config.clone(),
+            events: Vec::new(),
+            recorded_events: 0,
+            subscribers: Vec::new(),
+        }
+    }
+
+    // Starts the run over from the config: no traffic, lights back at the start of their
+    // cycle, fresh stats and clock. A `seed` replaces the configured one for the new run.
+    pub fn reset(&mut self, seed: Option<u64>) {
+        let mut config = self.config.clone();
+        config.seed = seed.or(config.seed);
+        let (show_heatmap, show_counts, show_noise) =
+            (self.show_heatmap, self.show_counts, self.show_noise);
+        let (parallel_vehicles, camera) = (self.parallel_vehicles, self.camera);
+        let subscribers = std::mem::take(&mut self.subscribers);
+        let background_generation = self.background_generation;
+        *self = Self::build(&config, self.registry.clone());
+        // Lanes closed during the run reopen on the new one.
+        self.background_generation = background_generation;
+        self.redraw_background();
+        self.show_heatmap = show_heatmap;
+        self.show_counts = show_counts;
+        self.show_noise = show_noise;
+        self.parallel_vehicles = parallel_vehicles;
+        self.camera = camera;
+        self.subscribers = subscribers;
+    }
+
+    pub fn layout(&self) -> &MapLayout {
+        &self.config.map
+    }
+
+    pub fn traffic_light(&self) -> Ref<'_, TrafficLight> {
+        self.agents.light()
+    }
+
+    pub fn traffic_light_mut(&mut self) -> &mut TrafficLight {
+        self.agents.light_mut()
+    }
+
+    // Every vehicle on the road, approach by approach in lane order, each approach's in the
+    // order they got onto it.
+    pub fn vehicles(&self) -> Roster<'_, Vehicle> {
+        self.agents.vehicles()
+    }
+
+    // The vehicles that came in on `approach`, in the order they got onto it.
+    pub fn vehicles_on(&self, approach: Direction) -> Roster<'_, Vehicle> {
+        self.agents.vehicles_on(approach)
+    }
+
+    pub fn vehicle_mut(&mut self, id: VehicleId) -> Option<&mut Vehicle> {
+        self.agents.vehicle_mut(id)
+    }
+
+    pub fn cyclists(&self) -> Roster<'_, Cyclist> {
+        self.agents.cyclists()
+    }
+
+    // Waiting at the corners for the walk phase they called.
+    pub fn waiting_pedestrians(&self) -> Roster<'_, Pedestrian> {
+        self.agents.waiting_pedestrians()
+    }
+
+    // Out on the crosswalks, or jaywalking across the road.
+    pub fn crossing_pedestrians(&self) -> Roster<'_, Pedestrian> {
+        self.agents.crossing_pedestrians()
+    }
+
+    // Vehicles held up on `approach`, plus arrivals waiting upstream to get onto it.
+    pub fn queue_length(&self, approach: Direction) -> usize {
+        let lane = self.lanes.iter().find(|lane| lane.direction == approach);
+        lane.map_or(0, |lane| lane.queue_length(self.agents.vehicles_on(approach).iter()))
+    }
+
+    // Each approach's detector, as it read at the last tick.
+    pub fn detectors(&self) -> Vec<Detector> {
+        self.agents.detectors()
+    }
+
+    // Takes everyone off the road, cyclists and pedestrians too, and puts `vehicles` on it
+    // in their place, keeping their ids.
+    pub fn replace_vehicles(&mut self, vehicles: impl IntoIterator<Item = Vehicle>) {
+        self.agents.clear_traffic();
+        for vehicle in vehicles {
+            self.agents.add_vehicle(vehicle);
+        }
+    }
+
+    // Reads the detectors off the traffic as it stands now.
+    fn detect(&mut self) -> Vec<Detector> {
+        self.agents.detect(&self.lanes);
+        self.agents.detectors()
+    }
+
+    // Moves stop lines and lights to `layout`, for this run and any after a reset. Each
+    // approach's capacity follows its stop line. A layout the current lights would give
+    // conflicting movements the right of way on is refused.
+    pub fn set_layout(&mut self, layout: MapLayout) -> Result<(), ConfigError> {
+        check_signal_plan(&self.config.lights, &layout)?;
+        for lane in &mut self.lanes {
+            lane.geometry = Geometry::new(lane.direction, &layout);
+        }
+        self.config.map = layout;
+        self.redraw_background();
+        Ok(())
+    }
+
+    // Retimes the lights to `lights`, for this run and any after a reset. Protected lefts
+    // have their own signal heads. Timing that would give conflicting movements the right
+    // of way together is refused, leaving the lights as they were.
+    pub fn set_timing(&mut self, lights: LightsConfig) -> Result<(), ConfigError> {
+        check_signal_plan(&lights, &self.config.map)?;
+        lights.apply_to(self.agents.light_mut());
+        self.config.lights = lights;
+        self.redraw_background();
+        Ok(())
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    // For this run and any after a reset.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.config.theme = theme;
+        if theme != self.theme {
+            self.theme = theme;
+            self.redraw_background();
+        }
+    }
+
+    fn redraw_background(&mut self) {
+        self.background_generation += 1;
+    }
+
+    // A channel that gets every event from here on, in order, for observers that don't
+    // drive the simulation themselves. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<SimEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn set_weather(&mut self, weather: Weather) {
+        if weather != self.weather {
+            self.weather = weather;
+            self.events.push(SimEvent::WeatherChanged { weather });
+        }
+    }
+
+    // Advances the simulation by one configured tick, a TICK physics step at a time so a
+    // long tick can't carry a vehicle past a stop line or a light change. A scenario's end
+    // cuts the tick short.
+    pub fn update(&mut self) {
+        let substeps = self.config.clock.substeps();
+        for step in 0..substeps {
+            if step > 0 && self.scenario_ended() {
+                break;
+            }
+            self.step(step + 1 == substeps);
+        }
+    }
+
+    // Simulated time each `update` covers.
+    pub fn tick(&self) -> Duration {
+        self.config.clock.tick()
+    }
+
+    // `ends_tick` marks the last step of an `update`.
+    fn step(&mut self, ends_tick: bool) {
+        self.time.tick();
+        let now = self.time.now();
+        let _tick = tracing::trace_span!("tick", time = now.as_secs_f32()).entered();
+        self.follow_script(now);
+        if let Some(event) = self.rail.update(now, &mut self.rng) {
+            self.crossing_changed(event, now);
+        }
+        let main_road = self.config.flashing.main_road_at(self.clock.hour(now));
+        if self.agents.light_mut().set_flashing(main_road, now) {
+            self.light_changed();
+        }
+        let (state, phase) = {
+            let light = self.agents.light();
+            (light.state, light.phase)
+        };
+        let detectors = self.detect();
+        if self.light_controller.update(self.agents.light_mut(), &detectors, now) {
+            self.light_changed();
+            self.follow_clearance(state, phase);
+        }
+        self.check_starvation(now);
+        if self.agents.light().is_walk() {
+            self.serve_pedestrians(now);
+        }
+        systems::walk(&mut self.agents, &mut self.events);
+        self.spawn_jaywalker(now);
+        while let Some(&(after, weather)) = self.weather_schedule.last() {
+            if after > now {
+                break;
+            }
+            self.weather_schedule.pop();
+            self.set_weather(weather);
+        }
+        self.release_trips(now);
+        while let Some(&(at, approach, lane)) = self.pending_closures.last() {
+            if at > now {
+                break;
+            }
+            self.pending_closures.pop();
+            self.set_lane_closure(approach, lane);
+        }
+        while let Some(&(at, approach)) = self.pending_incidents.last() {
+            if at > now {
+                break;
+            }
+            self.pending_incidents.pop();
+            self.cause_incident(approach);
+        }
+        self.spawn_demand();
+        let obstacles: Vec<Vehicle> = self.agents
+            .vehicles()
+            .iter()
+            .filter(|v| v.wrecked_until.is_some() || v.in_intersection())
+            .copied()
+            .collect();
+        for lane in &mut self.lanes {
+            let (agents, rng, events) = (&mut self.agents, &mut self.rng, &mut self.events);
+            lane.release_platoon(agents, now, rng, events);
+            lane.release_upstream(agents, now, rng, events);
+        }
+        self.move_traffic(now, &obstacles);
+        systems::watch_jaywalkers(&mut self.agents);
+        let vehicles = self.agents.vehicles();
+        for vehicle in vehicles.iter() {
+            self.heatmap.record(vehicle, TICK);
+            self.stats.state_time[vehicle.state.index()] += TICK;
+        }
+        if let Some(levels) = self.noise.record(vehicles.iter()) {
+            self.events.push(SimEvent::NoiseMeasured { levels });
+        }
+        drop(vehicles);
+        self.tow_wrecks(now);
+        self.detect_collisions();
+        self.detect_gridlock(now);
+        let roster = self.agents.vehicles();
+        let vehicles: Vec<&Vehicle> = roster.iter().collect();
+        if let Some(guard) = &mut self.motion_guard {
+            // Every jump is logged; a strict run also stops at it rather than carry on.
+            let teleported = guard.check(&vehicles);
+            if self.config.strict {
+                assert!(teleported.is_empty(), "vehicles {:?} jumped at {:?}", teleported, now);
+            }
+        }
+        let light = self.agents.light();
+        if let Some(webster) = &mut self.webster {
+            let events = &self.events[self.recorded_events..];
+            webster.observe(&vehicles, &light, events);
+        }
+        let events = &self.events[self.recorded_events..];
+        let headways = self.discharge_meter.observe(&vehicles, &light, events, now);
+        self.events.extend(headways);
+        if let Some(script) = &mut self.script {
+            let events = &self.events[self.recorded_events..];
+            self.script_commands = script.run(events, &self.light_phases, now, ends_tick);
+        }
+        drop((light, vehicles));
+        drop(roster);
+        self.record_events();
+        let approaches = self.lanes.iter().map(|lane| lane.direction);
+        let longest = approaches.map(|approach| self.queue_length(approach)).max().unwrap_or(0);
+        self.stats.max_queue = self.stats.max_queue.max(longest);
+    }
+
+    // Moves the traffic on by a tick, system by system: lane changes, then the room each
+    // vehicle has behind the one ahead, at the signals and giving way to others, then
+    // driving, and riding for the cyclists. Room is worked out on every approach before any
+    // vehicle moves, on worker threads once there is enough traffic, so each sees the others
+    // as they stood. Drivers deciding whether to run a light draw from a generator of their
+    // approach's own, seeded in lane order from the simulation's, so who enters the
+    // intersection first comes out the same however the threads are scheduled. Events
+    // follow in lane order.
+    fn move_traffic(&mut self, now: Duration, obstacles: &[Vehicle]) {
+        let (weather, gates_down) = (self.weather, self.rail.gates_down());
+        let signals: Vec<Signals> = {
+            let light = self.agents.light();
+            let vehicles = self.agents.vehicles();
+            self.lanes
+                .iter()
+                .map(|lane| {
+                    let direction = lane.direction;
+                    let head = light.head_for(direction);
+                    // Only a flashing red yields to the crossing road.
+                    let crossing = vehicles.iter().filter(|vehicle| {
+                        vehicle.approach != direction && vehicle.approach != opposite(direction)
+                    });
+                    Signals {
+                        head,
+                        oncoming_light: light.state_for(opposite(direction)),
+                        cross_traffic_close: head.ball == LightState::FlashingRed &&
+                            systems::cross_traffic_close(crossing),
+                        seed: self.rng.gen(),
+                    }
+                })
+                .collect()
+        };
+        let mut events: Vec<Vec<SimEvent>> = self.lanes.iter().map(|_| Vec::new()).collect();
+        let rows = self.agents.vehicle_rows::<&mut Vehicle>();
+        for ((lane, mut vehicles), events) in self.lanes.iter_mut().zip(rows).zip(&mut events) {
+            systems::change_lanes(lane, &mut vehicles, weather, events);
+        }
+
+        let rooms: Vec<Vec<Room>> = {
+            let (vehicles, cyclists) = (self.agents.vehicles(), self.agents.cyclists());
+            let pedestrians = self.agents.crossing_pedestrians();
+            let (vehicles, cyclists) = (vehicles.groups(), cyclists.groups());
+            let pedestrians: Vec<&Pedestrian> = pedestrians.iter().collect();
+            let plan = |(lane, signals): (&mut Lane, &Signals)| {
+                let (own, oncoming) = (node_id(lane.direction), node_id(opposite(lane.direction)));
+                let conflicts = Conflicts {
+                    oncoming: &vehicles[oncoming],
+                    oncoming_cyclists: &cyclists[oncoming],
+                    pedestrians: &pedestrians,
+                    gates_down,
+                    obstacles,
+                };
+                systems::plan(lane, &vehicles[own], &cyclists[own], signals, conflicts, weather)
+            };
+            if vehicles.iter().map(Vec::len).sum::<usize>() >= self.parallel_vehicles {
+                self.lanes.par_iter_mut().zip(&signals).map(plan).collect()
+            } else {
+                self.lanes.iter_mut().zip(&signals).map(plan).collect()
+            }
+        };
+        for (row, rooms) in self.agents.vehicle_rows::<&mut Room>().into_iter().zip(rooms) {
+            for (room, planned) in row.into_iter().zip(rooms) {
+                *room = planned;
+            }
+        }
+
+        let rows = self.agents.vehicle_rows::<(&mut Vehicle, &Room)>();
+        let finished: Vec<Vec<VehicleId>> = self.lanes
+            .iter()
+            .zip(rows)
+            .zip(&signals)
+            .zip(&mut events)
+            .map(|(((lane, vehicles), signals), events)| {
+                systems::drive(lane, vehicles, signals.head, weather, now, events)
+            })
+            .collect();
+        for (finished, events) in finished.iter().zip(&mut events) {
+            systems::leave(&mut self.agents, finished, events);
+        }
+
+        let steps: Vec<Vec<Option<f32>>> = {
+            let (vehicles, cyclists) = (self.agents.vehicles(), self.agents.cyclists());
+            let pedestrians = self.agents.crossing_pedestrians();
+            let (vehicles, cyclists) = (vehicles.groups(), cyclists.groups());
+            let pedestrians: Vec<&Pedestrian> = pedestrians.iter().collect();
+            self.lanes
+                .iter()
+                .zip(&signals)
+                .map(|(lane, signals)| {
+                    let (own, oncoming) =
+                        (node_id(lane.direction), node_id(opposite(lane.direction)));
+                    let conflicts = Conflicts {
+                        oncoming: &vehicles[oncoming],
+                        oncoming_cyclists: &cyclists[oncoming],
+                        pedestrians: &pedestrians,
+                        gates_down,
+                        obstacles,
+                    };
+                    let (cyclists, vehicles) = (&cyclists[own], &vehicles[own]);
+                    systems::pedal(lane.direction, cyclists, vehicles, signals, conflicts)
+                })
+                .collect()
+        };
+        systems::ride(&mut self.agents, &steps);
+        self.events.extend(events.into_iter().flatten());
+    }
+
+    // Carries out what the script's hooks asked for at the end of the last tick, so the
+    // events that follow reach them at the end of this one.
+    fn follow_script(&mut self, now: Duration) {
+        for command in std::mem::take(&mut self.script_commands) {
+            match command {
+                ScriptCommand::Spawn(approach) => self.spawn_vehicle(approach),
+                ScriptCommand::SpawnTrip(from, to) => {
+                    if let Err(e) = self.spawn_trip(from, to) {
+                        tracing::warn!("script trip: {}", e);
+                    }
+                }
+                ScriptCommand::SpawnPlatoon(approach, size) => self.spawn_platoon(approach, size),
+                ScriptCommand::RequestPhase(phase) => {
+                    if self.agents.light_mut().request_phase(phase, now) {
+                        self.light_changed();
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether the scenario's end time has come.
+    pub fn scenario_ended(&self) -> bool {
+        let end = self.config.scenario.end_secs;
+        end.is_some_and(|end| self.time.now().as_secs_f32() >= end)
+    }
+
+    fn record_events(&mut self) {
+        let time = self.time.now();
+        for event in &self.events[self.recorded_events..] {
+            self.stats.record(event, time);
+            trace_event(event, &self.agents.light());
+            self.subscribers.retain(|subscriber| subscriber.send(*event).is_ok());
+        }
+        self.recorded_events = self.events.len();
+        self.light_phases.clear();
+    }
+
+    // Raises `LightChanged`, noting the phase the light is in as it does.
+    fn light_changed(&mut self) {
+        self.events.push(SimEvent::LightChanged);
+        self.light_phases.push(self.agents.light().phase);
+    }
+
+    // A pedestrian arrives at `corner` and presses the button there, heading over one of
+    // its two crosswalks. During the walk they set off straight away; otherwise the
+    // controller inserts a walk phase after the next yellow, unless they jaywalk over
+    // against the signal without waiting for it.
+    pub fn press_walk_button(&mut self, corner: Corner) {
+        let now = self.time.now();
+        // A T intersection has no crosswalk over the road it doesn't have.
+        let crosswalks: Vec<Direction> = corner
+            .crosswalks()
+            .into_iter()
+            .filter(|&crosswalk| self.config.map.has_road(opposite(crosswalk)))
+            .collect();
+        let crosswalk = crosswalks[self.rng.gen_range(0..crosswalks.len())];
+        let speed = self.rng.gen_range(MIN_WALKING_SPEED..=MAX_WALKING_SPEED);
+        let pedestrian = Pedestrian::new(corner, crosswalk, now, speed);
+        let chance = self.config.jaywalking.against_signal_chance;
+        if !self.agents.light().is_walk() && chance > 0.0 && self.rng.gen_bool(chance) {
+            self.agents.add_crossing(Pedestrian { jaywalking: true, ..pedestrian });
+            return;
+        }
+        self.agents.add_waiting(pedestrian);
+        if self.agents.light().is_walk() {
+            self.serve_pedestrians(now);
+        } else {
+            self.agents.light_mut().call_walk();
+        }
+    }
+
+    pub fn press_random_walk_button(&mut self) {
+        let corner = CORNERS[self.rng.gen_range(0..CORNERS.len())];
+        self.press_walk_button(corner);
+    }
+
+    // Someone stepping out between the crosswalks, at the configured rate, from either curb
+    // of any road the map has and anywhere along it.
+    fn spawn_jaywalker(&mut self, now: Duration) {
+        let per_minute = self.config.jaywalking.mid_block_per_minute;
+        if per_minute == 0.0 {
+            return;
+        }
+        let chance = (per_minute / 60.0 * TICK.as_secs_f32()).min(1.0);
+        if !self.rng.gen_bool(chance as f64) {
+            return;
+        }
+        let crosswalks: Vec<Direction> = [
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+        ]
+            .into_iter()
+            .filter(|&crosswalk| self.config.map.has_road(opposite(crosswalk)))
+            .collect();
+        let crosswalk = crosswalks[self.rng.gen_range(0..crosswalks.len())];
+        let corners: Vec<Corner> = CORNERS
+            .into_iter()
+            .filter(|corner| corner.crosswalks().contains(&crosswalk))
+            .collect();
+        let corner = corners[self.rng.gen_range(0..corners.len())];
+        let distance = self.rng.gen_range(MID_BLOCK_NEAREST..MID_BLOCK_FARTHEST);
+        let speed = self.rng.gen_range(MIN_WALKING_SPEED..=MAX_WALKING_SPEED);
+        let jaywalker = Pedestrian::mid_block(corner, crosswalk, distance, now, speed);
+        self.agents.add_crossing(jaywalker);
+    }
+
+    fn serve_pedestrians(&mut self, now: Duration) {
+        for pedestrian in self.agents.waiting_pedestrians().iter() {
+            let wait = now - pedestrian.waiting_since;
+            self.events.push(SimEvent::PedestrianServed { corner: pedestrian.corner, wait });
+        }
+        self.agents.start_crossing();
+    }
+
+    // Cones off `lane` of `approach` over its work zone, replacing any closure there, or
+    // reopens the approach with None. Divided roads have no lane to spare.
+    pub fn set_lane_closure(&mut self, approach: Direction, lane: Option<usize>) {
+        if !self.config.map.has_approach(approach) || self.config.median.turn_bay().is_some() {
+            return;
+        }
+        let Some(road) = self.lanes.iter_mut().find(|road| road.direction == approach) else {
+            return;
+        };
+        if road.closed_lane == lane {
+            return;
+        }
+        road.set_closed_lane(&mut self.agents, lane);
+        self.redraw_background();
+        self.events.push(match lane {
+            Some(lane) => SimEvent::LaneClosed { approach, lane },
+            None => SimEvent::LaneReopened { approach },
+        });
+    }
+
+    // Reopens every closed lane, or if there are none closes a random lane of a random
+    // approach.
+    pub fn toggle_random_closure(&mut self) {
+        let closed: Vec<Direction> = self.lanes
+            .iter()
+            .filter(|road| road.closed_lane.is_some())
+            .map(|road| road.direction)
+            .collect();
+        if closed.is_empty() {
+            let approaches = self.approaches();
+            let approach = approaches[self.rng.gen_range(0..approaches.len())];
+            let lane = self.rng.gen_range(0..LANES_PER_DIRECTION);
+            self.set_lane_closure(approach, Some(lane));
+        }
+        for approach in closed {
+            self.set_lane_closure(approach, None);
+        }
+    }
+
+    // Wrecks the vehicle nearest the middle of the intersection, out of those from
+    // `approach` if given. Returns whether there was one to wreck.
+    pub fn cause_incident(&mut self, approach: Option<Direction>) -> bool {
+        if self.incidents.clearance_secs <= 0.0 {
+            return false;
+        }
+        let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        let distance = |v: &Vehicle| hypot(v.x - center.0, v.y - center.1);
+        let nearest = self.agents
+            .vehicles()
+            .iter()
+            .filter(|v| approach.is_none_or(|approach| v.approach == approach))
+            .filter(|v| v.wrecked_until.is_none())
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .map(|vehicle| vehicle.id);
+        let Some(id) = nearest else {
+            return false;
+        };
+        self.wreck(id);
+        true
+    }
+
+    fn wreck(&mut self, id: VehicleId) {
+        let until = self.time.now() + Duration::from_secs_f32(self.incidents.clearance_secs);
+        let Some(vehicle) = self.agents.vehicle_mut(id) else {
+            return;
+        };
+        if vehicle.wrecked_until.is_some() {
+            return;
+        }
+        vehicle.wrecked_until = Some(until);
+        vehicle.speed = 0.0;
+        self.events.push(SimEvent::VehicleWrecked { vehicle_id: vehicle.id });
+    }
+
+    // Takes wrecks whose clearance time is up off the road.
+    fn tow_wrecks(&mut self, now: Duration) {
+        let towed: Vec<VehicleId> = self.agents
+            .vehicles()
+            .iter()
+            .filter(|vehicle| vehicle.wrecked_until.is_some_and(|until| now >= until))
+            .map(|vehicle| vehicle.id)
+            .collect();
+        for vehicle_id in towed {
+            self.agents.remove_vehicle(vehicle_id);
+            self.events.push(SimEvent::WreckCleared { vehicle_id });
+        }
+    }
+
+    // Brings the crossing gates down for a train now, unless one is already crossing.
+    pub fn send_train(&mut self) {
+        let now = self.time.now();
+        if self.rail.send_train(now) {
+            self.crossing_changed(SimEvent::CrossingClosed, now);
+        }
+    }
+
+    // Takes the lights off the controller so the user can run them, or hands them back.
+    pub fn toggle_manual_control(&mut self) {
+        let manual = !self.agents.light().is_manual();
+        if self.agents.light_mut().set_manual(manual, self.time.now()) {
+            self.light_changed();
+        }
+        self.events.push(SimEvent::ManualControl { on: manual });
+    }
+
+    // Under manual control, every approach to red, or `phase` to green, at once.
+    pub fn override_lights(&mut self, phase: Option<Phase>) {
+        let now = self.time.now();
+        let changed = match phase {
+            Some(phase) => self.agents.light_mut().give_green(phase, now),
+            None => self.agents.light_mut().hold_all_red(now),
+        };
+        if changed {
+            self.light_changed();
+            self.events.push(SimEvent::ManualOverride { phase });
+        }
+    }
+
+    // Starts measuring for Webster's method, or stops and drops the measurements.
+    pub fn toggle_webster(&mut self) {
+        self.webster = match self.webster {
+            Some(_) => None,
+            None => Some(WebsterAnalysis::new(self.time.now())),
+        };
+    }
+
+    // Puts the fixed-time controller on Webster's timing from the flows measured so far;
+    // returns false if the analysis is off or has too little to go on yet.
+    pub fn apply_webster_timing(&mut self) -> bool {
+        let now = self.time.now();
+        let light = self.agents.light_mut();
+        let Some(webster) = &self.webster else {
+            return false;
+        };
+        for flow in webster.flows(now) {
+            tracing::info!(
+                approach = ?flow.approach,
+                route = ?flow.route,
+                demand = flow.demand.round(),
+                saturation_flow = flow.saturation_flow.map(f32::round),
+                "vehicles per hour"
+            );
+        }
+        let Some(timing) = webster.timing(now, light.change_interval()) else {
+            return false;
+        };
+        let greens = timing.north_south_green + timing.east_west_green;
+        light.green_time = greens / 2;
+        light.north_south_split = timing.north_south_green.as_secs_f32() / greens.as_secs_f32();
+        tracing::info!(
+            cycle = timing.cycle.as_secs_f32(),
+            north_south_green = timing.north_south_green.as_secs_f32(),
+            east_west_green = timing.east_west_green.as_secs_f32(),
+            "applied Webster's timing"
+        );
+        true
+    }
+
+    // Counts, at each change of road, the vehicles left in the intersection by the end of
+    // the yellow that cleared it during the all-red before the other road's green. A walk
+    // phase clears the intersection on its own and isn't counted.
+    fn follow_clearance(&mut self, state_before: LightState, phase_before: Phase) {
+        let light = self.agents.light();
+        let vehicles = self.agents.vehicles();
+        let yellow_ended =
+            state_before == LightState::Yellow &&
+            (light.is_all_red() || light.state == LightState::Green);
+        if yellow_ended {
+            let inside = vehicles
+                .iter()
+                .filter(|vehicle| {
+                    phase_before.serves(vehicle.approach) && vehicle.in_intersection()
+                })
+                .map(|vehicle| vehicle.id)
+                .collect();
+            self.clearing = Some((phase_before, inside));
+        }
+        if light.state != LightState::Green {
+            return;
+        }
+        let Some((phase, inside)) = self.clearing.take() else {
+            return;
+        };
+        // A road skipped for having no traffic in leaves the same one on.
+        if phase == light.phase || inside.is_empty() {
+            return;
+        }
+        let still_inside = vehicles
+            .iter()
+            .filter(|vehicle| inside.contains(&vehicle.id) && vehicle.in_intersection())
+            .count() as u32;
+        let cleared = (inside.len() as u32) - still_inside;
+        self.events.push(SimEvent::ClearanceChecked { cleared, still_inside });
+    }
+
+    // The tracks cross the east-west road, so it is held at red from the moment the gates
+    // start coming down, and gets the first green once they are back up to clear its queue.
+    // Without a road out to the tracks the lights carry on as usual.
+    fn crossing_changed(&mut self, event: SimEvent, now: Duration) {
+        let light_changed = if !self.config.map.has_road(RAIL_EXIT) {
+            false
+        } else if event == SimEvent::CrossingClosed {
+            self.agents.light_mut().preempt(Phase::NorthSouth, now)
+        } else {
+            self.agents.light_mut().release(now)
+        };
+        self.events.push(event);
+        if light_changed {
+            self.light_changed();
+        }
+    }
+
+    // Arrivals the spawn policy sends at the demand rate for the current time of day, on
+    // any approach, and at each approach's own rate.
+    fn spawn_demand(&mut self) {
+        let hour = self.clock.hour(self.time.now());
+        let detectors = self.detect();
+        let arrivals =
+            self.spawn_policy.arrivals(&self.demand, hour, &self.lanes, &detectors, &mut self.rng);
+        for approach in arrivals {
+            match approach {
+                Some(direction) => self.spawn_vehicle(direction),
+                None => self.spawn_random_vehicle(),
+            }
+        }
+    }
+
+    // Trips due by `now` arrive; any that find their approach full wait upstream.
+    fn release_trips(&mut self, now: Duration) {
+        while let Some(&(at, from, to)) = self.pending_trips.last() {
+            if at > now {
+                break;
+            }
+            self.pending_trips.pop();
+            let _ = self.spawn_trip(from, to);
+        }
+    }
+
+    // Starvation watchdog: ends the current green once traffic waiting on the other road has
+    // been held at red for the maximum red time.
+    fn check_starvation(&mut self, now: Duration) {
+        let light = self.agents.light();
+        let vehicles = self.agents.vehicles();
+        let starved = self.lanes.iter().find(|lane| {
+            light.red_time(lane.direction, now) >= light.max_red_time &&
+                vehicles.iter().any(|v| {
+                    v.approach == lane.direction && v.is_stopped() && v.state.on_approach()
+                })
+        });
+        let Some(approach) = starved.map(|lane| lane.direction) else {
+            return;
+        };
+        drop((light, vehicles));
+        if self.agents.light_mut().end_green(now) {
+            self.light_changed();
+            self.events.push(SimEvent::Starvation { approach });
+        }
+    }
+
+    // Vehicles inside the intersection don't answer to the lights, so a deadlock there (such
+    // as opposing left-turners each holding up the through traffic the other yields to) only
+    // clears by taking one of them off the road: the one stuck longest.
+    fn detect_gridlock(&mut self, now: Duration) {
+        let vehicles = self.agents.vehicles();
+        // Wrecks hold traffic up until they are towed, which clears the jam by itself.
+        let moving = vehicles.iter().any(|v| {
+            v.speed > 0.0 || v.dwell_until.is_some() || v.wrecked_until.is_some()
+        });
+        let stuck = vehicles.iter().filter(|v| v.in_intersection()).count();
+        if moving || stuck == 0 {
+            self.last_progress = now;
+            return;
+        }
+        if now - self.last_progress < Duration::from_secs_f32(self.gridlock.timeout_secs) {
+            return;
+        }
+        let blocking = vehicles
+            .iter()
+            .filter(|v| v.in_intersection())
+            .min_by_key(|v| v.wait_started)
+            .map(|v| v.id);
+        drop(vehicles);
+        if let Some(vehicle) = blocking.and_then(|id| self.agents.remove_vehicle(id)) {
+            self.events.push(SimEvent::Gridlock { stuck, removed_vehicle: vehicle.id });
+        }
+        self.last_progress = now;
+    }
+
+    pub fn drain_events(&mut self) -> Vec<SimEvent> {
+        self.record_events();
+        self.recorded_events = 0;
+        std::mem::take(&mut self.events)
+    }
+
+    fn detect_collisions(&mut self) {
+        let mut footprints = Vec::new();
+        for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let vehicles = self.agents.vehicles_on(approach);
+            let vehicles = vehicles
+                .entries()
+                .map(|(entity, vehicle)| (entity, vehicle_rect(vehicle), Some(vehicle.id)));
+            let cyclists = self.agents.cyclists_on(approach);
+            let cyclists = cyclists
+                .entries()
+                .map(|(entity, cyclist)| (entity, cyclist_rect(cyclist), None));
+            footprints.extend(
+                vehicles.chain(cyclists).map(|(entity, footprint, vehicle_id)| {
+                    (entity, approach, vehicle_id, footprint.grown(-MIN_OVERLAP / 2.0))
+                })
+            );
+        }
+        for (i, &(entity_a, approach_a, id_a, rect_a)) in footprints.iter().enumerate() {
+            for &(entity_b, approach_b, id_b, rect_b) in &footprints[i + 1..] {
+                if approach_a == approach_b || !rect_a.intersects(&rect_b) {
+                    continue;
+                }
+                let mut collided = |entity| self.agents.collided_flag(entity).is_some_and(|c| *c);
+                let already_collided = collided(entity_a) && collided(entity_b);
+                if !already_collided {
+                    self.events.push(SimEvent::Collision);
+                    for (entity, vehicle_id) in [(entity_a, id_a), (entity_b, id_b)] {
+                        if let Some(collided) = self.agents.collided_flag(entity) {
+                            *collided = true;
+                        }
+                        if let Some(vehicle_id) = vehicle_id {
+                            self.events.push(SimEvent::VehicleCollided { vehicle_id });
+                            if self.incidents.clearance_secs > 0.0 {
+                                self.wreck(vehicle_id);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Spawns a car travelling `direction` on any route open to it, if the intersection has
+    // a road for it to arrive on.
+    pub fn spawn_vehicle(&mut self, direction: Direction) {
+        let lane_index = match direction {
+            Direction::North => 0,
+            Direction::South => 1,
+            Direction::East => 2,
+            Direction::West => 3,
+        };
+        let routes: Vec<Route> = [Route::Straight, Route::Left, Route::Right]
+            .into_iter()
+            .filter(|&route| self.config.map.serves(direction, route))
+            .collect();
+        if routes.is_empty() {
+            return;
+        }
+        let route = routes[self.rng.gen_range(0..routes.len())];
+        let route = self.choose_route(direction, route, &routes);
+        self.arrive(lane_index, VehicleKind::Car, route);
+    }
+
+    // Route choice: a driver heading for a saturated exit may switch to one of the other
+    // `routes` whose exit has room.
+    fn choose_route(&mut self, direction: Direction, route: Route, routes: &[Route]) -> Route {
+        let choice = self.config.route_choice;
+        let saturated = |simulation: &Self, route| {
+            simulation.exit_load(turned_direction(direction, route)) >= choice.saturated_at
+        };
+        if choice.reroute_chance == 0.0 || !saturated(self, route) {
+            return route;
+        }
+        let open: Vec<Route> = routes
+            .iter()
+            .copied()
+            .filter(|&other| other != route && !saturated(self, other))
+            .collect();
+        if open.is_empty() || !self.rng.gen_bool(choice.reroute_chance) {
+            return route;
+        }
+        let to = open[self.rng.gen_range(0..open.len())];
+        self.events.push(SimEvent::RouteReassigned { approach: direction, from: route, to });
+        to
+    }
+
+    // Vehicles that have entered the intersection bound for the road at `exit` and not
+    // yet left the window along it.
+    fn exit_load(&self, exit: Direction) -> usize {
+        self.agents
+            .vehicles()
+            .iter()
+            .filter(|vehicle| {
+                turned_direction(vehicle.approach, vehicle.route) == exit &&
+                    vehicle.distance_to_intersection() < 0.0
+            })
+            .count()
+    }
+
+    // Injects a platoon of `size` cars travelling `direction`, entering nose to tail in one
+    // lane. They go straight where the approach allows it and take any open route otherwise.
+    pub fn spawn_platoon(&mut self, direction: Direction, size: u32) {
+        let routes: Vec<Route> = [Route::Straight, Route::Left, Route::Right]
+            .into_iter()
+            .filter(|&route| self.config.map.serves(direction, route))
+            .collect();
+        let Some(lane_index) = self.lanes.iter().position(|lane| lane.direction == direction) else {
+            return;
+        };
+        if routes.is_empty() {
+            return;
+        }
+        let members: Vec<Route> = (0..size)
+            .map(|_| {
+                if routes.contains(&Route::Straight) {
+                    Route::Straight
+                } else {
+                    routes[self.rng.gen_range(0..routes.len())]
+                }
+            })
+            .collect();
+        self.lanes[lane_index].inject_platoon(members, &mut self.rng);
+    }
+
+    // Directions traffic arrives travelling in: all four, or three at a T intersection.
+    fn approaches(&self) -> Vec<Direction> {
+        [Direction::North, Direction::South, Direction::East, Direction::West]
+            .into_iter()
+            .filter(|&direction| self.config.map.has_approach(direction))
+            .collect()
+    }
+
+    // Returns whether the arrival entered straight away rather than waiting upstream.
+    fn arrive(&mut self, lane_index: usize, kind: VehicleKind, route: Route) -> bool {
+        let now = self.time.now();
+        self.lanes[lane_index].arrive(
+            &mut self.agents,
+            kind,
+            route,
+            now,
+            &mut self.rng,
+            &mut self.events
+        )
+    }
+
+    // Spawns a car entering at the `from` road end that leaves at the `to` one, turning as
+    // needed. Returns whether it entered straight away rather than waiting upstream.
+    pub fn spawn_trip(&mut self, from: Direction, to: Direction) -> Result<bool, SimError> {
+        let Some(route) = route_between(opposite(from), to) else {
+            return Err(SimError::UTurn(from));
+        };
+        self.spawn_vehicle_with_route(opposite(from), route)
+    }
+
+    // Spawns a car travelling `direction` that takes `route`, whatever route choice would
+    // make of it, for setting up a particular conflict. Returns whether it entered straight
+    // away rather than waiting upstream.
+    pub fn spawn_vehicle_with_route(
+        &mut self,
+        direction: Direction,
+        route: Route
+    ) -> Result<bool, SimError> {
+        if !self.config.map.has_approach(direction) {
+            return Err(SimError::NoWayIn(opposite(direction)));
+        }
+        let exit = turned_direction(direction, route);
+        if !self.config.map.has_exit(exit) {
+            return Err(SimError::NoWayOut(exit));
+        }
+        let Some(lane_index) = self.lanes.iter().position(|lane| lane.direction == direction)
+        else {
+            return Ok(false);
+        };
+        Ok(self.arrive(lane_index, VehicleKind::Car, route))
+    }
+
+    // Spawns a bus on one of the fixed BUS_LINES the intersection has roads for, picked at
+    // random.
+    pub fn spawn_bus(&mut self) {
+        let lines: Vec<(Direction, Route)> = BUS_LINES
+            .into_iter()
+            .filter(|&(direction, route)| self.config.map.serves(direction, route))
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        let (direction, route) = lines[self.rng.gen_range(0..lines.len())];
+        if let Some(lane_index) = self.lanes.iter().position(|lane| lane.direction == direction) {
+            self.arrive(lane_index, VehicleKind::Bus, route);
+        }
+    }
+
+    // Cyclists only ride straight through, so none come up the stem of a T intersection.
+    pub fn spawn_cyclist(&mut self, direction: Direction) {
+        if !self.config.map.serves(direction, Route::Straight) {
+            return;
+        }
+        if let Some(lane) = self.lanes.iter_mut().find(|lane| lane.direction == direction) {
+            lane.spawn_cyclist(&mut self.agents, self.time.now(), &mut self.rng);
+        }
+    }
+
+    pub fn spawn_random_cyclist(&mut self) {
+        let directions: Vec<Direction> = self
+            .approaches()
+            .into_iter()
+            .filter(|&direction| self.config.map.serves(direction, Route::Straight))
+            .collect();
+        if directions.is_empty() {
+            return;
+        }
+        let direction = directions[self.rng.gen_range(0..directions.len())];
+        self.spawn_cyclist(direction);
+    }
+
+    pub fn spawn_random_vehicle(&mut self) {
+        let approaches = self.approaches();
+        let direction = approaches[self.rng.gen_range(0..approaches.len())];
+        self.spawn_vehicle(direction);
+    }
+
+    // Selects the vehicle at the point (x, y) in the world, or clears the selection if
+    // there is none.
+    pub fn select_at(&mut self, x: f32, y: f32) {
+        self.selected_vehicle = self.agents
+            .vehicles()
+            .iter()
+            .find(|vehicle| vehicle_rect(vehicle).contains(x, y))
+            .map(|vehicle| vehicle.id);
+    }
+
+    // Moves the selection to the next vehicle by id, wrapping around to the oldest.
+    pub fn select_next_vehicle(&mut self) {
+        let vehicles = self.agents.vehicles();
+        let ids = vehicles.iter().map(|vehicle| vehicle.id);
+        let current = self.selected_vehicle.unwrap_or_default();
+        let next = ids.clone().filter(|&id| id > current).min();
+        self.selected_vehicle = next.or_else(|| ids.min());
+    }
+
+    fn selected(&self) -> Option<Vehicle> {
+        let id = self.selected_vehicle?;
+        self.agents.vehicles().iter().find(|vehicle| vehicle.id == id).copied()
+    }
+
+    // How the world is drawn, at the configured scale and panned to the camera.
+    pub fn view(&self) -> View {
+        let (x, y) = self.camera;
+        View::new(self.config.display.meters_per_pixel).centered_on(x, y)
+    }
+
+    // Pans the camera to put (x, y) in the world in the middle of the window.
+    pub fn pan_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.view().centered_on(x, y).center;
+        if (x, y) != self.camera {
+            self.camera = (x, y);
+            self.redraw_background();
+        }
+    }
+
+    pub fn render(&self, renderer: &mut dyn Renderer) -> Result<(), RenderError> {
+        let palette = self.theme.palette();
+        let view = self.view();
+        renderer.clear(palette.ground)?;
+        renderer.draw_layer(self.background_generation, &mut |renderer| {
+            renderer.clear(palette.ground)?;
+            self.draw_roads(renderer, &view)?;
+            draw_track(renderer, &view)?;
+            self.draw_signal_housings(renderer, &view)
+        })?;
+        self.rail.draw(renderer, &view, self.time.now())?;
+        let screen = Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+        if let Some(tint) = self.weather.road_tint() {
+            renderer.draw_rect(screen, tint)?;
+        }
+        let darkness = self.clock.darkness(self.time.now());
+        if let Some(overlay) = night_overlay(darkness) {
+            renderer.draw_rect(screen, overlay)?;
+        }
+        self.draw_traffic_lights(renderer, &view)?;
+        self.draw_pedestrian_signals(renderer, &view)?;
+        let lights_on = darkness >= LIGHTS_ON_DARKNESS;
+        let now = self.time.now();
+        systems::draw_road_users(renderer, &view, &self.agents, palette, lights_on, now)?;
+        self.draw_selection(renderer, &view)?;
+        self.draw_rain(renderer)?;
+        if self.show_heatmap {
+            self.heatmap.draw(renderer, &view)?;
+        }
+        if self.show_counts {
+            self.draw_movement_counts(renderer, &view)?;
+        }
+        if self.show_noise {
+            noise::draw(renderer, &view, self.agents.vehicles().iter(), palette.text)?;
+        }
+        if let Some(webster) = &self.webster {
+            let change_interval = self.agents.light().change_interval();
+            webster.draw(renderer, palette, self.time.now(), change_interval)?;
+        }
+        if minimap::shows_more(&view) {
+            minimap::draw(renderer, &view, self, palette)?;
+        }
+        let text = palette.text;
+        renderer.draw_text(&format!("WEATHER: {}", self.weather.name()), 10, 10, text)?;
+        let time = self.clock.label(self.time.now());
+        renderer.draw_text(&format!("TIME: {}", time), 10, 30, text)?;
+        renderer.draw_text(&format!("ELAPSED: {}", self.time.label()), 10, 50, text)?;
+        if self.agents.light().is_manual() {
+            let label = "MANUAL CONTROL";
+            let x = (WINDOW_WIDTH as i32) / 2 - font::GLYPH_ADVANCE * (label.len() as i32) / 2;
+            renderer.draw_text(label, x, 30, palette.red)?;
+        }
+        Ok(())
+    }
+
+    // Each approach's movements at the corner on its right before the box, as "N>W 42"
+    // from the road end it came in on to the one it left by, with any buses among them.
+    fn draw_movement_counts(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View
+    ) -> Result<(), RenderError> {
+        let palette = self.theme.palette();
+        let counts = self.stats.classified_counts();
+        let letter = |end: Direction| format!("{:?}", end).chars().next().unwrap_or(' ');
+        for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let lines: Vec<String> = tmc_movements()
+                .filter(|&(a, route)| a == approach && self.config.map.serves(a, route))
+                .map(|(approach, route)| {
+                    let count = counts[tmc_column(approach, route)];
+                    let (from, to) = (opposite(approach), turned_direction(approach, route));
+                    let mut line = format!("{}>{} {}", letter(from), letter(to), count.total());
+                    if count.buses > 0 {
+                        line.push_str(&format!(" ({} BUS)", count.buses));
+                    }
+                    line
+                })
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            let width = lines.iter().map(|line| font::text_width(line)).max().unwrap_or(0) + 8;
+            let height = (lines.len() as i32) * COUNT_LINE_HEIGHT + 4;
+            // Out from the corner, away from the roads.
+            let (hx, hy) = heading(approach);
+            let (sx, sy) = (-hx - hy, hx - hy);
+            let (corner_x, corner_y) = view.point(
+                WORLD_WIDTH / 2.0 + sx * COUNT_OFFSET,
+                WORLD_HEIGHT / 2.0 + sy * COUNT_OFFSET
+            );
+            let x = if sx < 0.0 { corner_x - width } else { corner_x };
+            let y = if sy < 0.0 { corner_y - height } else { corner_y };
+            renderer.draw_rect(Rect::new(x, y, width as u32, height as u32), palette.panel)?;
+            for (i, line) in lines.iter().enumerate() {
+                let line_y = y + 4 + (i as i32) * COUNT_LINE_HEIGHT;
+                renderer.draw_text(line, x + 4, line_y, palette.text)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Falling rain streaks, redrawn at random every frame.
+    fn draw_rain(&self, renderer: &mut dyn Renderer) -> Result<(), RenderError> {
+        if self.weather != Weather::Rain {
+            return Ok(());
+        }
+        let mut rng = rand::thread_rng();
+        let streak_color = Color::rgba(180, 200, 255, 150);
+        for _ in 0..RAIN_STREAKS {
+            let x = rng.gen_range(0..WINDOW_WIDTH as i32);
+            let y = rng.gen_range(0..WINDOW_HEIGHT as i32);
+            renderer.draw_rect(Rect::new(x, y, 1, 8), streak_color)?;
+        }
+        Ok(())
+    }
+
+    // Each approach's signal lamps where the map puts them, in the housing
+    // `draw_signal_housings` drew: red, yellow and green, only the one showing lit, and
+    // beyond the green a left arrow lamp on approaches with protected lefts. Lit lamps are
+    // drawn last so they stay visible when the housing is only a few cells across in a
+    // terminal.
+    fn draw_traffic_lights(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View
+    ) -> Result<(), RenderError> {
+        let palette = self.theme.palette();
+        // Flashing lights are on for the first half of every second.
+        let flash_off = self.time.now().as_secs_f32().fract() >= 0.5;
+        let shows = |state: LightState| !(state.is_flashing() && flash_off);
+        for lane in self.lanes.iter().filter(|lane| self.config.map.has_approach(lane.direction)) {
+            let direction = lane.direction;
+            let head = self.agents.light().head_for(direction);
+            let arrow = self.agents.light().left_turns.is_protected(direction);
+            let (_, centers) = signal_head(
+                self.config.map.light_rect(direction),
+                direction,
+                if arrow { 4 } else { 3 }
+            );
+            let steady = match head.ball {
+                LightState::FlashingRed => LightState::Red,
+                LightState::FlashingYellow => LightState::Yellow,
+                state => state,
+            };
+            // Each lamp's parts, the color it shows and whether it is lit.
+            let mut lamps = Vec::new();
+            let aspects = [LightState::Red, LightState::Yellow, LightState::Green];
+            for (&(x, y), aspect) in centers.iter().zip(aspects) {
+                let on = aspect == steady && shows(head.ball);
+                lamps.push((vec![Area::centered(x, y, LAMP_SIZE, LAMP_SIZE)], aspect, on));
+            }
+            if let Some(&(x, y)) = centers.get(3) {
+                let aspect = head.left_arrow.unwrap_or(LightState::Green);
+                let parts = left_arrow_rects(x, y, direction).to_vec();
+                lamps.push((parts, aspect, head.left_arrow.is_some()));
+            }
+            lamps.sort_by_key(|&(_, _, on)| on);
+            for (parts, aspect, on) in lamps {
+                let color = palette.light(aspect);
+                let color = if on { color } else { unlit(color) };
+                for part in parts {
+                    renderer.draw_rect(view.rect(part), color)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Each approach's signal housing, with room for the arrow lamp where lefts are protected.
+    fn draw_signal_housings(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View
+    ) -> Result<(), RenderError> {
+        for lane in self.lanes.iter().filter(|lane| self.config.map.has_approach(lane.direction)) {
+            let direction = lane.direction;
+            let arrow = self.agents.light().left_turns.is_protected(direction);
+            let lamps = if arrow { 4 } else { 3 };
+            let (housing, _) = signal_head(self.config.map.light_rect(direction), direction, lamps);
+            renderer.draw_rect(view.rect(housing), HOUSING_COLOR)?;
+        }
+        Ok(())
+    }
+
+    // Each corner's button, lit once a walk is called, and its pedestrian signal: white
+    // for walk, orange for don't walk, flashing with the seconds left to clear counting
+    // down beside it. Anyone waiting lines up behind the button.
+    fn draw_pedestrian_signals(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View
+    ) -> Result<(), RenderError> {
+        let palette = self.theme.palette();
+        let light = &self.agents.light();
+        let button_color = if light.walk_called() {
+            Color::rgb(255, 160, 0)
+        } else {
+            Color::rgb(120, 120, 120)
+        };
+        let dont_walk = Color::rgb(255, 120, 0);
+        let clearance_left = light.clearance_left(self.time.now());
+        let signal_color = match (light.walk_signal, clearance_left) {
+            (WalkSignal::Walk, _) => Some(Color::rgb(255, 255, 255)),
+            // On for the first half of every second.
+            (WalkSignal::FlashingDontWalk, Some(left)) => {
+                (left.as_secs_f32().fract() >= 0.5).then_some(dont_walk)
+            }
+            _ => Some(dont_walk),
+        };
+        for corner in CORNERS {
+            renderer.draw_rect(view.rect(button_rect(corner)), button_color)?;
+            if let Some(color) = signal_color {
+                renderer.draw_rect(view.rect(signal_rect(corner)), color)?;
+            }
+            if let Some(left) = clearance_left {
+                let (x, y) = countdown_position(corner);
+                let (x, y) = view.point(x, y);
+                let seconds = left.as_secs_f32().ceil();
+                renderer.draw_text(&format!("{}", seconds), x, y, palette.text)?;
+            }
+            let waiting = self.agents.waiting_pedestrians();
+            let waiting = waiting.iter().filter(|p| p.corner == corner);
+            for (index, _) in waiting.enumerate() {
+                renderer.draw_rect(view.rect(waiting_rect(corner, index)), palette.pedestrian)?;
+            }
+        }
+        for pedestrian in self.agents.crossing_pedestrians().iter() {
+            renderer.draw_rect(view.rect(pedestrian_rect(pedestrian)), palette.pedestrian)?;
+        }
+        Ok(())
+    }
+
+    fn draw_roads(&self, renderer: &mut dyn Renderer, view: &View) -> Result<(), RenderError> {
+        let palette = self.theme.palette();
+        let (road_color, bike_lane_color) = (palette.road, palette.bike_lane);
+        let center_x = WORLD_WIDTH / 2.0;
+        let center_y = WORLD_HEIGHT / 2.0;
+        let paved_half = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH;
+        let h_paved = Area::new(0.0, center_y - paved_half, WORLD_WIDTH, paved_half * 2.0);
+        renderer.draw_rect(view.rect(h_paved), bike_lane_color)?;
+        let v_paved = Area::new(center_x - paved_half, 0.0, paved_half * 2.0, WORLD_HEIGHT);
+        renderer.draw_rect(view.rect(v_paved), bike_lane_color)?;
+        let h_road = Area::new(0.0, center_y - ROAD_WIDTH / 2.0, WORLD_WIDTH, ROAD_WIDTH);
+        renderer.draw_rect(view.rect(h_road), road_color)?;
+        let v_road = Area::new(center_x - ROAD_WIDTH / 2.0, 0.0, ROAD_WIDTH, WORLD_HEIGHT);
+        renderer.draw_rect(view.rect(v_road), road_color)?;
+        let (marking_color, center_line_color) = (palette.marking, palette.center_line);
+        let intersection_half_size = paved_half + 1.0;
+        let outside_intersection = |p: f32, center: f32| {
+            !(p > center - intersection_half_size && p < center + intersection_half_size)
+        };
+        // Solid center line separating the two directions of travel, and solid lines
+        // separating the bike lanes from the travel lanes.
+        for (offset, color) in [
+            (0.0, center_line_color),
+            (ROAD_WIDTH / 2.0, marking_color),
+            (-ROAD_WIDTH / 2.0, marking_color),
+        ] {
+            draw_solid_lines(renderer, view, offset, intersection_half_size, color)?;
+        }
+        let bus_stop_color = palette.bus_stop;
+        let map = &self.config.map;
+        let bus_lines = BUS_LINES
+            .into_iter()
+            .filter(|&(direction, route)| map.serves(direction, route));
+        for (direction, _) in bus_lines {
+            let stop = bus_stop_rect(direction);
+            let (x, y, w, h) = (stop.x, stop.y, stop.w, stop.h);
+            for edge in [
+                Area::new(x, y, w, MARKING_WIDTH),
+                Area::new(x, y + h - MARKING_WIDTH, w, MARKING_WIDTH),
+                Area::new(x, y, MARKING_WIDTH, h),
+                Area::new(x + w - MARKING_WIDTH, y, MARKING_WIDTH, h),
+            ] {
+                renderer.draw_rect(view.rect(edge), bus_stop_color)?;
+            }
+            renderer.draw_rect(view.rect(bus_shelter_rect(direction)), bus_stop_color)?;
+        }
+        // Zebra stripes running with the traffic across each crosswalk.
+        for lane in self.lanes.iter().filter(|lane| map.has_road(opposite(lane.direction))) {
+            let crosswalk = crosswalk_rect(lane.direction);
+            let along_x = crosswalk.w > crosswalk.h;
+            let length = if along_x { crosswalk.w } else { crosswalk.h };
+            let stripes = ((length - ZEBRA_SPACING / 2.0) / ZEBRA_SPACING).ceil() as i32;
+            for stripe in 0..stripes {
+                let offset = ZEBRA_SPACING / 4.0 + (stripe as f32) * ZEBRA_SPACING;
+                let stripe = if along_x {
+                    Area::new(crosswalk.x + offset, crosswalk.y, ZEBRA_SPACING / 2.0, crosswalk.h)
+                } else {
+                    Area::new(crosswalk.x, crosswalk.y + offset, crosswalk.w, ZEBRA_SPACING / 2.0)
+                };
+                renderer.draw_rect(view.rect(stripe), marking_color)?;
+            }
+        }
+        for lane in self.lanes.iter().filter(|lane| map.has_approach(lane.direction)) {
+            renderer.draw_rect(view.rect(map.stop_line_rect(lane.direction)), marking_color)?;
+            renderer.draw_rect(view.rect(bike_stop_line_rect(lane.direction)), marking_color)?;
+        }
+        for lane in &self.lanes {
+            let Some(closed) = lane.closed_lane else {
+                continue;
+            };
+            for cone in cone_rects(lane.direction, closed) {
+                renderer.draw_rect(view.rect(cone), CONE_COLOR)?;
+            }
+        }
+        // Dashed dividers between lanes travelling the same way.
+        let dashes = |length: f32| {
+            (0..(length / DASH_SPACING) as i32).map(|dash| (dash as f32) * DASH_SPACING)
+        };
+        for divider in 1..LANES_PER_DIRECTION {
+            for side in [-1.0, 1.0] {
+                let offset = side * (divider as f32) * LANE_WIDTH - MARKING_WIDTH / 2.0;
+                for x in dashes(WORLD_WIDTH).filter(|&x| outside_intersection(x, center_x)) {
+                    let dash = Area::new(x, center_y + offset, DASH_SPACING / 2.0, MARKING_WIDTH);
+                    renderer.draw_rect(view.rect(dash), marking_color)?;
+                }
+                for y in dashes(WORLD_HEIGHT).filter(|&y| outside_intersection(y, center_y)) {
+                    let dash = Area::new(center_x + offset, y, MARKING_WIDTH, DASH_SPACING / 2.0);
+                    renderer.draw_rect(view.rect(dash), marking_color)?;
+                }
+            }
+        }
+        // The dividers turn solid over the no-change zone before each stop line.
+        for lane in self.lanes.iter().filter(|lane| map.has_approach(lane.direction)) {
+            let Some(zone) = lane.no_change_zone else {
+                continue;
+            };
+            for line in solid_divider_rects(lane.direction, zone, lane.geometry.stop_line_setback) {
+                renderer.draw_rect(view.rect(line), marking_color)?;
+            }
+        }
+        // On a divided road the median covers the inner lane up to the turn bay, which a
+        // solid line parts from the through lane.
+        for lane in self.lanes.iter().filter(|lane| map.has_approach(lane.direction)) {
+            let Some(bay) = lane.turn_bay else {
+                continue;
+            };
+            let setback = lane.geometry.stop_line_setback;
+            for (area, color) in median_rects(lane.direction, bay, setback) {
+                renderer.draw_rect(view.rect(area), color)?;
+            }
+            let line = bay_line_rect(lane.direction, bay, setback);
+            renderer.draw_rect(view.rect(line), marking_color)?;
+        }
+        // A T intersection's missing road is open ground up to the through road's curb, and
+        // a one-way road is only paved on the side its traffic uses, edged with a white line.
+        for side in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let unused = match map.road(side) {
+                RoadEnd::TwoWay => continue,
+                RoadEnd::Inbound => side,
+                RoadEnd::Outbound => opposite(side),
+                RoadEnd::Missing => {
+                    let arm = arm_rect(side, -paved_half, paved_half);
+                    renderer.draw_rect(view.rect(arm), palette.ground)?;
+                    continue;
+                }
+            };
+            // Offsets across the arm are measured to the right of the way `side` faces.
+            let half_line = MARKING_WIDTH / 2.0;
+            let (from, to) = if unused == side {
+                (-half_line, paved_half)
+            } else {
+                (-paved_half, half_line)
+            };
+            renderer.draw_rect(view.rect(arm_rect(side, from, to)), palette.ground)?;
+            let edge = if unused == side {
+                (-half_line * 3.0, -half_line)
+            } else {
+                (half_line, half_line * 3.0)
+            };
+            renderer.draw_rect(view.rect(arm_rect(side, edge.0, edge.1)), marking_color)?;
+        }
+
+        Ok(())
+    }
+
+    // The selected vehicle's recent trail, fading with age, its planned path ahead and an
+    // outline around it with its state underneath.
+    fn draw_selection(&self, renderer: &mut dyn Renderer, view: &View) -> Result<(), RenderError> {
+        let Some(vehicle) = self.selected() else {
+            return Ok(());
+        };
+        vehicle.trail.draw(renderer, view, Color::rgb(255, 80, 200))?;
+        let mut from = (vehicle.x, vehicle.y);
+        for &to in vehicle.path.waypoints() {
+            draw_line(renderer, view, from, to, Color::rgba(255, 255, 255, 120))?;
+            from = to;
+        }
+        let rect = view.rect(vehicle_rect(&vehicle));
+        let outline = Color::rgb(255, 255, 255);
+        let (x, y, w, h) = (rect.x - 3, rect.y - 3, rect.w + 6, rect.h + 6);
+        renderer.draw_rect(Rect::new(x, y, w, 2), outline)?;
+        renderer.draw_rect(Rect::new(x, y + (h as i32) - 2, w, 2), outline)?;
+        renderer.draw_rect(Rect::new(x, y, 2, h), outline)?;
+        renderer.draw_rect(Rect::new(x + (w as i32) - 2, y, 2, h), outline)?;
+        renderer.draw_text(vehicle.state.label(), x, y + (h as i32) + 4, outline)
+    }
+}
+
+// A signal head of `lamps` lamps in a row across the road from the three-lamp `housing`
+// the map places, the first nearest the road: the housing grown to hold them all, and
+// each lamp's center.
+fn signal_head(housing: Area, direction: Direction, lamps: usize) -> (Area, Vec<(f32, f32)>) {
+    let (hx, hy) = heading(direction);
+    let (x, y) = (housing.x + housing.w / 2.0, housing.y + housing.h / 2.0);
+    // Away from the road, on whichever side of it the head stands.
+    let side = (x - WORLD_WIDTH / 2.0) * -hy + (y - WORLD_HEIGHT / 2.0) * hx;
+    let (ox, oy) = if side < 0.0 { (hy, -hx) } else { (-hy, hx) };
+    let pitch = LAMP_SIZE + LAMP_MARGIN;
+    // The housing's end nearest the road.
+    let half = housing.w.max(housing.h) / 2.0;
+    let (start_x, start_y) = (x - ox * half, y - oy * half);
+    let length = pitch * (lamps as f32) + LAMP_MARGIN;
+    let (middle_x, middle_y) = (start_x + ox * length / 2.0, start_y + oy * length / 2.0);
+    let grown = if hx == 0.0 {
+        Area::centered(middle_x, middle_y, length, housing.h)
+    } else {
+        Area::centered(middle_x, middle_y, housing.w, length)
+    };
+    let centers = (0..lamps)
+        .map(|index| {
+            let along = LAMP_MARGIN / 2.0 + pitch * (index as f32 + 0.5);
+            (start_x + ox * along, start_y + oy * along)
+        })
+        .collect();
+    (grown, centers)
+}
+
+// An arrow filling a lamp centered on (x, y), pointing the way `direction`'s lefts leave:
+// a shaft and a head of bars across it narrowing to the tip.
+fn left_arrow_rects(x: f32, y: f32, direction: Direction) -> [Area; 4] {
+    let (hx, hy) = heading(turned_direction(direction, Route::Left));
+    // Placed and sized in fractions of the lamp.
+    let bar = |ahead: f32, length: f32, across: f32| {
+        let w = (hx.abs() * length + hy.abs() * across) * LAMP_SIZE;
+        let h = (hy.abs() * length + hx.abs() * across) * LAMP_SIZE;
+        Area::centered(x + hx * ahead * LAMP_SIZE, y + hy * ahead * LAMP_SIZE, w, h)
+    };
+    [bar(-0.15, 0.5, 0.2), bar(0.15, 0.1, 0.6), bar(0.25, 0.1, 0.4), bar(0.35, 0.1, 0.2)]
+}
+
+// A lamp that is off: its color, faint behind the lens.
+fn unlit(color: Color) -> Color {
+    Color::rgb(color.r / 4, color.g / 4, color.b / 4)
+}
+
+// The arm of the road out to the `side` edge of the world, up to the box, between `from`
+// and `to` to the right of its center line when facing `side`.
+fn arm_rect(side: Direction, from: f32, to: f32) -> Area {
+    let center_x = WORLD_WIDTH / 2.0;
+    let center_y = WORLD_HEIGHT / 2.0;
+    let paved_half = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH;
+    let width = to - from;
+    match side {
+        Direction::North => Area::new(center_x + from, 0.0, width, center_y - paved_half),
+        Direction::South => {
+            Area::new(center_x - to, center_y + paved_half, width, center_y - paved_half)
+        }
+        Direction::East => {
+            Area::new(center_x + paved_half, center_y + from, center_x - paved_half, width)
+        }
+        Direction::West => Area::new(0.0, center_y - to, center_x - paved_half, width),
+    }
+}
+
+// Draws a continuous line `offset` from the center of both roads, broken only across the
+// intersection box.
+fn draw_solid_lines(
+    renderer: &mut dyn Renderer,
+    view: &View,
+    offset: f32,
+    intersection_half_size: f32,
+    color: Color
+) -> Result<(), RenderError> {
+    let center_x = WORLD_WIDTH / 2.0;
+    let center_y = WORLD_HEIGHT / 2.0;
+    let across = offset - MARKING_WIDTH / 2.0;
+    for (from, to) in [
+        (0.0, center_x - intersection_half_size),
+        (center_x + intersection_half_size, WORLD_WIDTH),
+    ] {
+        let line = Area::new(from, center_y + across, to - from, MARKING_WIDTH);
+        renderer.draw_rect(view.rect(line), color)?;
+    }
+    for (from, to) in [
+        (0.0, center_y - intersection_half_size),
+        (center_y + intersection_half_size, WORLD_HEIGHT),
+    ] {
+        let line = Area::new(center_x + across, from, MARKING_WIDTH, to - from);
+        renderer.draw_rect(view.rect(line), color)?;
+    }
+    Ok(())
+}
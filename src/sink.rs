@@ -0,0 +1,50 @@
+use serde::Deserialize;
+
+use crate::vehicle::Direction;
+
+pub const SINK_COUNT: usize = 4;
+// Sinks further inside the world than this would sit on the intersection's exits.
+pub const MAX_SINK_INSET: f32 = 25.0;
+
+// Every road end is a node where trips start and, as a sink, where they end. Nodes are
+// numbered by the side of the world they are on.
+pub fn node_id(end: Direction) -> usize {
+    match end {
+        Direction::North => 0,
+        Direction::South => 1,
+        Direction::East => 2,
+        Direction::West => 3,
+    }
+}
+
+pub fn node_name(id: usize) -> &'static str {
+    ["north", "south", "east", "west"][id]
+}
+
+// Where trips end on each road, in meters past the edge of the world measured to the
+// vehicle's center; negative values end them inside it.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sinks {
+    pub north: f32,
+    pub south: f32,
+    pub east: f32,
+    pub west: f32,
+}
+
+impl Default for Sinks {
+    fn default() -> Self {
+        Self { north: 5.0, south: 5.0, east: 5.0, west: 5.0 }
+    }
+}
+
+impl Sinks {
+    pub fn offset(&self, end: Direction) -> f32 {
+        match end {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East => self.east,
+            Direction::West => self.west,
+        }
+    }
+}
@@ -0,0 +1,746 @@
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::time::Duration;
+
+use crate::emissions::co2_kg;
+use crate::error::SimError;
+use crate::noise::equivalent_level;
+use crate::pedestrian::CloseCall;
+use crate::saturation::saturation_flow;
+use crate::simulation::SimEvent;
+use crate::sink::SINK_COUNT;
+use crate::units::mps_to_kmh;
+use crate::vehicle::{ Direction, Route, VehicleId, VehicleKind };
+
+// A red-light camera record; `time` is measured from the start of the simulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub vehicle_id: VehicleId,
+    pub approach: Direction,
+    pub time: Duration,
+}
+
+// A vehicle crossing an approach's speed measurement line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedSample {
+    pub approach: Direction,
+    pub speed: f32,
+    pub limit: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedSummary {
+    pub count: usize,
+    pub mean: f32,
+    pub percentile_85: f32,
+    // Fraction of vehicles measured above the limit.
+    pub speeding_share: f32,
+}
+
+// A vehicle's time between the travel time entry and exit lines of its movement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TravelTime {
+    pub approach: Direction,
+    pub route: Route,
+    pub time: Duration,
+}
+
+// A vehicle entering the intersection to make its movement, for the turning movement count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementCount {
+    pub approach: Direction,
+    pub route: Route,
+    pub kind: VehicleKind,
+    pub time: Duration,
+}
+
+// A vehicle's way through its approach, for calibrating arrivals against real counts: when
+// it got onto the road, reached the stop line or the queue for it, and left the line for the
+// intersection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arrival {
+    pub vehicle_id: VehicleId,
+    pub approach: Direction,
+    pub route: Route,
+    pub kind: VehicleKind,
+    pub entered_road: Duration,
+    pub arrived: Duration,
+    pub departed: Duration,
+}
+
+// Vehicles counted on one movement, by class.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClassifiedCount {
+    pub cars: u32,
+    pub buses: u32,
+}
+
+impl ClassifiedCount {
+    pub fn total(&self) -> u32 {
+        self.cars + self.buses
+    }
+}
+
+// A queued vehicle's headway behind the one ahead in its lane as the queue discharged on
+// green.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueHeadway {
+    pub approach: Direction,
+    pub route: Route,
+    pub headway: Duration,
+}
+
+// How often a completed vehicle came to a full stop and how long it stood still.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopSample {
+    pub approach: Direction,
+    pub kind: VehicleKind,
+    pub stops: u32,
+    pub idle: Duration,
+}
+
+// Equivalent sound levels in dB(A) at the corner receivers, in `CORNERS` order, over the
+// second ending at `time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseSample {
+    pub time: Duration,
+    pub levels: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopSummary {
+    pub count: usize,
+    pub mean_stops: f32,
+    pub max_stops: u32,
+    // Fraction of vehicles that never stopped.
+    pub no_stop_share: f32,
+    pub mean_idle: Duration,
+    pub median_idle: Duration,
+    pub percentile_95_idle: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TravelTimeSummary {
+    pub count: usize,
+    pub mean: Duration,
+    pub median: Duration,
+    pub percentile_95: Duration,
+}
+
+// Saturation flow in vehicles per hour of green per lane, from the mean headway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaturationFlowSummary {
+    pub count: usize,
+    pub mean_headway: Duration,
+    pub saturation_flow: f32,
+}
+
+// Every movement through the intersection as (approach, route).
+pub fn movements() -> impl Iterator<Item = (Direction, Route)> {
+    [Direction::North, Direction::South, Direction::East, Direction::West]
+        .into_iter()
+        .flat_map(|approach| {
+            [Route::Straight, Route::Left, Route::Right].map(|route| (approach, route))
+        })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub vehicles_completed: u32,
+    pub buses_completed: u32,
+    // Time spent stopped in traffic; bus dwell time at stops is not counted.
+    pub total_vehicle_delay: Duration,
+    pub total_bus_delay: Duration,
+    // Litres of fuel burned and kilograms of CO2 given off by completed vehicles of every
+    // kind, by the VT-Micro model.
+    pub fuel_used: f32,
+    pub co2_emitted: f32,
+    // Stops and idle time of each completed vehicle of every kind.
+    pub stop_samples: Vec<StopSample>,
+    pub noise_samples: Vec<NoiseSample>,
+    pub violations: Vec<Violation>,
+    pub speed_samples: Vec<SpeedSample>,
+    pub travel_times: Vec<TravelTime>,
+    pub queue_headways: Vec<QueueHeadway>,
+    pub movement_counts: Vec<MovementCount>,
+    pub arrivals: Vec<Arrival>,
+    // Completed trips of every vehicle kind, by origin node (row) and destination sink.
+    pub od_matrix: [[u32; SINK_COUNT]; SINK_COUNT],
+    pub gridlocks: u32,
+    // Arrivals that had to queue upstream of a full approach, and how many are still there.
+    pub arrivals_held_upstream: u32,
+    pub unserved_demand: u32,
+    // Greens forced by the starvation watchdog.
+    pub starvations: u32,
+    // Pedestrians who got the walk, and how long they waited after pressing the button.
+    pub pedestrians_served: u32,
+    pub total_pedestrian_wait: Duration,
+    // Jaywalkers who made it across, those a vehicle bore down on and those it came within
+    // half a meter of, which are counted among the conflicts too, and the vehicles that had
+    // to brake for one.
+    pub jaywalkers: u32,
+    pub jaywalker_conflicts: u32,
+    pub jaywalker_near_misses: u32,
+    pub emergency_brakes: u32,
+    // Trains that brought the crossing gates down.
+    pub trains: u32,
+    // Completed cars that met a lane closure on their approach, and their delay, which
+    // is also counted in the totals above.
+    pub work_zone_vehicles: u32,
+    pub total_work_zone_delay: Duration,
+    pub collisions: u32,
+    // Most vehicles waiting on one approach at once, including those held upstream.
+    pub max_queue: usize,
+    // Vehicles left as wrecks after collisions and incidents.
+    pub wrecks: u32,
+    // Times a full turn bay held a left-turner in the through lane, and for how long in all.
+    pub bay_overflows: u32,
+    pub bay_overflow_time: Duration,
+    // When each approach's current overflow started, in north, south, east, west order.
+    pub bay_overflow_since: [Option<Duration>; 4],
+    // Drivers kept from changing lanes across the solid lines of a no-change zone.
+    pub lane_change_violations: u32,
+    // Arrivals that picked another route because theirs led onto a saturated exit.
+    pub route_reassignments: u32,
+    // Vehicles left in the intersection at the end of their road's yellow that cleared it
+    // during the all-red, and those still in it when the other road's green began.
+    pub clearance_conflicts_prevented: u32,
+    pub clearance_conflicts: u32,
+    // Changes the user made to the lights by hand.
+    pub manual_overrides: u32,
+    // Completed vehicles of every kind and their total control delay, by approach in
+    // north, south, east, west order.
+    pub approach_vehicles: [u32; 4],
+    pub approach_control_delay: [Duration; 4],
+    // Time vehicles of every kind spent in each state, completed or not, by
+    // `VehicleState::index`.
+    pub state_time: [Duration; 5],
+}
+
+impl Stats {
+    pub fn record(&mut self, event: &SimEvent, time: Duration) {
+        match *event {
+            SimEvent::VehicleExited {
+                kind,
+                approach,
+                delay,
+                control_delay,
+                fuel,
+                stops,
+                idle,
+                origin,
+                destination,
+                work_zone,
+                ..
+            } => {
+                match kind {
+                    VehicleKind::Car => {
+                        self.vehicles_completed += 1;
+                        self.total_vehicle_delay += delay;
+                        if work_zone {
+                            self.work_zone_vehicles += 1;
+                            self.total_work_zone_delay += delay;
+                        }
+                    }
+                    VehicleKind::Bus => {
+                        self.buses_completed += 1;
+                        self.total_bus_delay += delay;
+                    }
+                }
+                self.stop_samples.push(StopSample { approach, kind, stops, idle });
+                self.fuel_used += fuel;
+                self.co2_emitted += co2_kg(kind, fuel);
+                self.od_matrix[origin][destination] += 1;
+                self.approach_vehicles[approach_index(approach)] += 1;
+                self.approach_control_delay[approach_index(approach)] += control_delay;
+            }
+            SimEvent::RedLightViolation { vehicle_id, approach } => {
+                self.violations.push(Violation { vehicle_id, approach, time });
+            }
+            SimEvent::SpeedMeasured { approach, speed, limit } => {
+                self.speed_samples.push(SpeedSample { approach, speed, limit });
+            }
+            SimEvent::NoiseMeasured { levels } => {
+                self.noise_samples.push(NoiseSample { time, levels });
+            }
+            SimEvent::TravelTimeMeasured { approach, route, time } => {
+                self.travel_times.push(TravelTime { approach, route, time });
+            }
+            SimEvent::QueueHeadway { approach, route, headway } => {
+                self.queue_headways.push(QueueHeadway { approach, route, headway });
+            }
+            SimEvent::VehicleEnteredIntersection {
+                vehicle_id,
+                approach,
+                route,
+                kind,
+                entered_road,
+                arrived,
+            } => {
+                self.movement_counts.push(MovementCount { approach, route, kind, time });
+                self.arrivals.push(Arrival {
+                    vehicle_id,
+                    approach,
+                    route,
+                    kind,
+                    entered_road,
+                    arrived,
+                    departed: time,
+                });
+            }
+            SimEvent::ArrivalQueued { .. } => {
+                self.arrivals_held_upstream += 1;
+                self.unserved_demand += 1;
+            }
+            SimEvent::ArrivalReleased { .. } => {
+                self.unserved_demand -= 1;
+            }
+            SimEvent::Starvation { .. } => {
+                self.starvations += 1;
+            }
+            SimEvent::Gridlock { .. } => {
+                self.gridlocks += 1;
+            }
+            SimEvent::PedestrianServed { wait, .. } => {
+                self.pedestrians_served += 1;
+                self.total_pedestrian_wait += wait;
+            }
+            SimEvent::Collision => {
+                self.collisions += 1;
+            }
+            SimEvent::VehicleWrecked { .. } => {
+                self.wrecks += 1;
+            }
+            SimEvent::CrossingClosed => {
+                self.trains += 1;
+            }
+            SimEvent::TurnBayOverflowed { approach } => {
+                self.bay_overflows += 1;
+                self.bay_overflow_since[approach_index(approach)] = Some(time);
+            }
+            SimEvent::TurnBayCleared { approach } => {
+                if let Some(since) = self.bay_overflow_since[approach_index(approach)].take() {
+                    self.bay_overflow_time += time - since;
+                }
+            }
+            SimEvent::LaneChangeViolation { .. } => {
+                self.lane_change_violations += 1;
+            }
+            SimEvent::RouteReassigned { .. } => {
+                self.route_reassignments += 1;
+            }
+            SimEvent::JaywalkerCrossed { close_call, .. } => {
+                self.jaywalkers += 1;
+                self.jaywalker_conflicts += close_call.is_some() as u32;
+                self.jaywalker_near_misses += (close_call == Some(CloseCall::NearMiss)) as u32;
+            }
+            SimEvent::EmergencyBrake { .. } => {
+                self.emergency_brakes += 1;
+            }
+            SimEvent::ManualOverride { .. } => {
+                self.manual_overrides += 1;
+            }
+            SimEvent::ClearanceChecked { cleared, still_inside } => {
+                self.clearance_conflicts_prevented += cleared;
+                self.clearance_conflicts += still_inside;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn speed_summary(&self, approaches: &[Direction]) -> Option<SpeedSummary> {
+        let mut speeds: Vec<f32> = Vec::new();
+        let mut speeding = 0;
+        for sample in self.speed_samples.iter().filter(|s| approaches.contains(&s.approach)) {
+            speeds.push(sample.speed);
+            if sample.speed > sample.limit {
+                speeding += 1;
+            }
+        }
+        if speeds.is_empty() {
+            return None;
+        }
+        speeds.sort_by(f32::total_cmp);
+        let count = speeds.len();
+        Some(SpeedSummary {
+            count,
+            mean: speeds.iter().sum::<f32>() / (count as f32),
+            percentile_85: speeds[nearest_rank(count, 0.85)],
+            speeding_share: (speeding as f32) / (count as f32),
+        })
+    }
+
+    // One CSV row per measured vehicle, in km/h, for comparing runs with different limits.
+    pub fn export_speeds(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,limit_kmh,speed_kmh").map_err(output)?;
+        for sample in &self.speed_samples {
+            let (limit, speed) = (mps_to_kmh(sample.limit), mps_to_kmh(sample.speed));
+            writeln!(out, "{:?},{:.1},{:.1}", sample.approach, limit, speed).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    pub fn stop_summary(&self) -> Option<StopSummary> {
+        if self.stop_samples.is_empty() {
+            return None;
+        }
+        let count = self.stop_samples.len();
+        let total_stops: u32 = self.stop_samples.iter().map(|sample| sample.stops).sum();
+        let stopped = self.stop_samples.iter().filter(|sample| sample.stops > 0).count();
+        let mut idle: Vec<Duration> = self.stop_samples.iter().map(|sample| sample.idle).collect();
+        idle.sort();
+        Some(StopSummary {
+            count,
+            mean_stops: (total_stops as f32) / (count as f32),
+            max_stops: self.stop_samples.iter().map(|sample| sample.stops).max().unwrap_or(0),
+            no_stop_share: ((count - stopped) as f32) / (count as f32),
+            mean_idle: idle.iter().sum::<Duration>() / (count as u32),
+            median_idle: idle[nearest_rank(count, 0.5)],
+            percentile_95_idle: idle[nearest_rank(count, 0.95)],
+        })
+    }
+
+    // One CSV row per completed vehicle with its stops and idle time in seconds, for
+    // comparing the distributions of runs whose delays are alike.
+    pub fn export_stops(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,kind,stops,idle").map_err(output)?;
+        for sample in &self.stop_samples {
+            writeln!(
+                out,
+                "{:?},{:?},{},{:.2}",
+                sample.approach,
+                sample.kind,
+                sample.stops,
+                sample.idle.as_secs_f32()
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    // One CSV row per vehicle that reached the intersection, approach by approach in the order
+    // they arrived at the stop line, with times in seconds from the start of the run. Arrivals
+    // held upstream of a full approach got onto the road once there was room.
+    pub fn export_arrivals(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,vehicle,kind,route,entered_road,arrived,departed")
+            .map_err(output)?;
+        let mut arrivals: Vec<&Arrival> = self.arrivals.iter().collect();
+        arrivals.sort_by_key(|a| (approach_index(a.approach), a.arrived, a.vehicle_id));
+        for arrival in arrivals {
+            writeln!(
+                out,
+                "{:?},{},{:?},{:?},{:.2},{:.2},{:.2}",
+                arrival.approach,
+                arrival.vehicle_id.0,
+                arrival.kind,
+                arrival.route,
+                arrival.entered_road.as_secs_f32(),
+                arrival.arrived.as_secs_f32(),
+                arrival.departed.as_secs_f32()
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    // The equivalent level at each corner receiver over the whole run; None before the
+    // first second is out.
+    pub fn noise_levels(&self) -> Option<[f32; 4]> {
+        let level = |i: usize| equivalent_level(self.noise_samples.iter().map(|s| s.levels[i]));
+        Some([level(0)?, level(1)?, level(2)?, level(3)?])
+    }
+
+    // One CSV row per second with the level at each corner receiver in dB(A).
+    pub fn export_noise(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "time,north_east,north_west,south_east,south_west").map_err(output)?;
+        for sample in &self.noise_samples {
+            let [ne, nw, se, sw] = sample.levels;
+            writeln!(
+                out,
+                "{:.2},{:.1},{:.1},{:.1},{:.1}",
+                sample.time.as_secs_f32(),
+                ne,
+                nw,
+                se,
+                sw
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    pub fn travel_time_summary(
+        &self,
+        approach: Direction,
+        route: Route
+    ) -> Option<TravelTimeSummary> {
+        let mut times: Vec<Duration> = self.travel_times
+            .iter()
+            .filter(|t| t.approach == approach && t.route == route)
+            .map(|t| t.time)
+            .collect();
+        if times.is_empty() {
+            return None;
+        }
+        times.sort();
+        let count = times.len();
+        Some(TravelTimeSummary {
+            count,
+            mean: times.iter().sum::<Duration>() / (count as u32),
+            median: times[nearest_rank(count, 0.5)],
+            percentile_95: times[nearest_rank(count, 0.95)],
+        })
+    }
+
+    // One CSV row per movement with measured travel times, in seconds.
+    pub fn export_travel_times(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,route,count,mean,median,p95").map_err(output)?;
+        for (approach, route) in movements() {
+            let Some(summary) = self.travel_time_summary(approach, route) else {
+                continue;
+            };
+            writeln!(
+                out,
+                "{:?},{:?},{},{:.3},{:.3},{:.3}",
+                approach,
+                route,
+                summary.count,
+                summary.mean.as_secs_f32(),
+                summary.median.as_secs_f32(),
+                summary.percentile_95.as_secs_f32()
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    pub fn saturation_flow_summary(
+        &self,
+        approach: Direction,
+        route: Route
+    ) -> Option<SaturationFlowSummary> {
+        let headways: Vec<Duration> = self.queue_headways
+            .iter()
+            .filter(|h| h.approach == approach && h.route == route)
+            .map(|h| h.headway)
+            .collect();
+        if headways.is_empty() {
+            return None;
+        }
+        let count = headways.len();
+        let mean_headway = headways.iter().sum::<Duration>() / (count as u32);
+        Some(SaturationFlowSummary {
+            count,
+            mean_headway,
+            saturation_flow: saturation_flow(mean_headway),
+        })
+    }
+
+    // One CSV row per movement with measured headways, for calibrating signal timing to
+    // the simulated vehicles.
+    pub fn export_saturation_flows(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,route,headways,mean_headway,saturation_flow")
+            .map_err(output)?;
+        for (approach, route) in movements() {
+            let Some(summary) = self.saturation_flow_summary(approach, route) else {
+                continue;
+            };
+            writeln!(
+                out,
+                "{:?},{:?},{},{:.3},{:.0}",
+                approach,
+                route,
+                summary.count,
+                summary.mean_headway.as_secs_f32(),
+                summary.saturation_flow
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+
+    // Vehicles making each movement over the whole run, by `tmc_movements` order.
+    pub fn turning_totals(&self) -> [u32; 12] {
+        let mut totals = [0; 12];
+        for count in &self.movement_counts {
+            totals[tmc_column(count.approach, count.route)] += 1;
+        }
+        totals
+    }
+
+    // Vehicles making each movement so far by class, by `tmc_movements` order.
+    pub fn classified_counts(&self) -> [ClassifiedCount; 12] {
+        let mut counts = [ClassifiedCount::default(); 12];
+        for count in &self.movement_counts {
+            let column = &mut counts[tmc_column(count.approach, count.route)];
+            match count.kind {
+                VehicleKind::Car => column.cars += 1,
+                VehicleKind::Bus => column.buses += 1,
+            }
+        }
+        counts
+    }
+
+    // The turning movement count table: a CSV row of counts per movement for each
+    // `interval` from the start of the run to the last vehicle counted, then the totals.
+    pub fn export_turning_counts(&self, path: &Path, interval: Duration) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        let mut header = vec!["start".to_string(), "end".to_string()];
+        for (approach, route) in tmc_movements() {
+            let route = match route {
+                Route::Left => "left",
+                Route::Straight => "thru",
+                Route::Right => "right",
+            };
+            header.push(format!("{}bound_{}", format!("{:?}", approach).to_lowercase(), route));
+        }
+        header.push("total".to_string());
+        writeln!(out, "{}", header.join(",")).map_err(output)?;
+        let row_of = |time: Duration| (time.as_secs_f64() / interval.as_secs_f64()) as usize;
+        let last = self.movement_counts.iter().map(|count| row_of(count.time)).max();
+        let mut rows = vec![[0u32; 12]; last.map_or(0, |last| last + 1)];
+        for count in &self.movement_counts {
+            rows[row_of(count.time)][tmc_column(count.approach, count.route)] += 1;
+        }
+        for (i, row) in rows.iter().enumerate() {
+            let start = interval * (i as u32);
+            let (start, end) = (clock_time(start), clock_time(start + interval));
+            write_counts(&mut out, &format!("{},{}", start, end), row).map_err(output)?;
+        }
+        write_counts(&mut out, "total,", &self.turning_totals()).map_err(output)?;
+        out.flush().map_err(output)
+    }
+
+    pub fn average_vehicle_delay(&self) -> Duration {
+        average(self.total_vehicle_delay, self.vehicles_completed)
+    }
+
+    pub fn average_bus_delay(&self) -> Duration {
+        average(self.total_bus_delay, self.buses_completed)
+    }
+
+    pub fn average_work_zone_delay(&self) -> Duration {
+        average(self.total_work_zone_delay, self.work_zone_vehicles)
+    }
+
+    // Average delay of the cars that never met a lane closure, to compare against.
+    pub fn average_open_road_delay(&self) -> Duration {
+        average(
+            self.total_vehicle_delay - self.total_work_zone_delay,
+            self.vehicles_completed - self.work_zone_vehicles
+        )
+    }
+
+    // Fuel and CO2 per completed vehicle of every kind.
+    pub fn fuel_per_vehicle(&self) -> f32 {
+        self.fuel_used / ((self.vehicles_completed + self.buses_completed).max(1) as f32)
+    }
+
+    pub fn co2_per_vehicle(&self) -> f32 {
+        self.co2_emitted / ((self.vehicles_completed + self.buses_completed).max(1) as f32)
+    }
+
+    pub fn average_pedestrian_wait(&self) -> Duration {
+        average(self.total_pedestrian_wait, self.pedestrians_served)
+    }
+
+    // Mean control delay of the vehicles completed from `approach`, if there were any.
+    pub fn average_control_delay(&self, approach: Direction) -> Option<Duration> {
+        let i = approach_index(approach);
+        let count = self.approach_vehicles[i];
+        (count > 0).then(|| average(self.approach_control_delay[i], count))
+    }
+
+    // One CSV row per approach with completed vehicles: their mean control delay in
+    // seconds and its level of service.
+    pub fn export_level_of_service(&self, path: &Path) -> Result<(), SimError> {
+        let output = |source| SimError::Output { path: path.to_path_buf(), source };
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        writeln!(out, "approach,vehicles,control_delay,los").map_err(output)?;
+        for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+            let Some(delay) = self.average_control_delay(approach) else {
+                continue;
+            };
+            writeln!(
+                out,
+                "{:?},{},{:.3},{}",
+                approach,
+                self.approach_vehicles[approach_index(approach)],
+                delay.as_secs_f32(),
+                level_of_service(delay)
+            ).map_err(output)?;
+        }
+        out.flush().map_err(output)
+    }
+}
+
+// The twelve movements in the column order of a turning movement count table: each
+// approach's left, through and right in turn.
+pub fn tmc_movements() -> impl Iterator<Item = (Direction, Route)> {
+    [Direction::North, Direction::South, Direction::East, Direction::West]
+        .into_iter()
+        .flat_map(|approach| {
+            [Route::Left, Route::Straight, Route::Right].map(|route| (approach, route))
+        })
+}
+
+pub fn tmc_column(approach: Direction, route: Route) -> usize {
+    let route = match route {
+        Route::Left => 0,
+        Route::Straight => 1,
+        Route::Right => 2,
+    };
+    approach_index(approach) * 3 + route
+}
+
+fn approach_index(approach: Direction) -> usize {
+    match approach {
+        Direction::North => 0,
+        Direction::South => 1,
+        Direction::East => 2,
+        Direction::West => 3,
+    }
+}
+
+// HCM level of service at a signalized intersection for a mean control delay per vehicle.
+pub fn level_of_service(delay: Duration) -> char {
+    match delay.as_secs_f32() {
+        d if d <= 10.0 => 'A',
+        d if d <= 20.0 => 'B',
+        d if d <= 35.0 => 'C',
+        d if d <= 55.0 => 'D',
+        d if d <= 80.0 => 'E',
+        _ => 'F',
+    }
+}
+
+fn write_counts(out: &mut impl Write, label: &str, counts: &[u32; 12]) -> std::io::Result<()> {
+    let total: u32 = counts.iter().sum();
+    let counts: Vec<String> = counts.iter().map(|count| count.to_string()).collect();
+    writeln!(out, "{},{},{}", label, counts.join(","), total)
+}
+
+// Simulated time since the start of the run as hours, minutes and seconds.
+fn clock_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+// Index of the `fraction` percentile in `count` sorted values, by the nearest-rank method.
+fn nearest_rank(count: usize, fraction: f32) -> usize {
+    let rank = ((count as f32) * fraction).ceil() as usize;
+    rank.clamp(1, count) - 1
+}
+
+fn average(total: Duration, count: u32) -> Duration {
+    if count == 0 { Duration::ZERO } else { total / count }
+}
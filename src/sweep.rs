@@ -0,0 +1,265 @@
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::time::Duration;
+use rayon::prelude::*;
+
+use crate::ab_test::PairedDifference;
+use crate::config::Config;
+use crate::error::{ ConfigError, SimError };
+use crate::plugin::{ PluginConfig, Registry };
+use crate::simulation::TrafficSimulation;
+use crate::stats::Stats;
+
+// A grid of light timings and demand levels, each run once per seed on top of a base
+// config for `ticks` ticks. Runs are independent and spread over all cores.
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    pub cycles_secs: Vec<f32>,
+    pub north_south_splits: Vec<f32>,
+    pub vehicles_per_minute: Vec<f32>,
+    pub seeds: u64,
+    pub ticks: u64,
+    // Light controller to run against the base config's on the same seeds, for a paired
+    // comparison at every point.
+    pub compare: Option<String>,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            cycles_secs: vec![12.0, 16.0, 24.0],
+            north_south_splits: vec![0.4, 0.5, 0.6],
+            vehicles_per_minute: vec![20.0, 40.0, 60.0],
+            seeds: 3,
+            ticks: 60_000,
+            compare: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SweepPoint {
+    pub cycle_secs: f32,
+    pub north_south_split: f32,
+    pub vehicles_per_minute: f32,
+}
+
+// Averages over a point's seeds.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepResult {
+    pub point: SweepPoint,
+    pub average_delay: Duration,
+    pub vehicles_per_hour: f32,
+    // Litres of fuel and kilograms of CO2 per completed vehicle.
+    pub fuel_per_vehicle: f32,
+    pub co2_per_vehicle: f32,
+    pub comparison: Option<SweepComparison>,
+}
+
+// How the compared light controller did at a point against the base config's.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepComparison {
+    pub average_delay: Duration,
+    pub vehicles_per_hour: f32,
+    pub fuel_per_vehicle: f32,
+    pub co2_per_vehicle: f32,
+    // Of each seed's average vehicle delay, in seconds.
+    pub delay_difference: PairedDifference,
+}
+
+impl Sweep {
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = Vec::new();
+        for &cycle_secs in &self.cycles_secs {
+            for &north_south_split in &self.north_south_splits {
+                for &vehicles_per_minute in &self.vehicles_per_minute {
+                    points.push(SweepPoint { cycle_secs, north_south_split, vehicles_per_minute });
+                }
+            }
+        }
+        points
+    }
+
+    // The config for one run; seeds count up from the base config's, or from zero.
+    pub fn config_for(
+        &self,
+        base: &Config,
+        point: SweepPoint,
+        run: u64
+    ) -> Result<Config, ConfigError> {
+        let mut config = base.clone();
+        config.seed = Some(base.seed.unwrap_or(0) + run);
+        config.demand.vehicles_per_minute = point.vehicles_per_minute;
+        config.lights.north_south_split = point.north_south_split;
+        config.lights.set_cycle(point.cycle_secs);
+        config.lights
+            .validate()
+            .map_err(|e| ConfigError::Invalid(format!("cycle of {}s: {}", point.cycle_secs, e)))?;
+        if point.vehicles_per_minute < 0.0 {
+            return Err(ConfigError::Invalid("demand must not be negative".to_string()));
+        }
+        Ok(config)
+    }
+
+    pub fn run_point(&self, base: &Config, point: SweepPoint) -> Result<SweepResult, ConfigError> {
+        let runs = (0..self.seeds)
+            .into_par_iter()
+            .map(|run| {
+                let config = self.config_for(base, point, run)?;
+                let stats = self.simulate(&config)?;
+                // The compared run differs only in its light controller.
+                let compared = self.compare
+                    .as_ref()
+                    .map(|controller| {
+                        let mut config = config.clone();
+                        config.plugins.light_controller = controller.clone();
+                        self.simulate(&config)
+                    })
+                    .transpose()?;
+                Ok((stats, compared))
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+        let base_runs: Vec<&Stats> = runs.iter().map(|(stats, _)| stats).collect();
+        let tick = base.clock.tick();
+        let (average_delay, vehicles_per_hour) = self.totals(&base_runs, tick);
+        let (fuel_per_vehicle, co2_per_vehicle) = emissions(&base_runs);
+        let compared_runs: Vec<&Stats> =
+            runs.iter().filter_map(|(_, compared)| compared.as_ref()).collect();
+        let comparison = if compared_runs.is_empty() {
+            None
+        } else {
+            let (compared_delay, compared_per_hour) = self.totals(&compared_runs, tick);
+            let (compared_fuel, compared_co2) = emissions(&compared_runs);
+            let pairs: Vec<(f32, f32)> = base_runs
+                .iter()
+                .zip(&compared_runs)
+                .map(|(base, compared)| {
+                    (
+                        base.average_vehicle_delay().as_secs_f32(),
+                        compared.average_vehicle_delay().as_secs_f32(),
+                    )
+                })
+                .collect();
+            PairedDifference::from_pairs(&pairs).map(|delay_difference| SweepComparison {
+                average_delay: compared_delay,
+                vehicles_per_hour: compared_per_hour,
+                fuel_per_vehicle: compared_fuel,
+                co2_per_vehicle: compared_co2,
+                delay_difference,
+            })
+        };
+        Ok(SweepResult {
+            point,
+            average_delay,
+            vehicles_per_hour,
+            fuel_per_vehicle,
+            co2_per_vehicle,
+            comparison,
+        })
+    }
+
+    fn simulate(&self, config: &Config) -> Result<Stats, ConfigError> {
+        let mut simulation = TrafficSimulation::with_config(config)?;
+        for _ in 0..self.ticks {
+            simulation.update();
+            simulation.drain_events();
+        }
+        Ok(simulation.stats)
+    }
+
+    // Average delay per vehicle and vehicles served per hour over all of `runs`, of `tick`
+    // each.
+    fn totals(&self, runs: &[&Stats], tick: Duration) -> (Duration, f32) {
+        let total_delay: Duration = runs.iter().map(|stats| stats.total_vehicle_delay).sum();
+        let completed: u32 = runs.iter().map(|stats| stats.vehicles_completed).sum();
+        let hours = tick.mul_f64((self.seeds * self.ticks) as f64).as_secs_f32() / 3600.0;
+        let average_delay = if completed == 0 { Duration::ZERO } else { total_delay / completed };
+        (average_delay, (completed as f32) / hours)
+    }
+
+    pub fn run(&self, base: &Config) -> Result<Vec<SweepResult>, ConfigError> {
+        if self.seeds == 0 || self.ticks == 0 {
+            let message = "a sweep needs at least one seed and one tick".to_string();
+            return Err(ConfigError::Invalid(message));
+        }
+        if let Some(controller) = &self.compare {
+            let plugins =
+                PluginConfig { light_controller: controller.clone(), ..base.plugins.clone() };
+            Registry::default().check(&plugins)?;
+        }
+        self.points()
+            .into_par_iter()
+            .map(|point| self.run_point(base, point))
+            .collect()
+    }
+}
+
+// Fuel and CO2 per completed vehicle over all of `runs`.
+fn emissions(runs: &[&Stats]) -> (f32, f32) {
+    let fuel: f32 = runs.iter().map(|stats| stats.fuel_used).sum();
+    let co2: f32 = runs.iter().map(|stats| stats.co2_emitted).sum();
+    let completed: u32 = runs
+        .iter()
+        .map(|stats| stats.vehicles_completed + stats.buses_completed)
+        .sum();
+    let completed = completed.max(1) as f32;
+    (fuel / completed, co2 / completed)
+}
+
+// One CSV row per configuration, delay in seconds, fuel per vehicle in litres and CO2 per
+// vehicle in kilograms.
+pub fn write_csv(results: &[SweepResult], path: &Path) -> Result<(), SimError> {
+    let output = |source| SimError::Output { path: path.to_path_buf(), source };
+    let mut out = BufWriter::new(File::create(path).map_err(output)?);
+    write!(
+        out,
+        "cycle_secs,north_south_split,vehicles_per_minute,average_delay,vehicles_per_hour,\
+         fuel_per_vehicle,co2_per_vehicle"
+    ).map_err(output)?;
+    // A compared controller adds its own columns, and the paired delay difference with its
+    // confidence interval, blank for a single seed.
+    let compared = results.iter().any(|result| result.comparison.is_some());
+    if compared {
+        write!(
+            out,
+            ",compared_average_delay,compared_vehicles_per_hour,compared_fuel_per_vehicle,\
+             compared_co2_per_vehicle,delay_difference,delay_difference_low,\
+             delay_difference_high"
+        ).map_err(output)?;
+    }
+    writeln!(out).map_err(output)?;
+    for result in results {
+        write!(
+            out,
+            "{},{},{},{:.3},{:.1},{:.4},{:.4}",
+            result.point.cycle_secs,
+            result.point.north_south_split,
+            result.point.vehicles_per_minute,
+            result.average_delay.as_secs_f32(),
+            result.vehicles_per_hour,
+            result.fuel_per_vehicle,
+            result.co2_per_vehicle
+        ).map_err(output)?;
+        if let Some(comparison) = &result.comparison {
+            let difference = comparison.delay_difference;
+            let (low, high) = match difference.confidence_interval {
+                Some((low, high)) => (format!("{:.3}", low), format!("{:.3}", high)),
+                None => (String::new(), String::new()),
+            };
+            write!(
+                out,
+                ",{:.3},{:.1},{:.4},{:.4},{:.3},{},{}",
+                comparison.average_delay.as_secs_f32(),
+                comparison.vehicles_per_hour,
+                comparison.fuel_per_vehicle,
+                comparison.co2_per_vehicle,
+                difference.mean,
+                low,
+                high
+            ).map_err(output)?;
+        }
+        writeln!(out).map_err(output)?;
+    }
+    out.flush().map_err(output)
+}
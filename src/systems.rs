@@ -0,0 +1,1059 @@
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use std::ops::Deref;
+use std::time::Duration;
+
+use crate::agents::Agents;
+use crate::bus::{ bus_stop_along, BUS_DWELL_TIME, BUS_STOP_LANE };
+use crate::clock::TICK;
+use crate::cyclist::{
+    cyclist_off_screen,
+    cyclist_rect,
+    move_cyclist,
+    Cyclist,
+    CYCLIST_LENGTH,
+    CYCLIST_MIN_GAP,
+    CYCLIST_WIDTH,
+};
+use crate::emissions::fuel_per_tick;
+use crate::error::RenderError;
+use crate::lane::{ occupies, Lane };
+use crate::median::MEDIAN_LANE;
+use crate::pedestrian::{ close_call, Pedestrian, PEDESTRIAN_SIZE };
+use crate::rail::{ distance_to_gate, RAIL_EXIT };
+use crate::render::{ Color, RectBatch, Renderer };
+use crate::simulation::SimEvent;
+use crate::sink::node_id;
+use crate::theme::Palette;
+use crate::traffic_light::{ LightState, SignalHead };
+use crate::units::{ per_tick, Area, View };
+use crate::vehicle::{
+    braking_distance,
+    distance_along,
+    following_gap,
+    move_vehicle,
+    opposite,
+    queue_space,
+    relative_offset,
+    relative_position,
+    shares_path,
+    stopping_speed,
+    turn_lane,
+    turn_point,
+    turned_direction,
+    vehicle_rect,
+    Direction,
+    Indicator,
+    Route,
+    Vehicle,
+    VehicleId,
+    VehicleKind,
+    VehicleState,
+};
+use crate::weather::Weather;
+use crate::work_zone::WORK_ZONE_SETBACK;
+use crate::{
+    ACCELERATION,
+    BIKE_LANE_WIDTH,
+    BRAKING_DECELERATION,
+    HORN_WAIT_THRESHOLD,
+    LANES_PER_DIRECTION,
+    LANE_CHANGE_LENGTH,
+    ROAD_WIDTH,
+    SAFETY_GAP,
+    VEHICLE_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+// How far ahead a slower leader makes a through car look for a faster lane.
+const OVERTAKE_LOOKAHEAD: f32 = queue_space(VehicleKind::Car) * 3.0;
+// Right-turners hold back while a through cyclist is this close to their turning path.
+const CYCLIST_YIELD_DISTANCE: f32 = 9.0;
+// Below this, in m/s, a vehicle counts as standing still. Well under what a tick's
+// acceleration adds, so one standing still can always move off.
+const MIN_MOVING_SPEED: f32 = 0.01;
+// Stretch before the stop line in which a driver caught by a yellow or red decides whether
+// to run it.
+const STOP_WINDOW: f32 = 3.0;
+// Distance before the stop line of the speed measurement line on each approach.
+const MEASUREMENT_SETBACK: f32 = 15.0;
+// A waiting left-turner accepts the gap if oncoming through traffic is further away than
+// it can travel in this many seconds.
+const CRITICAL_GAP_SECS: f32 = 0.9;
+// Share of their cruising speed drivers slow to through a flashing yellow.
+const FLASHING_YELLOW_SPEED_FACTOR: f32 = 0.6;
+// Pulling out from a standstill takes longer than turning across from a rolling start, so a
+// driver at a flashing red needs a bigger gap in the crossing road's traffic.
+const PULL_OUT_GAP_SECS: f32 = 1.5;
+// Rounding error in meters within which a vehicle just past a line is still on it.
+const LINE_SLACK: f32 = 0.001;
+// How close to the line a vehicle standing at a flashing red must be to have stopped for it.
+const STOPPED_AT_LINE: f32 = 0.5;
+const HEADLIGHT_COLOR: Color = Color::rgb(255, 250, 200);
+const TAILLIGHT_COLOR: Color = Color::rgb(220, 0, 0);
+const WRECK_COLOR: Color = Color::rgb(70, 60, 60);
+const HAZARD_COLOR: Color = Color::rgb(255, 170, 0);
+const BLINKER_COLOR: Color = Color::rgb(235, 90, 0);
+const VEHICLE_LIGHT_SIZE: f32 = 0.4;
+
+// How far a vehicle may still travel this tick before it has to be standing still, as
+// `keep_gaps`, `obey_signals` and `give_way` leave it for `drive`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Room {
+    pub limit: f32,
+    // Room left before a jaywalker in its path, to tell whether that is what it stops for.
+    pub to_jaywalker: Option<f32>,
+    // Whether the driver, caught by a yellow or red, decided this tick to run it.
+    pub runs_light: Option<bool>,
+}
+
+// Nothing in the way.
+impl Default for Room {
+    fn default() -> Self {
+        Self { limit: f32::INFINITY, to_jaywalker: None, runs_light: None }
+    }
+}
+
+impl Room {
+    fn cap(&mut self, limit: f32) {
+        self.limit = self.limit.min(limit);
+    }
+}
+
+// What an approach's traffic goes by this tick, worked out before any of it moves.
+#[derive(Debug, Clone, Copy)]
+pub struct Signals {
+    pub head: SignalHead,
+    pub oncoming_light: LightState,
+    // At a flashing red, whether traffic on the crossing road is too close to pull out in
+    // front of.
+    pub cross_traffic_close: bool,
+    // Seeds the approach's own generator, for the drivers deciding whether to run a light.
+    pub seed: u64,
+}
+
+// Road users this approach's traffic gives way to: traffic from the opposite approach,
+// pedestrians out on the crosswalks, trains at the level crossing while its gates are down,
+// and obstacles: wrecks, and vehicles from any approach standing still in the intersection.
+#[derive(Debug, Clone, Copy)]
+pub struct Conflicts<'a> {
+    pub oncoming: &'a [&'a Vehicle],
+    pub oncoming_cyclists: &'a [&'a Cyclist],
+    pub pedestrians: &'a [&'a Pedestrian],
+    pub gates_down: bool,
+    pub obstacles: &'a [Vehicle],
+}
+
+// Moves vehicles over towards the lane they want, front to back so each sees the moves
+// of those ahead, and holds back a left-turner beside a full turn bay.
+pub fn change_lanes(
+    lane: &mut Lane,
+    vehicles: &mut [&mut Vehicle],
+    weather: Weather,
+    events: &mut Vec<SimEvent>
+) {
+    let safety_gap = SAFETY_GAP * weather.gap_factor();
+    // Measured from the crossing road's bike lane, like the stop line.
+    let turn_bay = lane.turn_bay.map(|bay| bay + lane.geometry.stop_line_setback);
+    let no_change_zone = lane.no_change_zone.map(|zone| zone + lane.geometry.stop_line_setback);
+    for i in 0..vehicles.len() {
+        let closed_lane = lane.closed_lane;
+        let Some(to) = choose_lane(vehicles, i, safety_gap, closed_lane, turn_bay) else {
+            continue;
+        };
+        let vehicle = &mut *vehicles[i];
+        // Only the cones of a closed lane override the solid lines.
+        let escaping = closed_lane == Some(vehicle.lane) && in_work_zone(vehicle);
+        let in_zone = no_change_zone.is_some_and(|zone| distance_to_stop_line(vehicle) < zone);
+        if in_zone && !escaping {
+            if !vehicle.crossed_solid_line {
+                vehicle.crossed_solid_line = true;
+                events.push(SimEvent::LaneChangeViolation {
+                    vehicle_id: vehicle.id,
+                    approach: lane.direction,
+                });
+            }
+            continue;
+        }
+        vehicle.change_lane(to);
+    }
+    if let Some(bay) = turn_bay {
+        let overflowing = (0..vehicles.len()).any(|i| {
+            let vehicle = &vehicles[i];
+            waits_for_bay(vehicle) &&
+                vehicle.is_stopped() &&
+                distance_to_stop_line(vehicle) <= bay &&
+                !lane_has_gap(vehicles, i, MEDIAN_LANE, safety_gap)
+        });
+        if overflowing != lane.bay_overflowing {
+            lane.bay_overflowing = overflowing;
+            events.push(if overflowing {
+                SimEvent::TurnBayOverflowed { approach: lane.direction }
+            } else {
+                SimEvent::TurnBayCleared { approach: lane.direction }
+            });
+        }
+    }
+}
+
+// The room each of an approach's `vehicles` has this tick.
+pub fn plan(
+    lane: &Lane,
+    vehicles: &[&Vehicle],
+    cyclists: &[&Cyclist],
+    signals: &Signals,
+    conflicts: Conflicts,
+    weather: Weather
+) -> Vec<Room> {
+    let mut rooms = vec![Room::default(); vehicles.len()];
+    keep_gaps(lane, vehicles, &mut rooms, weather);
+    obey_signals(lane, vehicles, &mut rooms, signals, weather);
+    give_way(lane, vehicles, cyclists, &mut rooms, signals, conflicts, weather);
+    rooms
+}
+
+// Room behind the vehicle ahead, up to a bus's stop, and beside the mouth of a turn bay a
+// left-turner can't get into yet.
+pub fn keep_gaps(lane: &Lane, vehicles: &[&Vehicle], rooms: &mut [Room], weather: Weather) {
+    let safety_gap = SAFETY_GAP * weather.gap_factor();
+    let braking = BRAKING_DECELERATION * weather.braking_factor();
+    let turn_bay = lane.turn_bay.map(|bay| bay + lane.geometry.stop_line_setback);
+    for (i, room) in rooms.iter_mut().enumerate() {
+        let vehicle = vehicles[i];
+        if let Some((distance, leader)) = find_leader(vehicles, i) {
+            let leader = vehicles[leader];
+            // Only the part of the leader's speed taking it further along this vehicle's
+            // heading opens the gap; a leader turning across the path doesn't. One on the
+            // same path recedes along it at its full speed.
+            let (hx, hy) = vehicle.heading;
+            let receding = if shares_path(vehicle, leader) {
+                leader.speed
+            } else {
+                leader.speed * (leader.heading.0 * hx + leader.heading.1 * hy)
+            };
+            let gap = distance - following_gap(vehicle, leader, safety_gap) +
+                braking_distance(receding.max(0.0), braking);
+            room.cap(gap.max(0.0));
+        }
+        if let Some(to_stop) = distance_to_bus_stop(vehicle) {
+            room.cap(to_stop.max(0.0));
+        }
+        // Lefts that can't get into the bay wait beside its mouth rather than pass it.
+        if let Some(bay) = turn_bay.filter(|_| waits_for_bay(vehicle)) {
+            let beside = distance_to_stop_line(vehicle) - bay + queue_space(vehicle.kind);
+            room.cap(beside.max(0.0));
+        }
+    }
+}
+
+// Room up to the stop line for vehicles the signal holds: a stop at a flashing red until
+// the crossing road is clear, a yellow or red unless the driver decides to run it, and
+// caution speed through a flashing yellow.
+pub fn obey_signals(
+    lane: &Lane,
+    vehicles: &[&Vehicle],
+    rooms: &mut [Room],
+    signals: &Signals,
+    weather: Weather
+) {
+    let braking = BRAKING_DECELERATION * weather.braking_factor();
+    let mut rng = StdRng::seed_from_u64(signals.seed);
+    for (vehicle, room) in vehicles.iter().zip(rooms) {
+        let light = signals.head.for_route(vehicle.route);
+        let (to_stop_line, can_stop) = stop_line_ahead(lane, vehicle, braking);
+        if light == LightState::FlashingRed && to_stop_line >= 0.0 {
+            // A flashing red is a stop sign: a full stop at the line, then on once nothing
+            // on the crossing road is close.
+            let clear = !signals.cross_traffic_close;
+            if !(vehicle.stopped_at_line && clear) && can_stop {
+                room.cap(to_stop_line);
+            }
+        } else if !light.is_go() && to_stop_line >= 0.0 {
+            // Drivers caught by a yellow or red at the stop line decide once whether to run
+            // it; those too close to stop in time carry on regardless.
+            let mut runs_light = vehicle.runs_light;
+            let at_entrance = (0.0..STOP_WINDOW).contains(&to_stop_line);
+            if runs_light.is_none() && at_entrance {
+                runs_light = Some(rng.gen_bool(vehicle.profile.red_light_run_chance()));
+                room.runs_light = runs_light;
+            }
+            if runs_light != Some(true) && can_stop {
+                room.cap(to_stop_line);
+            }
+        }
+        if light == LightState::FlashingYellow && to_stop_line >= 0.0 {
+            let caution = vehicle.desired_speed * FLASHING_YELLOW_SPEED_FACTOR;
+            room.cap(to_stop_line + braking_distance(caution, braking));
+        }
+    }
+}
+
+// Room short of the road users a vehicle conflicts with: oncoming traffic across a left,
+// pedestrians, wrecks and whatever stands in the intersection, trains, and cyclists
+// across a right.
+pub fn give_way(
+    lane: &Lane,
+    vehicles: &[&Vehicle],
+    cyclists: &[&Cyclist],
+    rooms: &mut [Room],
+    signals: &Signals,
+    conflicts: Conflicts,
+    weather: Weather
+) {
+    let braking = BRAKING_DECELERATION * weather.braking_factor();
+    for (vehicle, room) in vehicles.iter().zip(rooms) {
+        let (to_stop_line, can_stop) = stop_line_ahead(lane, vehicle, braking);
+        let protected = signals.head.protects(vehicle.route);
+        if must_yield_to_oncoming(vehicle, conflicts, signals.oncoming_light, protected) {
+            room.cap(distance_to_turn(vehicle));
+        }
+        // Pedestrians still crossing keep traffic at the stop line until they are clear of
+        // its path, and anyone already past it stops short of them. Jaywalkers aren't
+        // looked out for, so they are only braked for once in the way.
+        let crossings = crosswalks_crossed(vehicle.approach, vehicle.route);
+        let crosswalk_busy = conflicts.pedestrians
+            .iter()
+            .any(|p| !p.jaywalking && crossings.contains(&p.crosswalk));
+        if crosswalk_busy && to_stop_line >= 0.0 && can_stop {
+            room.cap(to_stop_line);
+        }
+        let walkers = conflicts.pedestrians.iter().filter(|p| !p.jaywalking);
+        if let Some(distance) = distance_to_pedestrian(vehicle, walkers.copied()) {
+            room.cap(distance);
+        }
+        let jaywalkers = conflicts.pedestrians.iter().filter(|p| p.jaywalking);
+        room.to_jaywalker = distance_to_pedestrian(vehicle, jaywalkers.copied());
+        if let Some(distance) = room.to_jaywalker {
+            room.cap(distance);
+        }
+        // Wrecks are waited behind wherever they are. Nobody enters the intersection while
+        // something is in their way in it, so they can't get stuck behind it.
+        let wrecks = conflicts.obstacles.iter().filter(|o| o.wrecked_until.is_some());
+        if let Some(distance) = distance_to_obstacle(vehicle, wrecks) {
+            room.cap(distance);
+        }
+        let obstructed = distance_to_obstacle(vehicle, conflicts.obstacles.iter()).is_some();
+        if obstructed && to_stop_line >= 0.0 && can_stop {
+            room.cap(to_stop_line);
+        }
+        // Lowered gates stop traffic short of the tracks, and keep anything that would leave
+        // the intersection over them waiting at the stop line, out of the way.
+        if conflicts.gates_down {
+            let slowest = (vehicle.speed - per_tick(braking)).max(0.0);
+            let half_length = vehicle.length() / 2.0;
+            let to_gate = distance_to_gate(vehicle.direction, vehicle.x, half_length);
+            if let Some(distance) = to_gate {
+                if braking_distance(slowest, braking) <= distance + 0.05 {
+                    room.cap(distance);
+                }
+            }
+            let exit = turned_direction(vehicle.approach, vehicle.route);
+            if exit == RAIL_EXIT && to_stop_line >= 0.0 && can_stop {
+                room.cap(to_stop_line);
+            }
+        }
+        if must_yield_to_cyclist(vehicle, cyclists, signals.head.ball) {
+            let crossing = turn_point(vehicle.direction, vehicle.route);
+            let to_crossing = distance_along(vehicle.direction, vehicle.x, vehicle.y, crossing);
+            room.cap((to_crossing - queue_space(vehicle.kind)).max(0.0));
+        }
+    }
+}
+
+// Distance left to the approach's stop line, and whether the vehicle can still brake to a
+// stop at it.
+fn stop_line_ahead(lane: &Lane, vehicle: &Vehicle, braking: f32) -> (f32, bool) {
+    let to_stop_line =
+        snap_to_line(distance_to_stop_line(vehicle) - lane.geometry.stop_line_setback);
+    // Five centimeters of slack cover rounding while braking right up to the line.
+    let slowest = (vehicle.speed - per_tick(braking)).max(0.0);
+    (to_stop_line, braking_distance(slowest, braking) <= to_stop_line + 0.05)
+}
+
+// Moves an approach's vehicles on by a tick within their room, returning the ids of those
+// that reached the end of their path, front to back.
+pub fn drive(
+    lane: &Lane,
+    vehicles: Vec<(&mut Vehicle, &Room)>,
+    head: SignalHead,
+    weather: Weather,
+    now: Duration,
+    events: &mut Vec<SimEvent>
+) -> Vec<VehicleId> {
+    let braking = BRAKING_DECELERATION * weather.braking_factor();
+    let mut finished = Vec::new();
+    for (vehicle, room) in vehicles {
+        if room.runs_light.is_some() {
+            vehicle.runs_light = room.runs_light;
+        }
+        let light = head.for_route(vehicle.route);
+        if vehicle.wrecked_until.is_some() {
+            vehicle.speed = 0.0;
+            continue;
+        }
+        if let Some(dwell_until) = vehicle.dwell_until {
+            if now < dwell_until {
+                vehicle.fuel_used += fuel_per_tick(vehicle.kind, 0.0, 0.0);
+                continue;
+            }
+            vehicle.dwell_until = None;
+        }
+        let previous_speed = vehicle.speed;
+        let speed = next_speed(vehicle, room.limit, braking, weather);
+        // A driver standing still moves off only once they have reacted to the way opening,
+        // and starts reacting afresh if it closes again first.
+        let reacting =
+            previous_speed == 0.0 &&
+            speed > 0.0 &&
+            now < *vehicle.moving_off_at.get_or_insert(now + vehicle.reaction_time);
+        if !reacting {
+            vehicle.moving_off_at = None;
+        }
+        vehicle.speed = if reacting { 0.0 } else { speed };
+        let for_jaywalker = room.to_jaywalker == Some(room.limit);
+        let braking_for_jaywalker = for_jaywalker && vehicle.speed < previous_speed;
+        if braking_for_jaywalker && !vehicle.braking_for_jaywalker {
+            events.push(SimEvent::EmergencyBrake {
+                vehicle_id: vehicle.id,
+                approach: lane.direction,
+            });
+        }
+        vehicle.braking_for_jaywalker = braking_for_jaywalker;
+        vehicle.fuel_used += fuel_per_tick(vehicle.kind, previous_speed, vehicle.speed);
+        if vehicle.speed == 0.0 {
+            vehicle.stops += (previous_speed > 0.0) as u32;
+            vehicle.idle_time += TICK;
+        }
+        let to_stop_line = distance_to_stop_line(vehicle) - lane.geometry.stop_line_setback;
+        if light == LightState::FlashingRed && vehicle.speed == 0.0 {
+            vehicle.stopped_at_line |= to_stop_line < STOPPED_AT_LINE;
+        }
+        let free_flow = vehicle.desired_speed * weather.speed_factor();
+        vehicle.control_delay += TICK.mul_f32((1.0 - vehicle.speed / free_flow).max(0.0));
+        if vehicle.speed > 0.0 {
+            let before = distance_to_stop_line(vehicle);
+            let state = vehicle.state;
+            // Capped at the room left, which the speed only gives back up to rounding, so a
+            // vehicle braking onto the line stops on it rather than a hair past.
+            move_vehicle(vehicle, per_tick(vehicle.speed).min(room.limit));
+            if state.on_approach() && !vehicle.state.on_approach() {
+                events.push(SimEvent::VehicleEnteredIntersection {
+                    vehicle_id: vehicle.id,
+                    approach: lane.direction,
+                    route: vehicle.route,
+                    kind: vehicle.kind,
+                    entered_road: vehicle.entered_road,
+                    arrived: vehicle.arrived.unwrap_or(now),
+                });
+            }
+            if state != VehicleState::Turning && vehicle.state == VehicleState::Turning {
+                events.push(SimEvent::VehicleTurned {
+                    vehicle_id: vehicle.id,
+                    exit: vehicle.direction,
+                });
+            }
+            vehicle.trail.record((vehicle.x, vehicle.y));
+            let after = distance_to_stop_line(vehicle);
+            if before >= MEASUREMENT_SETBACK && after < MEASUREMENT_SETBACK {
+                events.push(SimEvent::SpeedMeasured {
+                    approach: vehicle.approach,
+                    speed: vehicle.speed,
+                    limit: lane.speed_limit,
+                });
+            }
+            let entry = lane.travel_times.entry_setback;
+            if before >= entry && after < entry {
+                vehicle.segment_entered = Some(now);
+            }
+            if vehicle.distance_past_intersection() >= lane.travel_times.exit_distance {
+                if let Some(entered) = vehicle.segment_entered.take() {
+                    events.push(SimEvent::TravelTimeMeasured {
+                        approach: vehicle.approach,
+                        route: vehicle.route,
+                        time: now - entered,
+                    });
+                }
+            }
+            // Red-light camera at the stop line, where a vehicle that didn't have to stop
+            // arrives.
+            if before >= 0.0 && after < 0.0 {
+                vehicle.arrived.get_or_insert(now);
+                if light == LightState::Red {
+                    events.push(SimEvent::RedLightViolation {
+                        vehicle_id: vehicle.id,
+                        approach: vehicle.approach,
+                    });
+                }
+            }
+            if let Some(wait_started) = vehicle.wait_started.take() {
+                vehicle.total_wait += now - wait_started;
+            }
+            vehicle.honked = false;
+            if distance_to_bus_stop(vehicle).is_some_and(|to_stop| to_stop <= 0.05) {
+                vehicle.served_stop = true;
+                vehicle.speed = 0.0;
+                vehicle.dwell_until = Some(now + BUS_DWELL_TIME);
+            }
+
+            if vehicle.path.is_finished() {
+                finished.push(vehicle.id);
+            }
+        } else {
+            let wait_started = *vehicle.wait_started.get_or_insert(now);
+            vehicle.update_state();
+            if vehicle.state == VehicleState::QueuedAtLight {
+                vehicle.arrived.get_or_insert(now);
+            }
+            if !vehicle.honked && now - wait_started >= HORN_WAIT_THRESHOLD {
+                vehicle.honked = true;
+                events.push(SimEvent::VehicleWaiting);
+            }
+        }
+    }
+    finished
+}
+
+// Takes the vehicles that reached the end of their path off the road, back to front.
+pub fn leave(agents: &mut Agents, finished: &[VehicleId], events: &mut Vec<SimEvent>) {
+    for &id in finished.iter().rev() {
+        let Some(vehicle) = agents.remove_vehicle(id) else {
+            continue;
+        };
+        events.push(SimEvent::VehicleExited {
+            vehicle_id: vehicle.id,
+            kind: vehicle.kind,
+            approach: vehicle.approach,
+            delay: vehicle.total_wait,
+            control_delay: vehicle.control_delay,
+            fuel: vehicle.fuel_used,
+            stops: vehicle.stops,
+            idle: vehicle.idle_time,
+            origin: node_id(opposite(vehicle.approach)),
+            destination: node_id(vehicle.direction),
+            work_zone: vehicle.through_work_zone,
+        });
+    }
+}
+
+// How far each of an approach's `cyclists` rides this tick: none while a cyclist or vehicle
+// blocks the bike lane ahead, and no further than the line while the light, the crosswalk,
+// the crossing road at a flashing red or the gates hold them. `vehicles` are the
+// approach's own, already moved on.
+pub fn pedal(
+    approach: Direction,
+    cyclists: &[&Cyclist],
+    vehicles: &[&Vehicle],
+    signals: &Signals,
+    conflicts: Conflicts
+) -> Vec<Option<f32>> {
+    let light = signals.head.ball;
+    let crossings = crosswalks_crossed(approach, Route::Straight);
+    let crosswalk_busy = conflicts.pedestrians
+        .iter()
+        .any(|p| !p.jaywalking && crossings.contains(&p.crosswalk));
+    cyclists
+        .iter()
+        .enumerate()
+        .map(|(i, cyclist)| {
+            let position = (cyclist.x, cyclist.y);
+            let blocked_by_cyclist = cyclists.iter().enumerate().any(|(j, other)| {
+                let (ahead, _) = relative_offset(cyclist.direction, position, (other.x, other.y));
+                j != i && ahead > 0.0 && ahead < CYCLIST_MIN_GAP
+            });
+            // Vehicles already cutting across the bike lane keep the right of way, and
+            // obstacles block it.
+            let others = vehicles.iter().copied().chain(conflicts.obstacles);
+            let blocked_by_vehicle = others.into_iter().any(|vehicle| {
+                let (ahead, sideways) = relative_offset(
+                    cyclist.direction,
+                    position,
+                    (vehicle.x, vehicle.y)
+                );
+                // The vehicle's footprint measured along and across the bike lane.
+                let footprint = vehicle_rect(vehicle);
+                let (along, across) = match cyclist.direction {
+                    Direction::North | Direction::South => (footprint.h, footprint.w),
+                    Direction::East | Direction::West => (footprint.w, footprint.h),
+                };
+                ahead > 0.0 &&
+                    ahead < (along + CYCLIST_LENGTH) / 2.0 + SAFETY_GAP &&
+                    sideways.abs() < (across + CYCLIST_WIDTH) / 2.0
+            });
+            if blocked_by_cyclist || blocked_by_vehicle {
+                return None;
+            }
+            // Cyclists held by the light or the gates ride right up to the line and stop on
+            // it. Five centimeters of slack keep one standing on the line from counting as
+            // past.
+            let mut step = per_tick(cyclist.speed);
+            let yielding = light == LightState::FlashingRed && signals.cross_traffic_close;
+            if holds_cyclists(light) || crosswalk_busy || yielding {
+                let to_stop_line = cyclist_distance_to_intersection(cyclist);
+                if to_stop_line > -0.05 {
+                    step = step.min(to_stop_line.max(0.0));
+                }
+            }
+            let half_length = CYCLIST_LENGTH / 2.0;
+            if conflicts.gates_down {
+                let to_gate = distance_to_gate(cyclist.direction, cyclist.x, half_length - 0.05);
+                if let Some(distance) = to_gate {
+                    step = step.min((distance - 0.05).max(0.0));
+                }
+            }
+            Some(step)
+        })
+        .collect()
+}
+
+// Moves every cyclist on by the steps `pedal` gave each approach's, and takes those that
+// rode off the edge of the world off the road.
+pub fn ride(agents: &mut Agents, steps: &[Vec<Option<f32>>]) {
+    for (cyclists, steps) in agents.cyclist_rows::<&mut Cyclist>().into_iter().zip(steps) {
+        for (cyclist, step) in cyclists.into_iter().zip(steps) {
+            if let Some(step) = *step {
+                move_cyclist(cyclist, step);
+            }
+        }
+    }
+    agents.remove_cyclists(cyclist_off_screen);
+}
+
+// Moves everyone on the crosswalks on by a tick, taking those across off the road.
+pub fn walk(agents: &mut Agents, events: &mut Vec<SimEvent>) {
+    for pedestrian in agents.crossing_rows::<&mut Pedestrian>() {
+        pedestrian.walk();
+        if pedestrian.jaywalking && pedestrian.has_crossed() {
+            events.push(SimEvent::JaywalkerCrossed {
+                crosswalk: pedestrian.crosswalk,
+                close_call: pedestrian.close_call,
+            });
+        }
+    }
+    agents.remove_crossing(Pedestrian::has_crossed);
+}
+
+// Keeps each jaywalker's closest call with the traffic as it now stands.
+pub fn watch_jaywalkers(agents: &mut Agents) {
+    let closest: Vec<_> = {
+        let vehicles = agents.vehicles();
+        let pedestrians = agents.crossing_pedestrians();
+        pedestrians
+            .iter()
+            .map(|pedestrian| {
+                let calls = vehicles.iter().filter_map(|v| close_call(v, pedestrian));
+                pedestrian.jaywalking.then(|| calls.max()).flatten()
+            })
+            .collect()
+    };
+    let pedestrians = agents.crossing_rows::<&mut Pedestrian>();
+    for (pedestrian, closest) in pedestrians.into_iter().zip(closest) {
+        pedestrian.close_call = pedestrian.close_call.max(closest);
+    }
+}
+
+// Draws every vehicle and cyclist, approach by approach, with their lights once it is dark.
+pub fn draw_road_users(
+    renderer: &mut dyn Renderer,
+    view: &View,
+    agents: &Agents,
+    palette: &Palette,
+    lights_on: bool,
+    now: Duration
+) -> Result<(), RenderError> {
+    // Bodies, then the lights on them, each a call per color however many there are.
+    let (mut bodies, mut lights) = (RectBatch::default(), RectBatch::default());
+    for approach in [Direction::North, Direction::South, Direction::East, Direction::West] {
+        for vehicle in agents.vehicles_on(approach).iter() {
+            if vehicle.wrecked_until.is_some() {
+                add_wreck(&mut bodies, &mut lights, view, vehicle, now);
+                continue;
+            }
+            bodies.add(view.rect(vehicle_rect(vehicle)), palette.vehicle(vehicle));
+            if lights_on {
+                add_vehicle_lights(&mut lights, view, vehicle);
+            }
+            add_blinkers(&mut lights, view, vehicle, now);
+        }
+        for cyclist in agents.cyclists_on(approach).iter() {
+            bodies.add(view.rect(cyclist_rect(cyclist)), palette.cyclist);
+        }
+    }
+    bodies.draw(renderer)?;
+    lights.draw(renderer)
+}
+
+// Headlights at the front corners and taillights at the rear, following the heading.
+fn add_vehicle_lights(lights: &mut RectBatch, view: &View, vehicle: &Vehicle) {
+    let (hx, hy) = vehicle.heading;
+    let along = vehicle.length() / 2.0 - VEHICLE_LIGHT_SIZE / 2.0;
+    let across = VEHICLE_WIDTH / 2.0 - VEHICLE_LIGHT_SIZE;
+    for (ahead, color) in [(along, HEADLIGHT_COLOR), (-along, TAILLIGHT_COLOR)] {
+        for side in [-1.0, 1.0] {
+            let x = vehicle.x + hx * ahead - hy * across * side;
+            let y = vehicle.y + hy * ahead + hx * across * side;
+            let light = Area::centered(x, y, VEHICLE_LIGHT_SIZE, VEHICLE_LIGHT_SIZE);
+            lights.add(view.rect(light), color);
+        }
+    }
+}
+
+// Front and rear corners on the side the vehicle is signalling, flashing about one and a
+// half times a second.
+fn add_blinkers(lights: &mut RectBatch, view: &View, vehicle: &Vehicle, now: Duration) {
+    let Some(indicator) = vehicle.indicator() else {
+        return;
+    };
+    if now.as_millis() % 700 >= 350 {
+        return;
+    }
+    let (hx, hy) = vehicle.heading;
+    let along = vehicle.length() / 2.0 - VEHICLE_LIGHT_SIZE / 2.0;
+    let across = VEHICLE_WIDTH / 2.0 - VEHICLE_LIGHT_SIZE / 2.0;
+    // To the right of the heading is positive.
+    let side = match indicator {
+        Indicator::Left => -1.0,
+        Indicator::Right => 1.0,
+    };
+    for ahead in [along, -along] {
+        let x = vehicle.x + hx * ahead - hy * across * side;
+        let y = vehicle.y + hy * ahead + hx * across * side;
+        let blinker = Area::centered(x, y, VEHICLE_LIGHT_SIZE, VEHICLE_LIGHT_SIZE);
+        lights.add(view.rect(blinker), BLINKER_COLOR);
+    }
+}
+
+// A burnt-out shell with its hazard lights flashing in the middle, on for the first half
+// of every second.
+fn add_wreck(
+    bodies: &mut RectBatch,
+    lights: &mut RectBatch,
+    view: &View,
+    vehicle: &Vehicle,
+    now: Duration
+) {
+    bodies.add(view.rect(vehicle_rect(vehicle)), WRECK_COLOR);
+    if now.as_millis() % 1000 < 500 {
+        let size = VEHICLE_LIGHT_SIZE * 2.0;
+        let hazard = Area::centered(vehicle.x, vehicle.y, size, size);
+        lights.add(view.rect(hazard), HAZARD_COLOR);
+    }
+}
+
+// The crosswalks a movement passes over, by the approach whose stop line each lies past:
+// its own on the way in and the one on the far side of its exit on the way out.
+fn crosswalks_crossed(approach: Direction, route: Route) -> [Direction; 2] {
+    [approach, opposite(turned_direction(approach, route))]
+}
+
+// Room left before the vehicle would run into someone crossing ahead of it.
+fn distance_to_pedestrian<'a>(
+    vehicle: &Vehicle,
+    pedestrians: impl Iterator<Item = &'a Pedestrian>
+) -> Option<f32> {
+    let reach = (VEHICLE_WIDTH + PEDESTRIAN_SIZE) / 2.0 + SAFETY_GAP;
+    let clearance = (vehicle.length() + PEDESTRIAN_SIZE) / 2.0 + SAFETY_GAP;
+    pedestrians
+        .filter_map(|pedestrian| {
+            let position = (pedestrian.x, pedestrian.y);
+            let (ahead, sideways) =
+                relative_offset(vehicle.direction, (vehicle.x, vehicle.y), position);
+            (ahead > 0.0 && sideways.abs() < reach).then_some((ahead - clearance).max(0.0))
+        })
+        .min_by(f32::total_cmp)
+}
+
+// Room left before the vehicle would run into an obstacle in its path. Its own approach's
+// traffic is left to the car-following, apart from wrecks.
+fn distance_to_obstacle<'a>(
+    vehicle: &Vehicle,
+    obstacles: impl Iterator<Item = &'a Vehicle>
+) -> Option<f32> {
+    let across = (-vehicle.heading.1, vehicle.heading.0);
+    obstacles
+        .filter(|other| {
+            other.id != vehicle.id &&
+                (other.wrecked_until.is_some() || other.approach != vehicle.approach)
+        })
+        .filter_map(|other| {
+            let (ahead, sideways) = relative_position(vehicle, other);
+            let reach = half_extent(vehicle, across) + half_extent(other, across);
+            let clearance =
+                half_extent(vehicle, vehicle.heading) +
+                half_extent(other, vehicle.heading) +
+                SAFETY_GAP;
+            (ahead > 0.0 && sideways.abs() < reach).then_some((ahead - clearance).max(0.0))
+        })
+        .min_by(f32::total_cmp)
+}
+
+// Accelerates towards the driver's speed for the weather, capped so the vehicle can still
+// brake to a stop within `room`, and never so fast it would overrun it this tick.
+fn next_speed(vehicle: &Vehicle, room: f32, braking: f32, weather: Weather) -> f32 {
+    let target = vehicle.desired_speed * weather.speed_factor();
+    let speed = if vehicle.speed > target {
+        (vehicle.speed - per_tick(braking)).max(target)
+    } else {
+        (vehicle.speed + per_tick(ACCELERATION)).min(target)
+    };
+    let speed = speed.min(stopping_speed(room, braking)).min(room / TICK.as_secs_f32());
+    if speed < MIN_MOVING_SPEED { 0.0 } else { speed }
+}
+
+// Distance left to a bus's stop while it still has to serve it.
+fn distance_to_bus_stop(vehicle: &Vehicle) -> Option<f32> {
+    if vehicle.kind != VehicleKind::Bus || vehicle.served_stop || vehicle.has_turned() {
+        return None;
+    }
+    let stop = bus_stop_along(vehicle.direction);
+    Some(distance_along(vehicle.direction, vehicle.x, vehicle.y, stop))
+}
+
+// Whether any of the vehicle is still alongside the cones of a closed lane.
+fn in_work_zone(vehicle: &Vehicle) -> bool {
+    distance_to_stop_line(vehicle) + vehicle.length() > WORK_ZONE_SETBACK
+}
+
+// Cars turn left from the bay on a divided road; buses don't turn left.
+fn uses_bay(vehicle: &Vehicle) -> bool {
+    vehicle.kind == VehicleKind::Car && vehicle.route == Route::Left
+}
+
+// A left-turner on a divided road still in the through lane.
+fn waits_for_bay(vehicle: &Vehicle) -> bool {
+    uses_bay(vehicle) && vehicle.lane != MEDIAN_LANE && !vehicle.has_turned()
+}
+
+// Vehicles stop short of the bike lane running along the crossing road.
+fn distance_to_stop_line(vehicle: &Vehicle) -> f32 {
+    snap_to_line(vehicle.distance_to_intersection() - BIKE_LANE_WIDTH)
+}
+
+// A vehicle that braked onto a line can end up a rounding error past it, and still counts
+// as at it.
+fn snap_to_line(distance: f32) -> f32 {
+    if distance > -LINE_SLACK { distance.max(0.0) } else { distance }
+}
+
+// Distance before the cyclist's front reaches the curb of the crossing road.
+fn cyclist_distance_to_intersection(cyclist: &Cyclist) -> f32 {
+    let center = match cyclist.direction {
+        Direction::North | Direction::South => WORLD_HEIGHT / 2.0,
+        Direction::East | Direction::West => WORLD_WIDTH / 2.0,
+    };
+    let curb = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + CYCLIST_LENGTH / 2.0;
+    distance_along(cyclist.direction, cyclist.x, cyclist.y, center) - curb
+}
+
+// Lefts without an arrow are permissive: the vehicle holds at the start of its turn while
+// there is no acceptable gap in oncoming through traffic, cyclists included, or an opposing
+// left is already turning. Once it has started turning it keeps going. Oncoming drivers'
+// intentions are only known from their turn signals, so one not signalling yet counts as
+// going straight. Protected lefts only wait for the opposing left, as both can have the
+// arrow and their paths cross in the box.
+fn must_yield_to_oncoming(
+    vehicle: &Vehicle,
+    conflicts: Conflicts,
+    oncoming_light: LightState,
+    protected: bool
+) -> bool {
+    if vehicle.route != Route::Left {
+        return false;
+    }
+    if distance_to_turn(vehicle) < 0.0 {
+        return false;
+    }
+    // Oncoming drivers at a flashing red go once they have stopped.
+    let moving_on = oncoming_light.is_go() || oncoming_light == LightState::FlashingRed;
+    let blocks = |distance: f32, crossing: f32, speed: f32| {
+        let cleared = distance < -crossing;
+        let arriving = moving_on && distance < speed * CRITICAL_GAP_SECS;
+        !cleared && (distance < 0.0 || arriving)
+    };
+    let vehicle_blocks = conflicts.oncoming.iter().any(|other| {
+        match other.indicator() {
+            None if other.has_turned() || protected => false,
+            None => {
+                let crossing = ROAD_WIDTH + other.length();
+                blocks(other.distance_to_intersection(), crossing, other.desired_speed)
+            }
+            Some(Indicator::Left) => distance_to_turn(other) < 0.0 && other.in_intersection(),
+            Some(Indicator::Right) => false,
+        }
+    });
+    let cyclist_blocks = !protected && conflicts.oncoming_cyclists.iter().any(|cyclist| {
+        let crossing = ROAD_WIDTH + BIKE_LANE_WIDTH * 2.0 + CYCLIST_LENGTH;
+        blocks(cyclist_distance_to_intersection(cyclist), crossing, cyclist.speed)
+    });
+    vehicle_blocks || cyclist_blocks
+}
+
+// Whether a vehicle on the crossing road is in the intersection, or near enough to it that
+// pulling out from the stop line would cut it off.
+pub fn cross_traffic_close<'a>(mut cross_traffic: impl Iterator<Item = &'a Vehicle>) -> bool {
+    cross_traffic.any(|other| {
+        let distance = other.distance_to_intersection();
+        let arriving = !other.has_turned() && distance < other.desired_speed * PULL_OUT_GAP_SECS;
+        other.in_intersection() || (distance >= 0.0 && arriving)
+    })
+}
+
+// Cyclists don't stop for flashing lights, only giving way at a flashing red.
+fn holds_cyclists(light: LightState) -> bool {
+    matches!(light, LightState::Red | LightState::Yellow)
+}
+
+// Distance along the approach before the vehicle's center reaches the start of its turn.
+fn distance_to_turn(vehicle: &Vehicle) -> f32 {
+    vehicle.distance_to_intersection() + vehicle.length() / 2.0
+}
+
+// A right turn cuts across the bike lane of its own approach, so the vehicle waits at the
+// turn while a through cyclist is approaching or still inside the crossing. Cyclists held
+// at a red light are not approaching.
+fn must_yield_to_cyclist(vehicle: &Vehicle, cyclists: &[&Cyclist], light: LightState) -> bool {
+    if vehicle.route != Route::Right || vehicle.has_turned() {
+        return false;
+    }
+    let crossing = turn_point(vehicle.direction, vehicle.route);
+    let clearance = (VEHICLE_WIDTH + CYCLIST_LENGTH) / 2.0;
+    cyclists.iter().any(|cyclist| {
+        let to_crossing = distance_along(cyclist.direction, cyclist.x, cyclist.y, crossing);
+        let held = holds_cyclists(light) && cyclist_distance_to_intersection(cyclist) >= 0.0;
+        to_crossing > -clearance && to_crossing < CYCLIST_YIELD_DISTANCE && !held
+    })
+}
+
+// Nearest vehicle ahead whose footprint overlaps the path this one sweeps, or that is
+// moving into its lane, as (center-to-center distance, index). The distance to one on the
+// same path is how much further along the path it is; otherwise it is measured along this
+// vehicle's heading.
+fn find_leader(vehicles: &[impl Deref<Target = Vehicle>], i: usize) -> Option<(f32, usize)> {
+    let vehicle = &*vehicles[i];
+    let across = (-vehicle.heading.1, vehicle.heading.0);
+    let remaining = vehicle.distance_to_path_end();
+    vehicles
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .filter_map(|(j, other)| {
+            if shares_path(vehicle, other) {
+                let ahead = remaining - other.distance_to_path_end();
+                return (ahead > 0.0).then_some((ahead, j));
+            }
+            let (ahead, sideways) = relative_position(vehicle, other);
+            let reach = half_extent(vehicle, across) + half_extent(other, across);
+            let merging = other.lane == vehicle.lane && other.direction == vehicle.direction;
+            (ahead > 0.0 && (sideways.abs() < reach || merging)).then_some((ahead, j))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+// Half the width of the vehicle's footprint measured along `axis`. Footprints stay square
+// to the road, so they reach further sideways from a diagonal heading.
+fn half_extent(vehicle: &Vehicle, (ax, ay): (f32, f32)) -> f32 {
+    let area = vehicle_rect(vehicle);
+    (area.w * ax.abs() + area.h * ay.abs()) / 2.0
+}
+
+// Whether `lane` has room for vehicle `i` next to the same-direction traffic in it.
+fn lane_has_gap(
+    vehicles: &[impl Deref<Target = Vehicle>],
+    i: usize,
+    lane: usize,
+    safety_gap: f32
+) -> bool {
+    let vehicle = &*vehicles[i];
+    vehicles
+        .iter()
+        .enumerate()
+        .filter(|&(j, other)| {
+            j != i && other.direction == vehicle.direction && occupies(other, lane)
+        })
+        .all(|(_, other)| {
+            relative_position(vehicle, other).0.abs() >= following_gap(vehicle, other, safety_gap)
+        })
+}
+
+// Distance to the nearest same-direction vehicle ahead in `lane`.
+fn lane_gap_ahead(vehicles: &[impl Deref<Target = Vehicle>], i: usize, lane: usize) -> f32 {
+    let vehicle = &*vehicles[i];
+    vehicles
+        .iter()
+        .enumerate()
+        .filter(|&(j, other)| {
+            j != i && other.direction == vehicle.direction && occupies(other, lane)
+        })
+        .map(|(_, other)| relative_position(vehicle, other).0)
+        .filter(|&ahead| ahead >= 0.0)
+        .fold(f32::INFINITY, f32::min)
+}
+
+// Simple gap-acceptance lane choice: turning vehicles work their way to their turn lane,
+// through vehicles stuck behind a slower or stopped leader move over when the next lane
+// is freer. Buses keep to the curb lane for their stop. Nobody moves into `closed_lane`
+// before the end of the work zone, and anyone caught in it moves out. On a divided road
+// only lefts move into the inner lane, once their front is beside the `turn_bay`.
+fn choose_lane(
+    vehicles: &[impl Deref<Target = Vehicle>],
+    i: usize,
+    safety_gap: f32,
+    closed_lane: Option<usize>,
+    turn_bay: Option<f32>
+) -> Option<usize> {
+    let vehicle = &*vehicles[i];
+    if
+        vehicle.wrecked_until.is_some() ||
+        vehicle.has_turned() ||
+        vehicle.is_changing_lanes() ||
+        vehicle.distance_to_intersection() < LANE_CHANGE_LENGTH
+    {
+        return None;
+    }
+
+    let in_zone = in_work_zone(vehicle);
+    let in_median = |lane: usize| {
+        lane == MEDIAN_LANE &&
+            turn_bay.is_some_and(|bay| !uses_bay(vehicle) || distance_to_stop_line(vehicle) > bay)
+    };
+    let open = |lane: usize| (!in_zone || closed_lane != Some(lane)) && !in_median(lane);
+    let neighbours = [vehicle.lane.checked_sub(1), Some(vehicle.lane + 1)];
+    let mut neighbours = neighbours
+        .into_iter()
+        .flatten()
+        .filter(|&lane| lane < LANES_PER_DIRECTION && open(lane));
+    let desired = match vehicle.kind {
+        VehicleKind::Car => turn_lane(vehicle.route),
+        VehicleKind::Bus => Some(BUS_STOP_LANE),
+    };
+    if let Some(desired) = desired.filter(|&lane| open(lane)) {
+        if desired == vehicle.lane {
+            return None;
+        }
+        let next = if desired > vehicle.lane { vehicle.lane + 1 } else { vehicle.lane - 1 };
+        // Swinging into the bay takes room ahead in the through lane; a left-turner right
+        // behind another waits for it to go first.
+        let swing = LANE_CHANGE_LENGTH + queue_space(vehicle.kind);
+        if turn_bay.is_some() && lane_gap_ahead(vehicles, i, vehicle.lane) < swing {
+            return None;
+        }
+        return (open(next) && lane_has_gap(vehicles, i, next, safety_gap)).then_some(next);
+    }
+    if !open(vehicle.lane) {
+        return neighbours.find(|&lane| lane_has_gap(vehicles, i, lane, safety_gap));
+    }
+    if vehicle.kind == VehicleKind::Bus {
+        return None;
+    }
+
+    let (leader_gap, leader) = find_leader(vehicles, i)?;
+    let leader_is_slower =
+        vehicles[leader].desired_speed < vehicle.desired_speed || vehicles[leader].is_stopped();
+    if leader_gap > OVERTAKE_LOOKAHEAD || !leader_is_slower {
+        return None;
+    }
+    neighbours.find(|&lane| {
+        lane_has_gap(vehicles, i, lane, safety_gap) &&
+            lane_gap_ahead(vehicles, i, lane) > leader_gap + queue_space(vehicle.kind)
+    })
+}
@@ -0,0 +1,471 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{ self, BufReader, BufWriter, Read, Write };
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use crate::clock::{ SimClock, TICK };
+use crate::error::{ RenderError, SimError };
+use crate::render::{ Rect, Renderer };
+use crate::simulation::TrafficSimulation;
+use crate::theme::Palette;
+use crate::traffic_light::LightState;
+use crate::ui::{ Mouse, Panel };
+use crate::vehicle::{ Direction, Route, Vehicle, VehicleId, VehicleKind };
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH };
+
+const MAGIC: &[u8; 6] = b"RITAPE";
+// Version 1 recorded positions and speeds in pixels; version 2 in meters and m/s.
+const VERSION: u8 = 2;
+// Tick flags, for what happened during the tick.
+const COLLISION: u8 = 1;
+const GRIDLOCK: u8 = 2;
+// Vehicle status bits.
+const COLLIDED: u8 = 1;
+const WRECKED: u8 = 2;
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+// Fastest playback either way, in ticks per tick of real time.
+const MAX_RATE: i32 = 8;
+const BAR_HEIGHT: u32 = 84;
+const MARK_WIDTH: u32 = 2;
+const MARK_HEIGHT: u32 = 8;
+
+// Where and how one vehicle was at the end of a tick: enough to draw it again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedVehicle {
+    pub id: VehicleId,
+    pub kind: VehicleKind,
+    pub approach: Direction,
+    pub direction: Direction,
+    pub route: Route,
+    pub lane: usize,
+    pub x: f32,
+    pub y: f32,
+    pub heading: (f32, f32),
+    pub speed: f32,
+    pub collided: bool,
+    pub wrecked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickRecord {
+    pub time: Duration,
+    // The light each approach showed, in North, South, East, West order.
+    pub lights: [LightState; 4],
+    pub collision: bool,
+    pub gridlock: bool,
+    pub vehicles: Vec<RecordedVehicle>,
+}
+
+// Writes every vehicle and light at the end of every tick to a deflated binary stream, for
+// `--playback` to step through afterwards. Cyclists and pedestrians are left out. Times
+// are as the simulation had them, so a reset shows up as time going back to zero.
+pub struct TapeWriter {
+    path: PathBuf,
+    out: GzEncoder<BufWriter<File>>,
+    last_time: Option<Duration>,
+    // The run's collision and gridlock counts as of the last tick written.
+    collisions: u32,
+    gridlocks: u32,
+    ticks: usize,
+}
+
+impl TapeWriter {
+    pub fn create(path: &Path) -> Result<Self, SimError> {
+        let file = File::create(path)
+            .map_err(|source| SimError::Output { path: path.to_path_buf(), source })?;
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            out: GzEncoder::new(BufWriter::new(file), Compression::default()),
+            last_time: None,
+            collisions: 0,
+            gridlocks: 0,
+            ticks: 0,
+        };
+        writer.write(|out| {
+            out.write_all(MAGIC)?;
+            out.write_all(&[VERSION])
+        })?;
+        Ok(writer)
+    }
+
+    // Writes the tick the simulation has just finished; front ends call this after every
+    // update. A tick already written, as while paused, is skipped.
+    pub fn record(&mut self, simulation: &TrafficSimulation) -> Result<(), SimError> {
+        let time = simulation.time.now();
+        if self.last_time == Some(time) {
+            return Ok(());
+        }
+        self.last_time = Some(time);
+        self.ticks += 1;
+        // The counts only drop when the run is reset.
+        let stats = &simulation.stats;
+        let mut flags = 0;
+        if stats.collisions > self.collisions {
+            flags |= COLLISION;
+        }
+        if stats.gridlocks > self.gridlocks {
+            flags |= GRIDLOCK;
+        }
+        (self.collisions, self.gridlocks) = (stats.collisions, stats.gridlocks);
+        let light = simulation.traffic_light();
+        let vehicles = simulation.vehicles();
+        self.write(|out| {
+            out.write_all(&(time.as_millis() as u64).to_le_bytes())?;
+            out.write_all(&[flags])?;
+            for direction in DIRECTIONS {
+                out.write_all(&[light_code(light.state_for(direction))])?;
+            }
+            out.write_all(&(vehicles.len() as u32).to_le_bytes())?;
+            for vehicle in vehicles.iter() {
+                write_vehicle(out, vehicle)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn finish(self) -> Result<(), SimError> {
+        let path = self.path;
+        let ticks = self.ticks;
+        self.out
+            .finish()
+            .and_then(|mut out| out.flush())
+            .map_err(|source| SimError::Output { path: path.clone(), source })?;
+        tracing::info!("{} ticks recorded to {}", ticks, path.display());
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        f: impl FnOnce(&mut GzEncoder<BufWriter<File>>) -> io::Result<()>
+    ) -> Result<(), SimError> {
+        f(&mut self.out).map_err(|source| SimError::Output { path: self.path.clone(), source })
+    }
+}
+
+fn write_vehicle(out: &mut impl Write, vehicle: &Vehicle) -> io::Result<()> {
+    out.write_all(&vehicle.id.0.to_le_bytes())?;
+    for value in [vehicle.x, vehicle.y, vehicle.heading.0, vehicle.heading.1, vehicle.speed] {
+        out.write_all(&value.to_le_bytes())?;
+    }
+    let kind = match vehicle.kind {
+        VehicleKind::Car => 0,
+        VehicleKind::Bus => 1,
+    };
+    let route = match vehicle.route {
+        Route::Straight => 0,
+        Route::Left => 1,
+        Route::Right => 2,
+    };
+    let mut status = 0;
+    if vehicle.collided {
+        status |= COLLIDED;
+    }
+    if vehicle.wrecked_until.is_some() {
+        status |= WRECKED;
+    }
+    let code = |direction| DIRECTIONS.iter().position(|&d| d == direction).unwrap_or(0) as u8;
+    let (approach, direction) = (code(vehicle.approach), code(vehicle.direction));
+    out.write_all(&[kind, approach, direction, route, vehicle.lane as u8, status])
+}
+
+fn light_code(state: LightState) -> u8 {
+    match state {
+        LightState::Red => 0,
+        LightState::Yellow => 1,
+        LightState::Green => 2,
+        LightState::FlashingYellow => 3,
+        LightState::FlashingRed => 4,
+    }
+}
+
+// A recording read back whole, with the ticks something went wrong in.
+#[derive(Debug, Clone, Default)]
+pub struct Tape {
+    pub ticks: Vec<TickRecord>,
+}
+
+impl Tape {
+    // A recording cut short, as by a crash, plays back as far as it goes.
+    pub fn load(path: &Path) -> Result<Self, SimError> {
+        let bad = |reason: String| SimError::Recording { path: path.to_path_buf(), reason };
+        let file = File::open(path).map_err(|e| bad(e.to_string()))?;
+        let mut input = GzDecoder::new(BufReader::new(file));
+        let mut header = [0; 7];
+        input.read_exact(&mut header).map_err(|e| bad(e.to_string()))?;
+        if &header[..6] != MAGIC {
+            return Err(bad("not a state recording".to_string()));
+        }
+        if header[6] != VERSION {
+            return Err(bad(format!("recording version {} is not supported", header[6])));
+        }
+        let mut ticks = Vec::new();
+        loop {
+            match read_tick(&mut input) {
+                Ok(Some(tick)) => ticks.push(tick),
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!("{}: recording cut short: {}", path.display(), e);
+                    break;
+                }
+            }
+        }
+        if ticks.is_empty() {
+            return Err(bad("no ticks recorded".to_string()));
+        }
+        Ok(Self { ticks })
+    }
+
+    // Simulated time between recorded ticks, as the recording's config had it.
+    pub fn tick(&self) -> Duration {
+        match &self.ticks[..] {
+            [first, second, ..] if second.time > first.time => second.time - first.time,
+            _ => TICK,
+        }
+    }
+
+    // Ticks with a collision or gridlock in them, in order.
+    pub fn marks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ticks
+            .iter()
+            .enumerate()
+            .filter(|(_, tick)| tick.collision || tick.gridlock)
+            .map(|(index, _)| index)
+    }
+}
+
+// None at the clean end of the stream.
+fn read_tick(input: &mut impl Read) -> io::Result<Option<TickRecord>> {
+    let mut time = [0; 8];
+    match input.read(&mut time[..1])? {
+        0 => return Ok(None),
+        _ => input.read_exact(&mut time[1..])?,
+    }
+    let mut header = [0; 9];
+    input.read_exact(&mut header)?;
+    let flags = header[0];
+    let mut lights = [LightState::Red; 4];
+    for (light, &code) in lights.iter_mut().zip(&header[1..5]) {
+        *light = match code {
+            0 => LightState::Red,
+            1 => LightState::Yellow,
+            2 => LightState::Green,
+            3 => LightState::FlashingYellow,
+            4 => LightState::FlashingRed,
+            _ => return Err(invalid("light state")),
+        };
+    }
+    let count = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
+    let mut vehicles = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        vehicles.push(read_vehicle(input)?);
+    }
+    Ok(
+        Some(TickRecord {
+            time: Duration::from_millis(u64::from_le_bytes(time)),
+            lights,
+            collision: flags & COLLISION != 0,
+            gridlock: flags & GRIDLOCK != 0,
+            vehicles,
+        })
+    )
+}
+
+fn read_vehicle(input: &mut impl Read) -> io::Result<RecordedVehicle> {
+    let mut bytes = [0; 30];
+    input.read_exact(&mut bytes)?;
+    let word = |i: usize| [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+    let float = |i: usize| f32::from_le_bytes(word(i));
+    let kind = match bytes[24] {
+        0 => VehicleKind::Car,
+        1 => VehicleKind::Bus,
+        _ => return Err(invalid("vehicle kind")),
+    };
+    let direction = |byte: u8| DIRECTIONS.get(byte as usize).copied();
+    let approach = direction(bytes[25]).ok_or_else(|| invalid("approach"))?;
+    let heading_closest = direction(bytes[26]).ok_or_else(|| invalid("direction"))?;
+    let route = match bytes[27] {
+        0 => Route::Straight,
+        1 => Route::Left,
+        2 => Route::Right,
+        _ => return Err(invalid("route")),
+    };
+    Ok(RecordedVehicle {
+        id: VehicleId(u32::from_le_bytes(word(0))),
+        kind,
+        approach,
+        direction: heading_closest,
+        route,
+        lane: bytes[28] as usize,
+        x: float(4),
+        y: float(8),
+        heading: (float(12), float(16)),
+        speed: float(20),
+        collided: bytes[29] & COLLIDED != 0,
+        wrecked: bytes[29] & WRECKED != 0,
+    })
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad {} in recording", what))
+}
+
+// Moves through a tape at a rate of whole ticks per tick of real time, backwards when
+// negative, and sets a simulation up to show the tick it is at.
+pub struct Playback {
+    pub tape: Tape,
+    position: usize,
+    rate: i32,
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new(tape: Tape) -> Self {
+        Self { tape, position: 0, rate: 1, paused: false }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn current(&self) -> &TickRecord {
+        &self.tape.ticks[self.position]
+    }
+
+    // Moves on by `ticks` of real time, stopping at either end.
+    pub fn advance(&mut self, ticks: u32) {
+        if self.paused {
+            return;
+        }
+        let target = (self.position as i64) + (ticks as i64) * (self.rate as i64);
+        let last = (self.tape.ticks.len() - 1) as i64;
+        if target <= 0 || target >= last {
+            self.paused = true;
+        }
+        self.position = target.clamp(0, last) as usize;
+    }
+
+    // Plays forward, faster each time up to MAX_RATE.
+    pub fn fast_forward(&mut self) {
+        self.rate = if self.rate < 1 { 1 } else { (self.rate * 2).min(MAX_RATE) };
+        self.paused = false;
+    }
+
+    // Plays backward, faster each time up to MAX_RATE.
+    pub fn rewind(&mut self) {
+        self.rate = if self.rate > -1 { -1 } else { (self.rate * 2).max(-MAX_RATE) };
+        self.paused = false;
+    }
+
+    // One tick either way, pausing there.
+    pub fn step(&mut self, forward: bool) {
+        self.paused = true;
+        self.seek(if forward { self.position + 1 } else { self.position.saturating_sub(1) });
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.tape.ticks.len() - 1);
+    }
+
+    // Jumps to the next collision or gridlock, or the one before, pausing there.
+    pub fn jump_to_mark(&mut self, forward: bool) -> bool {
+        let position = self.position;
+        let mark = if forward {
+            self.tape.marks().find(|&mark| mark > position)
+        } else {
+            self.tape.marks().filter(|&mark| mark < position).last()
+        };
+        if let Some(mark) = mark {
+            self.paused = true;
+            self.position = mark;
+        }
+        mark.is_some()
+    }
+
+    // Clears `simulation`'s road users and lights and puts those of the current tick in
+    // their place. Nothing else about the simulation changes.
+    pub fn pose(&self, simulation: &mut TrafficSimulation) {
+        let tick = self.current();
+        simulation.time = SimClock::at(tick.time);
+        simulation.traffic_light_mut().show(tick.lights);
+        let vehicles = tick.vehicles.iter().map(|recorded| {
+            let position = (recorded.x, recorded.y);
+            let mut vehicle = Vehicle::new(
+                recorded.kind,
+                recorded.approach,
+                recorded.route,
+                recorded.lane,
+                position,
+                recorded.speed,
+                0.0
+            );
+            vehicle.id = recorded.id;
+            vehicle.heading = recorded.heading;
+            vehicle.direction = recorded.direction;
+            vehicle.place();
+            vehicle.collided = recorded.collided;
+            vehicle.wrecked_until = recorded.wrecked.then_some(tick.time);
+            vehicle
+        });
+        simulation.replace_vehicles(vehicles);
+    }
+
+    // A scrub bar along the bottom of the window with the collisions and gridlocks marked
+    // over it, and buttons to rewind, pause and fast-forward.
+    pub fn draw(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        palette: &Palette,
+        mouse: Mouse
+    ) -> Result<(), RenderError> {
+        let area = bar_area();
+        let last = self.tape.ticks.len() - 1;
+        let state = match (self.paused, self.rate) {
+            (true, _) => "PAUSED".to_string(),
+            (false, rate) if rate < 0 => format!("REWINDING {}X", -rate),
+            (false, rate) => format!("PLAYING {}X", rate),
+        };
+        let label = format!(
+            "TICK {}/{} AT {:.2}S - {}",
+            self.position,
+            last,
+            self.current().time.as_secs_f32(),
+            state
+        );
+        // The marks sit just above the scrub bar's track, which is inset like the panel's.
+        let track_x = area.x + 10;
+        let track_width = (area.w - 20) as f32;
+        for mark in self.tape.marks() {
+            let tick = &self.tape.ticks[mark];
+            let color = if tick.collision { palette.red } else { palette.yellow };
+            let x = track_x + (((mark as f32) / (last.max(1) as f32)) * track_width) as i32;
+            let y = area.y - (MARK_HEIGHT as i32);
+            renderer.draw_rect(Rect::new(x, y, MARK_WIDTH, MARK_HEIGHT), color)?;
+        }
+        let mut panel = Panel::begin(renderer, palette, mouse, area)?;
+        let position = self.position as f32;
+        if let Some(position) = panel.slider(&label, position, 0.0..=last.max(1) as f32)? {
+            self.seek(position.round() as usize);
+        }
+        let pause = if self.paused { "PLAY" } else { "PAUSE" };
+        match panel.buttons(&["<<", pause, ">>"])? {
+            Some(0) => self.rewind(),
+            Some(1) => self.paused = !self.paused,
+            Some(_) => self.fast_forward(),
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+fn bar_area() -> Rect {
+    let width = WINDOW_WIDTH - 20;
+    Rect::new(10, (WINDOW_HEIGHT - BAR_HEIGHT - 10) as i32, width, BAR_HEIGHT)
+}
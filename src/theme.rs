@@ -0,0 +1,137 @@
+use serde::Deserialize;
+
+use crate::render::Color;
+use crate::traffic_light::LightState;
+use crate::vehicle::{ Route, Vehicle, VehicleKind };
+
+// The set of colors roads, road users, signals and panels are drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    // Routes and signals in the Okabe-Ito palette, which stays distinct with the common
+    // forms of color blindness.
+    Colorblind,
+}
+
+impl Theme {
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            Theme::Dark => &DARK,
+            Theme::Light => &LIGHT,
+            Theme::Colorblind => &COLORBLIND,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub ground: Color,
+    pub road: Color,
+    pub bike_lane: Color,
+    pub marking: Color,
+    pub center_line: Color,
+    pub bus_stop: Color,
+    // Cars by the route they take.
+    pub straight: Color,
+    pub left: Color,
+    pub right: Color,
+    pub bus: Color,
+    pub cyclist: Color,
+    pub pedestrian: Color,
+    pub red: Color,
+    pub yellow: Color,
+    pub green: Color,
+    // On-screen text, and the panels and controls it sits on.
+    pub text: Color,
+    pub panel: Color,
+    pub control: Color,
+    pub control_hover: Color,
+    pub slider_fill: Color,
+}
+
+impl Palette {
+    pub fn route(&self, route: Route) -> Color {
+        match route {
+            Route::Straight => self.straight,
+            Route::Left => self.left,
+            Route::Right => self.right,
+        }
+    }
+
+    pub fn vehicle(&self, vehicle: &Vehicle) -> Color {
+        match vehicle.kind {
+            VehicleKind::Car => self.route(vehicle.route),
+            VehicleKind::Bus => self.bus,
+        }
+    }
+
+    pub fn light(&self, state: LightState) -> Color {
+        match state {
+            LightState::Red | LightState::FlashingRed => self.red,
+            LightState::Yellow | LightState::FlashingYellow => self.yellow,
+            LightState::Green => self.green,
+        }
+    }
+}
+
+const DARK: Palette = Palette {
+    ground: Color::rgb(50, 50, 50),
+    road: Color::rgb(100, 100, 100),
+    bike_lane: Color::rgb(80, 120, 90),
+    marking: Color::rgb(255, 255, 255),
+    center_line: Color::rgb(230, 200, 0),
+    bus_stop: Color::rgb(240, 200, 0),
+    straight: Color::rgb(0, 255, 0),
+    left: Color::rgb(255, 255, 0),
+    right: Color::rgb(255, 165, 0),
+    bus: Color::rgb(40, 110, 255),
+    cyclist: Color::rgb(0, 200, 255),
+    pedestrian: Color::rgb(240, 160, 220),
+    red: Color::rgb(255, 0, 0),
+    yellow: Color::rgb(255, 200, 0),
+    green: Color::rgb(0, 255, 0),
+    text: Color::rgb(255, 255, 255),
+    panel: Color::rgba(20, 20, 30, 200),
+    control: Color::rgb(70, 70, 80),
+    control_hover: Color::rgb(100, 100, 120),
+    slider_fill: Color::rgb(90, 150, 230),
+};
+
+const LIGHT: Palette = Palette {
+    ground: Color::rgb(205, 210, 195),
+    road: Color::rgb(150, 150, 155),
+    bike_lane: Color::rgb(130, 175, 140),
+    marking: Color::rgb(255, 255, 255),
+    center_line: Color::rgb(240, 200, 0),
+    bus_stop: Color::rgb(200, 150, 0),
+    straight: Color::rgb(0, 160, 60),
+    left: Color::rgb(235, 195, 0),
+    right: Color::rgb(235, 120, 0),
+    bus: Color::rgb(30, 90, 220),
+    cyclist: Color::rgb(0, 140, 200),
+    pedestrian: Color::rgb(200, 80, 170),
+    red: Color::rgb(220, 0, 0),
+    yellow: Color::rgb(240, 180, 0),
+    green: Color::rgb(0, 190, 0),
+    text: Color::rgb(20, 20, 30),
+    panel: Color::rgba(240, 240, 245, 220),
+    control: Color::rgb(200, 200, 210),
+    control_hover: Color::rgb(175, 175, 195),
+    slider_fill: Color::rgb(60, 120, 210),
+};
+
+const COLORBLIND: Palette = Palette {
+    straight: Color::rgb(86, 180, 233),
+    left: Color::rgb(240, 228, 66),
+    right: Color::rgb(213, 94, 0),
+    bus: Color::rgb(0, 114, 178),
+    cyclist: Color::rgb(0, 158, 115),
+    pedestrian: Color::rgb(204, 121, 167),
+    red: Color::rgb(213, 94, 0),
+    yellow: Color::rgb(240, 228, 66),
+    green: Color::rgb(0, 158, 115),
+    ..DARK
+};
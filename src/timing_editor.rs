@@ -0,0 +1,153 @@
+use std::ops::RangeInclusive;
+
+use crate::config::LightsConfig;
+use crate::error::RenderError;
+use crate::render::{ Rect, Renderer };
+use crate::simulation::TrafficSimulation;
+use crate::traffic_light::Phase;
+use crate::ui::{ Mouse, Panel };
+use crate::WINDOW_WIDTH;
+
+// The arrow keys change the selected time by this much.
+const STEP_SECS: f32 = 0.5;
+
+// A time the editor shows and changes, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Row {
+    Green(Phase),
+    Yellow,
+    AllRed,
+    MinGreen,
+    MaxRed,
+}
+
+const ROWS: [Row; 6] = [
+    Row::Green(Phase::NorthSouth),
+    Row::Green(Phase::EastWest),
+    Row::Yellow,
+    Row::AllRed,
+    Row::MinGreen,
+    Row::MaxRed,
+];
+
+impl Row {
+    fn label(self) -> &'static str {
+        match self {
+            Row::Green(Phase::NorthSouth) => "NORTH-SOUTH GREEN",
+            Row::Green(Phase::EastWest) => "EAST-WEST GREEN",
+            Row::Yellow => "YELLOW",
+            Row::AllRed => "ALL-RED",
+            Row::MinGreen => "MIN GREEN",
+            Row::MaxRed => "MAX RED",
+        }
+    }
+
+    fn range(self) -> RangeInclusive<f32> {
+        match self {
+            Row::Green(_) => 1.0..=40.0,
+            Row::Yellow => 1.0..=6.0,
+            Row::AllRed => 0.0..=5.0,
+            Row::MinGreen => 1.0..=15.0,
+            Row::MaxRed => 10.0..=120.0,
+        }
+    }
+
+    fn get(self, timing: &LightsConfig) -> f32 {
+        match self {
+            Row::Green(phase) => timing.green_for(phase),
+            Row::Yellow => timing.yellow_secs,
+            Row::AllRed => timing.all_red_secs,
+            Row::MinGreen => timing.min_green_secs,
+            Row::MaxRed => timing.max_red_secs,
+        }
+    }
+
+    // Kept in range and to a tenth of a second.
+    fn set(self, timing: &mut LightsConfig, secs: f32) {
+        let range = self.range();
+        let secs = ((secs * 10.0).round() / 10.0).clamp(*range.start(), *range.end());
+        match self {
+            Row::Green(phase) => timing.set_green_for(phase, secs),
+            Row::Yellow => timing.yellow_secs = secs,
+            Row::AllRed => timing.all_red_secs = secs,
+            Row::MinGreen => timing.min_green_secs = secs,
+            Row::MaxRed => timing.max_red_secs = secs,
+        }
+    }
+}
+
+// Each road's green, the yellow and all-red between them, the shortest green the actuated
+// controller gives and the longest red the watchdog allows, dragged on sliders or picked
+// with the up and down arrows and changed with left and right. Changes retime the running
+// lights at once and last through a reset; saving writes them into the config file.
+#[derive(Debug, Clone, Default)]
+pub struct TimingEditor {
+    pub open: bool,
+    selected: usize,
+}
+
+impl TimingEditor {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // Moves the selection `rows` down, or up for negative, wrapping around.
+    pub fn select(&mut self, rows: i32) {
+        let count = ROWS.len() as i32;
+        self.selected = (((self.selected as i32) + rows).rem_euclid(count)) as usize;
+    }
+
+    // Changes the selected time by `steps` key presses, negative to shorten it.
+    pub fn adjust(&self, simulation: &mut TrafficSimulation, steps: f32) {
+        let row = ROWS[self.selected];
+        let mut timing = LightsConfig::from_light(&simulation.traffic_light());
+        let secs = row.get(&timing) + steps * STEP_SECS;
+        row.set(&mut timing, secs);
+        if let Err(e) = simulation.set_timing(timing) {
+            tracing::warn!("{}", e);
+        }
+    }
+
+    // Whether (x, y) is over the editor, so clicks there leave the scene alone.
+    pub fn covers(&self, x: i32, y: i32) -> bool {
+        self.open && area().intersects(&Rect::new(x, y, 1, 1))
+    }
+
+    // On the right below the control panel; returns whether saving was asked for.
+    pub fn draw(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        mouse: Mouse,
+        simulation: &mut TrafficSimulation
+    ) -> Result<bool, RenderError> {
+        let palette = simulation.theme().palette();
+        let mut timing = LightsConfig::from_light(&simulation.traffic_light());
+        let mut changed = false;
+        let mut panel = Panel::begin(renderer, palette, mouse, area())?;
+        panel.label("SIGNAL TIMING")?;
+        for (i, row) in ROWS.into_iter().enumerate() {
+            let marker = if i == self.selected { ">" } else { " " };
+            let label = format!("{} {} {:.1}S", marker, row.label(), row.get(&timing));
+            if let Some(secs) = panel.slider(&label, row.get(&timing), row.range())? {
+                row.set(&mut timing, secs);
+                self.selected = i;
+                changed = true;
+            }
+        }
+        panel.label(&format!("CYCLE {:.1}S", timing.cycle_secs()))?;
+        let clicked = panel.buttons(&["SAVE", "CLOSE"])?;
+        if changed {
+            if let Err(e) = simulation.set_timing(timing) {
+                tracing::warn!("{}", e);
+            }
+        }
+        if clicked == Some(1) {
+            self.open = false;
+        }
+        Ok(clicked == Some(0))
+    }
+}
+
+fn area() -> Rect {
+    Rect::new((WINDOW_WIDTH as i32) - 330, 220, 320, 350)
+}
@@ -0,0 +1,602 @@
+use serde::{ Deserialize, Serialize };
+use std::time::Duration;
+
+use crate::vehicle::{ opposite, Direction, Route };
+
+pub const GREEN_TIME: Duration = Duration::from_secs(6);
+pub const YELLOW_TIME: Duration = Duration::from_secs(2);
+pub const ALL_RED_TIME: Duration = Duration::from_secs(1);
+pub const MIN_GREEN_TIME: Duration = Duration::from_secs(3);
+pub const MAX_RED_TIME: Duration = Duration::from_secs(30);
+pub const WALK_TIME: Duration = Duration::from_secs(5);
+pub const CLEARANCE_TIME: Duration = Duration::from_secs(4);
+pub const LEFT_ARROW_TIME: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightState {
+    Red,
+    Yellow,
+    Green,
+    // Flashing operation: go with caution, or stop and go once the way is clear.
+    FlashingYellow,
+    FlashingRed,
+}
+
+impl LightState {
+    pub fn is_flashing(self) -> bool {
+        matches!(self, LightState::FlashingYellow | LightState::FlashingRed)
+    }
+
+    // Whether traffic may carry on through without stopping first.
+    pub fn is_go(self) -> bool {
+        matches!(self, LightState::Green | LightState::FlashingYellow)
+    }
+}
+
+// What the pedestrian signals show. Pedestrians may only start crossing on `Walk`; the
+// flashing don't-walk gives those already out time to finish.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalkSignal {
+    Walk,
+    FlashingDontWalk,
+    DontWalk,
+}
+
+// How an approach's lefts are signalled. Permissive lefts only get the green ball and give
+// way to oncoming traffic. Protected-permissive ones also get a green arrow, with the
+// oncoming approach held at red, at the start or the end of their road's green.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeftTurnPhasing {
+    #[default]
+    Permissive,
+    ProtectedPermissive,
+}
+
+// Left-turn phasing per approach, by direction of travel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeftTurns {
+    pub north: LeftTurnPhasing,
+    pub south: LeftTurnPhasing,
+    pub east: LeftTurnPhasing,
+    pub west: LeftTurnPhasing,
+}
+
+impl LeftTurns {
+    pub fn for_direction(&self, direction: Direction) -> LeftTurnPhasing {
+        match direction {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East => self.east,
+            Direction::West => self.west,
+        }
+    }
+
+    pub fn is_protected(&self, direction: Direction) -> bool {
+        self.for_direction(direction) == LeftTurnPhasing::ProtectedPermissive
+    }
+}
+
+// Whether protected lefts' arrow leads their road's green, followed by a yellow arrow and
+// then the green ball as permissive lefts, or lags it: the oncoming approach gets its
+// yellow and red while the lefts, permissive until then, get the arrow to the road's end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeftTurnSequence {
+    #[default]
+    Lead,
+    Lag,
+}
+
+// Left-turn sequence per approach, by direction of travel; only protected lefts use it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeftTurnSequences {
+    pub north: LeftTurnSequence,
+    pub south: LeftTurnSequence,
+    pub east: LeftTurnSequence,
+    pub west: LeftTurnSequence,
+}
+
+impl LeftTurnSequences {
+    pub fn for_direction(&self, direction: Direction) -> LeftTurnSequence {
+        match direction {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East => self.east,
+            Direction::West => self.west,
+        }
+    }
+}
+
+// Where the served road's green has got to with its protected lefts' arrows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArrowStage {
+    // The leading lefts' arrow, green then yellow.
+    Leading(LightState),
+    // The yellow for the traffic facing lagging lefts.
+    Cutting,
+    // The lagging lefts' arrow, to the end of the road's green.
+    Lagging,
+}
+
+// What one approach's signal shows: the ball for all its traffic, and the left arrow
+// while its protected lefts have one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalHead {
+    pub ball: LightState,
+    pub left_arrow: Option<LightState>,
+}
+
+impl SignalHead {
+    // The light traffic taking `route` obeys. A yellow arrow under a green ball ends the
+    // protection, not the lefts' green.
+    pub fn for_route(self, route: Route) -> LightState {
+        match (route, self.left_arrow) {
+            (Route::Left, Some(LightState::Green)) => LightState::Green,
+            (Route::Left, Some(LightState::Yellow)) if self.ball != LightState::Green => {
+                LightState::Yellow
+            }
+            _ => self.ball,
+        }
+    }
+
+    // Whether traffic taking `route` goes without giving way to oncoming traffic.
+    pub fn protects(self, route: Route) -> bool {
+        route == Route::Left && self.left_arrow == Some(LightState::Green)
+    }
+}
+
+// The two opposing approaches of a road share a phase, so lefts on green are permissive
+// unless they have an arrow.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    NorthSouth,
+    EastWest,
+}
+
+impl Phase {
+    pub fn serves(self, direction: Direction) -> bool {
+        match self {
+            Phase::NorthSouth => matches!(direction, Direction::North | Direction::South),
+            Phase::EastWest => matches!(direction, Direction::East | Direction::West),
+        }
+    }
+
+    pub fn next(self) -> Phase {
+        match self {
+            Phase::NorthSouth => Phase::EastWest,
+            Phase::EastWest => Phase::NorthSouth,
+        }
+    }
+}
+
+// Fixed-time controller: the served road gets green then yellow while the other is red,
+// then both are held at red for the all-red interval so traffic still in the intersection
+// clears before the other road's green. A pedestrian call inserts a walk phase after the
+// next yellow, with every approach held at red through the walk and the clearance interval
+// after it, before the other road's green. A train at the level crossing preempts the
+// controller: the road it holds is kept at green until the gates are up, then the other
+// road is served first. A road with no traffic into the intersection is skipped, leaving
+// the other at green. Approaches with protected-permissive lefts get their arrow for
+// `left_arrow_time`, with the opposite approach at red until the arrow is out: leading
+// arrows at the start of the road's green, followed by a yellow arrow, lagging ones after
+// the opposite approach's yellow once the green is up, taking the road's yellow with
+// them. A green cut short still runs its lagging arrows. In flashing operation the cycle
+// stands still: one road flashes yellow and the other red until the served road's green
+// resumes. Under manual control the lights only change when the user picks a road, and
+// show no arrows. Times are simulated time since the start of the run.
+pub struct TrafficLight {
+    pub phase: Phase,
+    pub state: LightState,
+    // Average green per road; the north-south road gets `north_south_split` of the total.
+    pub green_time: Duration,
+    pub north_south_split: f32,
+    pub yellow_time: Duration,
+    // Zero goes straight from one road's yellow to the other's green.
+    pub all_red_time: Duration,
+    // Shortest green a controller that ends greens early gives a road.
+    pub min_green_time: Duration,
+    // Longest a road with traffic waiting is kept at red before its green is forced.
+    pub max_red_time: Duration,
+    pub walk_time: Duration,
+    pub clearance_time: Duration,
+    pub left_arrow_time: Duration,
+    pub left_turns: LeftTurns,
+    pub left_turn_sequences: LeftTurnSequences,
+    pub walk_signal: WalkSignal,
+    // Set while the served road has protected lefts on an arrow or about to get one, and
+    // through its yellow so the approach held for them stays at red.
+    arrow: Option<ArrowStage>,
+    // A pedestrian has pressed a button since the last walk phase.
+    walk_called: bool,
+    // The road held at green while a train is at the crossing.
+    preempted_for: Option<Phase>,
+    // A road whose ends are both one-way out of the intersection, or missing.
+    idle_phase: Option<Phase>,
+    // The road flashing yellow during flashing operation.
+    flashing_for: Option<Phase>,
+    // The user runs the lights, with the controller, flashing and preemption suspended.
+    manual: bool,
+    last_change: Duration,
+    // When the served road's green began, and so the other road's red.
+    phase_started: Duration,
+}
+
+impl Default for TrafficLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrafficLight {
+    pub fn new() -> Self {
+        Self {
+            phase: Phase::NorthSouth,
+            state: LightState::Green,
+            green_time: GREEN_TIME,
+            north_south_split: 0.5,
+            yellow_time: YELLOW_TIME,
+            all_red_time: ALL_RED_TIME,
+            min_green_time: MIN_GREEN_TIME,
+            max_red_time: MAX_RED_TIME,
+            walk_time: WALK_TIME,
+            clearance_time: CLEARANCE_TIME,
+            left_arrow_time: LEFT_ARROW_TIME,
+            left_turns: LeftTurns::default(),
+            left_turn_sequences: LeftTurnSequences::default(),
+            walk_signal: WalkSignal::DontWalk,
+            arrow: None,
+            walk_called: false,
+            preempted_for: None,
+            idle_phase: None,
+            flashing_for: None,
+            manual: false,
+            last_change: Duration::ZERO,
+            phase_started: Duration::ZERO,
+        }
+    }
+
+    // Advances the cycle, returning whether any light changed.
+    pub fn update(&mut self, now: Duration) -> bool {
+        if self.flashing_for.is_some() || self.manual {
+            return false;
+        }
+        let elapsed = now.saturating_sub(self.last_change);
+        let green_elapsed = now.saturating_sub(self.phase_started);
+        let arrow_ends = match self.arrow {
+            Some(ArrowStage::Leading(LightState::Green) | ArrowStage::Lagging) => {
+                elapsed >= self.left_arrow_time
+            }
+            Some(ArrowStage::Leading(_) | ArrowStage::Cutting) => elapsed >= self.yellow_time,
+            None => false,
+        };
+        let held = self.preempted_for == Some(self.phase);
+        // A road held for a train keeps its lagging arrow until the train has gone.
+        let arrow_ends = arrow_ends && !(held && self.arrow == Some(ArrowStage::Lagging));
+        let green_ends = green_elapsed >= self.phase_green_time() && self.green_can_end();
+        match self.state {
+            LightState::Green if arrow_ends => self.advance_arrow(),
+            LightState::Green if held => {
+                return false;
+            }
+            LightState::Green if green_ends && !self.lag_underway() => self.finish_green(),
+            LightState::Yellow if elapsed >= self.yellow_time && self.walk_called => {
+                self.walk_called = false;
+                self.state = LightState::Red;
+                self.walk_signal = WalkSignal::Walk;
+            }
+            LightState::Yellow if elapsed >= self.yellow_time && !self.all_red_time.is_zero() => {
+                self.state = LightState::Red;
+            }
+            LightState::Yellow if elapsed >= self.yellow_time => self.start_next_phase(now),
+            LightState::Red if self.is_all_red() && elapsed >= self.all_red_time => {
+                self.start_next_phase(now);
+            }
+            LightState::Red if self.is_walk() && elapsed >= self.walk_time => {
+                self.walk_signal = WalkSignal::FlashingDontWalk;
+            }
+            LightState::Red if self.clearance_left(now) == Some(Duration::ZERO) => {
+                self.walk_signal = WalkSignal::DontWalk;
+                self.start_next_phase(now);
+            }
+            _ => {
+                return false;
+            }
+        }
+        self.last_change = now;
+        true
+    }
+
+    fn start_next_phase(&mut self, now: Duration) {
+        let next = self.phase.next();
+        let next = if self.idle_phase == Some(next) { self.phase } else { next };
+        self.phase = self.preempted_for.unwrap_or(next);
+        self.state = LightState::Green;
+        self.phase_started = now;
+        self.start_leading_arrow();
+    }
+
+    // Lights the arrow if any approach on the served road has leading lefts.
+    fn start_leading_arrow(&mut self) {
+        let leading = self.served(|light, d| light.leads(d));
+        self.arrow = leading.then_some(ArrowStage::Leading(LightState::Green));
+    }
+
+    fn advance_arrow(&mut self) {
+        self.arrow = match self.arrow {
+            Some(ArrowStage::Leading(LightState::Green)) => {
+                Some(ArrowStage::Leading(LightState::Yellow))
+            }
+            Some(ArrowStage::Cutting) => Some(ArrowStage::Lagging),
+            Some(ArrowStage::Lagging) => {
+                self.state = LightState::Yellow;
+                Some(ArrowStage::Lagging)
+            }
+            _ => None,
+        };
+    }
+
+    // Ends the served road's green, through its lagging lefts' arrow if it has any.
+    fn finish_green(&mut self) {
+        if self.arrow.is_none() && self.served(|light, d| light.lags(d)) {
+            self.arrow = Some(ArrowStage::Cutting);
+        } else {
+            self.state = LightState::Yellow;
+        }
+    }
+
+    // The green is already on its way out through a lagging arrow.
+    fn lag_underway(&self) -> bool {
+        matches!(self.arrow, Some(ArrowStage::Cutting | ArrowStage::Lagging))
+    }
+
+    // Whether `test` holds for any approach on the served road.
+    fn served(&self, test: impl Fn(&Self, Direction) -> bool) -> bool {
+        let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+        directions.into_iter().any(|d| self.phase.serves(d) && test(self, d))
+    }
+
+    fn leads(&self, direction: Direction) -> bool {
+        self.left_turns.is_protected(direction) &&
+            self.left_turn_sequences.for_direction(direction) == LeftTurnSequence::Lead
+    }
+
+    fn lags(&self, direction: Direction) -> bool {
+        self.left_turns.is_protected(direction) &&
+            self.left_turn_sequences.for_direction(direction) == LeftTurnSequence::Lag
+    }
+
+    fn phase_green_time(&self) -> Duration {
+        self.green_for(self.phase)
+    }
+
+    // The full green `phase` gets each cycle, its share of both roads' green.
+    pub fn green_for(&self, phase: Phase) -> Duration {
+        let share = match phase {
+            Phase::NorthSouth => self.north_south_split,
+            Phase::EastWest => 1.0 - self.north_south_split,
+        };
+        (self.green_time * 2).mul_f32(share)
+    }
+
+    // How long the road `direction` is on has been red; zero while it is being served.
+    pub fn red_time(&self, direction: Direction, now: Duration) -> Duration {
+        if self.phase.serves(direction) {
+            Duration::ZERO
+        } else {
+            now.saturating_sub(self.phase_started)
+        }
+    }
+
+    // Cuts the current green short so the other road is served next. Returns whether the
+    // light changed.
+    pub fn end_green(&mut self, now: Duration) -> bool {
+        let held =
+            self.preempted_for == Some(self.phase) ||
+            !self.green_can_end() ||
+            self.flashing_for.is_some() ||
+            self.manual;
+        if self.state != LightState::Green || held || self.lag_underway() {
+            return false;
+        }
+        self.finish_green();
+        self.last_change = now;
+        true
+    }
+
+    // Moves on to `phase` through the usual yellow; returns false if it is already served.
+    pub fn request_phase(&mut self, phase: Phase, now: Duration) -> bool {
+        phase != self.phase && self.end_green(now)
+    }
+
+    // Holds `phase` at green until `release`, ending the other road's green straight away.
+    pub fn preempt(&mut self, phase: Phase, now: Duration) -> bool {
+        self.preempted_for = Some(phase);
+        phase != self.phase && self.end_green(now)
+    }
+
+    // Ends the preemption with the held road's yellow, so the other road goes next.
+    pub fn release(&mut self, now: Duration) -> bool {
+        self.preempted_for.take().is_some() && self.end_green(now)
+    }
+
+    // Skips `phase`, which has no approach to serve, moving straight off it if it is on.
+    pub fn set_idle_phase(&mut self, phase: Option<Phase>) {
+        self.idle_phase = phase;
+        if phase == Some(self.phase) {
+            self.phase = self.phase.next();
+        }
+    }
+
+    // Starts flashing operation with `main_road` flashing yellow, or with None ends it,
+    // giving that road its green. Flashing only starts from a green, without a walk
+    // called, so the cycle is left at a clean point; until then the call is repeated.
+    // Returns whether any light changed.
+    pub fn set_flashing(&mut self, main_road: Option<Phase>, now: Duration) -> bool {
+        if main_road == self.flashing_for || self.manual {
+            return false;
+        }
+        match (self.flashing_for, main_road) {
+            (None, Some(_)) if self.state != LightState::Green || self.walk_called => {
+                return false;
+            }
+            (Some(main_road), None) => {
+                self.phase = main_road;
+                self.state = LightState::Green;
+                self.phase_started = now;
+                self.start_leading_arrow();
+            }
+            _ => {}
+        }
+        self.flashing_for = main_road;
+        self.last_change = now;
+        true
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        self.flashing_for.is_some()
+    }
+
+    // Hands the lights to the user, ending any flashing operation with the main road's
+    // green, or gives them back, with the road last picked at green if none was. Returns
+    // whether any light changed.
+    pub fn set_manual(&mut self, manual: bool, now: Duration) -> bool {
+        if manual == self.manual {
+            return false;
+        }
+        let changed = match manual {
+            true => self.set_flashing(None, now) | self.arrow.take().is_some(),
+            false => self.give_green(self.phase, now),
+        };
+        self.manual = manual;
+        changed
+    }
+
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+
+    // Under manual control, turns every approach red at once, with no yellow.
+    pub fn hold_all_red(&mut self, now: Duration) -> bool {
+        if !self.manual || self.is_all_red() {
+            return false;
+        }
+        self.state = LightState::Red;
+        self.walk_signal = WalkSignal::DontWalk;
+        self.last_change = now;
+        true
+    }
+
+    // Under manual control, gives `phase` its green at once, whatever the other road shows.
+    pub fn give_green(&mut self, phase: Phase, now: Duration) -> bool {
+        if !self.manual || (phase == self.phase && self.state == LightState::Green) {
+            return false;
+        }
+        self.phase = phase;
+        self.state = LightState::Green;
+        self.walk_signal = WalkSignal::DontWalk;
+        self.arrow = None;
+        self.phase_started = now;
+        self.last_change = now;
+        true
+    }
+
+    // The green only gives way to a road with traffic to serve, a walk or a train.
+    fn green_can_end(&self) -> bool {
+        self.idle_phase != Some(self.phase.next()) ||
+            self.walk_called ||
+            self.preempted_for.is_some()
+    }
+
+    pub fn is_preempted(&self) -> bool {
+        self.preempted_for.is_some()
+    }
+
+    // Asks for a walk phase at the end of the current green.
+    pub fn call_walk(&mut self) {
+        self.walk_called = true;
+    }
+
+    pub fn walk_called(&self) -> bool {
+        self.walk_called
+    }
+
+    // Whether pedestrians may start crossing.
+    pub fn is_walk(&self) -> bool {
+        self.walk_signal == WalkSignal::Walk
+    }
+
+    // Time left to finish crossing while the don't-walk flashes.
+    pub fn clearance_left(&self, now: Duration) -> Option<Duration> {
+        (self.walk_signal == WalkSignal::FlashingDontWalk).then(|| {
+            self.clearance_time.saturating_sub(now.saturating_sub(self.last_change))
+        })
+    }
+
+    // Both roads are at red between one's yellow and the other's green, with no walk.
+    pub fn is_all_red(&self) -> bool {
+        self.state == LightState::Red && self.walk_signal == WalkSignal::DontWalk
+    }
+
+    // Time lost to each change of road: the yellow and the all-red after it.
+    pub fn change_interval(&self) -> Duration {
+        self.yellow_time + self.all_red_time
+    }
+
+    // Shows `states`, by approach in North, South, East, West order, as when playing back a
+    // recording. Opposite approaches share a road's state; nothing is timed from this.
+    pub fn show(&mut self, states: [LightState; 4]) {
+        let [north, _, east, _] = states;
+        self.arrow = None;
+        self.flashing_for = match (north, east) {
+            (LightState::FlashingYellow, _) => Some(Phase::NorthSouth),
+            (_, LightState::FlashingYellow) => Some(Phase::EastWest),
+            _ => None,
+        };
+        (self.phase, self.state) = match (north, east) {
+            (LightState::Red, LightState::Red) => (self.phase, LightState::Red),
+            (LightState::Red, state) => (Phase::EastWest, state),
+            (state, _) => (Phase::NorthSouth, state),
+        };
+    }
+
+    pub fn state_for(&self, direction: Direction) -> LightState {
+        match self.flashing_for {
+            Some(main_road) if main_road.serves(direction) => LightState::FlashingYellow,
+            Some(_) => LightState::FlashingRed,
+            None if self.phase.serves(direction) => self.served_state(direction),
+            None => LightState::Red,
+        }
+    }
+
+    // The ball and arrow `direction`'s signal shows.
+    pub fn head_for(&self, direction: Direction) -> SignalHead {
+        let lit = match self.arrow {
+            _ if self.flashing_for.is_some() || !self.phase.serves(direction) => None,
+            Some(ArrowStage::Leading(arrow)) if self.leads(direction) => Some(arrow),
+            Some(ArrowStage::Lagging) if self.lags(direction) => Some(LightState::Green),
+            _ => None,
+        };
+        let left_arrow = match self.state {
+            LightState::Green => lit,
+            LightState::Yellow => lit.map(|_| LightState::Yellow),
+            _ => None,
+        };
+        SignalHead { ball: self.state_for(direction), left_arrow }
+    }
+
+    // The ball on an approach of the served road, held back while the opposite approach's
+    // lefts have their arrow.
+    fn served_state(&self, direction: Direction) -> LightState {
+        let facing = opposite(direction);
+        match self.arrow {
+            Some(ArrowStage::Leading(_)) if self.leads(facing) => LightState::Red,
+            Some(ArrowStage::Cutting) if self.lags(facing) => LightState::Yellow,
+            Some(ArrowStage::Lagging) if self.lags(facing) => LightState::Red,
+            _ => self.state,
+        }
+    }
+}
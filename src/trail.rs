@@ -0,0 +1,86 @@
+use crate::error::RenderError;
+use crate::render::{ Color, Rect, Renderer };
+use crate::units::View;
+
+pub const TRAIL_POINTS: usize = 64;
+// Distance travelled between recorded positions.
+const TRAIL_SPACING: f32 = 0.5;
+// In pixels, whatever the scale.
+const DOT_SIZE: u32 = 3;
+
+// Recent positions of a vehicle, oldest first, kept in a ring inline so vehicles stay
+// `Copy`. Only the last TRAIL_POINTS are kept.
+#[derive(Debug, Clone, Copy)]
+pub struct Trail {
+    points: [(f32, f32); TRAIL_POINTS],
+    len: usize,
+    // Index of the oldest point once the ring is full.
+    start: usize,
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Self { points: [(0.0, 0.0); TRAIL_POINTS], len: 0, start: 0 }
+    }
+}
+
+impl Trail {
+    // Adds `position` once it is TRAIL_SPACING from the last recorded one.
+    pub fn record(&mut self, position: (f32, f32)) {
+        if let Some(last) = self.points().last() {
+            let (dx, dy) = (position.0 - last.0, position.1 - last.1);
+            if dx * dx + dy * dy < TRAIL_SPACING * TRAIL_SPACING {
+                return;
+            }
+        }
+        if self.len < TRAIL_POINTS {
+            self.points[self.len] = position;
+            self.len += 1;
+        } else {
+            self.points[self.start] = position;
+            self.start = (self.start + 1) % TRAIL_POINTS;
+        }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        (0..self.len).map(move |i| self.points[(self.start + i) % TRAIL_POINTS])
+    }
+
+    // Older parts of the trail fade out.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        view: &View,
+        color: Color
+    ) -> Result<(), RenderError> {
+        let points: Vec<(f32, f32)> = self.points().collect();
+        for (i, pair) in points.windows(2).enumerate() {
+            let recency = ((i + 2) as f32) / (points.len() as f32);
+            let faded = Color::rgba(color.r, color.g, color.b, (recency * 255.0) as u8);
+            draw_line(renderer, view, pair[0], pair[1], faded)?;
+        }
+        Ok(())
+    }
+}
+
+// A line of small squares between two points in the world, as the renderers only draw
+// rectangles.
+pub fn draw_line(
+    renderer: &mut dyn Renderer,
+    view: &View,
+    from: (f32, f32),
+    to: (f32, f32),
+    color: Color
+) -> Result<(), RenderError> {
+    let (from, to) = (view.point(from.0, from.1), view.point(to.0, to.1));
+    let (from, to) = ((from.0 as f32, from.1 as f32), (to.0 as f32, to.1 as f32));
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = ((dx * dx + dy * dy).sqrt() / (DOT_SIZE as f32)).ceil().max(1.0) as usize;
+    let half = (DOT_SIZE as f32) / 2.0;
+    for step in 0..=steps {
+        let t = (step as f32) / (steps as f32);
+        let (x, y) = (from.0 + dx * t - half, from.1 + dy * t - half);
+        renderer.draw_rect(Rect::new(x as i32, y as i32, DOT_SIZE, DOT_SIZE), color)?;
+    }
+    Ok(())
+}
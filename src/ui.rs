@@ -0,0 +1,116 @@
+use std::ops::RangeInclusive;
+
+use crate::error::RenderError;
+use crate::render::{ font, Rect, Renderer };
+use crate::theme::Palette;
+
+const PADDING: i32 = 10;
+const SPACING: i32 = 8;
+const TRACK_HEIGHT: i32 = 12;
+const BUTTON_HEIGHT: i32 = 26;
+
+// Mouse state for the current frame, fed from the window's events.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mouse {
+    pub x: i32,
+    pub y: i32,
+    pub down: bool,
+    // Whether the left button went down this frame.
+    pub clicked: bool,
+}
+
+impl Mouse {
+    fn over(&self, rect: Rect) -> bool {
+        self.x >= rect.x &&
+            self.x < rect.x + (rect.w as i32) &&
+            self.y >= rect.y &&
+            self.y < rect.y + (rect.h as i32)
+    }
+}
+
+// Immediate-mode widgets stacked top to bottom: each call draws its widget straight away
+// and reports what the mouse did to it this frame.
+pub struct Panel<'a> {
+    renderer: &'a mut dyn Renderer,
+    palette: &'a Palette,
+    mouse: Mouse,
+    x: i32,
+    width: i32,
+    cursor_y: i32,
+}
+
+impl<'a> Panel<'a> {
+    pub fn begin(
+        renderer: &'a mut dyn Renderer,
+        palette: &'a Palette,
+        mouse: Mouse,
+        area: Rect
+    ) -> Result<Self, RenderError> {
+        renderer.draw_rect(area, palette.panel)?;
+        Ok(Self {
+            renderer,
+            palette,
+            mouse,
+            x: area.x + PADDING,
+            width: (area.w as i32) - 2 * PADDING,
+            cursor_y: area.y + PADDING,
+        })
+    }
+
+    pub fn label(&mut self, text: &str) -> Result<(), RenderError> {
+        self.renderer.draw_text(text, self.x, self.cursor_y, self.palette.text)?;
+        self.cursor_y += font::GLYPH_HEIGHT * font::GLYPH_SCALE + SPACING;
+        Ok(())
+    }
+
+    // A labelled track with a handle at `value`; returns the new value while it is dragged.
+    pub fn slider(
+        &mut self,
+        label: &str,
+        value: f32,
+        range: RangeInclusive<f32>
+    ) -> Result<Option<f32>, RenderError> {
+        self.renderer.draw_text(label, self.x, self.cursor_y, self.palette.text)?;
+        self.cursor_y += font::GLYPH_HEIGHT * font::GLYPH_SCALE + SPACING / 2;
+        let track = Rect::new(self.x, self.cursor_y, self.width as u32, TRACK_HEIGHT as u32);
+        self.cursor_y += TRACK_HEIGHT + SPACING;
+
+        let (min, max) = (*range.start(), *range.end());
+        let mut changed = None;
+        if self.mouse.down && self.mouse.over(track) {
+            let fraction = ((self.mouse.x - track.x) as f32) / ((track.w - 1) as f32);
+            changed = Some(min + fraction.clamp(0.0, 1.0) * (max - min));
+        }
+        let shown = changed.unwrap_or(value).clamp(min, max);
+        let filled = ((shown - min) / (max - min) * (track.w as f32)) as u32;
+        self.renderer.draw_rect(track, self.palette.control)?;
+        let fill = Rect::new(track.x, track.y, filled, track.h);
+        self.renderer.draw_rect(fill, self.palette.slider_fill)?;
+        let handle = Rect::new(track.x + (filled as i32) - 3, track.y - 2, 6, track.h + 4);
+        self.renderer.draw_rect(handle, self.palette.text)?;
+        Ok(changed)
+    }
+
+    // A row of equally wide buttons; returns the index of the one clicked this frame.
+    pub fn buttons(&mut self, labels: &[&str]) -> Result<Option<usize>, RenderError> {
+        let count = labels.len() as i32;
+        let button_width = (self.width - SPACING * (count - 1)) / count;
+        let mut clicked = None;
+        for (i, label) in labels.iter().enumerate() {
+            let x = self.x + (i as i32) * (button_width + SPACING);
+            let rect = Rect::new(x, self.cursor_y, button_width as u32, BUTTON_HEIGHT as u32);
+            let hovered = self.mouse.over(rect);
+            if hovered && self.mouse.clicked {
+                clicked = Some(i);
+            }
+            let color = if hovered { self.palette.control_hover } else { self.palette.control };
+            self.renderer.draw_rect(rect, color)?;
+            let text_x = x + (button_width - font::text_width(label)) / 2;
+            let text_height = font::GLYPH_HEIGHT * font::GLYPH_SCALE;
+            let text_y = self.cursor_y + (BUTTON_HEIGHT - text_height) / 2;
+            self.renderer.draw_text(label, text_x, text_y, self.palette.text)?;
+        }
+        self.cursor_y += BUTTON_HEIGHT + SPACING;
+        Ok(clicked)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::clock::TICK;
+use crate::render::Rect;
+use crate::{ WINDOW_HEIGHT, WINDOW_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// The simulation works in meters, seconds, meters per second and meters per second squared
+// throughout. Pixels only come in where the world is drawn or pointed at, through a View,
+// and km/h only where speeds are configured or reported.
+
+// The whole world fits the window at 10 cm a pixel.
+pub const DEFAULT_METERS_PER_PIXEL: f32 = 0.1;
+
+pub fn kmh_to_mps(kmh: f32) -> f32 {
+    kmh / 3.6
+}
+
+pub fn mps_to_kmh(mps: f32) -> f32 {
+    mps * 3.6
+}
+
+// What `rate` a second adds up to over one TICK: the distance covered at a speed, or the
+// speed gained at an acceleration.
+pub fn per_tick(rate: f32) -> f32 {
+    rate * TICK.as_secs_f32()
+}
+
+// A rectangle in world meters, for footprints and anything else laid out on the ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Area {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    // `w` by `h` around the point (x, y).
+    pub fn centered(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self::new(x - w / 2.0, y - h / 2.0, w, h)
+    }
+
+    pub fn intersects(&self, other: &Area) -> bool {
+        self.x < other.x + other.w &&
+            other.x < self.x + self.w &&
+            self.y < other.y + other.h &&
+            other.y < self.y + self.h
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (self.x..self.x + self.w).contains(&x) && (self.y..self.y + self.h).contains(&y)
+    }
+
+    // Widened by `margin` on every side.
+    pub fn grown(&self, margin: f32) -> Self {
+        Self::new(self.x - margin, self.y - margin, self.w + margin * 2.0, self.h + margin * 2.0)
+    }
+}
+
+// How the world appears in the window: `center` on the window's, `meters_per_pixel` to
+// the pixel. Larger scales show more of the world around the intersection, smaller ones
+// zoom in on it, and the camera can then pan across the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    pub meters_per_pixel: f32,
+    // The point in the world at the middle of the window.
+    pub center: (f32, f32),
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self::new(DEFAULT_METERS_PER_PIXEL)
+    }
+}
+
+impl View {
+    pub fn new(meters_per_pixel: f32) -> Self {
+        Self { meters_per_pixel, center: (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0) }
+    }
+
+    // Panned to put (x, y) in the middle of the window, as near as it can without showing
+    // past the edge of the world. A view that fits the whole world stays on its center.
+    pub fn centered_on(self, x: f32, y: f32) -> Self {
+        let clamp = |at: f32, window: u32, world: f32| {
+            let half = (window as f32) / 2.0 * self.meters_per_pixel;
+            if half * 2.0 >= world { world / 2.0 } else { at.clamp(half, world - half) }
+        };
+        let center = (clamp(x, WINDOW_WIDTH, WORLD_WIDTH), clamp(y, WINDOW_HEIGHT, WORLD_HEIGHT));
+        Self { center, ..self }
+    }
+
+    pub fn pixels(&self, meters: f32) -> i32 {
+        (meters / self.meters_per_pixel).round() as i32
+    }
+
+    pub fn point(&self, x: f32, y: f32) -> (i32, i32) {
+        let (center_x, center_y) = self.center;
+        let x = (x - center_x) / self.meters_per_pixel + (WINDOW_WIDTH as f32) / 2.0;
+        let y = (y - center_y) / self.meters_per_pixel + (WINDOW_HEIGHT as f32) / 2.0;
+        (x.round() as i32, y.round() as i32)
+    }
+
+    // Edges are mapped rather than sizes, so neighbouring areas still meet, and anything
+    // with an area keeps at least a pixel.
+    pub fn rect(&self, area: Area) -> Rect {
+        let (x0, y0) = self.point(area.x, area.y);
+        let (x1, y1) = self.point(area.x + area.w, area.y + area.h);
+        let w = if area.w <= 0.0 { 0 } else { (x1 - x0).max(1) as u32 };
+        let h = if area.h <= 0.0 { 0 } else { (y1 - y0).max(1) as u32 };
+        Rect::new(x0, y0, w, h)
+    }
+
+    // The point in the world under window pixel (x, y).
+    pub fn to_world(&self, x: i32, y: i32) -> (f32, f32) {
+        let x = ((x as f32) - (WINDOW_WIDTH as f32) / 2.0) * self.meters_per_pixel;
+        let y = ((y as f32) - (WINDOW_HEIGHT as f32) / 2.0) * self.meters_per_pixel;
+        (x + self.center.0, y + self.center.1)
+    }
+}
@@ -0,0 +1,527 @@
+use serde::{ Deserialize, Serialize };
+use std::fmt;
+use std::time::Duration;
+
+use crate::bus::BUS_LENGTH;
+use crate::clock::TICK;
+use crate::driver::DriverProfile;
+use crate::path::{ plan_path, Path };
+use crate::trail::Trail;
+use crate::units::Area;
+use crate::{
+    CAR_LENGTH,
+    LANES_PER_DIRECTION,
+    LANE_WIDTH,
+    ROAD_WIDTH,
+    SAFETY_GAP,
+    VEHICLE_WIDTH,
+    WORLD_HEIGHT,
+    WORLD_WIDTH,
+};
+
+// How far before the intersection drivers put their turn signal on.
+pub const SIGNAL_DISTANCE: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    Straight,
+    Left,
+    Right,
+}
+
+// Which turn signal a driver has on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indicator {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VehicleKind {
+    Car,
+    Bus,
+}
+
+impl VehicleKind {
+    pub const fn length(self) -> f32 {
+        match self {
+            VehicleKind::Car => CAR_LENGTH,
+            VehicleKind::Bus => BUS_LENGTH,
+        }
+    }
+}
+
+// Where a vehicle is on its trip, which the driving and the stats go by. It only moves on
+// through these in order, apart from queueing and moving off again on the approach; a
+// vehicle going straight through never turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VehicleState {
+    Approaching,
+    // Standing still short of the intersection.
+    QueuedAtLight,
+    // In the intersection, still heading the way it came in.
+    InIntersection,
+    // In the intersection, swung round towards its exit road.
+    Turning,
+    // Out the far side of the intersection.
+    Exiting,
+}
+
+impl VehicleState {
+    pub const ALL: [VehicleState; 5] = [
+        VehicleState::Approaching,
+        VehicleState::QueuedAtLight,
+        VehicleState::InIntersection,
+        VehicleState::Turning,
+        VehicleState::Exiting,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VehicleState::Approaching => "approaching",
+            VehicleState::QueuedAtLight => "queued at light",
+            VehicleState::InIntersection => "in intersection",
+            VehicleState::Turning => "turning",
+            VehicleState::Exiting => "exiting",
+        }
+    }
+
+    // Short of the intersection, moving or not.
+    pub fn on_approach(self) -> bool {
+        matches!(self, VehicleState::Approaching | VehicleState::QueuedAtLight)
+    }
+
+    // Where a vehicle put down at its position stands, as one restored from a recording.
+    fn placed(vehicle: &Vehicle) -> Self {
+        let turned = vehicle.direction != vehicle.approach;
+        if vehicle.in_intersection() {
+            if turned { VehicleState::Turning } else { VehicleState::InIntersection }
+        } else if turned || vehicle.distance_to_intersection() < 0.0 {
+            VehicleState::Exiting
+        } else if vehicle.speed == 0.0 {
+            VehicleState::QueuedAtLight
+        } else {
+            VehicleState::Approaching
+        }
+    }
+}
+
+// Stays with a vehicle for its whole trip and is never reused within a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize)]
+pub struct VehicleId(pub u32);
+
+impl fmt::Display for VehicleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// `approach` is the road the vehicle entered on and `direction` the compass direction it is
+// currently heading closest to. `lane` is the travel lane it is in or moving to (0 is next
+// to the center line); `path` holds the waypoints planned to get it there and beyond.
+#[derive(Debug, Clone, Copy)]
+pub struct Vehicle {
+    pub id: VehicleId,
+    pub x: f32,
+    pub y: f32,
+    pub approach: Direction,
+    pub direction: Direction,
+    pub heading: (f32, f32),
+    pub route: Route,
+    pub kind: VehicleKind,
+    pub state: VehicleState,
+    pub lane: usize,
+    pub path: Path,
+    // How far past the world's edge the path ends, at the sink on the exit road.
+    pub exit_offset: f32,
+    // Current speed, and the speed the driver cruises at on a clear road, in m/s.
+    pub speed: f32,
+    pub desired_speed: f32,
+    pub profile: DriverProfile,
+    // How long the driver takes to move off once the way ahead opens, and when a driver
+    // standing still and reacting to it will.
+    pub reaction_time: Duration,
+    pub moving_off_at: Option<Duration>,
+    // Whether the driver carries on through the yellow or red they met, once decided.
+    pub runs_light: Option<bool>,
+    // Came to a full stop at the line of a flashing red, so may go once the way is clear.
+    pub stopped_at_line: bool,
+    // Timers in simulated time since the start of the run.
+    pub wait_started: Option<Duration>,
+    // When the vehicle got onto its approach, and when it first stopped in the queue for the
+    // stop line or, not stopping, crossed it.
+    pub entered_road: Duration,
+    pub arrived: Option<Duration>,
+    pub total_wait: Duration,
+    // Time lost against driving the whole way at the free-flow speed.
+    pub control_delay: Duration,
+    // Litres burned since entering the road, from its speed and acceleration each tick.
+    pub fuel_used: f32,
+    // Times the vehicle came to a full stop, and how long it stood still in all; a bus's
+    // dwell at its stops is not counted.
+    pub stops: u32,
+    pub idle_time: Duration,
+    pub honked: bool,
+    pub collided: bool,
+    pub dwell_until: Option<Duration>,
+    pub served_stop: bool,
+    // Left where it crashed, blocking the road, until it is towed away at this time.
+    pub wrecked_until: Option<Duration>,
+    // When the vehicle crossed the travel time entry line, until it reaches the exit line.
+    pub segment_entered: Option<Duration>,
+    // A lane of the approach was closed while the vehicle was on it.
+    pub through_work_zone: bool,
+    // The driver wanted to change lanes across a solid line, counted once as a violation.
+    pub crossed_solid_line: bool,
+    // Braking for a jaywalker in its path, counted once as an emergency stop.
+    pub braking_for_jaywalker: bool,
+    pub trail: Trail,
+}
+
+impl Vehicle {
+    pub fn new(
+        kind: VehicleKind,
+        direction: Direction,
+        route: Route,
+        lane: usize,
+        position: (f32, f32),
+        speed: f32,
+        exit_offset: f32
+    ) -> Self {
+        let mut vehicle = Self {
+            // Handed out by the simulation once the vehicle is on the road.
+            id: VehicleId(0),
+            x: position.0,
+            y: position.1,
+            approach: direction,
+            direction,
+            heading: heading(direction),
+            route,
+            kind,
+            state: VehicleState::Approaching,
+            lane,
+            path: Path::default(),
+            exit_offset,
+            speed,
+            desired_speed: speed,
+            profile: DriverProfile::Normal,
+            reaction_time: Duration::ZERO,
+            moving_off_at: None,
+            runs_light: None,
+            stopped_at_line: false,
+            wait_started: None,
+            entered_road: Duration::ZERO,
+            arrived: None,
+            total_wait: Duration::ZERO,
+            control_delay: Duration::ZERO,
+            fuel_used: 0.0,
+            stops: 0,
+            idle_time: Duration::ZERO,
+            honked: false,
+            collided: false,
+            dwell_until: None,
+            served_stop: false,
+            wrecked_until: None,
+            segment_entered: None,
+            through_work_zone: false,
+            crossed_solid_line: false,
+            braking_for_jaywalker: false,
+            trail: Trail::default(),
+        };
+        vehicle.path = plan_path(direction, route, lane, position, exit_offset);
+        vehicle.state = VehicleState::placed(&vehicle);
+        vehicle
+    }
+
+    // Where a vehicle put down mid-trip stands, once its heading is set.
+    pub fn place(&mut self) {
+        self.state = VehicleState::placed(self);
+    }
+
+    // Moves the state on once the vehicle has moved or come to a stop this tick.
+    pub fn update_state(&mut self) {
+        let inside = self.in_intersection();
+        self.state = match self.state {
+            VehicleState::Exiting => VehicleState::Exiting,
+            state if state.on_approach() && !inside => {
+                if self.speed == 0.0 {
+                    VehicleState::QueuedAtLight
+                } else {
+                    VehicleState::Approaching
+                }
+            }
+            _ if !inside => VehicleState::Exiting,
+            // Swung round once heading nearer the exit road's way than the approach's.
+            _ if self.direction != self.approach => VehicleState::Turning,
+            _ => VehicleState::InIntersection,
+        };
+    }
+
+    pub fn length(&self) -> f32 {
+        self.kind.length()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.wait_started.is_some() || self.dwell_until.is_some()
+    }
+
+    pub fn has_turned(&self) -> bool {
+        match self.state {
+            VehicleState::Turning => true,
+            VehicleState::Exiting => self.route != Route::Straight,
+            _ => false,
+        }
+    }
+
+    // Whether any part of the vehicle is inside the box where the roads cross.
+    pub fn in_intersection(&self) -> bool {
+        let reach = (ROAD_WIDTH + self.length()) / 2.0;
+        (self.x - WORLD_WIDTH / 2.0).abs() < reach && (self.y - WORLD_HEIGHT / 2.0).abs() < reach
+    }
+
+    // Continuous lane coordinate of the current position, between two lanes mid-change.
+    pub fn lateral(&self) -> f32 {
+        let center_x = WORLD_WIDTH / 2.0;
+        let center_y = WORLD_HEIGHT / 2.0;
+        let offset = match self.direction {
+            Direction::North => self.x - center_x,
+            Direction::South => center_x - self.x,
+            Direction::East => self.y - center_y,
+            Direction::West => center_y - self.y,
+        };
+        offset / LANE_WIDTH - 0.5
+    }
+
+    // The turn signal the driver shows others: on from SIGNAL_DISTANCE before the
+    // intersection until the vehicle is out the other side. Other drivers read this, not
+    // the route.
+    pub fn indicator(&self) -> Option<Indicator> {
+        let out = self.distance_past_intersection() > 0.0;
+        if out || self.distance_to_intersection() > SIGNAL_DISTANCE {
+            return None;
+        }
+        match self.route {
+            Route::Straight => None,
+            Route::Left => Some(Indicator::Left),
+            Route::Right => Some(Indicator::Right),
+        }
+    }
+
+    // Distance left to drive along the planned path, to the sink.
+    pub fn distance_to_path_end(&self) -> f32 {
+        self.path.length_from((self.x, self.y))
+    }
+
+    pub fn is_changing_lanes(&self) -> bool {
+        (self.lateral() - (self.lane as f32)).abs() > 0.01
+    }
+
+    // Re-plans the path from the current position to run through `lane` instead.
+    pub fn change_lane(&mut self, lane: usize) {
+        self.lane = lane;
+        self.path = plan_path(self.approach, self.route, lane, (self.x, self.y), self.exit_offset);
+    }
+
+    // Distance left along the approach before the front reaches the intersection box,
+    // negative once inside.
+    pub fn distance_to_intersection(&self) -> f32 {
+        let center_x = WORLD_WIDTH / 2.0;
+        let center_y = WORLD_HEIGHT / 2.0;
+        let to_center = match self.approach {
+            Direction::North => self.y - center_y,
+            Direction::South => center_y - self.y,
+            Direction::East => center_x - self.x,
+            Direction::West => self.x - center_x,
+        };
+        to_center - ROAD_WIDTH / 2.0 - self.length() / 2.0
+    }
+
+    // How far the vehicle's center is beyond the far side of the intersection, along the
+    // way it is heading.
+    pub fn distance_past_intersection(&self) -> f32 {
+        let center = match self.direction {
+            Direction::North | Direction::South => WORLD_HEIGHT / 2.0,
+            Direction::East | Direction::West => WORLD_WIDTH / 2.0,
+        };
+        -distance_along(self.direction, self.x, self.y, center) - ROAD_WIDTH / 2.0
+    }
+}
+
+// Center-to-center distance to keep behind `leader` when stopped, given the bumper-to-bumper
+// `safety_gap` drivers currently keep.
+pub fn following_gap(vehicle: &Vehicle, leader: &Vehicle, safety_gap: f32) -> f32 {
+    (vehicle.length() + leader.length()) / 2.0 + safety_gap
+}
+
+// Road a vehicle of `kind` takes up in a queue, with the gap it leaves to the one ahead.
+pub const fn queue_space(kind: VehicleKind) -> f32 {
+    kind.length() + SAFETY_GAP
+}
+
+// Distance covered while braking to a standstill, one tick at a time.
+pub fn braking_distance(speed: f32, deceleration: f32) -> f32 {
+    (speed * speed) / (2.0 * deceleration) + (speed * TICK.as_secs_f32()) / 2.0
+}
+
+// Highest speed from which a vehicle can still stop within `distance`.
+pub fn stopping_speed(distance: f32, deceleration: f32) -> f32 {
+    let per_tick = deceleration * TICK.as_secs_f32();
+    (2.0 * deceleration * distance + (per_tick * per_tick) / 4.0).sqrt() - per_tick / 2.0
+}
+
+pub fn heading(direction: Direction) -> (f32, f32) {
+    match direction {
+        Direction::North => (0.0, -1.0),
+        Direction::South => (0.0, 1.0),
+        Direction::East => (1.0, 0.0),
+        Direction::West => (-1.0, 0.0),
+    }
+}
+
+// Perpendicular coordinate (x for north/south traffic, y for east/west) of a lane center.
+// Traffic keeps to the right, so each direction's lanes sit on its right of the center line.
+pub fn lane_center(direction: Direction, lane: f32) -> f32 {
+    offset_from_center(direction, LANE_WIDTH * (lane + 0.5))
+}
+
+// Perpendicular coordinate `offset` meters to the right of the center line for `direction`.
+pub fn offset_from_center(direction: Direction, offset: f32) -> f32 {
+    let center_x = WORLD_WIDTH / 2.0;
+    let center_y = WORLD_HEIGHT / 2.0;
+    match direction {
+        Direction::North => center_x + offset,
+        Direction::South => center_x - offset,
+        Direction::East => center_y + offset,
+        Direction::West => center_y - offset,
+    }
+}
+
+// How far a point travelling in `direction` still has to go to reach `coordinate` on its
+// axis of travel; negative once past it.
+pub fn distance_along(direction: Direction, x: f32, y: f32, coordinate: f32) -> f32 {
+    match direction {
+        Direction::North => y - coordinate,
+        Direction::South => coordinate - y,
+        Direction::East => coordinate - x,
+        Direction::West => x - coordinate,
+    }
+}
+
+// Position of `other` relative to `vehicle` as (ahead, sideways) along its heading.
+pub fn relative_position(vehicle: &Vehicle, other: &Vehicle) -> (f32, f32) {
+    offset_along(vehicle.heading, (vehicle.x, vehicle.y), (other.x, other.y))
+}
+
+pub fn relative_offset(direction: Direction, from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    offset_along(heading(direction), from, to)
+}
+
+fn offset_along((hx, hy): (f32, f32), from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    (dx * hx + dy * hy, dx * -hy + dy * hx)
+}
+
+// Whether `leader` is ahead on the very path `vehicle` is following: same approach, route
+// and lane, with neither moving between lanes. Their spacing is then measured along the
+// path, which stays exact through the turn where straight-line offsets don't.
+pub fn shares_path(vehicle: &Vehicle, leader: &Vehicle) -> bool {
+    vehicle.approach == leader.approach &&
+        vehicle.route == leader.route &&
+        vehicle.lane == leader.lane &&
+        !vehicle.is_changing_lanes() &&
+        !leader.is_changing_lanes()
+}
+
+pub fn move_vehicle(vehicle: &mut Vehicle, distance: f32) {
+    let mut position = (vehicle.x, vehicle.y);
+    if let Some(heading) = vehicle.path.advance(&mut position, distance) {
+        vehicle.heading = heading;
+        vehicle.direction = nearest_direction(heading);
+    }
+    (vehicle.x, vehicle.y) = position;
+    vehicle.update_state();
+}
+
+fn nearest_direction((hx, hy): (f32, f32)) -> Direction {
+    if hx.abs() > hy.abs() {
+        if hx > 0.0 { Direction::East } else { Direction::West }
+    } else if hy > 0.0 {
+        Direction::South
+    } else {
+        Direction::North
+    }
+}
+
+pub fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::South,
+        Direction::South => Direction::North,
+        Direction::East => Direction::West,
+        Direction::West => Direction::East,
+    }
+}
+
+// The route that takes traffic on the `approach` road out along `exit`; none for U-turns.
+pub fn route_between(approach: Direction, exit: Direction) -> Option<Route> {
+    [Route::Straight, Route::Left, Route::Right]
+        .into_iter()
+        .find(|&route| turned_direction(approach, route) == exit)
+}
+
+pub fn turned_direction(direction: Direction, route: Route) -> Direction {
+    match route {
+        Route::Straight => direction,
+        Route::Left =>
+            match direction {
+                Direction::North => Direction::West,
+                Direction::South => Direction::East,
+                Direction::East => Direction::North,
+                Direction::West => Direction::South,
+            }
+        Route::Right =>
+            match direction {
+                Direction::North => Direction::East,
+                Direction::South => Direction::West,
+                Direction::East => Direction::South,
+                Direction::West => Direction::North,
+            }
+    }
+}
+
+// Lefts are made from the lane next to the center line, rights from the curb lane.
+pub fn turn_lane(route: Route) -> Option<usize> {
+    match route {
+        Route::Straight => None,
+        Route::Left => Some(0),
+        Route::Right => Some(LANES_PER_DIRECTION - 1),
+    }
+}
+
+// Coordinate along the approach axis where a turning vehicle's path crosses the center
+// of its exit lane.
+pub fn turn_point(direction: Direction, route: Route) -> f32 {
+    let exit_lane = turn_lane(route).unwrap_or(0);
+    lane_center(turned_direction(direction, route), exit_lane as f32)
+}
+
+pub fn vehicle_rect(vehicle: &Vehicle) -> Area {
+    let length = vehicle.length();
+    let (w, h) = match vehicle.direction {
+        Direction::North | Direction::South => (VEHICLE_WIDTH, length),
+        Direction::East | Direction::West => (length, VEHICLE_WIDTH),
+    };
+    Area::centered(vehicle.x, vehicle.y, w, h)
+}
@@ -0,0 +1,66 @@
+use serde::Deserialize;
+
+use crate::render::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Ice,
+}
+
+impl Weather {
+    pub fn next(self) -> Weather {
+        match self {
+            Weather::Clear => Weather::Rain,
+            Weather::Rain => Weather::Ice,
+            Weather::Ice => Weather::Clear,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "CLEAR",
+            Weather::Rain => "RAIN",
+            Weather::Ice => "ICE",
+        }
+    }
+
+    // Multiplier on how hard vehicles can brake.
+    pub fn braking_factor(self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 0.6,
+            Weather::Ice => 0.25,
+        }
+    }
+
+    // Multiplier on the safety gap drivers keep to the vehicle ahead.
+    pub fn gap_factor(self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 1.5,
+            Weather::Ice => 2.5,
+        }
+    }
+
+    // Multiplier on the speed drivers are willing to go.
+    pub fn speed_factor(self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 0.85,
+            Weather::Ice => 0.6,
+        }
+    }
+
+    // Tint laid over the road surface, if any.
+    pub fn road_tint(self) -> Option<Color> {
+        match self {
+            Weather::Clear => None,
+            Weather::Rain => Some(Color::rgba(0, 0, 30, 90)),
+            Weather::Ice => Some(Color::rgba(210, 235, 255, 70)),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use crate::clock::TICK;
+use crate::error::RenderError;
+use crate::render::{ Rect, Renderer };
+use crate::simulation::SimEvent;
+use crate::stats::{ tmc_column, tmc_movements };
+use crate::theme::Palette;
+use crate::traffic_light::{ LightState, Phase, TrafficLight };
+use crate::vehicle::{ Direction, Route, Vehicle, VehicleId, VehicleState };
+
+// Webster's cycle is capped as usual in practice, and each road is given at least this
+// much green however light its traffic.
+const MAX_CYCLE_SECS: f32 = 120.0;
+const MIN_GREEN_SECS: f32 = 4.0;
+// A movement's saturation flow is only trusted after this many queued vehicles have been
+// seen to discharge.
+const MIN_DISCHARGED: u32 = 5;
+
+// Measures every movement's arrival rate and saturation flow from the traffic as it runs,
+// for Webster's method. Saturation flow is the rate queued vehicles of the movement enter
+// on green, over the green time during which some of them were still queued, so it takes
+// in start-up losses and any shared-lane blocking.
+pub struct WebsterAnalysis {
+    started: Duration,
+    // By movement, in turning movement count column order.
+    arrivals: [u32; 12],
+    discharged: [u32; 12],
+    saturated_green: [Duration; 12],
+    // Vehicles that have stopped on their approach and not yet entered the intersection,
+    // with their movement.
+    queued: Vec<(VehicleId, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WebsterTiming {
+    pub cycle: Duration,
+    // Highest demand to saturation flow ratio among each road's movements.
+    pub north_south_ratio: f32,
+    pub east_west_ratio: f32,
+    pub north_south_green: Duration,
+    pub east_west_green: Duration,
+}
+
+// A movement's measured demand and, once there is enough to go on, its saturation flow,
+// both in vehicles per hour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementFlow {
+    pub approach: Direction,
+    pub route: Route,
+    pub demand: f32,
+    pub saturation_flow: Option<f32>,
+}
+
+impl WebsterAnalysis {
+    pub fn new(now: Duration) -> Self {
+        Self {
+            started: now,
+            arrivals: [0; 12],
+            discharged: [0; 12],
+            saturated_green: [Duration::ZERO; 12],
+            queued: Vec::new(),
+        }
+    }
+
+    // Takes in one tick, after the lanes have moved, with the events it raised.
+    pub fn observe(&mut self, vehicles: &[&Vehicle], light: &TrafficLight, events: &[SimEvent]) {
+        for event in events {
+            match *event {
+                SimEvent::VehicleSpawned { approach, route, .. } => {
+                    self.arrivals[tmc_column(approach, route)] += 1;
+                }
+                SimEvent::VehicleEnteredIntersection { vehicle_id, approach, .. } => {
+                    let Some(i) = self.queued.iter().position(|&(id, _)| id == vehicle_id) else {
+                        continue;
+                    };
+                    let (_, movement) = self.queued.swap_remove(i);
+                    if light.state_for(approach) == LightState::Green {
+                        self.discharged[movement] += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let on_road = |id| vehicles.iter().any(|v| v.id == id);
+        // Towed or removed to break a gridlock rather than driven off.
+        self.queued.retain(|&(id, _)| on_road(id));
+        for vehicle in vehicles {
+            let waiting =
+                vehicle.state == VehicleState::QueuedAtLight && vehicle.wrecked_until.is_none();
+            if waiting && !self.queued.iter().any(|&(id, _)| id == vehicle.id) {
+                self.queued.push((vehicle.id, tmc_column(vehicle.approach, vehicle.route)));
+            }
+        }
+        for (i, (approach, _)) in tmc_movements().enumerate() {
+            let queued = self.queued.iter().any(|&(_, movement)| movement == i);
+            if queued && light.state_for(approach) == LightState::Green {
+                self.saturated_green[i] += TICK;
+            }
+        }
+    }
+
+    pub fn flows(&self, now: Duration) -> Vec<MovementFlow> {
+        let hours = now.saturating_sub(self.started).as_secs_f32() / 3600.0;
+        tmc_movements()
+            .enumerate()
+            .map(|(i, (approach, route))| {
+                let green_hours = self.saturated_green[i].as_secs_f32() / 3600.0;
+                MovementFlow {
+                    approach,
+                    route,
+                    demand: if hours > 0.0 { (self.arrivals[i] as f32) / hours } else { 0.0 },
+                    saturation_flow: (self.discharged[i] >= MIN_DISCHARGED)
+                        .then(|| (self.discharged[i] as f32) / green_hours),
+                }
+            })
+            .collect()
+    }
+
+    // Webster's optimal cycle, (1.5 L + 5) / (1 - Y), and its green split in proportion to
+    // each road's critical flow ratio. The change interval, the yellow and all-red, is
+    // taken as each phase's lost time, as the measured saturation flows already allow for
+    // start-up. None until every road has a movement with a measured saturation flow.
+    pub fn timing(&self, now: Duration, change_interval: Duration) -> Option<WebsterTiming> {
+        let flows = self.flows(now);
+        let critical_ratio = |phase: Phase| {
+            flows
+                .iter()
+                .filter(|flow| phase.serves(flow.approach))
+                .filter_map(|flow| Some(flow.demand / flow.saturation_flow?))
+                .max_by(f32::total_cmp)
+        };
+        let north_south_ratio = critical_ratio(Phase::NorthSouth)?;
+        let east_west_ratio = critical_ratio(Phase::EastWest)?;
+        let lost = 2.0 * change_interval.as_secs_f32();
+        let total = north_south_ratio + east_west_ratio;
+        // Past saturation the formula breaks down, and the longest cycle serves best.
+        let cycle = if total < 1.0 {
+            ((1.5 * lost + 5.0) / (1.0 - total)).min(MAX_CYCLE_SECS)
+        } else {
+            MAX_CYCLE_SECS
+        };
+        let green = |ratio: f32| {
+            let share = if total > 0.0 { ratio / total } else { 0.5 };
+            Duration::from_secs_f32(((cycle - lost) * share).max(MIN_GREEN_SECS))
+        };
+        let north_south_green = green(north_south_ratio);
+        let east_west_green = green(east_west_ratio);
+        Some(WebsterTiming {
+            cycle: north_south_green + east_west_green + change_interval * 2,
+            north_south_ratio,
+            east_west_ratio,
+            north_south_green,
+            east_west_green,
+        })
+    }
+
+    // Flow ratios by movement and the resulting timing, in the bottom right corner.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        palette: &Palette,
+        now: Duration,
+        change_interval: Duration
+    ) -> Result<(), RenderError> {
+        let (x, y) = (790, 580);
+        renderer.draw_rect(Rect::new(x - 8, y - 8, 208, 196), palette.panel)?;
+        let mut lines = vec![
+            format!("WEBSTER {}S", now.saturating_sub(self.started).as_secs()),
+            "     L   T   R".to_string(),
+        ];
+        let flows = self.flows(now);
+        for (approach, movements) in ["NB", "SB", "EB", "WB"].iter().zip(flows.chunks(3)) {
+            let ratios: Vec<String> = movements
+                .iter()
+                .map(|flow| match flow.saturation_flow {
+                    // Flow ratios are below one on any road that clears its queues.
+                    Some(saturation) => {
+                        let ratio = (flow.demand / saturation).min(9.99);
+                        format!("{:>4}", format!("{:.2}", ratio).trim_start_matches('0'))
+                    }
+                    None => "  --".to_string(),
+                })
+                .collect();
+            lines.push(format!("{} {}", approach, ratios.join("")));
+        }
+        match self.timing(now, change_interval) {
+            Some(timing) => {
+                lines.push(format!("CYCLE {:.0}S", timing.cycle.as_secs_f32()));
+                lines.push(format!("NS GREEN {:.0}S", timing.north_south_green.as_secs_f32()));
+                lines.push(format!("EW GREEN {:.0}S", timing.east_west_green.as_secs_f32()));
+            }
+            None => lines.push("MEASURING".to_string()),
+        }
+        for (i, line) in lines.iter().enumerate() {
+            renderer.draw_text(line, x, y + (i as i32) * 20, palette.text)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use crate::render::Color;
+use crate::units::Area;
+use crate::vehicle::{ heading, lane_center, Direction };
+use crate::{ BIKE_LANE_WIDTH, LANE_WIDTH, ROAD_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+// A closed lane is coned off from the world's edge to this far before the stop line, which
+// leaves turning traffic room to move back into it.
+pub const WORK_ZONE_SETBACK: f32 = 12.0;
+pub const CONE_COLOR: Color = Color::rgb(255, 110, 0);
+const CONE_SIZE: f32 = 0.8;
+const CONE_SPACING: f32 = 3.0;
+
+// Cones along both edges of `lane` on `approach` over the length of the work zone.
+pub fn cone_rects(approach: Direction, lane: usize) -> Vec<Area> {
+    let (hx, hy) = heading(approach);
+    let center = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    let across = lane_center(approach, lane as f32);
+    let zone_end = ROAD_WIDTH / 2.0 + BIKE_LANE_WIDTH + WORK_ZONE_SETBACK;
+    let road_end = if hx == 0.0 { center.1 } else { center.0 };
+    let count = ((road_end - zone_end) / CONE_SPACING).ceil().max(0.0) as usize;
+    let mut cones = Vec::new();
+    for along in (0..count).map(|i| zone_end + (i as f32) * CONE_SPACING) {
+        for side in [-1.0, 1.0] {
+            let edge = across + (side * (LANE_WIDTH - CONE_SIZE)) / 2.0;
+            // Upstream of the stop line is against the heading.
+            let (x, y) = if hx == 0.0 {
+                (edge, center.1 - hy * along)
+            } else {
+                (center.0 - hx * along, edge)
+            };
+            cones.push(Area::centered(x, y, CONE_SIZE, CONE_SIZE));
+        }
+    }
+    cones
+}
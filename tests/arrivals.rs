@@ -0,0 +1,63 @@
+// Runs steady random arrivals on one approach and checks the arrival log times each vehicle
+// in order through its approach, and that the gaps between arrivals come out at the
+// configured rate, as a calibration against real counts would read them.
+
+use std::fs;
+use std::time::Duration;
+
+use road_intersection::config::Config;
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::stats::Stats;
+use road_intersection::vehicle::Direction;
+
+const VEHICLES_PER_MINUTE: f32 = 12.0;
+const TICKS: u32 = 60_000;
+
+fn eastbound_arrivals() -> Stats {
+    let mut config = Config { seed: Some(5), ..Config::default() };
+    config.demand.approaches.east = VEHICLES_PER_MINUTE;
+    let mut simulation = TrafficSimulation::with_config(&config).unwrap();
+    for _ in 0..TICKS {
+        simulation.update();
+        simulation.drain_events();
+    }
+    simulation.stats
+}
+
+#[test]
+fn arrivals_follow_each_vehicle_through_its_approach() {
+    let stats = eastbound_arrivals();
+    assert_eq!(stats.arrivals.len(), stats.movement_counts.len());
+    assert!(stats.arrivals.len() > 50, "only {} arrivals", stats.arrivals.len());
+    for arrival in &stats.arrivals {
+        assert_eq!(arrival.approach, Direction::East);
+        assert!(
+            arrival.entered_road < arrival.arrived && arrival.arrived <= arrival.departed,
+            "{:?}",
+            arrival
+        );
+    }
+    // Arrivals at random are a Poisson stream, with gaps averaging a minute over the rate.
+    let mut entered: Vec<Duration> = stats.arrivals.iter().map(|a| a.entered_road).collect();
+    entered.sort();
+    let gaps: Vec<f32> = entered.windows(2).map(|w| (w[1] - w[0]).as_secs_f32()).collect();
+    let mean_gap = gaps.iter().sum::<f32>() / (gaps.len() as f32);
+    let expected = 60.0 / VEHICLES_PER_MINUTE;
+    assert!((mean_gap - expected).abs() < expected * 0.2, "mean gap {:.2}s", mean_gap);
+}
+
+#[test]
+fn the_arrival_log_has_a_row_per_vehicle_in_arrival_order() {
+    let stats = eastbound_arrivals();
+    let path = std::env::temp_dir().join(format!("arrivals-{}.csv", std::process::id()));
+    stats.export_arrivals(&path).unwrap();
+    let log = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let mut lines = log.lines();
+    assert_eq!(lines.next(), Some("approach,vehicle,kind,route,entered_road,arrived,departed"));
+    let arrived: Vec<f32> = lines
+        .map(|line| line.split(',').nth(5).unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(arrived.len(), stats.arrivals.len());
+    assert!(arrived.windows(2).all(|w| w[0] <= w[1]));
+}
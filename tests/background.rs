@@ -0,0 +1,74 @@
+// Renders frames through a renderer that only notes the background layer's key, and checks
+// the key holds while just the traffic moves and changes with what the layer shows.
+
+use road_intersection::config::Config;
+use road_intersection::error::RenderError;
+use road_intersection::render::{ Color, DrawLayer, Rect, Renderer, Texture };
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::theme::Theme;
+use road_intersection::vehicle::Direction;
+
+#[derive(Default)]
+struct LayerKeys {
+    keys: Vec<u64>,
+}
+
+impl Renderer for LayerKeys {
+    fn clear(&mut self, _color: Color) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, _rect: Rect, _color: Color) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn draw_texture(&mut self, _texture: &Texture, _dst: Rect) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn draw_layer(&mut self, key: u64, _draw: &mut DrawLayer) -> Result<(), RenderError> {
+        self.keys.push(key);
+        Ok(())
+    }
+}
+
+fn background(simulation: &TrafficSimulation) -> u64 {
+    let mut renderer = LayerKeys::default();
+    simulation.render(&mut renderer).unwrap();
+    renderer.keys[0]
+}
+
+#[test]
+fn the_background_is_drawn_again_only_when_it_changes() {
+    let mut config = Config { seed: Some(3), ..Config::default() };
+    config.demand.vehicles_per_minute = 60.0;
+    let mut simulation = TrafficSimulation::with_config(&config).unwrap();
+    let mut key = background(&simulation);
+    for _ in 0..500 {
+        simulation.update();
+        assert_eq!(background(&simulation), key);
+    }
+    let changes: [&dyn Fn(&mut TrafficSimulation); 4] = [
+        &|simulation| simulation.set_lane_closure(Direction::North, Some(0)),
+        &|simulation| simulation.set_theme(Theme::Light),
+        &|simulation| {
+            let mut layout = *simulation.layout();
+            layout.approach_mut(Direction::East).stop_line_setback = 4.0;
+            simulation.set_layout(layout).unwrap();
+        },
+        &|simulation| simulation.reset(None),
+    ];
+    for change in changes {
+        change(&mut simulation);
+        let changed = background(&simulation);
+        assert_ne!(changed, key);
+        key = changed;
+    }
+    // Setting what is already shown changes nothing.
+    simulation.set_theme(Theme::Light);
+    assert_eq!(background(&simulation), key);
+}
@@ -0,0 +1,56 @@
+// Runs the same seeded traffic with short and long ticks and checks the long ones, stepped
+// through at the physics step, come out just as the short ones do: the same events in the
+// same order and every vehicle in the same place.
+
+use std::fs;
+use std::time::Duration;
+
+use road_intersection::config::Config;
+use road_intersection::simulation::{ SimEvent, TrafficSimulation };
+use road_intersection::vehicle::VehicleId;
+
+const SECONDS: u64 = 120;
+
+fn busy(tick_ms: u32) -> Config {
+    let mut config = Config { seed: Some(42), ..Config::default() };
+    config.demand.vehicles_per_minute = 120.0;
+    config.clock.tick_ms = tick_ms;
+    config
+}
+
+// Every event raised over the run, the time it ended at and where each vehicle was then.
+fn run(tick_ms: u32) -> (Vec<SimEvent>, Duration, Vec<(VehicleId, f32, f32)>) {
+    let mut simulation = TrafficSimulation::with_config(&busy(tick_ms)).unwrap();
+    let mut events = Vec::new();
+    for _ in 0..SECONDS * 1000 / (tick_ms as u64) {
+        simulation.update();
+        events.extend(simulation.drain_events());
+    }
+    let vehicles = simulation
+        .vehicles()
+        .iter()
+        .map(|vehicle| (vehicle.id, vehicle.x, vehicle.y))
+        .collect();
+    (events, simulation.time.now(), vehicles)
+}
+
+#[test]
+fn long_ticks_run_the_same_traffic_as_short_ones() {
+    let short = run(10);
+    assert!(!short.0.is_empty(), "the run raised no events");
+    assert_eq!(short.1, Duration::from_secs(SECONDS));
+    for tick_ms in [50, 100, 1000] {
+        assert!(run(tick_ms) == short, "{} ms ticks ran differently", tick_ms);
+    }
+}
+
+#[test]
+fn a_tick_must_be_whole_physics_steps() {
+    let path = std::env::temp_dir().join(format!("clock-{}.toml", std::process::id()));
+    let ticks = [(10, true), (250, true), (1000, true), (0, false), (15, false), (2000, false)];
+    for (tick_ms, valid) in ticks {
+        fs::write(&path, format!("[clock]\ntick_ms = {}\n", tick_ms)).unwrap();
+        assert_eq!(Config::load(&path).is_ok(), valid, "tick_ms = {}", tick_ms);
+    }
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,89 @@
+// Checks the movement conflict matrix and that the signal plans the config can set up are
+// all accepted, while a display giving crossing movements a green together is caught.
+
+use road_intersection::config::LightsConfig;
+use road_intersection::conflicts::{ check_signal_plan, ConflictMatrix };
+use road_intersection::map::MapLayout;
+use road_intersection::traffic_light::{
+    LeftTurnPhasing,
+    LeftTurnSequence,
+    LeftTurnSequences,
+    LeftTurns,
+    LightState,
+    SignalHead,
+};
+use road_intersection::vehicle::{ Direction, Route };
+
+const RED: SignalHead = SignalHead { ball: LightState::Red, left_arrow: None };
+const GREEN: SignalHead = SignalHead { ball: LightState::Green, left_arrow: None };
+
+#[test]
+fn matrix_knows_which_movements_cross() {
+    let matrix = ConflictMatrix::new(&MapLayout::default());
+    let north = |route| (Direction::North, route);
+    let south = |route| (Direction::South, route);
+    let east = |route| (Direction::East, route);
+    // Opposing through traffic and rights keep apart, as do opposing lefts.
+    assert!(!matrix.conflicts(north(Route::Straight), south(Route::Straight)));
+    assert!(!matrix.conflicts(north(Route::Right), south(Route::Straight)));
+    assert!(!matrix.conflicts(north(Route::Left), south(Route::Left)));
+    // A left crosses oncoming through traffic and merges with the oncoming right.
+    assert!(matrix.conflicts(north(Route::Left), south(Route::Straight)));
+    assert!(matrix.conflicts(north(Route::Left), south(Route::Right)));
+    // The crossing road's traffic crosses everything but rights away from it.
+    assert!(matrix.conflicts(north(Route::Straight), east(Route::Straight)));
+    assert!(matrix.conflicts(north(Route::Left), east(Route::Left)));
+    assert!(matrix.conflicts(north(Route::Right), east(Route::Straight)));
+    assert!(!matrix.conflicts(north(Route::Right), east(Route::Left)));
+    assert!(!matrix.conflicts(north(Route::Straight), north(Route::Left)));
+}
+
+#[test]
+fn permissive_lefts_yield_but_arrows_must_not_face_a_green() {
+    let matrix = ConflictMatrix::new(&MapLayout::default());
+    let road = |north: SignalHead, south: SignalHead| {
+        move |direction| match direction {
+            Direction::North => north,
+            Direction::South => south,
+            _ => RED,
+        }
+    };
+    assert_eq!(matrix.conflict_in(road(GREEN, GREEN)), None);
+    let arrow = SignalHead { left_arrow: Some(LightState::Green), ..GREEN };
+    assert_eq!(matrix.conflict_in(road(arrow, RED)), None);
+    let lefts_only = SignalHead { left_arrow: Some(LightState::Green), ..RED };
+    assert_eq!(matrix.conflict_in(road(lefts_only, lefts_only)), None);
+    let (a, b) = matrix.conflict_in(road(arrow, GREEN)).expect("an arrow against a green");
+    assert!([a, b].contains(&(Direction::North, Route::Left)));
+    // Nor may the crossing road have a green alongside.
+    let crossing = |direction| match direction {
+        Direction::East => GREEN,
+        direction => road(GREEN, RED)(direction),
+    };
+    assert!(matrix.conflict_in(crossing).is_some());
+}
+
+#[test]
+fn every_left_turn_plan_is_accepted() {
+    let phasings = [LeftTurnPhasing::Permissive, LeftTurnPhasing::ProtectedPermissive];
+    let sequences = [LeftTurnSequence::Lead, LeftTurnSequence::Lag];
+    for n in 0..256 {
+        let pick = |shift: usize| n >> shift & 1;
+        let lights = LightsConfig {
+            left_turns: LeftTurns {
+                north: phasings[pick(0)],
+                south: phasings[pick(1)],
+                east: phasings[pick(2)],
+                west: phasings[pick(3)],
+            },
+            left_turn_sequence: LeftTurnSequences {
+                north: sequences[pick(4)],
+                south: sequences[pick(5)],
+                east: sequences[pick(6)],
+                west: sequences[pick(7)],
+            },
+            ..LightsConfig::default()
+        };
+        check_signal_plan(&lights, &MapLayout::default()).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+// Runs a seeded scenario and checks its event log comes out the same every time, and the
+// same as it was recorded, and that the platform-independent math matches the standard
+// library's closely enough to stand in for it.
+
+use std::path::Path;
+
+use road_intersection::config::Config;
+use road_intersection::determinism::{ hash_run, EventLogHash };
+use road_intersection::portable_math;
+use road_intersection::scenario::Scenario;
+use road_intersection::simulation::TrafficSimulation;
+
+// The example scenario runs two minutes.
+const TICKS: u64 = 12_000;
+// The hash of the example scenario with seed 42, as recorded on Linux. A change that
+// alters what the simulation does on purpose changes it too: run
+// `road_intersection --config <seed 42> --scenario scenario.example.toml --ticks 12000
+// --event-hash` and paste in the new value. Any other platform must agree with it.
+const EXPECTED: u64 = 0xdde2_89f1_6c23_1cca;
+
+fn example(seed: u64) -> Config {
+    let scenario = Scenario::load(Path::new("scenario.example.toml")).unwrap();
+    Config { seed: Some(seed), scenario, ..Config::default() }
+}
+
+#[test]
+fn a_seeded_scenario_repeats_its_event_log() {
+    let first = hash_run(&example(42), TICKS).unwrap();
+    assert!(first.events > 0, "the scenario raised no events");
+    assert_eq!(first, hash_run(&example(42), TICKS).unwrap());
+    assert_ne!(first.hash, hash_run(&example(7), TICKS).unwrap().hash);
+}
+
+#[test]
+fn the_event_log_matches_the_recorded_hash() {
+    let hash = hash_run(&example(42), TICKS).unwrap();
+    assert_eq!(hash.hash, EXPECTED, "event log hash {:016x}", hash.hash);
+}
+
+// As `hash_run`, with the approaches updating on worker threads from `parallel_vehicles`
+// vehicles up.
+fn hash_threaded(config: &Config, parallel_vehicles: usize) -> EventLogHash {
+    let mut simulation = TrafficSimulation::with_config(config).unwrap();
+    simulation.parallel_vehicles = parallel_vehicles;
+    let mut hash = EventLogHash::default();
+    for _ in 0..TICKS {
+        simulation.update();
+        let now = simulation.time.now();
+        for event in simulation.drain_events() {
+            hash.record(now, &event);
+        }
+    }
+    hash
+}
+
+#[test]
+fn threaded_updates_match_sequential_ones() {
+    let mut config = example(42);
+    config.demand.vehicles_per_minute = 300.0;
+    // More threads than approaches, however many cores there are.
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+    let threaded = pool.install(|| hash_threaded(&config, 0));
+    assert_eq!(threaded, hash_threaded(&config, usize::MAX));
+}
+
+#[test]
+fn portable_math_matches_the_standard_library() {
+    let close = |ours: f64, std: f64| {
+        assert!((ours - std).abs() <= 1e-12 * std.abs().max(1.0), "{} against {}", ours, std);
+    };
+    for i in -400..=400 {
+        let x = (i as f64) * 0.37;
+        close(portable_math::exp(x), x.exp());
+        close(portable_math::exp10(x / 20.0), 10f64.powf(x / 20.0));
+        let (sin, cos) = portable_math::sin_cos(x);
+        close(sin, x.sin());
+        close(cos, x.cos());
+        let y = (i as f64).abs() * 13.7 + 1e-3;
+        close(portable_math::ln(y), y.ln());
+        close(portable_math::log10(y), y.log10());
+    }
+    assert_eq!(portable_math::exp(-800.0), 0.0);
+    assert_eq!(portable_math::ln(1.0), 0.0);
+    assert_eq!(portable_math::hypot(3.0, 4.0), 5.0);
+}
@@ -0,0 +1,133 @@
+// Queues traffic on a red held by hand, gives it the green and checks how the queue starts
+// up and discharges with drivers' reaction times.
+
+use std::collections::{ HashMap, HashSet };
+use std::time::Duration;
+
+use road_intersection::config::{ Config, ReactionConfig };
+use road_intersection::simulation::{ SimEvent, TrafficSimulation };
+use road_intersection::traffic_light::Phase;
+use road_intersection::vehicle::{ Direction, Vehicle, VehicleId };
+
+// Enough arrivals from the west to fill the eastbound approach.
+const QUEUED: usize = 24;
+const QUEUE_TICKS: u32 = 6000;
+const DISCHARGE_TICKS: u32 = 6000;
+
+// A simulation with no random arrivals and the lights under manual control, all red.
+fn held_at_red(seed: u64, reactions: ReactionConfig) -> TrafficSimulation {
+    let mut config = Config { seed: Some(seed), reactions, ..Config::default() };
+    config.demand.vehicles_per_minute = 0.0;
+    let mut simulation = TrafficSimulation::with_config(&config).unwrap();
+    simulation.toggle_manual_control();
+    simulation.override_lights(None);
+    simulation
+}
+
+// Builds a queue of through traffic on the eastbound approach while every light is red.
+fn queue_eastbound(simulation: &mut TrafficSimulation) {
+    for _ in 0..QUEUED {
+        simulation.spawn_trip(Direction::West, Direction::East).unwrap();
+    }
+    for _ in 0..QUEUE_TICKS {
+        simulation.update();
+    }
+}
+
+fn eastbound(simulation: &TrafficSimulation) -> Vec<Vehicle> {
+    simulation.vehicles_on(Direction::East).iter().copied().collect()
+}
+
+// Turns the east-west road green and runs until the queue has discharged, returning when
+// each vehicle standing in the eastbound queue moved off after the green, by travel lane
+// in the order they did, and the saturation headways measured.
+fn discharge(simulation: &mut TrafficSimulation) -> (Vec<Vec<Duration>>, Vec<Duration>) {
+    let events = simulation.subscribe();
+    let green = simulation.time.now();
+    simulation.override_lights(Some(Phase::EastWest));
+    let queued: HashSet<VehicleId> = eastbound(simulation)
+        .iter()
+        .filter(|vehicle| vehicle.speed == 0.0)
+        .map(|vehicle| vehicle.id)
+        .collect();
+    let mut moved_off: HashMap<VehicleId, (usize, Duration)> = HashMap::new();
+    for _ in 0..DISCHARGE_TICKS {
+        simulation.update();
+        let now = simulation.time.now() - green;
+        for vehicle in &eastbound(simulation) {
+            if vehicle.speed > 0.0 && queued.contains(&vehicle.id) {
+                moved_off.entry(vehicle.id).or_insert((vehicle.lane, now));
+            }
+        }
+    }
+    let mut starts = vec![Vec::new(); 2];
+    for &(lane, at) in moved_off.values() {
+        starts[lane].push(at);
+    }
+    for lane in &mut starts {
+        lane.sort();
+    }
+    let headways = events
+        .try_iter()
+        .filter_map(|event| match event {
+            SimEvent::QueueHeadway { approach: Direction::East, headway, .. } => Some(headway),
+            _ => None,
+        })
+        .collect();
+    (starts, headways)
+}
+
+fn mean(durations: &[Duration]) -> Duration {
+    durations.iter().sum::<Duration>() / (durations.len() as u32)
+}
+
+#[test]
+fn queue_starts_up_one_reaction_after_another() {
+    let reactions = ReactionConfig { min_secs: 1.0, max_secs: 1.0 };
+    let mut simulation = held_at_red(1, reactions);
+    queue_eastbound(&mut simulation);
+    let (starts, _) = discharge(&mut simulation);
+    let reaction = Duration::from_secs(1);
+    for lane in &starts {
+        assert!(lane.len() >= 5, "only {} vehicles queued in a lane", lane.len());
+        // The front vehicle reacts to the green, within a tick or two of it.
+        let front = lane[0];
+        assert!(front >= reaction && front <= reaction + Duration::from_millis(100));
+        // Everyone behind reacts to the vehicle ahead moving off, so no earlier.
+        for pair in lane.windows(2) {
+            let lag = pair[1] - pair[0];
+            assert!(lag >= reaction, "moved off {:?} after the vehicle ahead", lag);
+        }
+    }
+}
+
+#[test]
+fn drivers_moving_off_at_once_discharge_faster() {
+    let reactions = ReactionConfig { min_secs: 0.0, max_secs: 0.0 };
+    let mut simulation = held_at_red(1, reactions);
+    queue_eastbound(&mut simulation);
+    let (_, instant) = discharge(&mut simulation);
+    let mut simulation = held_at_red(1, ReactionConfig::default());
+    queue_eastbound(&mut simulation);
+    let (_, reacting) = discharge(&mut simulation);
+    assert!(!instant.is_empty() && !reacting.is_empty());
+    assert!(mean(&instant) < mean(&reacting));
+}
+
+#[test]
+fn saturation_headways_are_realistic() {
+    let mut headways = Vec::new();
+    for seed in 1..=4 {
+        let mut simulation = held_at_red(seed, ReactionConfig::default());
+        queue_eastbound(&mut simulation);
+        headways.extend(discharge(&mut simulation).1);
+    }
+    assert!(headways.len() >= 10, "only {} headways measured", headways.len());
+    // Field studies put saturation headways at around two seconds.
+    let mean = mean(&headways);
+    assert!(
+        mean >= Duration::from_millis(1500) && mean <= Duration::from_millis(2500),
+        "mean saturation headway {:?}",
+        mean
+    );
+}
@@ -0,0 +1,364 @@
+// Drives the simulation with random spawn sequences and controller actions and checks after
+// every tick that nothing it guarantees has been broken.
+
+use proptest::prelude::*;
+use proptest::test_runner::FileFailurePersistence;
+
+use road_intersection::config::{
+    Config,
+    FlashingConfig,
+    JaywalkingConfig,
+    LightsConfig,
+    ReactionConfig,
+};
+use road_intersection::lane::MAX_PLATOON_SIZE;
+use road_intersection::map::{ MapLayout, RoadEnd, MAX_STOP_LINE_SETBACK };
+use road_intersection::map_file::MapFile;
+use road_intersection::median::{ MAX_TURN_BAY_LENGTH, MIN_TURN_BAY_LENGTH };
+use road_intersection::no_change_zone::MAX_NO_CHANGE_ZONE;
+use road_intersection::plugin::Registry;
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::traffic_light::{
+    LeftTurnPhasing,
+    LeftTurnSequence,
+    LeftTurnSequences,
+    LeftTurns,
+    Phase,
+};
+use road_intersection::vehicle::{ vehicle_rect, Direction, Route, VehicleKind, VehicleState };
+use road_intersection::{ LANE_CHANGE_LENGTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+const TICKS: u32 = 4000;
+// Vehicles leave past the world's edge: at most the default sink offset plus a bus length.
+const EDGE_MARGIN: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Spawn(Direction),
+    SpawnRandom,
+    SpawnBus,
+    SpawnCyclist,
+    SpawnPlatoon(Direction, u32),
+    Trip(Direction, Direction),
+    SpawnRoute(Direction, Route),
+    SetDemand(f32),
+    SetApproachDemand(Direction, f32),
+    CycleWeather,
+    RequestPhase(Phase),
+    PressWalk,
+    SendTrain,
+    ToggleClosure,
+    CauseIncident,
+    ToggleWebster,
+    ApplyWebster,
+    SetStopLine(Direction, f32),
+    ToggleManual,
+    OverrideLights(Option<Phase>),
+    // A phase's green, then the yellow and all-red, as the timing editor sets them.
+    Retime(Phase, f32, f32, f32),
+}
+
+fn direction() -> impl Strategy<Value = Direction> {
+    prop_oneof![
+        Just(Direction::North),
+        Just(Direction::South),
+        Just(Direction::East),
+        Just(Direction::West)
+    ]
+}
+
+fn left_turns() -> impl Strategy<Value = LeftTurns> {
+    let phasing = prop_oneof![
+        Just(LeftTurnPhasing::Permissive),
+        Just(LeftTurnPhasing::ProtectedPermissive)
+    ];
+    [phasing.clone(), phasing.clone(), phasing.clone(), phasing]
+        .prop_map(|[north, south, east, west]| LeftTurns { north, south, east, west })
+}
+
+fn left_turn_sequences() -> impl Strategy<Value = LeftTurnSequences> {
+    let sequence = prop_oneof![Just(LeftTurnSequence::Lead), Just(LeftTurnSequence::Lag)];
+    [sequence.clone(), sequence.clone(), sequence.clone(), sequence]
+        .prop_map(|[north, south, east, west]| LeftTurnSequences { north, south, east, west })
+}
+
+fn route() -> impl Strategy<Value = Route> {
+    prop_oneof![Just(Route::Straight), Just(Route::Left), Just(Route::Right)]
+}
+
+// Mostly the four-way cross, otherwise any mix of one-way and missing roads the simulator
+// can run.
+fn layout() -> impl Strategy<Value = MapLayout> {
+    let road = prop_oneof![
+        12 => Just(RoadEnd::TwoWay),
+        1 => Just(RoadEnd::Inbound),
+        1 => Just(RoadEnd::Outbound),
+        1 => Just(RoadEnd::Missing)
+    ];
+    [road.clone(), road.clone(), road.clone(), road]
+        .prop_map(|roads| MapLayout { roads, ..MapLayout::default() })
+        .prop_filter("a network the simulator runs", |layout| {
+            let map = MapFile::from_layout(layout);
+            map.validate().is_ok() && map.layout().is_ok()
+        })
+}
+
+fn command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        4 => direction().prop_map(Command::Spawn),
+        2 => Just(Command::SpawnRandom),
+        1 => Just(Command::SpawnBus),
+        1 => Just(Command::SpawnCyclist),
+        1 => (direction(), 1..=MAX_PLATOON_SIZE)
+            .prop_map(|(direction, size)| Command::SpawnPlatoon(direction, size)),
+        2 => (direction(), direction()).prop_map(|(from, to)| Command::Trip(from, to)),
+        2 => (direction(), route())
+            .prop_map(|(direction, route)| Command::SpawnRoute(direction, route)),
+        1 => (0.0f32..120.0).prop_map(Command::SetDemand),
+        1 => (direction(), 0.0f32..60.0)
+            .prop_map(|(direction, rate)| Command::SetApproachDemand(direction, rate)),
+        1 => Just(Command::CycleWeather),
+        1 => prop_oneof![Just(Phase::NorthSouth), Just(Phase::EastWest)]
+            .prop_map(Command::RequestPhase),
+        1 => Just(Command::PressWalk),
+        1 => Just(Command::SendTrain),
+        1 => Just(Command::ToggleClosure),
+        1 => Just(Command::CauseIncident),
+        1 => Just(Command::ToggleWebster),
+        2 => Just(Command::ApplyWebster),
+        1 => (direction(), 0.0f32..=MAX_STOP_LINE_SETBACK)
+            .prop_map(|(direction, setback)| Command::SetStopLine(direction, setback)),
+        1 => Just(Command::ToggleManual),
+        2 => prop::option::of(prop_oneof![Just(Phase::NorthSouth), Just(Phase::EastWest)])
+            .prop_map(Command::OverrideLights),
+        1 => (
+            prop_oneof![Just(Phase::NorthSouth), Just(Phase::EastWest)],
+            1.0f32..=40.0,
+            1.0f32..=6.0,
+            0.0f32..=5.0
+        ).prop_map(|(phase, green, yellow, all_red)| Command::Retime(phase, green, yellow, all_red))
+    ]
+}
+
+fn apply(simulation: &mut TrafficSimulation, command: Command) {
+    match command {
+        Command::Spawn(direction) => simulation.spawn_vehicle(direction),
+        Command::SpawnRandom => simulation.spawn_random_vehicle(),
+        Command::SpawnBus => simulation.spawn_bus(),
+        Command::SpawnCyclist => simulation.spawn_random_cyclist(),
+        Command::SpawnPlatoon(direction, size) => simulation.spawn_platoon(direction, size),
+        Command::Trip(from, to) => {
+            // A trip back to where it started is refused, which is fine here.
+            let _ = simulation.spawn_trip(from, to);
+        }
+        Command::SpawnRoute(direction, route) => {
+            // So is a route into or out of a missing road.
+            let _ = simulation.spawn_vehicle_with_route(direction, route);
+        }
+        Command::SetDemand(rate) => simulation.demand.vehicles_per_minute = rate,
+        Command::SetApproachDemand(direction, rate) => {
+            *simulation.demand.approaches.rate_mut(direction) = rate;
+        }
+        Command::CycleWeather => simulation.set_weather(simulation.weather.next()),
+        Command::RequestPhase(phase) => {
+            let now = simulation.time.now();
+            simulation.traffic_light_mut().request_phase(phase, now);
+        }
+        Command::PressWalk => simulation.press_random_walk_button(),
+        Command::SendTrain => simulation.send_train(),
+        Command::ToggleClosure => simulation.toggle_random_closure(),
+        Command::CauseIncident => {
+            simulation.cause_incident(None);
+        }
+        Command::ToggleWebster => simulation.toggle_webster(),
+        Command::ApplyWebster => {
+            simulation.apply_webster_timing();
+        }
+        Command::SetStopLine(direction, setback) => {
+            let mut layout = *simulation.layout();
+            layout.approach_mut(direction).stop_line_setback = setback;
+            simulation.set_layout(layout).unwrap();
+        }
+        Command::ToggleManual => simulation.toggle_manual_control(),
+        Command::OverrideLights(phase) => simulation.override_lights(phase),
+        Command::Retime(phase, green, yellow, all_red) => {
+            let mut timing = LightsConfig::from_light(&simulation.traffic_light());
+            timing.set_green_for(phase, green);
+            timing.yellow_secs = yellow;
+            timing.all_red_secs = all_red;
+            simulation.set_timing(timing).unwrap();
+        }
+    }
+}
+
+// `counts` holds how many vehicles each approach had before the tick. Moving a stop line
+// back can leave an approach holding more than its new capacity; it then takes no more on
+// until it is back under.
+fn check_invariants(simulation: &TrafficSimulation, counts: &[usize]) -> Result<(), String> {
+    let time = simulation.time.now().as_secs_f32();
+    for (lane, &before) in simulation.lanes.iter().zip(counts) {
+        let vehicles = simulation.vehicles_on(lane.direction);
+        let (occupied, capacity) = (lane.occupied(vehicles.iter()), lane.geometry.capacity);
+        if occupied > capacity && vehicles.len() > before {
+            return Err(format!(
+                "{:.2}s: {} vehicles from {:?} taking {:.1} m, capacity {:.1} m",
+                time,
+                vehicles.len(),
+                lane.direction,
+                occupied,
+                capacity
+            ));
+        }
+        for (i, vehicle) in vehicles.iter().enumerate() {
+            let on_map =
+                vehicle.x >= -EDGE_MARGIN &&
+                vehicle.y >= -EDGE_MARGIN &&
+                vehicle.x <= WORLD_WIDTH + EDGE_MARGIN &&
+                vehicle.y <= WORLD_HEIGHT + EDGE_MARGIN;
+            if !on_map || !vehicle.x.is_finite() || !vehicle.y.is_finite() {
+                return Err(format!(
+                    "{:.2}s: vehicle #{} off the map at ({}, {})",
+                    time,
+                    vehicle.id,
+                    vehicle.x,
+                    vehicle.y
+                ));
+            }
+            // A vehicle is in one of the intersection's states just while it is in the box,
+            // and only turning on a turn.
+            let in_box =
+                matches!(vehicle.state, VehicleState::InIntersection | VehicleState::Turning);
+            let turning_straight =
+                vehicle.state == VehicleState::Turning && vehicle.route == Route::Straight;
+            if in_box != vehicle.in_intersection() || turning_straight {
+                return Err(format!(
+                    "{:.2}s: vehicle #{} {:?} at ({}, {})",
+                    time,
+                    vehicle.id,
+                    vehicle.state,
+                    vehicle.x,
+                    vehicle.y
+                ));
+            }
+            // Vehicles from one approach follow each other and must never overlap; crossing
+            // traffic can, as red-light runners cause collisions on purpose. A bus's tail
+            // sweeps over the next lane as it turns, which waiting cars don't make room for.
+            // Footprints stay square to the road, so two vehicles turning side by side on
+            // their own arcs can seem to touch without doing so.
+            for other in vehicles.iter().skip(i + 1) {
+                let turning_bus = [vehicle, other]
+                    .iter()
+                    .any(|v| v.kind == VehicleKind::Bus && v.in_intersection());
+                let side_by_side =
+                    vehicle.lane != other.lane &&
+                    vehicle.in_intersection() &&
+                    other.in_intersection();
+                let overlap = vehicle_rect(vehicle).intersects(&vehicle_rect(other));
+                if overlap && !turning_bus && !side_by_side {
+                    return Err(format!(
+                        "{:.2}s: vehicles from {:?} overlap: {:?} and {:?}",
+                        time,
+                        lane.direction,
+                        vehicle,
+                        other
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 32,
+        failure_persistence: Some(Box::new(
+            FileFailurePersistence::Direct("tests/invariants.proptest-regressions")
+        )),
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn invariants_hold_under_random_traffic(
+        seed in any::<u64>(),
+        layout in layout(),
+        turn_bay in prop::option::weighted(0.25, MIN_TURN_BAY_LENGTH..=MAX_TURN_BAY_LENGTH),
+        no_change_zone in prop::option::weighted(0.25, 0.0f32..=MAX_NO_CHANGE_ZONE),
+        (spawn_policy, light_controller, driver_model) in (
+            prop::sample::select(vec!["random", "regular", "target-density"]),
+            prop::sample::select(vec!["fixed-time", "actuated"]),
+            prop::sample::select(vec!["mixed", "cautious"])
+        ),
+        (reroute_chance, saturated_at) in (0.0f64..=1.0, 1usize..12),
+        (min_reaction, reaction_spread) in (0.0f32..=2.0, 0.0f32..=1.5),
+        (left_turns, left_turn_sequence, left_arrow_secs) in (
+            left_turns(),
+            left_turn_sequences(),
+            1.0f32..=8.0
+        ),
+        flashing in prop::option::weighted(
+            0.25,
+            prop_oneof![Just(Phase::NorthSouth), Just(Phase::EastWest)]
+        ),
+        jaywalking in prop::option::weighted(0.25, (0.0f64..=1.0, 0.0f32..=30.0)),
+        commands in prop::collection::vec((0u32..200, command()), 1..60)
+    ) {
+        // Strict, so a vehicle jumping fails the case rather than only being logged.
+        let mut config = Config { seed: Some(seed), strict: true, ..Config::default() };
+        config.map = layout;
+        config.median.turn_bay_length = turn_bay.unwrap_or(0.0);
+        // On a divided road the zone has to leave room to move into the bay.
+        let zone_limit = turn_bay.map_or(MAX_NO_CHANGE_ZONE, |bay| bay - LANE_CHANGE_LENGTH);
+        config.lane_changes.no_change_zone = no_change_zone.unwrap_or(0.0).min(zone_limit);
+        config.plugins.spawn_policy = spawn_policy.to_string();
+        config.demand.target.vehicles = 30;
+        config.plugins.light_controller = light_controller.to_string();
+        config.plugins.driver_model = driver_model.to_string();
+        config.route_choice.reroute_chance = reroute_chance;
+        config.route_choice.saturated_at = saturated_at;
+        config.lights.left_turns = left_turns;
+        config.lights.left_turn_sequence = left_turn_sequence;
+        config.lights.left_arrow_secs = left_arrow_secs;
+        config.reactions = ReactionConfig {
+            min_secs: min_reaction,
+            max_secs: min_reaction + reaction_spread,
+        };
+        if let Some((against_signal_chance, mid_block_per_minute)) = jaywalking {
+            config.jaywalking = JaywalkingConfig { against_signal_chance, mid_block_per_minute };
+        }
+        // Runs start at 8:00 and cover about four hours, so the flashing starts and ends.
+        if let Some(main_road) = flashing {
+            config.flashing = FlashingConfig {
+                enabled: true,
+                from_hour: 9.0,
+                to_hour: 11.0,
+                main_road,
+            };
+        }
+        let mut simulation = TrafficSimulation::with_registry(&config, Registry::default())
+            .expect("built-in plugins");
+        let mut commands = commands.into_iter();
+        let mut next = commands.next();
+        let mut wait = 0;
+        for _ in 0..TICKS {
+            while let Some((delay, command)) = next {
+                if wait < delay {
+                    break;
+                }
+                apply(&mut simulation, command);
+                wait = 0;
+                next = commands.next();
+            }
+            wait += 1;
+            let counts: Vec<usize> = simulation.lanes
+                .iter()
+                .map(|lane| simulation.vehicles_on(lane.direction).len())
+                .collect();
+            simulation.update();
+            simulation.drain_events();
+            if let Err(e) = check_invariants(&simulation, &counts) {
+                prop_assert!(false, "{}", e);
+            }
+        }
+    }
+}
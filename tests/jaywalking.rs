@@ -0,0 +1,95 @@
+// Runs busy traffic past jaywalkers and checks drivers brake for them without running into
+// them or into each other, and that every close call is counted.
+
+use road_intersection::config::{ Config, JaywalkingConfig };
+use road_intersection::pedestrian::{ pedestrian_rect, CloseCall };
+use road_intersection::simulation::{ SimEvent, TrafficSimulation };
+use road_intersection::stats::Stats;
+use road_intersection::vehicle::vehicle_rect;
+
+const TICKS: u32 = 30_000;
+
+fn with_jaywalking(jaywalking: JaywalkingConfig) -> TrafficSimulation {
+    let mut config = Config { seed: Some(11), jaywalking, ..Config::default() };
+    config.demand.vehicles_per_minute = 90.0;
+    TrafficSimulation::with_config(&config).unwrap()
+}
+
+// Vehicles from one approach in the same lane, neither yet in the intersection, that overlap.
+fn rear_ended(simulation: &TrafficSimulation) -> bool {
+    simulation.lanes.iter().any(|lane| {
+        let vehicles = simulation.vehicles_on(lane.direction);
+        let waiting: Vec<_> = vehicles.iter().filter(|v| !v.in_intersection()).collect();
+        waiting.iter().enumerate().any(|(i, vehicle)| {
+            waiting[i + 1..].iter().any(|other| {
+                other.lane == vehicle.lane &&
+                    vehicle_rect(vehicle).intersects(&vehicle_rect(other))
+            })
+        })
+    })
+}
+
+#[test]
+fn drivers_brake_hard_for_jaywalkers_and_every_close_call_is_counted() {
+    let jaywalking = JaywalkingConfig { against_signal_chance: 0.0, mid_block_per_minute: 20.0 };
+    let mut simulation = with_jaywalking(jaywalking);
+    let mut tally = Stats::default();
+    for _ in 0..TICKS {
+        simulation.update();
+        for event in simulation.drain_events() {
+            tally.record(&event, simulation.time.now());
+        }
+        assert!(!rear_ended(&simulation), "rear-ended at {:?}", simulation.time.now());
+        // Any moving vehicle that touches a jaywalker has been counted as a near miss.
+        for vehicle in simulation.vehicles().iter() {
+            for jaywalker in simulation.crossing_pedestrians().iter() {
+                let hit = vehicle.speed > 0.0 &&
+                    vehicle_rect(vehicle).intersects(&pedestrian_rect(jaywalker));
+                let near_miss = jaywalker.close_call == Some(CloseCall::NearMiss);
+                assert!(!hit || near_miss, "{:?} hit {:?}", vehicle, jaywalker);
+            }
+        }
+    }
+    let stats = &simulation.stats;
+    assert!(stats.jaywalkers > 50, "only {} jaywalkers", stats.jaywalkers);
+    assert!(stats.jaywalker_conflicts > 0 && stats.emergency_brakes > 0, "{:?}", stats);
+    assert!(stats.jaywalker_near_misses <= stats.jaywalker_conflicts);
+    assert_eq!(
+        (tally.jaywalkers, tally.jaywalker_conflicts, tally.emergency_brakes),
+        (stats.jaywalkers, stats.jaywalker_conflicts, stats.emergency_brakes)
+    );
+}
+
+#[test]
+fn pedestrians_sure_to_jaywalk_never_wait_for_the_walk() {
+    let jaywalking = JaywalkingConfig { against_signal_chance: 1.0, mid_block_per_minute: 0.0 };
+    let mut simulation = with_jaywalking(jaywalking);
+    let mut crossed = 0;
+    for tick in 0..TICKS {
+        if tick % 500 == 0 && !simulation.traffic_light().is_walk() {
+            simulation.press_random_walk_button();
+            assert!(simulation.waiting_pedestrians().is_empty());
+        }
+        simulation.update();
+        for event in simulation.drain_events() {
+            assert!(!matches!(event, SimEvent::PedestrianServed { .. }), "{:?}", event);
+            crossed += matches!(event, SimEvent::JaywalkerCrossed { .. }) as u32;
+        }
+    }
+    assert!(crossed > 0);
+    assert!(!simulation.traffic_light().is_walk());
+}
+
+#[test]
+fn nobody_jaywalks_by_default() {
+    let mut simulation = with_jaywalking(JaywalkingConfig::default());
+    for tick in 0..TICKS {
+        if tick % 500 == 0 {
+            simulation.press_random_walk_button();
+        }
+        simulation.update();
+        assert!(simulation.crossing_pedestrians().iter().all(|p| !p.jaywalking));
+    }
+    assert_eq!(simulation.stats.jaywalkers, 0);
+    assert_eq!(simulation.stats.emergency_brakes, 0);
+}
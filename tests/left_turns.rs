@@ -0,0 +1,222 @@
+// Runs the signal controller under every mix of permissive, leading and lagging lefts and
+// checks that no two movements that cross ever have a green together.
+
+use std::time::Duration;
+
+use road_intersection::traffic_light::{
+    LeftTurnPhasing,
+    LeftTurnSequence,
+    LeftTurnSequences,
+    LeftTurns,
+    LightState,
+    Phase,
+    TrafficLight,
+};
+use road_intersection::vehicle::{ Direction, Route };
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+const ROUTES: [Route; 3] = [Route::Straight, Route::Left, Route::Right];
+const STEP: Duration = Duration::from_millis(100);
+const RUN: Duration = Duration::from_secs(240);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lefts {
+    Permissive,
+    Leading,
+    Lagging,
+}
+
+// The controller with each approach's lefts, in North, South, East, West order.
+fn light(lefts: [Lefts; 4]) -> TrafficLight {
+    let phasing = |lefts: Lefts| match lefts {
+        Lefts::Permissive => LeftTurnPhasing::Permissive,
+        _ => LeftTurnPhasing::ProtectedPermissive,
+    };
+    let sequence = |lefts: Lefts| match lefts {
+        Lefts::Lagging => LeftTurnSequence::Lag,
+        _ => LeftTurnSequence::Lead,
+    };
+    let [north, south, east, west] = lefts;
+    let mut light = TrafficLight::new();
+    light.green_time = Duration::from_secs(15);
+    light.left_turns = LeftTurns {
+        north: phasing(north),
+        south: phasing(south),
+        east: phasing(east),
+        west: phasing(west),
+    };
+    light.left_turn_sequences = LeftTurnSequences {
+        north: sequence(north),
+        south: sequence(south),
+        east: sequence(east),
+        west: sequence(west),
+    };
+    light
+}
+
+// Every mix of lefts over the four approaches.
+fn all_lefts() -> impl Iterator<Item = [Lefts; 4]> {
+    let options = [Lefts::Permissive, Lefts::Leading, Lefts::Lagging];
+    (0..81).map(move |mut n| {
+        let mut lefts = [Lefts::Permissive; 4];
+        for left in &mut lefts {
+            *left = options[n % 3];
+            n /= 3;
+        }
+        lefts
+    })
+}
+
+// Movements from different roads always cross. On the same road, a left on its arrow
+// crosses everything oncoming but the other left, which it only waits for once turning.
+fn conflicting_greens(light: &TrafficLight) -> Option<String> {
+    for a in DIRECTIONS {
+        for b in DIRECTIONS.into_iter().filter(|&b| b != a) {
+            let (head_a, head_b) = (light.head_for(a), light.head_for(b));
+            let same_road = Phase::NorthSouth.serves(a) == Phase::NorthSouth.serves(b);
+            for (route_a, route_b) in ROUTES.into_iter().flat_map(|r| ROUTES.map(|s| (r, s))) {
+                let both_go =
+                    head_a.for_route(route_a).is_go() && head_b.for_route(route_b).is_go();
+                let crossing =
+                    !same_road ||
+                    (head_a.protects(route_a) && route_b != Route::Left) ||
+                    (head_b.protects(route_b) && route_a != Route::Left);
+                if both_go && crossing {
+                    return Some(format!(
+                        "{:?} {:?} on {:?} and {:?} {:?} on {:?}",
+                        a,
+                        route_a,
+                        head_a,
+                        b,
+                        route_b,
+                        head_b
+                    ));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Steps `light` through `RUN`, letting `act` poke it before each step, and fails on the
+// first conflicting green.
+fn run(mut light: TrafficLight, mut act: impl FnMut(&mut TrafficLight, Duration)) {
+    let mut now = Duration::ZERO;
+    while now < RUN {
+        act(&mut light, now);
+        light.update(now);
+        if let Some(conflict) = conflicting_greens(&light) {
+            panic!("{:?}: {}", now, conflict);
+        }
+        now += STEP;
+    }
+}
+
+#[test]
+fn no_conflicting_greens_on_fixed_time() {
+    for lefts in all_lefts() {
+        run(light(lefts), |_, _| {});
+    }
+}
+
+#[test]
+fn no_conflicting_greens_with_greens_cut_short() {
+    for lefts in all_lefts() {
+        run(light(lefts), |light, now| {
+            if now.as_millis() % 3700 == 0 {
+                light.end_green(now);
+            }
+        });
+    }
+}
+
+#[test]
+fn no_conflicting_greens_with_walks_and_trains() {
+    for lefts in all_lefts() {
+        run(light(lefts), |light, now| {
+            match now.as_secs() {
+                20 | 75 => light.call_walk(),
+                40 => {
+                    light.preempt(Phase::EastWest, now);
+                }
+                60 => {
+                    light.release(now);
+                }
+                _ => {}
+            }
+        });
+    }
+}
+
+// What `direction`'s signal shows, as (ball, left arrow), at each step its road is served
+// for the second time, the first being from the start with no leading arrows.
+fn timeline(
+    mut light: TrafficLight,
+    direction: Direction
+) -> Vec<(LightState, Option<LightState>)> {
+    let mut now = Duration::ZERO;
+    let mut seen = Vec::new();
+    let (mut served, mut starts) = (true, 0);
+    loop {
+        light.update(now);
+        let serves = light.phase.serves(direction);
+        starts += (serves && !served) as u32;
+        served = serves;
+        match starts {
+            1 if serves => {
+                let head = light.head_for(direction);
+                seen.push((head.ball, head.left_arrow));
+            }
+            1 => return seen,
+            _ => {}
+        }
+        now += STEP;
+    }
+}
+
+#[test]
+fn leading_arrows_open_the_green_and_lagging_ones_close_it() {
+    let lefts = [Lefts::Leading, Lefts::Lagging, Lefts::Permissive, Lefts::Permissive];
+    let light = || light(lefts);
+    let north = timeline(light(), Direction::North);
+    let south = timeline(light(), Direction::South);
+    // North's arrow comes on with its green, while South waits at red.
+    assert_eq!(north[0], (LightState::Green, Some(LightState::Green)));
+    assert_eq!(south[0].0, LightState::Red);
+    // South's arrow comes last, after North's yellow, and ends with South's yellow.
+    let lagging = south
+        .iter()
+        .position(|&head| head == (LightState::Green, Some(LightState::Green)))
+        .expect("South's lagging arrow");
+    assert!(north[..lagging].contains(&(LightState::Yellow, None)));
+    assert_eq!(north[lagging].0, LightState::Red);
+    let after = &south[lagging..];
+    let end = after.iter().position(|&(ball, _)| ball != LightState::Green).unwrap();
+    assert_eq!(after[end], (LightState::Yellow, Some(LightState::Yellow)));
+    // Both approaches are permissive in between.
+    let permissive = (LightState::Green, None);
+    let both = (0..lagging).any(|i| north[i] == permissive && south[i] == permissive);
+    assert!(both, "no permissive green between the arrows");
+}
+
+#[test]
+fn a_green_cut_short_still_gives_its_lagging_arrow() {
+    let mut light = light([Lefts::Lagging; 4]);
+    light.update(Duration::ZERO);
+    assert!(light.end_green(Duration::from_secs(1)));
+    let mut now = Duration::from_secs(1);
+    let mut arrow = false;
+    while light.state == LightState::Green {
+        // Asking again doesn't cut the lagging arrow short.
+        assert!(!light.end_green(now));
+        now += STEP;
+        light.update(now);
+        arrow |= light.head_for(Direction::North).left_arrow == Some(LightState::Green);
+    }
+    assert!(arrow, "no lagging arrow before the yellow");
+}
@@ -0,0 +1,116 @@
+// Renders frames through a renderer that notes every rect, and checks the minimap inset
+// appears once the window is zoomed in on part of the world, with each approach tinted by
+// its queue.
+
+use road_intersection::config::Config;
+use road_intersection::error::RenderError;
+use road_intersection::minimap::{ minimap_rect, to_world };
+use road_intersection::render::{ Color, DrawLayer, Rect, Renderer, Texture };
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::vehicle::Direction;
+use road_intersection::{ ROAD_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH, WORLD_HEIGHT, WORLD_WIDTH };
+
+#[derive(Default)]
+struct Rects {
+    rects: Vec<(Rect, Color)>,
+}
+
+impl Renderer for Rects {
+    fn clear(&mut self, _color: Color) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), RenderError> {
+        self.rects.push((rect, color));
+        Ok(())
+    }
+
+    fn draw_texture(&mut self, _texture: &Texture, _dst: Rect) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn draw_layer(&mut self, _key: u64, draw: &mut DrawLayer) -> Result<(), RenderError> {
+        draw(self)
+    }
+}
+
+fn with_meters_per_pixel(meters_per_pixel: f32) -> TrafficSimulation {
+    let mut config = Config { seed: Some(9), ..Config::default() };
+    config.display.meters_per_pixel = meters_per_pixel;
+    config.demand.vehicles_per_minute = 0.0;
+    TrafficSimulation::with_config(&config).unwrap()
+}
+
+// What the frame draws on the minimap, with the world point at the middle of each rect.
+fn minimap_rects(simulation: &TrafficSimulation) -> Vec<((f32, f32), Color)> {
+    let mut renderer = Rects::default();
+    simulation.render(&mut renderer).unwrap();
+    let area = minimap_rect();
+    if !renderer.rects.iter().any(|&(rect, _)| rect == area) {
+        return Vec::new();
+    }
+    renderer.rects
+        .iter()
+        .filter(|(rect, _)| rect.w > 0 && rect.h > 0)
+        .filter_map(|&(rect, color)| {
+            let middle = to_world(rect.x + (rect.w as i32) / 2, rect.y + (rect.h as i32) / 2)?;
+            Some((middle, color))
+        })
+        .collect()
+}
+
+#[test]
+fn the_minimap_only_shows_once_the_window_leaves_out_part_of_the_world() {
+    assert!(minimap_rects(&with_meters_per_pixel(0.1)).is_empty());
+    assert!(minimap_rects(&with_meters_per_pixel(0.2)).is_empty());
+    assert!(!minimap_rects(&with_meters_per_pixel(0.05)).is_empty());
+}
+
+#[test]
+fn each_approach_is_tinted_by_how_far_its_queue_backs_up() {
+    let mut simulation = with_meters_per_pixel(0.05);
+    simulation.spawn_platoon(Direction::North, 10);
+    let palette = simulation.theme().palette();
+    let rects = minimap_rects(&simulation);
+    let (center_x, center_y) = (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+    // Northbound traffic comes up from the south end, right of the center line.
+    let northbound = |(x, y): (f32, f32)| x > center_x && y > center_y + ROAD_WIDTH / 2.0;
+    let tinted = |color: Color| {
+        rects
+            .iter()
+            .filter(move |&&(_, c)| (c.r, c.g, c.b) == (color.r, color.g, color.b))
+            .map(|&(at, _)| at)
+    };
+    assert!(tinted(palette.red).any(northbound));
+    assert_eq!(tinted(palette.green).count(), 3);
+    assert!(tinted(palette.green).all(|at| !northbound(at)));
+}
+
+#[test]
+fn minimap_pixels_map_to_the_whole_world() {
+    let area = minimap_rect();
+    assert_eq!(to_world(area.x, area.y), Some((0.0, 0.0)));
+    let (x, y) = to_world(area.x + (area.w as i32) / 2, area.y + (area.h as i32) / 2).unwrap();
+    assert!((x - WORLD_WIDTH / 2.0).abs() < 1.0 && (y - WORLD_HEIGHT / 2.0).abs() < 1.0);
+    assert_eq!(to_world(area.x - 1, area.y), None);
+    assert_eq!(to_world(area.x, area.y + (area.h as i32)), None);
+}
+
+#[test]
+fn panning_to_a_minimap_point_centers_the_window_on_it_within_the_world() {
+    let mut simulation = with_meters_per_pixel(0.04);
+    let area = minimap_rect();
+    let (x, y) = to_world(area.x + (area.w as i32) / 4, area.y + (area.h as i32) / 4).unwrap();
+    simulation.pan_to(x, y);
+    let view = simulation.view();
+    let (middle_x, middle_y) = view.to_world((WINDOW_WIDTH as i32) / 2, (WINDOW_HEIGHT as i32) / 2);
+    assert!((middle_x - x).abs() < 0.1 && (middle_y - y).abs() < 0.1);
+    // Past the corner of the world the window stops at its edges.
+    simulation.pan_to(-10.0, -10.0);
+    let (left, top) = simulation.view().to_world(0, 0);
+    assert!(left.abs() < 0.1 && top.abs() < 0.1, "window starts at ({}, {})", left, top);
+}
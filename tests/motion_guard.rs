@@ -0,0 +1,33 @@
+// Moves a vehicle further in one tick than it could drive and checks the motion guard only
+// logs it in an ordinary run, and stops a strict one.
+
+use road_intersection::config::Config;
+use road_intersection::simulation::TrafficSimulation;
+use road_intersection::vehicle::Direction;
+
+// Runs until a vehicle is on the road, then carries it well up the road at once.
+fn jump_a_vehicle(strict: bool) -> TrafficSimulation {
+    let config = Config { strict, ..Config::default() };
+    let mut simulation = TrafficSimulation::with_config(&config).unwrap();
+    simulation.spawn_vehicle(Direction::North);
+    while simulation.vehicles().is_empty() {
+        simulation.update();
+    }
+    let id = simulation.vehicles().iter().next().expect("a vehicle on the road").id;
+    simulation.vehicle_mut(id).expect("a vehicle on the road").y -= 20.0;
+    simulation.update();
+    simulation
+}
+
+#[test]
+fn an_ordinary_run_carries_on_past_a_jump() {
+    let mut simulation = jump_a_vehicle(false);
+    simulation.update();
+    assert_eq!(simulation.vehicles().len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "jumped")]
+fn a_strict_run_stops_at_a_jump() {
+    jump_a_vehicle(true);
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "scripting")]
+// Runs scripts through their hooks and checks what they ask for happens, and that scripts
+// that can't run are refused when loaded.
+
+use std::path::Path;
+
+use road_intersection::config::{ Config, LightsConfig };
+use road_intersection::script::Script;
+use road_intersection::simulation::{ SimEvent, TrafficSimulation };
+use road_intersection::traffic_light::Phase;
+use road_intersection::vehicle::Direction;
+
+fn load(name: &str, source: &str) -> Script {
+    let path = std::env::temp_dir().join(format!("{}-{}.rhai", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let script = Script::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    script.unwrap()
+}
+
+// No traffic but what the script sends.
+fn with_script(script: Script) -> TrafficSimulation {
+    let mut config = Config { seed: Some(3), script: Some(script), ..Config::default() };
+    config.demand.vehicles_per_minute = 0.0;
+    TrafficSimulation::with_config(&config).unwrap()
+}
+
+// The approach of each vehicle that got onto the road over `seconds`, and when the lights
+// first changed.
+fn run(simulation: &mut TrafficSimulation, seconds: f32) -> (Vec<Direction>, Option<f32>) {
+    let (mut spawned, mut changed) = (Vec::new(), None);
+    while simulation.time.now().as_secs_f32() < seconds {
+        simulation.update();
+        for event in simulation.drain_events() {
+            match event {
+                SimEvent::VehicleSpawned { approach, .. } => spawned.push(approach),
+                SimEvent::LightChanged => {
+                    changed = changed.or(Some(simulation.time.now().as_secs_f32()));
+                }
+                _ => {}
+            }
+        }
+    }
+    (spawned, changed)
+}
+
+#[test]
+fn a_script_spawns_a_vehicle_and_requests_a_phase() {
+    let script = load(
+        "tick",
+        r#"
+        fn on_tick(time) {
+            if time >= 2.0 && this.done == () {
+                spawn_vehicle("west");
+                request_phase("east_west");
+                this.done = true;
+            }
+        }
+        "#
+    );
+    let mut simulation = with_script(script);
+    let (spawned, changed) = run(&mut simulation, 2.5);
+    assert_eq!(spawned, [Direction::West]);
+    // Well short of the end of the north-south green.
+    assert!(changed.is_some_and(|at| (2.0..2.1).contains(&at)), "changed at {:?}", changed);
+    run(&mut simulation, 10.0);
+    assert_eq!(simulation.traffic_light().phase, Phase::EastWest);
+}
+
+#[test]
+fn hooks_answer_spawns_and_light_changes() {
+    let script = load(
+        "events",
+        r#"
+        fn on_spawn(approach, kind) {
+            if approach == "north" && kind == "car" {
+                spawn_trip("north", "west");
+            }
+        }
+
+        fn on_light_change(phase) {
+            if phase == "east_west" && this.sent == () {
+                spawn_platoon("east", 3);
+                this.sent = true;
+            }
+        }
+        "#
+    );
+    let mut simulation = with_script(script);
+    simulation.spawn_vehicle(Direction::North);
+    let (spawned, _) = run(&mut simulation, 20.0);
+    let count = |approach| spawned.iter().filter(|&&spawned| spawned == approach).count();
+    // The trip enters at the north end, travelling south.
+    assert_eq!((count(Direction::North), count(Direction::South)), (1, 1));
+    assert_eq!(count(Direction::East), 3);
+}
+
+#[test]
+fn each_light_change_gets_the_phase_it_left_the_light_in() {
+    let script = load(
+        "phases",
+        r#"
+        fn on_light_change(phase) {
+            if phase == "east_west" {
+                spawn_vehicle("east");
+            } else {
+                spawn_vehicle("south");
+            }
+        }
+        "#
+    );
+    let mut simulation = with_script(script);
+    // Under manual control, two changes before the hooks next run: over to east-west and
+    // back.
+    simulation.toggle_manual_control();
+    simulation.override_lights(Some(Phase::EastWest));
+    simulation.override_lights(Some(Phase::NorthSouth));
+    let (spawned, _) = run(&mut simulation, 5.0);
+    let count = |approach| spawned.iter().filter(|&&spawned| spawned == approach).count();
+    assert_eq!((count(Direction::East), count(Direction::South)), (1, 1));
+}
+
+#[test]
+fn on_tick_runs_once_a_tick_however_many_steps_it_takes() {
+    let script = load(
+        "ticks",
+        r#"
+        fn on_tick(time) {
+            this.ticks = if this.ticks == () { 1 } else { this.ticks + 1 };
+            if this.ticks == 20 {
+                spawn_vehicle("west");
+            }
+        }
+        "#
+    );
+    let mut config = Config { seed: Some(3), script: Some(script), ..Config::default() };
+    config.demand.vehicles_per_minute = 0.0;
+    config.clock.tick_ms = 100;
+    let mut simulation = TrafficSimulation::with_config(&config).unwrap();
+    let spawned = |simulation: &mut TrafficSimulation| {
+        simulation.update();
+        let events = simulation.drain_events();
+        events.iter().filter(|event| matches!(event, SimEvent::VehicleSpawned { .. })).count()
+    };
+    // Asked for at the end of the twentieth tick, not the twentieth 10 ms step.
+    let first = (0..40).position(|_| spawned(&mut simulation) > 0);
+    assert!(first.is_some_and(|tick| tick >= 20), "spawned on tick {:?}", first);
+}
+
+#[test]
+fn the_example_script_calls_the_east_west_green_for_its_cars() {
+    let script = Script::load(Path::new("script.example.rhai")).unwrap();
+    let mut simulation = with_script(script);
+    simulation.set_timing(LightsConfig { green_secs: 30.0, ..LightsConfig::default() }).unwrap();
+    let (spawned, changed) = run(&mut simulation, 12.0);
+    assert_eq!(spawned, [Direction::West; 3]);
+    // The third car, ten seconds in, calls the green long before north-south's runs out.
+    assert!(changed.is_some_and(|at| (10.0..10.1).contains(&at)), "changed at {:?}", changed);
+}
+
+#[test]
+fn scripts_that_cannot_run_are_refused() {
+    for source in ["fn on_tick(time {", "fn on_spawn(approach) {}"] {
+        let path = std::env::temp_dir().join(format!("refused-{}.rhai", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        assert!(Script::load(&path).is_err(), "{}", source);
+        std::fs::remove_file(&path).unwrap();
+    }
+}